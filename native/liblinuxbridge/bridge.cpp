@@ -252,13 +252,20 @@ void event_callback(XPointer p, XRecordInterceptData *hook)
     std::array<char, 10> buffer;
     int res = XLookupString(&event, buffer.data(), buffer.size(), NULL, NULL);
 
+    // Modifiers held during the keypress, used to detect the hotkeys
+    int32_t modifier_state = 0;
+    if (event.state & ControlMask) modifier_state |= MODIFIER_CTRL;
+    if (event.state & ShiftMask) modifier_state |= MODIFIER_SHIFT;
+    if (event.state & Mod1Mask) modifier_state |= MODIFIER_ALT;
+    if (event.state & Mod4Mask) modifier_state |= MODIFIER_META;
+
     switch (event_type) {
         case KeyPress:
             //printf ("%d %d %s\n", key_code, res, buffer.data());
             if (res > 0 && key_code != 22) {  // Printable character, but not backspace
-                keypress_callback(context_instance, buffer.data(), buffer.size(), 0, key_code);
+                keypress_callback(context_instance, buffer.data(), buffer.size(), 0, key_code, modifier_state);
             }else{ // Modifier key
-                keypress_callback(context_instance, NULL, 0, 1, key_code);
+                keypress_callback(context_instance, NULL, 0, 1, key_code, modifier_state);
             }
             break;
         default:
@@ -278,9 +285,15 @@ void delete_string(int32_t count) {
     }
 }
 
-void left_arrow(int32_t count) {
+void send_key(const char * key_name, int32_t count) {
+    for (int i = 0; i<count; i++) {
+        xdo_send_keysequence_window(xdo_context, CURRENTWINDOW, key_name, 8000);
+    }
+}
+
+void select_left(int32_t count) {
     for (int i = 0; i<count; i++) {
-        xdo_send_keysequence_window(xdo_context, CURRENTWINDOW, "Left", 8000);
+        xdo_send_keysequence_window(xdo_context, CURRENTWINDOW, "Shift+Left", 8000);
     }
 }
 
@@ -44,11 +44,20 @@ extern "C" int32_t eventloop();
  */
 extern "C" void cleanup();
 
+/*
+ * Bits of the modifiers held during a keypress, passed to the keypress callback
+ */
+#define MODIFIER_CTRL 1
+#define MODIFIER_SHIFT 2
+#define MODIFIER_ALT 4
+#define MODIFIER_META 8
+
 /*
  * Called when a new keypress is made, the first argument is an char array,
- * while the second is the size of the array.
+ * while the second is the size of the array. The last one holds the MODIFIER_* bits
+ * of the modifiers held during the keypress.
  */
-typedef void (*KeypressCallback)(void * self, const char *buffer, int32_t len, int32_t is_modifier, int32_t key_code);
+typedef void (*KeypressCallback)(void * self, const char *buffer, int32_t len, int32_t is_modifier, int32_t key_code, int32_t modifier_state);
 
 extern KeypressCallback keypress_callback;
 
@@ -68,9 +77,14 @@ extern "C" void send_string(const char * string);
 extern "C" void delete_string(int32_t count);
 
 /*
- * Send the left arrow keypress, *count* times.
+ * Send the keypress of the given key, identified by its X11 keysym name (for example "Tab"), *count* times.
+ */
+extern "C" void send_key(const char * key_name, int32_t count);
+
+/*
+ * Send the Shift+Left arrow keypress, *count* times, selecting the text on the left.
  */
-extern "C" void left_arrow(int32_t count);
+extern "C" void select_left(int32_t count);
 
 /*
  * Trigger normal paste ( Pressing CTRL+V )
@@ -44,7 +44,15 @@
             const char * chars = [event.characters UTF8String];
             int len = event.characters.length;
 
-            keypress_callback(context_instance, chars, len, 0, event.keyCode);
+            // Modifiers held during the keypress, used to detect the hotkeys
+            NSEventModifierFlags flags = [event modifierFlags];
+            int32_t modifier_state = 0;
+            if (flags & NSEventModifierFlagControl) modifier_state |= MODIFIER_CTRL;
+            if (flags & NSEventModifierFlagShift) modifier_state |= MODIFIER_SHIFT;
+            if (flags & NSEventModifierFlagOption) modifier_state |= MODIFIER_ALT;
+            if (flags & NSEventModifierFlagCommand) modifier_state |= MODIFIER_META;
+
+            keypress_callback(context_instance, chars, len, 0, event.keyCode, modifier_state);
             //NSLog(@"keydown: %@, %d", event.characters, event.keyCode);
         }else{
             // Because this event is triggered for both the press and release of a modifier, trigger the callback
@@ -52,7 +60,7 @@
             if (([event modifierFlags] & (NSEventModifierFlagShift | NSEventModifierFlagCommand |
                 NSEventModifierFlagControl | NSEventModifierFlagOption)) == 0) {
 
-                keypress_callback(context_instance, NULL, 0, 1, event.keyCode);
+                keypress_callback(context_instance, NULL, 0, 1, event.keyCode, 0);
             }
 
             //NSLog(@"keydown: %d", event.keyCode);
@@ -42,11 +42,20 @@ int32_t eventloop();
  */
 int32_t headless_eventloop();
 
+/*
+ * Bits of the modifiers held during a keypress, passed to the keypress callback
+ */
+#define MODIFIER_CTRL 1
+#define MODIFIER_SHIFT 2
+#define MODIFIER_ALT 4
+#define MODIFIER_META 8
+
 /*
  * Called when a new keypress is made, the first argument is an char array,
- * while the second is the size of the array.
+ * while the second is the size of the array. The last one holds the MODIFIER_* bits
+ * of the modifiers held during the keypress.
  */
-typedef void (*KeypressCallback)(void * self, const char *buffer, int32_t len, int32_t is_modifier, int32_t key_code);
+typedef void (*KeypressCallback)(void * self, const char *buffer, int32_t len, int32_t is_modifier, int32_t key_code, int32_t modifier_state);
 
 extern KeypressCallback keypress_callback;
 
@@ -70,11 +79,21 @@ void send_vkey(int32_t vk);
  */
 void send_multi_vkey(int32_t vk, int32_t count);
 
+/*
+ * Press the given Virtual Keys together, releasing them in reverse order
+ */
+void send_chord(const int32_t * vks, int32_t count);
+
 /*
  * Send the backspace keypress, *count* times.
  */
 void delete_string(int32_t count);
 
+/*
+ * Send the Shift+Left arrow keypress, *count* times, selecting the text on the left.
+ */
+void select_left(int32_t count);
+
 /*
  * Trigger normal paste ( Pressing CMD+V )
  */
@@ -85,6 +104,12 @@ void trigger_paste();
  */
 void trigger_copy();
 
+/*
+ * Return 1 if an application enabled the secure event input, for example
+ * because a password field is focused, 0 otherwise.
+ */
+int32_t is_secure_input_active();
+
 // UI
 
 /*
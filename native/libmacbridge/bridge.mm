@@ -20,6 +20,7 @@
 #include "bridge.h"
 
 #import <Foundation/Foundation.h>
+#include <Carbon/Carbon.h>
 #include "AppDelegate.h"
 #include <stdio.h>
 #include <string.h>
@@ -156,6 +157,53 @@ void send_multi_vkey(int32_t vk, int32_t count) {
     });
 }
 
+void send_chord(const int32_t * vks, int32_t count) {
+    // Copy the keys, as the block is executed after the caller returns
+    std::vector<int32_t> keys(vks, vks + count);
+
+    dispatch_async(dispatch_get_main_queue(), ^(void) {
+        for (auto it = keys.begin(); it != keys.end(); ++it) {
+            CGEventRef keydown;
+            keydown = CGEventCreateKeyboardEvent(NULL, *it, true);
+            CGEventPost(kCGHIDEventTap, keydown);
+            CFRelease(keydown);
+
+            usleep(2000);
+        }
+
+        for (auto it = keys.rbegin(); it != keys.rend(); ++it) {
+            CGEventRef keyup;
+            keyup = CGEventCreateKeyboardEvent(NULL, *it, false);
+            CGEventPost(kCGHIDEventTap, keyup);
+            CFRelease(keyup);
+
+            usleep(2000);
+        }
+    });
+}
+
+void select_left(int32_t count) {
+    dispatch_async(dispatch_get_main_queue(), ^(void) {
+        for (int i = 0; i < count; i++) {
+            CGEventRef keydown;
+            keydown = CGEventCreateKeyboardEvent(NULL, 0x7B, true);  // Left arrow
+            CGEventSetFlags(keydown, kCGEventFlagMaskShift);
+            CGEventPost(kCGHIDEventTap, keydown);
+            CFRelease(keydown);
+
+            usleep(500);
+
+            CGEventRef keyup;
+            keyup = CGEventCreateKeyboardEvent(NULL, 0x7B, false);
+            CGEventSetFlags(keyup, kCGEventFlagMaskShift);
+            CGEventPost(kCGHIDEventTap, keyup);
+            CFRelease(keyup);
+
+            usleep(500);
+        }
+    });
+}
+
 void trigger_paste() {
     dispatch_async(dispatch_get_main_queue(), ^(void) {
         CGEventRef keydown;
@@ -221,6 +269,10 @@ void trigger_copy() {
     });
 }
 
+int32_t is_secure_input_active() {
+    return IsSecureEventInputEnabled() ? 1 : 0;
+}
+
 int32_t get_active_app_bundle(char * buffer, int32_t size) {
     NSRunningApplication *frontApp = [[NSWorkspace sharedWorkspace] frontmostApplication];
     NSString *bundlePath = [frontApp bundleURL].path;
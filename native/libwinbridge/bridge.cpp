@@ -251,6 +251,13 @@ LRESULT CALLBACK window_procedure(HWND window, unsigned int msg, WPARAM wp, LPAR
                     // Refer to issue: https://github.com/federico-terzi/espanso/issues/86
                     UINT flags = 1 << 2;
 
+                    // Modifiers held during the keypress, used to detect the hotkeys
+                    int32_t modifier_state = 0;
+                    if (lpKeyState[VK_CONTROL] & 0x80) modifier_state |= MODIFIER_CTRL;
+                    if (lpKeyState[VK_SHIFT] & 0x80) modifier_state |= MODIFIER_SHIFT;
+                    if (lpKeyState[VK_MENU] & 0x80) modifier_state |= MODIFIER_ALT;
+                    if ((lpKeyState[VK_LWIN] | lpKeyState[VK_RWIN]) & 0x80) modifier_state |= MODIFIER_META;
+
                     int result = ToUnicodeEx(raw->data.keyboard.VKey, raw->data.keyboard.MakeCode, lpKeyState.data(), buffer.data(), buffer.size(), flags, currentKeyboardLayout);
 
                     //std::cout << result << " " << buffer[0] << " " << raw->data.keyboard.VKey << std::endl;
@@ -258,9 +265,9 @@ LRESULT CALLBACK window_procedure(HWND window, unsigned int msg, WPARAM wp, LPAR
                     // We need to call the callback in two different ways based on the type of key
                     // The only modifier we use that has a result > 0 is the BACKSPACE, so we have to consider it.
                     if (result >= 1 && raw->data.keyboard.VKey != VK_BACK) {
-                        keypress_callback(manager_instance, reinterpret_cast<uint16_t*>(buffer.data()), buffer.size(), 0, raw->data.keyboard.VKey, is_key_down);
+                        keypress_callback(manager_instance, reinterpret_cast<uint16_t*>(buffer.data()), buffer.size(), 0, raw->data.keyboard.VKey, is_key_down, modifier_state);
                     }else{
-                        keypress_callback(manager_instance, nullptr, 0, 1, raw->data.keyboard.VKey, is_key_down);
+                        keypress_callback(manager_instance, nullptr, 0, 1, raw->data.keyboard.VKey, is_key_down, modifier_state);
                     }
                 }
             }
@@ -499,6 +506,59 @@ void send_multi_vkey(int32_t vk, int32_t count) {
     SendInput(vec.size(), vec.data(), sizeof(INPUT));
 }
 
+void send_chord(const int32_t * vks, int32_t count) {
+    std::vector<INPUT> vec;
+
+    for (int i = 0; i < count; i++) {
+        INPUT input = { 0 };
+
+        input.type = INPUT_KEYBOARD;
+        input.ki.wVk = vks[i];
+        input.ki.dwFlags = 0; // 0 for key press
+        vec.push_back(input);
+    }
+
+    for (int i = count - 1; i >= 0; i--) {
+        INPUT input = { 0 };
+
+        input.type = INPUT_KEYBOARD;
+        input.ki.wVk = vks[i];
+        input.ki.dwFlags = KEYEVENTF_KEYUP; // KEYEVENTF_KEYUP for key release
+        vec.push_back(input);
+    }
+
+    SendInput(vec.size(), vec.data(), sizeof(INPUT));
+}
+
+void select_left(int32_t count) {
+    std::vector<INPUT> vec;
+
+    INPUT input = { 0 };
+
+    input.type = INPUT_KEYBOARD;
+    input.ki.wScan = 0;
+    input.ki.time = 0;
+    input.ki.dwExtraInfo = 0;
+    input.ki.wVk = VK_SHIFT;
+    input.ki.dwFlags = 0; // 0 for key press
+    vec.push_back(input);
+
+    for (int i = 0; i < count; i++) {
+        input.ki.wVk = VK_LEFT;
+        input.ki.dwFlags = 0; // 0 for key press
+        vec.push_back(input);
+
+        input.ki.dwFlags = KEYEVENTF_KEYUP; // KEYEVENTF_KEYUP for key release
+        vec.push_back(input);
+    }
+
+    input.ki.wVk = VK_SHIFT;
+    input.ki.dwFlags = KEYEVENTF_KEYUP; // KEYEVENTF_KEYUP for key release
+    vec.push_back(input);
+
+    SendInput(vec.size(), vec.data(), sizeof(INPUT));
+}
+
 void trigger_paste() {
     std::vector<INPUT> vec;
 
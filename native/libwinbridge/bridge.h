@@ -35,11 +35,20 @@ extern void * manager_instance;
  */
 extern "C" int32_t initialize(void * self, wchar_t * ico_path, wchar_t * bmp_path);
 
+/*
+ * Bits of the modifiers held during a keypress, passed to the keypress callback
+ */
+#define MODIFIER_CTRL 1
+#define MODIFIER_SHIFT 2
+#define MODIFIER_ALT 4
+#define MODIFIER_META 8
+
 /*
  * Called when a new keypress is made, the first argument is an int array,
- * while the second is the size of the array.
+ * while the second is the size of the array. The last one holds the MODIFIER_* bits
+ * of the modifiers held during the keypress.
  */
-typedef void (*KeypressCallback)(void * self, uint16_t *buffer, int32_t len, int32_t is_modifier, int32_t key_code, int32_t is_key_down);
+typedef void (*KeypressCallback)(void * self, uint16_t *buffer, int32_t len, int32_t is_modifier, int32_t key_code, int32_t is_key_down, int32_t modifier_state);
 extern KeypressCallback keypress_callback;
 
 /*
@@ -69,11 +78,21 @@ extern "C" void send_vkey(int32_t vk);
  */
 extern "C" void send_multi_vkey(int32_t vk, int32_t count);
 
+/*
+ * Press the given virtual keys together, releasing them in reverse order
+ */
+extern "C" void send_chord(const int32_t * vks, int32_t count);
+
 /*
  * Send the backspace keypress, *count* times.
  */
 extern "C" void delete_string(int32_t count);
 
+/*
+ * Send the Shift+Left arrow keypress, *count* times, selecting the text on the left.
+ */
+extern "C" void select_left(int32_t count);
+
 /*
  * Send the Paste keyboard shortcut (CTRL+V)
  */
@@ -35,11 +35,12 @@ extern {
 
     // Keyboard
     pub fn register_keypress_callback(cb: extern fn(_self: *mut c_void, *const u8,
-                                                i32, i32, i32));
+                                                i32, i32, i32, i32));
 
     pub fn send_string(string: *const c_char);
     pub fn delete_string(count: i32);
-    pub fn left_arrow(count: i32);
+    pub fn send_key(key_name: *const c_char, count: i32);
+    pub fn select_left(count: i32);
     pub fn trigger_paste();
     pub fn trigger_terminal_paste();
     pub fn trigger_shift_ins_paste();
@@ -52,12 +52,15 @@ extern {
 
     // Keyboard
     pub fn register_keypress_callback(cb: extern fn(_self: *mut c_void, *const u8,
-                                                i32, i32, i32));
+                                                i32, i32, i32, i32));
 
     pub fn send_string(string: *const c_char);
     pub fn send_vkey(vk: i32);
     pub fn send_multi_vkey(vk: i32, count: i32);
+    pub fn send_chord(vks: *const i32, count: i32);
     pub fn delete_string(count: i32);
+    pub fn select_left(count: i32);
     pub fn trigger_paste();
     pub fn trigger_copy();
+    pub fn is_secure_input_active() -> i32;
 }
\ No newline at end of file
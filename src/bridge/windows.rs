@@ -51,13 +51,15 @@ extern {
 
     // KEYBOARD
     pub fn register_keypress_callback(cb: extern fn(_self: *mut c_void, *const u16,
-                                                i32, i32, i32, i32));
+                                                i32, i32, i32, i32, i32));
 
     pub fn eventloop();
     pub fn send_string(string: *const u16);
     pub fn send_vkey(vk: i32);
     pub fn send_multi_vkey(vk: i32, count: i32);
+    pub fn send_chord(vks: *const i32, count: i32);
     pub fn delete_string(count: i32);
+    pub fn select_left(count: i32);
     pub fn trigger_paste();
     pub fn trigger_copy();
 }
\ No newline at end of file
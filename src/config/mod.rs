@@ -21,24 +21,44 @@ extern crate dirs;
 
 use std::path::{Path, PathBuf};
 use std::{fs};
-use crate::matcher::{Match, MatchVariable};
+use crate::matcher::{Match, MatchVariable, MatchGroup, MatchContentType, MergeStrategy};
+use crate::matcher::index::MatchIndex;
 use std::fs::{File, create_dir_all};
 use std::io::Read;
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, Serializer};
+use serde::ser::SerializeStruct;
 use crate::event::KeyModifier;
 use crate::keyboard::PasteShortcut;
+use crate::system::SystemManager;
 use std::collections::{HashSet, HashMap};
-use log::{error};
+use log::{debug, error, warn, LevelFilter};
 use std::fmt;
 use std::error::Error;
 use walkdir::WalkDir;
+use std::time::SystemTime;
+use std::cell::{Ref, RefCell};
+use std::ops::Deref;
+use std::rc::Rc;
+use regex::Regex;
+use semver::{Version, VersionReq};
 
 pub(crate) mod runtime;
 
 const DEFAULT_CONFIG_FILE_CONTENT : &str = include_str!("../res/config.yml");
 
 pub const DEFAULT_CONFIG_FILE_NAME : &str = "default.yml";
+pub const DEFAULT_CONFIG_ENV_VAR : &str = "ESPANSO_DEFAULT_CONFIG";
+pub const COMBINED_CONFIG_ENV_VAR : &str = "ESPANSO_CONFIG_FILE";
 const USER_CONFIGS_FOLDER_NAME: &str = "user";
+const PROFILES_FOLDER_NAME: &str = "profiles";
+const PACKAGE_MANIFEST_FILE_NAME: &str = "_manifest.yml";
+const CONFIG_CACHE_FILE_NAME: &str = "config_cache.json";
+
+// Fields renamed across versions, as (old name, new name). Configs still using
+// the old names are rewritten by ConfigSet::migrate, run with `espanso migrate`.
+const RENAMED_FIELDS: &[(&str, &str)] = &[
+    ("exclude_default_matches", "exclude_default_entries"),
+];
 
 // Default values for primitives
 fn default_name() -> String{ "default".to_owned() }
@@ -46,15 +66,19 @@ fn default_parent() -> String{ "self".to_owned() }
 fn default_filter_title() -> String{ "".to_owned() }
 fn default_filter_class() -> String{ "".to_owned() }
 fn default_filter_exec() -> String{ "".to_owned() }
+fn default_order() -> i32 { 0 }
 fn default_log_level() -> i32 { 0 }
 fn default_conflict_check() -> bool{ true }
 fn default_ipc_server_port() -> i32 { 34982 }
+fn default_ipc_secret() -> Option<String> { None }
 fn default_use_system_agent() -> bool { true }
 fn default_config_caching_interval() -> i32 { 800 }
+fn default_cache_configs() -> bool { false }
 fn default_word_separators() -> Vec<char> { vec![' ', ',', '.', '\r', '\n', 22u8 as char] }
+fn default_word_separator_strings() -> Vec<String> { Vec::new() }
 fn default_toggle_interval() -> u32 { 230 }
 fn default_toggle_key() -> KeyModifier { KeyModifier::ALT }
-fn default_preserve_clipboard() -> bool {false}
+fn default_preserve_clipboard() -> bool {true}
 fn default_passive_match_regex() -> String{ "(?P<name>:\\p{L}+)(/(?P<args>.*)/)?".to_owned() }
 fn default_passive_arg_delimiter() -> char { '/' }
 fn default_passive_arg_escape() -> char { '\\' }
@@ -63,10 +87,26 @@ fn default_enable_passive() -> bool { false }
 fn default_enable_active() -> bool { true }
 fn default_action_noop_interval() -> u128 { 500 }
 fn default_backspace_limit() -> i32 { 3 }
+fn default_max_buffer_size() -> usize { 256 }
 fn default_restore_clipboard_delay() -> i32 { 300 }
+fn default_clipboard_threshold() -> usize { 0 }
 fn default_exclude_default_entries() -> bool {false}
+fn default_clear_default_matches() -> bool {false}
+fn default_strict() -> bool {false}
+fn default_follow_symlinks() -> bool {true}
+fn default_collect_stats() -> bool {false}
+fn default_enabled_packages() -> Vec<String> { Vec::new() }
+fn default_disabled_packages() -> Vec<String> { Vec::new() }
+fn default_show_notifications() -> bool {true}
+fn default_disable_in_password_fields() -> bool { cfg!(target_os = "macos") }
+fn default_trigger_escape_char() -> Option<char> {None}
+fn default_include() -> Vec<String> { vec!["*.yml".to_owned()] }
+fn default_exclude() -> Vec<String> { Vec::new() }
+fn default_espanso_version() -> Option<String> { None }
 fn default_matches() -> Vec<Match> { Vec::new() }
 fn default_global_vars() -> Vec<MatchVariable> { Vec::new() }
+fn default_groups() -> Vec<MatchGroup> { Vec::new() }
+fn default_imports() -> Vec<String> { Vec::new() }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Configs {
@@ -85,23 +125,55 @@ pub struct Configs {
     #[serde(default = "default_filter_exec")]
     pub filter_exec: String,
 
+    // Precedence of a specific config over the others targeting the same window: the
+    // lower order wins. Configs with the same order are sorted by name.
+    #[serde(default = "default_order")]
+    pub order: i32,
+
     #[serde(default = "default_log_level")]
     pub log_level: i32,
 
     #[serde(default = "default_conflict_check")]
     pub conflict_check: bool,
 
+    // Changes to the transport, the input backend and the port require a restart,
+    // as they are not applied when the configuration is reloaded
+    #[serde(default)]
+    pub ipc_transport: IPCTransport,
+
+    // Only used on Linux
+    #[serde(default)]
+    pub linux_input_backend: LinuxInputBackend,
+
+    // Only used by the 'tcp' transport
     #[serde(default = "default_ipc_server_port")]
     pub ipc_server_port: i32,
 
+    // If present, every IPC request must carry this secret to be accepted
+    #[serde(default = "default_ipc_secret")]
+    pub ipc_secret: Option<String>,
+
     #[serde(default = "default_use_system_agent")]
     pub use_system_agent: bool,
 
     #[serde(default = "default_config_caching_interval")]
     pub config_caching_interval: i32,
 
+    // If true, the parsed configs are saved in the config directory and loaded from there
+    // at startup, as long as none of their files changed, to avoid parsing them again.
+    #[serde(default = "default_cache_configs")]
+    pub cache_configs: bool,
+
     #[serde(default = "default_word_separators")]
-    pub word_separators: Vec<char>,  // TODO: add parsing test
+    pub word_separators: Vec<char>,
+
+    // Multi-character word separators, such as ", ". The single char `word_separators`
+    // take precedence: these are only consulted when the current char is not a separator
+    // by itself, and match when the last typed chars end with one of the strings.
+    // They are only handled when leading a word match: the first chars of the separator
+    // already break the match of a preceding word trigger, so they can't end it.
+    #[serde(default = "default_word_separator_strings")]
+    pub word_separator_strings: Vec<String>,
 
     #[serde(default = "default_toggle_key")]
     pub toggle_key: KeyModifier,
@@ -109,6 +181,12 @@ pub struct Configs {
     #[serde(default = "default_toggle_interval")]
     pub toggle_interval: u32,
 
+    #[serde(default)]
+    pub toggle_mode: ToggleMode,
+
+    // If true, the clipboard content is saved before pasting a replacement and restored
+    // after restore_clipboard_delay. Only text content can be preserved, the other kinds
+    // of content (such as images) are lost.
     #[serde(default = "default_preserve_clipboard")]
     pub preserve_clipboard: bool,
 
@@ -139,24 +217,134 @@ pub struct Configs {
     #[serde(default = "default_backspace_limit")]
     pub backspace_limit: i32,
 
+    // Maximum number of typed chars kept by the matcher to inspect the partial matches.
+    // Longer triggers still match, but they can't be shown entirely while typed. It also
+    // caps the chars that can be deleted with backspace, regardless of 'backspace_limit'.
+    #[serde(default = "default_max_buffer_size")]
+    pub max_buffer_size: usize,
+
     #[serde(default = "default_restore_clipboard_delay")]
     pub restore_clipboard_delay: i32,
 
     #[serde(default)]
     pub backend: BackendType,
 
+    // Replacements longer than this number of chars are expanded with the Clipboard
+    // backend, regardless of the 'backend' option. If 0, the threshold is disabled.
+    #[serde(default = "default_clipboard_threshold")]
+    pub clipboard_threshold: usize,
+
+    #[serde(default)]
+    pub backspace_mode: BackspaceMode,
+
     #[serde(default = "default_exclude_default_entries")]
     pub exclude_default_entries: bool,
 
+    // If set, a trigger typed right after this char is not expanded, and the char is removed
+    #[serde(default = "default_trigger_escape_char")]
+    pub trigger_escape_char: Option<char>,
+
+    // If true, the matches defined in the default config are discarded, while
+    // the ones of the configs (and packages) having it as parent are kept
+    #[serde(default = "default_clear_default_matches")]
+    pub clear_default_matches: bool,
+
+    // If true, the symlinked directories found in the user and package directories are
+    // scanned as well. Symlink loops are detected and skipped with a warning.
+    #[serde(default = "default_follow_symlinks")]
+    pub follow_symlinks: bool,
+
+    // Glob patterns matched against the names of the package directories. If not empty,
+    // only the packages matching at least one of them are loaded.
+    #[serde(default = "default_enabled_packages")]
+    pub enabled_packages: Vec<String>,
+
+    // Names (or glob patterns) of the package directories that are not loaded, so that
+    // packages can be turned off without uninstalling them. They win over enabled_packages.
+    #[serde(default = "default_disabled_packages")]
+    pub disabled_packages: Vec<String>,
+
+    // If true, the number of expansions of each trigger is saved in the config directory,
+    // so that the unused matches can be found. Otherwise they are only counted in memory.
+    #[serde(default = "default_collect_stats")]
+    pub collect_stats: bool,
+
+    // If true, config files containing unknown fields (for example because of a typo)
+    // are rejected instead of ignoring those fields. Can also be enabled with the
+    // ESPANSO_STRICT environment variable.
+    #[serde(default = "default_strict")]
+    pub strict: bool,
+
+    // If false, espanso doesn't show a notification when toggled
+    // or when the configuration couldn't be reloaded
+    #[serde(default = "default_show_notifications")]
+    pub show_notifications: bool,
+
+    // If true, no expansion happens while a password field is focused. Only
+    // supported on macOS, where it's enabled by default.
+    #[serde(default = "default_disable_in_password_fields")]
+    pub disable_in_password_fields: bool,
+
+    #[serde(default = "default_include")]
+    pub include: Vec<String>,
+
+    #[serde(default = "default_exclude")]
+    pub exclude: Vec<String>,
+
+    // Version requirement on espanso, such as ">=0.5.0", mostly used by packages
+    #[serde(default = "default_espanso_version")]
+    pub espanso_version: Option<String>,
+
+    // Files whose matches and global variables are merged into the config, relative to
+    // the directory of the config file. When two sources define the same trigger (or
+    // variable name), the precedence is decided as follows:
+    //  - a later entry of the imports list overrides the earlier ones
+    //  - the matches defined in the importing file override all the imports
+    // Imported files can't import other files.
+    #[serde(default = "default_imports")]
+    pub imports: Vec<String>,
+
     #[serde(default = "default_matches")]
     pub matches: Vec<Match>,
 
+    // Variables available to every match of the config, including the ones imported from
+    // other files, so that a shared file of imports can define them once. A variable of the
+    // match with the same name takes precedence over the global one.
     #[serde(default = "default_global_vars")]
-    pub global_vars: Vec<MatchVariable>
+    pub global_vars: Vec<MatchVariable>,
+
+    // Flattened into the matches once the config is loaded
+    #[serde(default = "default_groups")]
+    pub groups: Vec<MatchGroup>,
+
+    // Automatically built from the matches once the config is loaded, used by the matcher
+    #[serde(skip)]
+    pub match_index: MatchIndex,
+
+    #[serde(skip)]
+    filter_regexps: FilterRegexps,
+
+    // Fields of the config file that don't correspond to any config field
+    #[serde(skip)]
+    unknown_fields: Vec<String>,
+
+    // Files of the matches defined with 'replace_file', including the imported ones
+    #[serde(skip)]
+    replace_files: Vec<PathBuf>,
 
 }
 
-// Macro used to validate config fields
+// Global fields that specific configs are still allowed to override, for example
+// code editors might need their own word separators. Every other validated field is
+// reserved to the default config. Move a field name here to make it overridable.
+const OVERRIDABLE_FIELDS: &[&str] = &[
+    "log_level",
+    "backend",
+    "word_separators",
+    "word_separator_strings",
+];
+
+// Macro used to validate config fields, the name of the fields that differ from the default are added to the result
 #[macro_export]
 macro_rules! validate_field {
     ($result:expr, $field:expr, $def_value:expr) => {
@@ -165,8 +353,7 @@ macro_rules! validate_field {
             if field_name.starts_with("self.") {
                 field_name = &field_name[5..];  // Remove the 'self.' prefix
             }
-            error!("Validation error, parameter '{}' is reserved and can be only used in the default.yml config file", field_name);
-            $result = false;
+            $result.push(field_name.to_owned());
         }
     };
 }
@@ -176,17 +363,29 @@ impl Configs {
      * Validate the Config instance.
      * It makes sure that user defined config instances do not define
      * attributes reserved to the default config.
+     * Return the names of the reserved fields that were used, if any.
      */
-    fn validate_user_defined_config(&self) -> bool {
-        let mut result = true;
+    fn validate_user_defined_config(&self) -> Vec<String> {
+        let mut result: Vec<String> = Vec::new();
 
-        validate_field!(result, self.config_caching_interval, default_config_caching_interval());
         validate_field!(result, self.log_level, default_log_level());
+        validate_field!(result, self.backend, BackendType::default());
+        validate_field!(result, self.clipboard_threshold, default_clipboard_threshold());
+        validate_field!(result, self.word_separators, default_word_separators());
+        validate_field!(result, self.word_separator_strings, default_word_separator_strings());
+
+        validate_field!(result, self.config_caching_interval, default_config_caching_interval());
+        validate_field!(result, self.cache_configs, default_cache_configs());
         validate_field!(result, self.conflict_check, default_conflict_check());
         validate_field!(result, self.toggle_key, default_toggle_key());
         validate_field!(result, self.toggle_interval, default_toggle_interval());
+        validate_field!(result, self.toggle_mode, ToggleMode::default());
         validate_field!(result, self.backspace_limit, default_backspace_limit());
+        validate_field!(result, self.max_buffer_size, default_max_buffer_size());
+        validate_field!(result, self.ipc_transport, IPCTransport::default());
+        validate_field!(result, self.linux_input_backend, LinuxInputBackend::default());
         validate_field!(result, self.ipc_server_port, default_ipc_server_port());
+        validate_field!(result, self.ipc_secret, default_ipc_secret());
         validate_field!(result, self.use_system_agent, default_use_system_agent());
         validate_field!(result, self.preserve_clipboard, default_preserve_clipboard());
         validate_field!(result, self.passive_match_regex, default_passive_match_regex());
@@ -195,15 +394,193 @@ impl Configs {
         validate_field!(result, self.passive_key, default_passive_key());
         validate_field!(result, self.action_noop_interval, default_action_noop_interval());
         validate_field!(result, self.restore_clipboard_delay, default_restore_clipboard_delay());
+        validate_field!(result, self.clear_default_matches, default_clear_default_matches());
+        validate_field!(result, self.strict, default_strict());
+        validate_field!(result, self.follow_symlinks, default_follow_symlinks());
+        validate_field!(result, self.enabled_packages, default_enabled_packages());
+        validate_field!(result, self.disabled_packages, default_disabled_packages());
+        validate_field!(result, self.collect_stats, default_collect_stats());
+        validate_field!(result, self.show_notifications, default_show_notifications());
+        validate_field!(result, self.disable_in_password_fields, default_disable_in_password_fields());
+        validate_field!(result, self.include, default_include());
+        validate_field!(result, self.exclude, default_exclude());
+
+        result.retain(|field_name| !OVERRIDABLE_FIELDS.contains(&field_name.as_str()));
+
+        for field_name in result.iter() {
+            error!("Validation error, parameter '{}' is reserved and can be only used in the default.yml config file", field_name);
+        }
 
         result
     }
 }
 
+// Regexps of the window filters, each compiled the first time it is evaluated, so
+// that the configs of apps that are never focused don't slow down the startup.
+#[derive(Clone, Debug, Default)]
+struct FilterRegexps {
+    title: RefCell<Option<Option<Regex>>>,
+    class: RefCell<Option<Option<Regex>>>,
+    exec: RefCell<Option<Option<Regex>>>,
+
+    // Number of regexps compiled, used by tests
+    #[cfg(test)]
+    compile_count: std::cell::Cell<usize>,
+}
+
+impl Configs {
+    /// Check if this config applies to the window with the given properties. A config
+    /// without filters (such as the default one) applies to every window, otherwise the
+    /// window must be targeted by at least one of the filters. Filters are regexes, and
+    /// an invalid one never matches.
+    pub fn is_active_for(&self, title: &str, class: &str, exec: &str) -> bool {
+        !self.has_filters() || self.matches_window(Some(title), Some(class), Some(exec))
+    }
+
+    /// Check if any of the filter_title, filter_class or filter_exec fields is defined.
+    pub fn has_filters(&self) -> bool {
+        !self.filter_title.is_empty() || !self.filter_class.is_empty() || !self.filter_exec.is_empty()
+    }
+
+    /// Check if the window with the given properties is targeted by the filters of this config.
+    pub fn matches_window(&self, title: Option<&str>, class: Option<&str>, exec: Option<&str>) -> bool {
+        title.map_or(false, |title| self.matches_title(title)) ||
+            exec.map_or(false, |exec| self.matches_exec(exec)) ||
+            class.map_or(false, |class| self.matches_class(class))
+    }
+
+    /// Rank of the most specific filter of the config, used to order the configs
+    /// targeting the same window: exec > class > title > none.
+    fn filter_specificity(&self) -> u8 {
+        if !self.filter_exec.is_empty() {
+            3
+        }else if !self.filter_class.is_empty() {
+            2
+        }else if !self.filter_title.is_empty() {
+            1
+        }else{
+            0
+        }
+    }
+
+    pub fn matches_title(&self, title: &str) -> bool {
+        self.title_regex().as_ref().map_or(false, |regex| regex.is_match(title))
+    }
+
+    pub fn matches_class(&self, class: &str) -> bool {
+        self.class_regex().as_ref().map_or(false, |regex| regex.is_match(class))
+    }
+
+    pub fn matches_exec(&self, exec: &str) -> bool {
+        self.exec_regex().as_ref().map_or(false, |regex| regex.is_match(exec))
+    }
+
+    fn title_regex(&self) -> Ref<'_, Option<Regex>> {
+        self.filter_regex(&self.filter_regexps.title, &self.filter_title, "filter_title")
+    }
+
+    fn class_regex(&self) -> Ref<'_, Option<Regex>> {
+        self.filter_regex(&self.filter_regexps.class, &self.filter_class, "filter_class")
+    }
+
+    fn exec_regex(&self) -> Ref<'_, Option<Regex>> {
+        self.filter_regex(&self.filter_regexps.exec, &self.filter_exec, "filter_exec")
+    }
+
+    fn filter_regex<'a>(&self, cache: &'a RefCell<Option<Option<Regex>>>, filter: &str, field_name: &str) -> Ref<'a, Option<Regex>> {
+        if cache.borrow().is_none() {
+            let regex = if filter.is_empty() {
+                None
+            }else{
+                #[cfg(test)]
+                {
+                    self.filter_regexps.compile_count.set(self.filter_regexps.compile_count.get() + 1);
+                }
+
+                let res = Regex::new(filter);
+                if let Ok(regex) = res {
+                    Some(regex)
+                }else{
+                    warn!("Invalid regex in '{}' field of configuration {}, ignoring it...", field_name, self.name);
+                    None
+                }
+            };
+
+            *cache.borrow_mut() = Some(regex);
+        }
+
+        Ref::map(cache.borrow(), |regex| regex.as_ref().unwrap())
+    }
+}
+
+impl Configs {
+    /// Build the trigger index of the matches of this config.
+    pub fn build_match_index(&self) -> MatchIndex {
+        MatchIndex::new(&self.matches)
+    }
+
+    /// Return the number of chars of the longest trigger that can be typed, used to size
+    /// the buffer of the matcher. It's computed once, when the match index is built.
+    pub fn max_trigger_length(&self) -> usize {
+        self.match_index.max_trigger_length()
+    }
+
+    /*
+     * Make sure the running espanso version satisfies the 'espanso_version'
+     * requirement of the config, if present. An unparsable requirement is
+     * considered unmet.
+     */
+    fn check_espanso_version(&self, path: &Path) -> Result<(), ConfigLoadError> {
+        if let Some(requirement) = &self.espanso_version {
+            let current_version = Version::parse(env!("CARGO_PKG_VERSION")).unwrap();
+            let satisfied = match VersionReq::parse(requirement) {
+                Ok(version_req) => version_req.matches(&current_version),
+                Err(_) => false,
+            };
+
+            if !satisfied {
+                return Err(ConfigLoadError::IncompatibleVersion(path.to_owned(), requirement.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Map the numeric 'log_level' option to the corresponding log level:
+    /// 0 (or less) is Warn, 1 is Info, 2 is Debug and 3 (or more) is Trace.
+    pub fn level_filter(&self) -> LevelFilter {
+        match self.log_level {
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            level if level >= 3 => LevelFilter::Trace,
+            _ => LevelFilter::Warn,
+        }
+    }
+}
+
+// Determines how the toggle key has to be pressed to enable or disable espanso.
+// With DoublePress, the two presses must happen within 'toggle_interval' milliseconds.
+// With SinglePress, every press of the key toggles espanso, including the ones
+// made as part of a shortcut (such as ALT+TAB).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ToggleMode {
+    SinglePress,
+    DoublePress,
+}
+impl Default for ToggleMode {
+    fn default() -> Self {
+        ToggleMode::DoublePress
+    }
+}
+
+// With Auto, the replacement is injected and, if the injection fails, pasted
+// through the clipboard. Platforms that can't detect a failed injection never
+// fall back, so Auto behaves like Inject on them.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BackendType {
     Inject,
-    Clipboard
+    Clipboard,
+    Auto,
 }
 impl Default for BackendType {
     // The default backend varies based on the operating system.
@@ -224,8 +601,75 @@ impl Default for BackendType {
     }
 }
 
+// Determines how the chars of a trigger are counted when deleting it.
+// Some applications remove a whole grapheme cluster (such as a ZWJ emoji sequence)
+// with a single backspace, while others remove a single codepoint at a time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackspaceMode {
+    Codepoint,
+    Grapheme
+}
+impl Default for BackspaceMode {
+    fn default() -> Self {
+        BackspaceMode::Codepoint
+    }
+}
+
+// Determines how the daemon and the client commands communicate.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IPCTransport {
+    Tcp,
+    Unix,
+    Pipe,
+}
+
+// WINDOWS DEFAULT
+#[cfg(target_os = "windows")]
+impl Default for IPCTransport {
+    fn default() -> Self {
+        IPCTransport::Tcp
+    }
+}
+
+// UNIX DEFAULT
+#[cfg(not(target_os = "windows"))]
+impl Default for IPCTransport {
+    fn default() -> Self {
+        IPCTransport::Unix
+    }
+}
+
+// Determines how keystrokes are injected on Linux. X11 uses libxdo and supports every
+// feature, but under Wayland it only reaches the XWayland applications. Wayland uses the
+// 'wtype' tool (which must be installed), supporting text, key presses and shortcuts, but
+// not the detection of terminals, so the Default paste shortcut is always CTRL+V.
+// With Auto, the backend is chosen based on the type of the current session.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinuxInputBackend {
+    Auto,
+    X11,
+    Wayland,
+}
+impl Default for LinuxInputBackend {
+    fn default() -> Self {
+        LinuxInputBackend::Auto
+    }
+}
+
 impl Configs {
     fn load_config(path: &Path) -> Result<Configs, ConfigLoadError> {
+        let mut config = Configs::parse_config_file(path)?;
+
+        let config_dir = path.parent().unwrap_or(Path::new(""));
+        config.resolve_imports(config_dir)?;
+
+        Ok(config)
+    }
+
+    fn parse_config_file(path: &Path) -> Result<Configs, ConfigLoadError> {
         let file_res = File::open(path);
         if let Ok(mut file) = file_res {
             let mut contents = String::new();
@@ -235,696 +679,4152 @@ impl Configs {
                 return Err(ConfigLoadError::UnableToReadFile)
             }
 
-            let config_res = serde_yaml::from_str(&contents);
+            let mut value: serde_yaml::Value = serde_yaml::from_str(&contents)
+                .map_err(|e| ConfigLoadError::InvalidYAML(path.to_owned(), e))?;
+            interpolate_config_values(&mut value)
+                .map_err(|name| ConfigLoadError::UndefinedVariable(path.to_owned(), name))?;
 
-            match config_res {
-                Ok(config) => Ok(config),
-                Err(e) => {
-                    Err(ConfigLoadError::InvalidYAML(path.to_owned(), e.to_string()))
-                }
-            }
+            let config_dir = path.parent().unwrap_or(Path::new(""));
+            let mut config = Configs::from_yaml_value(value, config_dir)
+                .map_err(|e| ConfigLoadError::InvalidYAML(path.to_owned(), e))?;
+            config.set_source_file(path);
+
+            Ok(config)
         }else{
             Err(ConfigLoadError::FileNotFound)
         }
     }
 
-    fn merge_config(&mut self, new_config: Configs) {
-        // Merge matches
-        let mut merged_matches = new_config.matches;
-        let mut match_trigger_set = HashSet::new();
-        merged_matches.iter().for_each(|m| {
-            match_trigger_set.insert(m.trigger.clone());
-        });
-        let parent_matches : Vec<Match> = self.matches.iter().filter(|&m| {
-            !match_trigger_set.contains(&m.trigger)
-        }).cloned().collect();
-
-        merged_matches.extend(parent_matches);
-        self.matches = merged_matches;
-
-        // Merge global variables
-        let mut merged_global_vars = new_config.global_vars;
-        let mut vars_name_set = HashSet::new();
-        merged_global_vars.iter().for_each(|m| {
-            vars_name_set.insert(m.name.clone());
-        });
-        let parent_vars : Vec<MatchVariable> = self.global_vars.iter().filter(|&m| {
-            !vars_name_set.contains(&m.name)
-        }).cloned().collect();
-
-        merged_global_vars.extend(parent_vars);
-        self.global_vars = merged_global_vars;
+    fn set_source_file(&mut self, path: &Path) {
+        for m in self.matches.iter_mut() {
+            m.source_file = Some(path.to_owned());
+        }
     }
 
-    fn merge_default(&mut self, default: &Configs) {
-        // Merge matches
-        let mut match_trigger_set = HashSet::new();
-        self.matches.iter().for_each(|m| {
-            match_trigger_set.insert(m.trigger.clone());
-        });
-        let default_matches : Vec<Match> = default.matches.iter().filter(|&m| {
-            !match_trigger_set.contains(&m.trigger)
-        }).cloned().collect();
-
-        self.matches.extend(default_matches);
-
-        // Merge global variables
-        let mut vars_name_set = HashSet::new();
-        self.global_vars.iter().for_each(|m| {
-            vars_name_set.insert(m.name.clone());
-        });
-        let default_vars : Vec<MatchVariable> = default.global_vars.iter().filter(|&m| {
-            !vars_name_set.contains(&m.name)
-        }).cloned().collect();
-
-        self.global_vars.extend(default_vars);
-
+    /// Return the paths of the files imported by the config, stored in the given directory.
+    fn import_paths(&self, config_dir: &Path) -> Vec<PathBuf> {
+        self.imports.iter().map(|import| config_dir.join(import)).collect()
     }
-}
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct ConfigSet {
-    pub default: Configs,
-    pub specific: Vec<Configs>,
-}
-
-impl ConfigSet {
-    pub fn load(config_dir: &Path, package_dir: &Path) -> Result<ConfigSet, ConfigLoadError> {
-        if !config_dir.is_dir() {
-            return Err(ConfigLoadError::InvalidConfigDirectory)
-        }
 
-        // Load default configuration
-        let default_file = config_dir.join(DEFAULT_CONFIG_FILE_NAME);
-        let default = Configs::load_config(default_file.as_path())?;
-
-        // Analyze which config files has to be loaded
-
-        let mut target_files = Vec::new();
+    /// Merge the matches and global variables of the imported files, folding them in
+    /// order with the `merge_config` semantics, so that later imports override the earlier
+    /// ones. The entries defined in the config itself are then merged on top of them.
+    fn resolve_imports(&mut self, config_dir: &Path) -> Result<(), ConfigLoadError> {
+        let mut imported: Option<Configs> = None;
+        for path in self.import_paths(config_dir) {
+            let config = Configs::parse_config_file(&path).map_err(|e| {
+                error!("Unable to import {:?}: {}", path, e);
+                e
+            })?;
+            self.replace_files.extend(config.replace_files.iter().cloned());
+
+            if !config.imports.is_empty() {
+                warn!("Imported file {:?} can't import other files, its imports will be ignored", path);
+            }
 
-        let specific_dir = config_dir.join(USER_CONFIGS_FOLDER_NAME);
-        if specific_dir.exists() {
-            let dir_entry = WalkDir::new(specific_dir);
-            target_files.extend(dir_entry);
+            imported = Some(match imported {
+                Some(mut merged) => {
+                    merged.merge_config(config);
+                    merged
+                },
+                None => config,
+            });
         }
 
-        if package_dir.exists() {
-            let dir_entry = WalkDir::new(package_dir);
-            target_files.extend(dir_entry);
+        if let Some(mut imported) = imported {
+            // Swap the entries, so that the ones of the config are merged on top of the imported ones
+            std::mem::swap(&mut self.matches, &mut imported.matches);
+            std::mem::swap(&mut self.global_vars, &mut imported.global_vars);
+            self.merge_config(imported);
         }
 
-        // Load the user defined config files
-
-        let mut name_set = HashSet::new();
-        let mut children_map: HashMap<String, Vec<Configs>> = HashMap::new();
-        let mut root_configs = Vec::new();
-        root_configs.push(default);
+        Ok(())
+    }
 
-        for entry in target_files {
-            if let Ok(entry) = entry {
-                let path = entry.path();
+    /// Parse the config from the given YAML document, the replace files of the
+    /// matches are resolved relative to the given directory.
+    fn from_yaml_value(value: serde_yaml::Value, config_dir: &Path) -> Result<Configs, serde_yaml::Error> {
+        let unknown_fields = find_unknown_fields(&value);
 
-                // Skip non-yaml config files
-                if path.extension().unwrap_or_default().to_str().unwrap_or_default() != "yml" {
-                    continue;
-                }
+        let mut config: Configs = serde_yaml::from_value(value)?;
+        config.prepare(unknown_fields, config_dir)?;
 
-                let mut config = Configs::load_config(&path)?;
+        Ok(config)
+    }
 
-                // Make sure the config does not contain reserved fields
-                if !config.validate_user_defined_config() {
-                    return Err(ConfigLoadError::InvalidParameter(path.to_owned()))
-                }
+    // Complete the parsed config, flattening the groups and loading the replace files
+    fn prepare(&mut self, unknown_fields: Vec<String>, config_dir: &Path) -> Result<(), serde_yaml::Error> {
+        self.flatten_groups()?;
+        self.unknown_fields = unknown_fields;
+        self.retain_os_matches(std::env::consts::OS);
+        self.replace_files = self.matches.iter_mut().filter_map(|m| m.load_replace_file(config_dir)).collect();
 
-                // No name specified, defaulting to the path name
-                if config.name == "default" {
-                    config.name = path.to_str().unwrap_or_default().to_owned();
-                }
+        Ok(())
+    }
 
-                if name_set.contains(&config.name) {
-                    return Err(ConfigLoadError::NameDuplicate(path.to_owned()));
-                }
+    /// Serialize the config to YAML, in a form that can be loaded again. The fields computed
+    /// at load time, such as the match index, are not included.
+    pub fn to_yaml(&self) -> String {
+        match serde_yaml::to_string(self) {
+            Ok(yaml) => yaml,
+            Err(e) => {
+                error!("Unable to serialize the config '{}': {}", self.name, e);
+                String::new()
+            },
+        }
+    }
 
-                name_set.insert(config.name.clone());
+    /// Drop the matches that are not available on the given platform.
+    fn retain_os_matches(&mut self, os: &str) {
+        self.matches.retain(|m| m.is_available_on(os));
+    }
 
-                if config.parent == "self" {  // No parent, root config
-                    root_configs.push(config);
-                }else{  // Children config
-                    let children_vec = children_map.entry(config.parent.clone()).or_default();
-                    children_vec.push(config);
-                }
-            }else{
-                eprintln!("Warning: Unable to read config file: {}", entry.unwrap_err())
+    /// Check the fields of the config file, rejecting the unknown ones in strict mode
+    /// and warning about them otherwise.
+    fn check_unknown_fields(&self, path: &Path, strict: bool, warnings: &mut Vec<ConfigWarning>) -> Result<(), ConfigLoadError> {
+        if let Some(field) = self.unknown_fields.first() {
+            if strict {
+                return Err(ConfigLoadError::UnknownField(path.to_owned(), field.clone()));
             }
-        }
 
-        // Merge the children config files
-        let mut configs = Vec::new();
-        for root_config in root_configs {
-            let config = ConfigSet::reduce_configs(root_config, &children_map);
-            configs.push(config);
+            for field in self.unknown_fields.iter() {
+                warnings.push(ConfigWarning::UnknownField(path.to_owned(), field.clone()));
+            }
         }
 
-        // Separate default from specific
-        let default= configs.get(0).unwrap().clone();
-        let mut specific = (&configs[1..]).to_vec().clone();
+        Ok(())
+    }
 
-        // Add default entries to specific configs when needed
-        for config in specific.iter_mut() {
-            if !config.exclude_default_entries {
-                config.merge_default(&default);
+    /// Check the matches of the config file, rejecting the empty triggers, as they would
+    /// fire constantly, and warning about the replacements that would expand to nothing.
+    fn check_empty_matches(&self, path: &Path, warnings: &mut Vec<ConfigWarning>) -> Result<(), ConfigLoadError> {
+        for m in self.matches.iter() {
+            if m.triggers().iter().any(|trigger| trigger.is_empty()) {
+                return Err(ConfigLoadError::EmptyTrigger(path.to_owned()));
             }
-        }
 
-        // Check if some triggers are conflicting with each other
-        // For more information, see: https://github.com/federico-terzi/espanso/issues/135
-        if default.conflict_check {
-            for s in specific.iter() {
-                let has_conflicts = Self::has_conflicts(&default, &specific);
-                if has_conflicts {
-                    eprintln!("Warning: some triggers had conflicts and may not behave as intended");
-                    eprintln!("To turn off this check, add \"conflict_check: false\" in the configuration");
+            // Image matches have no text, while vars and hotkeys make the match useful anyway
+            if let MatchContentType::Text(content) = &m.content {
+                if content.replace.is_empty() && content.replace_file.is_none() &&
+                    content.vars.is_empty() && m.hotkey.is_none() {
+                    warnings.push(ConfigWarning::EmptyReplacement(path.to_owned(), m.trigger().to_owned()));
                 }
             }
         }
 
-        Ok(ConfigSet {
-            default,
-            specific
-        })
+        Ok(())
     }
 
-    fn reduce_configs(target: Configs, children_map: &HashMap<String, Vec<Configs>>) -> Configs {
-        if children_map.contains_key(&target.name) {
-            let mut target = target;
-            for children in children_map.get(&target.name).unwrap() {
-                let children = Self::reduce_configs(children.clone(), children_map);
-                target.merge_config(children);
+    /// Make sure the labels of the matches are unique, as tools use them to address the matches.
+    /// The matches reusing a label are rejected in strict mode, and skipped with a warning otherwise.
+    /// As the matches of a child config come first once merged, they win over the parent ones.
+    fn remove_duplicate_labels(&mut self, strict: bool, warnings: &mut Vec<ConfigWarning>) -> Result<(), ConfigLoadError> {
+        let mut labels = HashSet::new();
+        let mut duplicates = Vec::new();
+        self.matches.retain(|m| {
+            match &m.label {
+                Some(label) if !labels.insert(label.clone()) => {
+                    duplicates.push(label.clone());
+                    false
+                },
+                _ => true,
             }
-            target
-        }else{
-            target
-        }
-    }
-
-    pub fn load_default() -> Result<ConfigSet, ConfigLoadError> {
-        // Configuration related
-
-        let config_dir = crate::context::get_config_dir();
+        });
 
-        let default_file = config_dir.join(DEFAULT_CONFIG_FILE_NAME);
+        for label in duplicates {
+            if strict {
+                return Err(ConfigLoadError::DuplicateLabel(self.name.clone(), label));
+            }
 
-        // If config file does not exist, create one from template
-        if !default_file.exists() {
-            let result = fs::write(&default_file, DEFAULT_CONFIG_FILE_CONTENT);
-            if result.is_err() {
-                return Err(ConfigLoadError::UnableToCreateDefaultConfig)
+            let warning = ConfigWarning::DuplicateLabel(self.name.clone(), label);
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
             }
         }
 
-        // Create auxiliary directories
+        Ok(())
+    }
 
-        let user_config_dir = config_dir.join(USER_CONFIGS_FOLDER_NAME);
-        if !user_config_dir.exists() {
-            let res = create_dir_all(user_config_dir.as_path());
-            if res.is_err() {
-                return Err(ConfigLoadError::UnableToCreateDefaultConfig)
+    /// Check all the fields that are compiled or parsed lazily (such as the filter regexes
+    /// and the date formats), returning a description of each invalid one.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        for (field_name, regex) in vec![("filter_title", &self.filter_title), ("filter_class", &self.filter_class),
+                                        ("filter_exec", &self.filter_exec), ("passive_match_regex", &self.passive_match_regex)] {
+            if !regex.is_empty() {
+                if let Err(e) = Regex::new(regex) {
+                    errors.push(format!("invalid regex in '{}': {}", field_name, e));
+                }
             }
         }
 
+        errors.extend(self.global_vars.iter().flat_map(MatchVariable::validate)
+            .map(|error| format!("global var: {}", error)));
+        errors.extend(self.matches.iter().flat_map(Match::validate));
 
-        // Packages
+        errors
+    }
 
-        let package_dir = crate::context::get_package_dir();
-        let res = create_dir_all(package_dir.as_path());
-        if res.is_err() {
-            return Err(ConfigLoadError::UnableToCreateDefaultConfig)  // TODO: change error type
+    fn check_validity(&self, path: &Path) -> Result<(), ConfigLoadError> {
+        let errors = self.validate();
+        if !errors.is_empty() {
+            return Err(ConfigLoadError::ValidationFailed(path.to_owned(), errors));
         }
 
-        return ConfigSet::load(config_dir.as_path(), package_dir.as_path());
+        Ok(())
     }
 
-    fn has_conflicts(default: &Configs, specific: &Vec<Configs>) -> bool {
-        let mut sorted_triggers : Vec<String> = default.matches.iter().map(|t| {
-            t.trigger.clone()
-        }).collect();
-        sorted_triggers.sort();
-
-        let mut has_conflicts = Self::list_has_conflicts(&sorted_triggers);
-
-        for s in specific.iter() {
-            let mut specific_triggers : Vec<String> = s.matches.iter().map(|t| {
-                t.trigger.clone()
-            }).collect();
-            specific_triggers.sort();
-            has_conflicts |= Self::list_has_conflicts(&specific_triggers);
+    /// Move the matches of the groups after the other matches, applying the group options.
+    fn flatten_groups(&mut self) -> Result<(), serde_yaml::Error> {
+        let groups = std::mem::replace(&mut self.groups, Vec::new());
+        for group in groups {
+            let group_matches = group.into_matches()?;
+            self.matches.extend(group_matches);
         }
 
-        has_conflicts
+        Ok(())
     }
 
-    fn list_has_conflicts(sorted_list: &Vec<String>) -> bool {
-        if sorted_list.len() <= 1 {
-            return false
-        }
+    fn merge_config(&mut self, new_config: Configs) {
+        // Merge matches, the parent ones are moved instead of being cloned
+        let mut merged_matches = new_config.matches;
+        Self::merge_with_parent_matches(&mut merged_matches, &self.matches);
+        let parent_matches : Vec<Match> = {
+            let match_trigger_set: HashSet<&str> = merged_matches.iter()
+                .flat_map(|m| m.triggers().iter().map(String::as_str)).collect();
+            std::mem::replace(&mut self.matches, Vec::new()).into_iter().filter(|m| {
+                !m.has_any_trigger(&match_trigger_set)
+            }).collect()
+        };
 
-        let mut has_conflicts = false;
+        merged_matches.extend(parent_matches);
+        self.matches = merged_matches;
 
-        for (i, item) in sorted_list.iter().skip(1).enumerate() {
-            let previous = &sorted_list[i];
-            if item.starts_with(previous) {
-                has_conflicts = true;
-                eprintln!("Warning: trigger '{}' is conflicting with '{}' and may not behave as intended", item, previous);
+        // Merge global variables
+        let mut merged_global_vars = new_config.global_vars;
+        let parent_vars : Vec<MatchVariable> = {
+            let vars_name_set: HashSet<&str> = merged_global_vars.iter().map(|v| v.name.as_str()).collect();
+            std::mem::replace(&mut self.global_vars, Vec::new()).into_iter().filter(|v| {
+                !vars_name_set.contains(v.name.as_str())
+            }).collect()
+        };
+
+        merged_global_vars.extend(parent_vars);
+        self.global_vars = merged_global_vars;
+    }
+
+    // Combine the matches overriding a parent one, according to their merge strategy.
+    // The parent matches are discarded afterwards by the caller, as with Replace.
+    fn merge_with_parent_matches(matches: &mut Vec<Match>, parent_matches: &[Match]) {
+        for m in matches.iter_mut().filter(|m| m.merge != MergeStrategy::Replace) {
+            let parent = parent_matches.iter().find(|parent| {
+                parent.triggers().iter().any(|trigger| m.triggers().contains(trigger))
+            });
+            if let Some(parent) = parent {
+                m.merge_with_parent(parent);
             }
         }
-
-        has_conflicts
     }
-}
 
-pub trait ConfigManager<'a> {
-    fn active_config(&'a self) -> &'a Configs;
-    fn default_config(&'a self) -> &'a Configs;
-    fn matches(&'a self) -> &'a Vec<Match>;
-}
+    fn merge_default(&mut self, default: &Configs) {
+        // Merge matches, the default ones are shared by many configs, so they must be cloned
+        Self::merge_with_parent_matches(&mut self.matches, &default.matches);
+        let default_matches : Vec<Match> = {
+            let match_trigger_set: HashSet<&str> = self.matches.iter()
+                .flat_map(|m| m.triggers().iter().map(String::as_str)).collect();
+            default.matches.iter().filter(|&m| {
+                !m.has_any_trigger(&match_trigger_set)
+            }).cloned().collect()
+        };
 
-// Error handling
-#[derive(Debug, PartialEq)]
-pub enum ConfigLoadError {
-    FileNotFound,
-    UnableToReadFile,
-    InvalidYAML(PathBuf, String),
-    InvalidConfigDirectory,
-    InvalidParameter(PathBuf),
-    NameDuplicate(PathBuf),
-    UnableToCreateDefaultConfig,
+        self.matches.extend(default_matches);
+
+        // Merge global variables
+        let default_vars : Vec<MatchVariable> = {
+            let vars_name_set: HashSet<&str> = self.global_vars.iter().map(|v| v.name.as_str()).collect();
+            default.global_vars.iter().filter(|&v| {
+                !vars_name_set.contains(v.name.as_str())
+            }).cloned().collect()
+        };
+
+        self.global_vars.extend(default_vars);
+    }
 }
 
-impl fmt::Display for ConfigLoadError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ConfigLoadError::FileNotFound =>  write!(f, "File not found"),
-            ConfigLoadError::UnableToReadFile =>  write!(f, "Unable to read config file"),
-            ConfigLoadError::InvalidYAML(path, e) => write!(f, "Error parsing YAML file '{}', invalid syntax: {}", path.to_str().unwrap_or_default(), e),
-            ConfigLoadError::InvalidConfigDirectory =>  write!(f, "Invalid config directory"),
-            ConfigLoadError::InvalidParameter(path) =>  write!(f, "Invalid parameter in '{}', use of reserved parameters in used defined configs is not permitted", path.to_str().unwrap_or_default()),
-            ConfigLoadError::NameDuplicate(path) =>  write!(f, "Found duplicate 'name' in '{}', please use different names", path.to_str().unwrap_or_default()),
-            ConfigLoadError::UnableToCreateDefaultConfig =>  write!(f, "Could not generate default config file"),
+/// Glob pattern used to select which config files should be loaded.
+/// Patterns containing a '/' are matched against the path relative to the scanned
+/// directory, while the others are matched against the file name only.
+struct GlobPattern {
+    regex: Regex,
+    match_file_name: bool,
+}
+
+impl GlobPattern {
+    fn new(pattern: &str) -> GlobPattern {
+        let mut regex_str = String::from("^");
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' => {
+                    if chars.peek() == Some(&'*') {  // Double star, match across directories
+                        chars.next();
+                        if chars.peek() == Some(&'/') {
+                            chars.next();
+                            regex_str.push_str("(?:.*/)?");
+                        }else{
+                            regex_str.push_str(".*");
+                        }
+                    }else{
+                        regex_str.push_str("[^/]*");
+                    }
+                },
+                '?' => {
+                    regex_str.push_str("[^/]");
+                },
+                _ => {
+                    regex_str.push_str(&regex::escape(&c.to_string()));
+                },
+            }
+        }
+        regex_str.push('$');
+
+        GlobPattern {
+            // All the special characters are escaped, so the regex is always valid
+            regex: Regex::new(&regex_str).unwrap(),
+            match_file_name: !pattern.contains('/'),
+        }
+    }
+
+    fn is_match(&self, relative_path: &Path) -> bool {
+        if self.match_file_name {
+            let file_name = relative_path.file_name().unwrap_or_default().to_string_lossy();
+            self.regex.is_match(&file_name)
+        }else{
+            // Normalize the separators, so that the same patterns work on Windows
+            let components: Vec<String> = relative_path.components().map(|c| {
+                c.as_os_str().to_string_lossy().into_owned()
+            }).collect();
+            self.regex.is_match(&components.join("/"))
         }
     }
 }
 
-impl Error for ConfigLoadError {
-    fn description(&self) -> &str {
-        match self {
-            ConfigLoadError::FileNotFound => "File not found",
-            ConfigLoadError::UnableToReadFile => "Unable to read config file",
-            ConfigLoadError::InvalidYAML(_, _) => "Error parsing YAML file, invalid syntax",
-            ConfigLoadError::InvalidConfigDirectory => "Invalid config directory",
-            ConfigLoadError::InvalidParameter(_) => "Invalid parameter, use of reserved parameters in user defined configs is not permitted",
-            ConfigLoadError::NameDuplicate(_) => "Found duplicate 'name' in some configurations, please use different names",
-            ConfigLoadError::UnableToCreateDefaultConfig => "Could not generate default config file",
+/// In-memory cache of the parsed config files, used to avoid parsing again
+/// the files that didn't change since the last load.
+/// Entries are keyed by path and invalidated when the modification time changes,
+/// the ones of the files that are no longer loaded are evicted after each load.
+#[derive(Default)]
+pub struct ConfigCache {
+    entries: HashMap<PathBuf, (SystemTime, Configs)>,
+
+    // Files requested since the last eviction
+    used: HashSet<PathBuf>,
+
+    // Number of times the YAML parser has been invoked, used by tests
+    #[cfg(test)]
+    parse_count: usize,
+}
+
+impl ConfigCache {
+    pub fn new() -> ConfigCache {
+        ConfigCache::default()
+    }
+
+    fn load_config(&mut self, path: &Path) -> Result<Configs, ConfigLoadError> {
+        self.used.insert(path.to_owned());
+        let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+
+        if let Some(modified) = modified {
+            if let Some((cached_modified, config)) = self.entries.get(path) {
+                // The imported files could have changed, even if the config file didn't
+                if *cached_modified == modified && config.imports.is_empty() {
+                    return Ok(config.clone());
+                }
+            }
+        }
+
+        #[cfg(test)]
+        {
+            self.parse_count += 1;
+        }
+
+        let config = Configs::load_config(path)?;
+
+        if let Some(modified) = modified {
+            self.entries.insert(path.to_owned(), (modified, config.clone()));
         }
+
+        Ok(config)
+    }
+
+    // Remove the entries of the files that were not requested since the last call
+    fn evict_unused(&mut self) {
+        let used = std::mem::replace(&mut self.used, HashSet::new());
+        self.entries.retain(|path, _| used.contains(path));
     }
 }
 
+// Window with fixed properties, used to select the config of a window that is not focused
+struct WindowProperties<'a> {
+    title: &'a str,
+    class: &'a str,
+    exec: &'a str,
+}
 
+impl <'a> SystemManager for WindowProperties<'a> {
+    fn get_current_window_title(&self) -> Option<String> {
+        Some(self.title.to_owned())
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Write;
-    use tempfile::{NamedTempFile, TempDir};
-    use std::any::Any;
-    use crate::matcher::{TextContent, MatchContentType};
+    fn get_current_window_class(&self) -> Option<String> {
+        Some(self.class.to_owned())
+    }
 
-    const TEST_WORKING_CONFIG_FILE : &str = include_str!("../res/test/working_config.yml");
-    const TEST_CONFIG_FILE_WITH_BAD_YAML : &str = include_str!("../res/test/config_with_bad_yaml.yml");
+    fn get_current_window_executable(&self) -> Option<String> {
+        Some(self.exec.to_owned())
+    }
+}
 
-    // Test Configs
+// Parsed ConfigSet persisted on disk, along with the modification times of the
+// source files and the values of the environment variables they reference, used
+// to detect when it becomes stale. The warnings are logged again on each hit.
+// The cache is only valid for the espanso version that wrote it.
+#[derive(Serialize, Deserialize)]
+struct ConfigSetDiskCache {
+    version: String,
+    sources: Vec<(PathBuf, SystemTime)>,
+    env_vars: Vec<(String, Option<String>)>,
+    warnings: Vec<ConfigWarning>,
+    config_set: ConfigSet,
+}
 
-    fn create_tmp_file(string: &str) -> NamedTempFile {
-        let file = NamedTempFile::new().unwrap();
-        file.as_file().write_all(string.as_bytes());
-        file
+// Merged configs of a ConfigSet, see ConfigSet::export_effective
+#[derive(Serialize)]
+struct EffectiveConfigSet<'a> {
+    default: &'a Configs,
+    specific: &'a [Configs],
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConfigSet {
+    pub default: Configs,
+    pub specific: Vec<Configs>,
+
+    #[serde(default)]
+    packages: Vec<PackageManifest>,
+
+    // Files imported by the configs and read by their matches with 'replace_file',
+    // used to detect when the disk cache becomes stale
+    #[serde(default)]
+    imported_files: Vec<PathBuf>,
+}
+
+// Result of ConfigSet::explain, describing where the expansion of a trigger comes from
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpansionExplanation {
+    pub matched: Match,
+    pub source_file: Option<PathBuf>,
+    pub config_name: String,
+}
+
+// Metadata of a package, defined in the optional _manifest.yml file of the package directory.
+// The packages without a manifest, or without a name in it, are named after their directory.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PackageManifest {
+    #[serde(default)]
+    pub name: String,
+
+    #[serde(default)]
+    pub version: String,
+
+    #[serde(default)]
+    pub author: String,
+
+    #[serde(default)]
+    pub description: String,
+}
+
+impl PackageManifest {
+    fn from_dir_name(package_path: &Path) -> PackageManifest {
+        PackageManifest {
+            name: package_path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+            version: String::new(),
+            author: String::new(),
+            description: String::new(),
+        }
     }
 
-    fn variant_eq<T>(a: &T, b: &T) -> bool {
-        std::mem::discriminant(a) == std::mem::discriminant(b)
+    fn load(path: &Path) -> Result<PackageManifest, ConfigLoadError> {
+        let content = fs::read_to_string(path)
+            .map_err(|_| ConfigLoadError::UnableToReadFile)?;
+
+        let mut manifest: PackageManifest = serde_yaml::from_str(&content)
+            .map_err(|e| ConfigLoadError::InvalidYAML(path.to_owned(), e))?;
+        if manifest.name.is_empty() {
+            manifest.name = PackageManifest::from_dir_name(path.parent().unwrap_or(path)).name;
+        }
+
+        Ok(manifest)
+    }
+}
+
+lazy_static! {
+    // Names of the fields that can be defined in a config file, taken from the
+    // serialized representation so that they don't need to be listed by hand
+    static ref KNOWN_CONFIG_FIELDS: HashSet<String> = {
+        let config: Configs = serde_yaml::from_str("name: default").unwrap();
+        match serde_yaml::to_value(&config) {
+            Ok(serde_yaml::Value::Mapping(mapping)) => {
+                mapping.into_iter().filter_map(|(key, _)| key.as_str().map(str::to_owned)).collect()
+            },
+            _ => HashSet::new(),
+        }
+    };
+
+    static ref CONFIG_VAR_REGEX: Regex = Regex::new(r"\$\{(?P<name>\w+)(?::-(?P<default>[^}]*))?\}").unwrap();
+}
+
+// Fields whose strings are not interpolated, as replacements could legitimately contain ${...}
+const NOT_INTERPOLATED_FIELDS: &[&str] = &["matches", "global_vars", "groups"];
+
+/// Resolve the ${VAR} and ${VAR:-default} references to environment variables in the top
+/// level fields of the given YAML config, returning the name of the first undefined variable.
+/// A value made only of a reference is parsed again, so that it can hold a number or a boolean.
+fn interpolate_config_values(value: &mut serde_yaml::Value) -> Result<(), String> {
+    let mapping = match value {
+        serde_yaml::Value::Mapping(mapping) => mapping,
+        _ => return Ok(()),
+    };
+
+    for (key, field) in mapping.iter_mut() {
+        if key.as_str().map_or(false, |key| NOT_INTERPOLATED_FIELDS.contains(&key)) {
+            continue;
+        }
+
+        match field {
+            serde_yaml::Value::Sequence(items) => {
+                for item in items.iter_mut() {
+                    interpolate_string_value(item)?;
+                }
+            },
+            _ => interpolate_string_value(field)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn interpolate_string_value(value: &mut serde_yaml::Value) -> Result<(), String> {
+    let resolved = match value.as_str() {
+        Some(text) if CONFIG_VAR_REGEX.is_match(text) => {
+            let mut resolved = String::new();
+            let mut last_end = 0;
+            for caps in CONFIG_VAR_REGEX.captures_iter(text) {
+                let reference = caps.get(0).unwrap();
+                let name = caps.name("name").unwrap().as_str();
+                let var_value = match (std::env::var(name), caps.name("default")) {
+                    (Ok(var_value), _) => var_value,
+                    (Err(_), Some(default)) => default.as_str().to_owned(),
+                    (Err(_), None) => return Err(name.to_owned()),
+                };
+
+                resolved.push_str(&text[last_end..reference.start()]);
+                resolved.push_str(&var_value);
+                last_end = reference.end();
+            }
+            resolved.push_str(&text[last_end..]);
+
+            let is_single_reference = CONFIG_VAR_REGEX.find(text)
+                .map_or(false, |reference| reference.start() == 0 && reference.end() == text.len());
+            match serde_yaml::from_str(&resolved) {
+                Ok(scalar @ serde_yaml::Value::Number(_)) | Ok(scalar @ serde_yaml::Value::Bool(_)) if is_single_reference => scalar,
+                _ => serde_yaml::Value::String(resolved),
+            }
+        },
+        _ => return Ok(()),
+    };
+
+    *value = resolved;
+    Ok(())
+}
+
+/// Return the top level fields of the given YAML config that are not config fields.
+fn find_unknown_fields(value: &serde_yaml::Value) -> Vec<String> {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            mapping.iter().filter_map(|(key, _)| key.as_str().map(str::to_owned))
+                .filter(|key| !KNOWN_CONFIG_FIELDS.contains(key))
+                .collect()
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Return the name of the default config file chosen with the ESPANSO_DEFAULT_CONFIG
+/// environment variable, if any, to switch between profiles without renaming files.
+pub fn default_config_file_name_from_env() -> Option<String> {
+    match std::env::var(DEFAULT_CONFIG_ENV_VAR) {
+        Ok(value) if !value.trim().is_empty() => Some(value.trim().to_owned()),
+        _ => None,
+    }
+}
+
+/// Return the path of the combined config file chosen with the ESPANSO_CONFIG_FILE
+/// environment variable, if any, loaded instead of the files of the config directory.
+pub fn combined_config_file_from_env() -> Option<PathBuf> {
+    match std::env::var(COMBINED_CONFIG_ENV_VAR) {
+        Ok(value) if !value.trim().is_empty() => Some(PathBuf::from(value.trim())),
+        _ => None,
+    }
+}
+
+// Pair the given files with their modification time, skipping the missing ones
+fn modification_times(paths: Vec<PathBuf>) -> Vec<(PathBuf, SystemTime)> {
+    paths.into_iter().filter_map(|path| {
+        let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()?;
+        Some((path, modified))
+    }).collect()
+}
+
+// Pair the environment variables referenced by the given files with their current value.
+// The whole files are searched, including the matches that are not interpolated, as
+// listing more variables than needed only makes the cache stricter.
+fn referenced_env_vars(sources: &[(PathBuf, SystemTime)]) -> Vec<(String, Option<String>)> {
+    let mut names: Vec<String> = Vec::new();
+    for (path, _) in sources.iter() {
+        if let Ok(content) = fs::read_to_string(path) {
+            names.extend(CONFIG_VAR_REGEX.captures_iter(&content).map(|caps| caps["name"].to_owned()));
+        }
+    }
+    names.sort();
+    names.dedup();
+
+    names.into_iter().map(|name| {
+        let value = std::env::var(&name).ok();
+        (name, value)
+    }).collect()
+}
+
+/// Return the path of the default config file in the given directory,
+/// falling back to default.yml if no name is given.
+pub fn default_config_path(config_dir: &Path, default_file_name: Option<&str>) -> PathBuf {
+    config_dir.join(default_file_name.unwrap_or(DEFAULT_CONFIG_FILE_NAME))
+}
+
+/// Return the directory of the profile with the given name.
+pub fn profile_path(config_dir: &Path, name: &str) -> PathBuf {
+    config_dir.join(PROFILES_FOLDER_NAME).join(name)
+}
+
+/// Check if the strict mode was requested with the ESPANSO_STRICT environment variable.
+fn is_strict_env_set() -> bool {
+    match std::env::var("ESPANSO_STRICT") {
+        Ok(value) => !value.is_empty() && value != "0" && value.to_lowercase() != "false",
+        Err(_) => false,
+    }
+}
+
+// Paths of the existing default and user config files of the given config directory
+fn config_file_paths(config_dir: &Path, default_file_name: Option<&str>) -> Vec<PathBuf> {
+    let mut paths = vec![default_config_path(config_dir, default_file_name)];
+
+    let specific_dir = config_dir.join(USER_CONFIGS_FOLDER_NAME);
+    if specific_dir.exists() {
+        paths.extend(WalkDir::new(&specific_dir).into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.path().to_owned())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "yml")));
+    }
+
+    paths.retain(|path| path.is_file());
+    paths
+}
+
+/// Rename the deprecated top level fields of the given YAML config, leaving the
+/// rest of the file (including comments) untouched. Return None if nothing changed.
+fn migrate_fields(content: &str) -> Option<String> {
+    let mut result = content.to_owned();
+
+    // Top level fields share the indentation of the first key in the file
+    let indent: String = content.lines()
+        .find(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|line| line.chars().take_while(|c| *c == ' ' || *c == '\t').collect())
+        .unwrap_or_default();
+
+    for (old_name, new_name) in RENAMED_FIELDS.iter() {
+        let old_regex = Regex::new(&format!(r"(?m)^({}){}([ \t]*):", indent, regex::escape(old_name))).unwrap();
+        if !old_regex.is_match(&result) {
+            continue;
+        }
+
+        // Renaming the field would produce a duplicate key
+        let new_regex = Regex::new(&format!(r"(?m)^{}{}[ \t]*:", indent, regex::escape(new_name))).unwrap();
+        if new_regex.is_match(&result) {
+            warn!("Both '{}' and its deprecated name '{}' are defined, skipping the migration", new_name, old_name);
+            continue;
+        }
+
+        result = old_regex.replace_all(&result, format!("${{1}}{}${{2}}:", new_name).as_str()).into_owned();
+    }
+
+    if result != content {
+        Some(result)
+    }else{
+        None
+    }
+}
+
+impl ConfigSet {
+    // Set made only of the given default config, used by the test config managers
+    #[cfg(test)]
+    pub(crate) fn from_default(default: Configs) -> ConfigSet {
+        ConfigSet {
+            default,
+            specific: Vec::new(),
+            packages: Vec::new(),
+            imported_files: Vec::new(),
+        }
+    }
+
+    /// Load the ConfigSet from the given directories, using the given file name
+    /// as the default config, or default.yml if None.
+    pub fn load(config_dir: &Path, package_dir: &Path, default_file_name: Option<&str>) -> Result<ConfigSet, ConfigLoadError> {
+        ConfigSet::load_with_cache(config_dir, package_dir, default_file_name, &mut ConfigCache::new())
+    }
+
+    /// Load the ConfigSet, skipping the parsing of the files that are already
+    /// present in the given cache and didn't change since then.
+    pub fn load_with_cache(config_dir: &Path, package_dir: &Path, default_file_name: Option<&str>,
+                           cache: &mut ConfigCache) -> Result<ConfigSet, ConfigLoadError> {
+        let mut warnings = Vec::new();
+        let config_set = ConfigSet::load_collecting_warnings(config_dir, package_dir, default_file_name,
+                                                             cache, &mut warnings)?;
+        log_warnings(&warnings);
+
+        Ok(config_set)
+    }
+
+    /// Most verbose log level among the configs of the set, as the specific configs
+    /// can raise the verbosity of the default one. It's applied when the set is loaded.
+    pub fn level_filter(&self) -> LevelFilter {
+        self.specific.iter()
+            .map(Configs::level_filter)
+            .fold(self.default.level_filter(), std::cmp::max)
+    }
+
+    /// Build the trigger index of every config in the set, it must be called
+    /// again if the matches are modified after loading.
+    pub fn build_match_indexes(&mut self) {
+        self.default.match_index = self.default.build_match_index();
+        for config in self.specific.iter_mut() {
+            config.match_index = config.build_match_index();
+        }
+    }
+
+    /// Return the position in `specific` of the config used for the window described by the
+    /// given system manager, or None if the default config should be used. The first config
+    /// matching the title wins, then the executable and finally the class are checked.
+    pub fn active_position(&self, system_manager: &dyn SystemManager) -> Option<usize> {
+        // TODO: optimize performance by avoiding some of these checks if no Configs use the filters
+
+        debug!("Requested config for window:");
+
+        let active_title = system_manager.get_current_window_title();
+
+        if let Some(title) = active_title {
+            debug!("=> Title: '{}'", title);
+
+            for (position, config) in self.specific.iter().enumerate() {
+                if config.matches_title(&title) {
+                    debug!("Matched 'filter_title' for '{}' config, using custom settings.",
+                           config.name);
+
+                    return Some(position)
+                }
+            }
+        }
+
+        let active_executable = system_manager.get_current_window_executable();
+
+        if let Some(executable) = active_executable {
+            debug!("=> Executable: '{}'", executable);
+
+            for (position, config) in self.specific.iter().enumerate() {
+                if config.matches_exec(&executable) {
+                    debug!("Matched 'filter_exec' for '{}' config, using custom settings.",
+                           config.name);
+
+                    return Some(position)
+                }
+            }
+        }
+
+        let active_class = system_manager.get_current_window_class();
+
+        if let Some(class) = active_class {
+            debug!("=> Class: '{}'", class);
+
+            for (position, config) in self.specific.iter().enumerate() {
+                if config.matches_class(&class) {
+                    debug!("Matched 'filter_class' for '{}' config, using custom settings.",
+                           config.name);
+
+                    return Some(position)
+                }
+            }
+        }
+
+        // No matches, return the default mapping
+        debug!("No matches for custom configs, using default settings.");
+        None
+    }
+
+    /// Return the config used for the window with the given properties, selected
+    /// as espanso does at runtime (see active_position).
+    pub fn config_for_window(&self, title: &str, class: &str, exec: &str) -> &Configs {
+        let window = WindowProperties { title, class, exec };
+        match self.active_position(&window) {
+            Some(position) => &self.specific[position],
+            None => &self.default,
+        }
+    }
+
+    /// Return the matches that apply to the window with the given properties, which are
+    /// the ones of the config selected for the window (see config_for_window).
+    pub fn active_matches(&self, title: &str, class: &str, exec: &str) -> Vec<&Match> {
+        self.config_for_window(title, class, exec).matches.iter().collect()
+    }
+
+    /// Merge all the specific configs targeting the window with the given properties on top
+    /// of the default one, from the broadest to the narrowest (see filter_specificity), so that
+    /// the most specific definition of a trigger or a global var wins. Between configs with the
+    /// same specificity, the one with the lower order wins. Only the matches and the global vars
+    /// are merged, the other options are the default ones.
+    pub fn resolve_for_window(&self, title: &str, class: &str, exec: &str) -> Configs {
+        let applicable = self.applicable_configs(title, class, exec);
+
+        let mut resolved = self.default.clone();
+        if applicable.iter().any(|config| config.exclude_default_entries) {
+            resolved.matches.clear();
+            resolved.global_vars.clear();
+        }
+
+        for config in applicable {
+            let mut layer = config.clone();
+
+            // The entries inherited from the default would otherwise override those of the broader configs
+            if !config.exclude_default_entries {
+                layer.matches.retain(|m| !self.default.matches.contains(m));
+                layer.global_vars.retain(|v| !self.default.global_vars.contains(v));
+            }
+
+            resolved.merge_config(layer);
+        }
+
+        resolved.match_index = resolved.build_match_index();
+        resolved
+    }
+
+    // Specific configs targeting the window, in the order they are merged by resolve_for_window
+    fn applicable_configs(&self, title: &str, class: &str, exec: &str) -> Vec<&Configs> {
+        let mut applicable: Vec<(usize, &Configs)> = self.specific.iter().enumerate()
+            .filter(|(_, config)| config.matches_window(Some(title), Some(class), Some(exec)))
+            .collect();
+
+        // The configs merged later win, and the specific ones are already sorted by order
+        applicable.sort_by_key(|(position, config)| (config.filter_specificity(), std::cmp::Reverse(*position)));
+        applicable.into_iter().map(|(_, config)| config).collect()
+    }
+
+    /// Explain which match expands the given trigger in the window with the given properties,
+    /// along with the config and the file defining it, using the config selected for the
+    /// window at runtime (see config_for_window). Return None if no active match has the trigger.
+    pub fn explain(&self, trigger: &str, title: &str, class: &str, exec: &str) -> Option<ExpansionExplanation> {
+        let selected = self.config_for_window(title, class, exec);
+        let m = selected.matches.iter().find(|m| m.triggers().iter().any(|t| t == trigger))?;
+
+        // The specific configs include the default matches, which are reported as such
+        let config = if self.default.matches.contains(m) {
+            &self.default
+        }else{
+            selected
+        };
+
+        Some(ExpansionExplanation {
+            matched: m.clone(),
+            source_file: m.source_file.clone(),
+            config_name: config.name.clone(),
+        })
+    }
+
+    /// Return the matches of all the configs, the default ones first. The matches that the
+    /// specific configs inherit from the default one are listed only once, while the ones
+    /// overriding a default trigger are listed separately, as they apply to other windows.
+    pub fn all_matches(&self) -> Vec<&Match> {
+        let mut matches = Vec::new();
+        for config in std::iter::once(&self.default).chain(self.specific.iter()) {
+            let mut triggers = HashSet::new();
+            for m in config.matches.iter() {
+                let inherited = !std::ptr::eq(config, &self.default) && self.default.matches.contains(m);
+                if !inherited && !m.has_any_trigger(&triggers) {
+                    triggers.extend(m.triggers().iter().map(String::as_str));
+                    matches.push(m);
+                }
+            }
+        }
+
+        matches
+    }
+
+    /// Return the matches annotated with the given tag, across all the configs.
+    pub fn matches_with_tag(&self, tag: &str) -> Vec<&Match> {
+        self.all_matches().into_iter().filter(|m| m.has_tag(tag)).collect()
+    }
+
+    /// Serialize the default and specific configs to YAML, after the parent, default and
+    /// package matches are merged, exactly as they are used by the matcher. Meant for debugging.
+    pub fn export_effective(&self) -> String {
+        let effective = EffectiveConfigSet {
+            default: &self.default,
+            specific: &self.specific,
+        };
+
+        match serde_yaml::to_string(&effective) {
+            Ok(yaml) => yaml,
+            Err(e) => {
+                error!("Unable to export the effective config: {}", e);
+                String::new()
+            },
+        }
+    }
+
+    /// Return the metadata of the loaded packages, sorted by name. Packages without
+    /// a manifest are listed with their directory name and empty metadata.
+    pub fn packages(&self) -> Vec<PackageManifest> {
+        self.packages.clone()
+    }
+
+    /// Same as load, but the non-fatal problems found while loading (such as conflicting
+    /// triggers or unknown fields in lenient mode) are returned instead of being logged.
+    pub fn load_with_warnings(config_dir: &Path, package_dir: &Path, default_file_name: Option<&str>)
+                              -> Result<(ConfigSet, Vec<ConfigWarning>), ConfigLoadError> {
+        let mut warnings = Vec::new();
+        let config_set = ConfigSet::load_collecting_warnings(config_dir, package_dir, default_file_name,
+                                                             &mut ConfigCache::new(), &mut warnings)?;
+
+        Ok((config_set, warnings))
+    }
+
+    fn load_collecting_warnings(config_dir: &Path, package_dir: &Path, default_file_name: Option<&str>,
+                                cache: &mut ConfigCache, warnings: &mut Vec<ConfigWarning>) -> Result<ConfigSet, ConfigLoadError> {
+        if !config_dir.is_dir() {
+            return Err(ConfigLoadError::InvalidConfigDirectory)
+        }
+
+        // Load default configuration
+        let default_file = default_config_path(config_dir, default_file_name);
+        let mut default = cache.load_config(default_file.as_path())?;
+        let mut imported_files = default.import_paths(config_dir);
+        imported_files.extend(default.replace_files.iter().cloned());
+        default.check_espanso_version(default_file.as_path())?;
+
+        let strict = default.strict || is_strict_env_set();
+        default.check_unknown_fields(default_file.as_path(), strict, warnings)?;
+        default.check_empty_matches(default_file.as_path(), warnings)?;
+        default.check_validity(default_file.as_path())?;
+
+        if default.clear_default_matches {
+            default.matches.clear();
+        }
+
+        // Analyze which config files has to be loaded. The order in which WalkDir returns
+        // the entries depends on the platform, so they are sorted by name to make the loads
+        // (including the duplicate name errors and the merges) reproducible.
+
+        let mut target_files = Vec::new();
+
+        let specific_dir = config_dir.join(USER_CONFIGS_FOLDER_NAME);
+        if specific_dir.exists() {
+            let dir_entry = WalkDir::new(&specific_dir).follow_links(default.follow_symlinks)
+                .sort_by(|a, b| a.file_name().cmp(b.file_name()));
+            target_files.extend(dir_entry.into_iter().map(|entry| (specific_dir.clone(), entry)));
+        }
+
+        if package_dir.exists() {
+            // The disabled packages are pruned, so their directories are not even walked
+            let enabled_patterns: Vec<GlobPattern> = default.enabled_packages.iter().map(|p| GlobPattern::new(p)).collect();
+            let disabled_patterns: Vec<GlobPattern> = default.disabled_packages.iter().map(|p| GlobPattern::new(p)).collect();
+            let dir_entry = WalkDir::new(package_dir).follow_links(default.follow_symlinks)
+                .sort_by(|a, b| a.file_name().cmp(b.file_name())).into_iter()
+                .filter_entry(|entry| {
+                    if entry.depth() != 1 || !entry.file_type().is_dir() {
+                        return true;
+                    }
+                    let name = Path::new(entry.file_name());
+                    if disabled_patterns.iter().any(|p| p.is_match(name)) {
+                        return false;
+                    }
+                    enabled_patterns.is_empty() || enabled_patterns.iter().any(|p| p.is_match(name))
+                });
+            target_files.extend(dir_entry.map(|entry| (package_dir.to_owned(), entry)));
+        }
+
+        let include_patterns: Vec<GlobPattern> = default.include.iter().map(|p| GlobPattern::new(p)).collect();
+        let exclude_patterns: Vec<GlobPattern> = default.exclude.iter().map(|p| GlobPattern::new(p)).collect();
+
+        // Load the user defined config files
+
+        let mut user_configs = Vec::new();
+        let mut packages: Vec<(PathBuf, PackageManifest)> = Vec::new();
+
+        for (root_dir, entry) in target_files {
+            if let Ok(entry) = entry {
+                let path = entry.path();
+
+                // Each package is listed even without a manifest, which then replaces the defaults
+                if root_dir == package_dir && entry.depth() == 1 && entry.file_type().is_dir() {
+                    packages.push((path.to_owned(), PackageManifest::from_dir_name(path)));
+                    continue;
+                }
+
+                // Package manifests only carry metadata, they are not match configs
+                if entry.file_name() == PACKAGE_MANIFEST_FILE_NAME {
+                    if root_dir == package_dir {
+                        let package_path = path.parent().unwrap_or(path);
+
+                        // A broken manifest only affects the metadata, so the package is loaded anyway
+                        let manifest = match PackageManifest::load(path) {
+                            Ok(manifest) => manifest,
+                            Err(e) => {
+                                warn!("Unable to load the package manifest {:?}, using the default metadata: {}", path, e);
+                                PackageManifest::from_dir_name(package_path)
+                            },
+                        };
+                        match packages.iter_mut().find(|(dir, _)| dir == package_path) {
+                            Some((_, package)) => *package = manifest,
+                            None => packages.push((package_path.to_owned(), manifest)),
+                        }
+                    }
+                    continue;
+                }
+
+                // Skip the files that are not selected by the include and exclude patterns
+                let relative_path = path.strip_prefix(&root_dir).unwrap_or(path);
+                if !include_patterns.iter().any(|p| p.is_match(relative_path)) ||
+                    exclude_patterns.iter().any(|p| p.is_match(relative_path)) {
+                    continue;
+                }
+
+                let config = cache.load_config(&path)?;
+                imported_files.extend(config.import_paths(path.parent().unwrap_or(Path::new(""))));
+                imported_files.extend(config.replace_files.iter().cloned());
+                user_configs.push((path.to_owned(), config));
+            }else{
+                eprintln!("Warning: Unable to read config file: {}", entry.unwrap_err())
+            }
+        }
+        cache.evict_unused();
+
+        let mut packages: Vec<PackageManifest> = packages.into_iter().map(|(_, manifest)| manifest).collect();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut config_set = ConfigSet::from_configs(default, user_configs, packages, strict, warnings)?;
+        config_set.imported_files = imported_files;
+
+        Ok(config_set)
+    }
+
+    /// Load the ConfigSet from a single file, containing the default config and the specific
+    /// ones as a top level `configs` list. The first config of the list is the default one.
+    pub fn load_from_file(path: &Path) -> Result<ConfigSet, ConfigLoadError> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) => return Err(ConfigLoadError::FileNotFound),
+        };
+
+        let combined: serde_yaml::Value = serde_yaml::from_str(&contents)
+            .map_err(|e| ConfigLoadError::InvalidYAML(path.to_owned(), e))?;
+        let combined_configs = match combined.get("configs") {
+            Some(serde_yaml::Value::Sequence(configs)) => configs.clone(),
+            _ => {
+                let e = <serde_yaml::Error as serde::de::Error>::custom("missing the top level 'configs' list");
+                return Err(ConfigLoadError::InvalidYAML(path.to_owned(), e));
+            },
+        };
+
+        let config_dir = path.parent().unwrap_or(Path::new(""));
+        let mut configs = Vec::new();
+        for (i, mut value) in combined_configs.into_iter().enumerate() {
+            // Each config is identified by its position in the file
+            let config_path = PathBuf::from(format!("{}:configs[{}]", path.to_str().unwrap_or_default(), i));
+
+            interpolate_config_values(&mut value)
+                .map_err(|name| ConfigLoadError::UndefinedVariable(config_path.clone(), name))?;
+
+            let mut config = Configs::from_yaml_value(value, config_dir)
+                .map_err(|e| ConfigLoadError::InvalidYAML(config_path.clone(), e))?;
+            config.set_source_file(&config_path);
+            configs.push((config_path, config));
+        }
+
+        if configs.is_empty() {
+            let e = <serde_yaml::Error as serde::de::Error>::custom("the 'configs' list must contain at least the default config");
+            return Err(ConfigLoadError::InvalidYAML(path.to_owned(), e));
+        }
+
+        let (default_path, mut default) = configs.remove(0);
+        default.check_espanso_version(&default_path)?;
+
+        let mut warnings = Vec::new();
+        let strict = default.strict || is_strict_env_set();
+        default.check_unknown_fields(&default_path, strict, &mut warnings)?;
+        default.check_empty_matches(&default_path, &mut warnings)?;
+        default.check_validity(&default_path)?;
+
+        if default.clear_default_matches {
+            default.matches.clear();
+        }
+
+        let config_set = ConfigSet::from_configs(default, configs, Vec::new(), strict, &mut warnings)?;
+        log_warnings(&warnings);
+
+        Ok(config_set)
+    }
+
+    /// Build the ConfigSet from the given default config and the user defined ones,
+    /// validating the latter and merging the children configs into their parents.
+    fn from_configs(default: Configs, user_configs: Vec<(PathBuf, Configs)>, packages: Vec<PackageManifest>,
+                    strict: bool, warnings: &mut Vec<ConfigWarning>) -> Result<ConfigSet, ConfigLoadError> {
+        let mut name_set = HashSet::new();
+        let mut children_map: HashMap<String, Vec<Configs>> = HashMap::new();
+        let mut root_configs = Vec::new();
+        root_configs.push(default);
+
+        for (path, mut config) in user_configs {
+            // Make sure the config does not contain reserved fields
+            let invalid_fields = config.validate_user_defined_config();
+            if !invalid_fields.is_empty() {
+                return Err(ConfigLoadError::InvalidParameter(path, invalid_fields))
+            }
+
+            config.check_espanso_version(&path)?;
+            config.check_unknown_fields(&path, strict, warnings)?;
+            config.check_empty_matches(&path, warnings)?;
+            config.check_validity(&path)?;
+
+            // No name specified, defaulting to the path name
+            if config.name == "default" {
+                config.name = path.to_str().unwrap_or_default().to_owned();
+            }
+
+            if name_set.contains(&config.name) {
+                return Err(ConfigLoadError::NameDuplicate(path));
+            }
+
+            name_set.insert(config.name.clone());
+
+            if config.parent == "self" {  // No parent, root config
+                root_configs.push(config);
+            }else{  // Children config
+                let children_vec = children_map.entry(config.parent.clone()).or_default();
+                children_vec.push(config);
+            }
+        }
+
+        // Merge the children config files, separating default from specific
+        let mut configs = root_configs.into_iter()
+            .map(|root_config| ConfigSet::reduce_configs(root_config, &mut children_map));
+        let mut default = configs.next().unwrap();
+        let mut specific: Vec<Configs> = configs.collect();
+
+        // The files are discovered in a platform dependent order, so the precedence is made explicit
+        specific.sort_by(|a, b| a.order.cmp(&b.order).then_with(|| a.name.cmp(&b.name)));
+
+        // Add default entries to specific configs when needed
+        default.remove_duplicate_labels(strict, warnings)?;
+        for config in specific.iter_mut() {
+            if !config.exclude_default_entries {
+                config.merge_default(&default);
+            }
+            config.remove_duplicate_labels(strict, warnings)?;
+        }
+
+        // Check if some triggers are conflicting with each other
+        // For more information, see: https://github.com/federico-terzi/espanso/issues/135
+        if default.conflict_check {
+            for (trigger, prefix) in Self::trigger_conflicts(&default, &specific) {
+                let warning = ConfigWarning::TriggerConflict(trigger, prefix);
+                // The default matches are checked again in each specific config
+                if !warnings.contains(&warning) {
+                    warnings.push(warning);
+                }
+            }
+        }
+
+        let mut config_set = ConfigSet {
+            default,
+            specific,
+            packages,
+            imported_files: Vec::new(),
+        };
+        config_set.build_match_indexes();
+
+        // Check if some specific configs are targeting the same applications
+        if config_set.default.conflict_check {
+            for (first, second) in config_set.detect_filter_conflicts() {
+                warnings.push(ConfigWarning::FilterConflict(first, second));
+            }
+        }
+
+        let max_buffer_size = config_set.default.max_buffer_size;
+        for config in std::iter::once(&config_set.default).chain(config_set.specific.iter()) {
+            for trigger in config.matches.iter().flat_map(|m| m.triggers()) {
+                if trigger.chars().count() > max_buffer_size {
+                    let warning = ConfigWarning::TriggerExceedsBufferSize(trigger.clone(), max_buffer_size);
+                    if !warnings.contains(&warning) {
+                        warnings.push(warning);
+                    }
+                }
+            }
+        }
+
+        Ok(config_set)
+    }
+
+    fn reduce_configs(target: Configs, children_map: &mut HashMap<String, Vec<Configs>>) -> Configs {
+        // As config names are unique, the children of each config are only needed once
+        if let Some(children) = children_map.remove(&target.name) {
+            let mut target = target;
+            for children in children {
+                let children = Self::reduce_configs(children, children_map);
+                target.merge_config(children);
+            }
+            target
+        }else{
+            target
+        }
+    }
+
+    /// Load the ConfigSet from the disk cache stored in the config directory, if
+    /// none of the source files and of the environment variables they reference
+    /// changed since it was written. Otherwise, parse the config files and rewrite the cache,
+    /// unless `cache_configs` is disabled in the default config.
+    pub fn load_cached(config_dir: &Path, package_dir: &Path, default_file_name: Option<&str>) -> Result<ConfigSet, ConfigLoadError> {
+        ConfigSet::load_cached_with(config_dir, package_dir, default_file_name, &mut ConfigCache::new())
+    }
+
+    fn load_cached_with(config_dir: &Path, package_dir: &Path, default_file_name: Option<&str>,
+                        cache: &mut ConfigCache) -> Result<ConfigSet, ConfigLoadError> {
+        let cache_file = config_dir.join(CONFIG_CACHE_FILE_NAME);
+        let sources = ConfigSet::list_source_files(config_dir, package_dir, default_file_name);
+
+        let disk_cache = fs::read_to_string(&cache_file).ok()
+            .and_then(|content| serde_json::from_str::<ConfigSetDiskCache>(&content).ok());
+
+        // The cache doesn't keep track of the unknown fields, so it can't be used to validate them
+        let disk_cache = if is_strict_env_set() { None } else { disk_cache };
+        let disk_cache = disk_cache.filter(|disk_cache| disk_cache.version == env!("CARGO_PKG_VERSION"));
+
+        if let Some(disk_cache) = disk_cache {
+            // The imported files are only known after parsing the configs
+            let mut sources = sources.clone();
+            sources.extend(modification_times(disk_cache.config_set.imported_files.clone()));
+            sources.sort();
+
+            if disk_cache.sources == sources && disk_cache.env_vars == referenced_env_vars(&sources) {
+                log_warnings(&disk_cache.warnings);
+                let mut config_set = disk_cache.config_set;
+                config_set.build_match_indexes();
+                return Ok(config_set);
+            }
+        }
+
+        let mut warnings = Vec::new();
+        let config_set = ConfigSet::load_collecting_warnings(config_dir, package_dir, default_file_name,
+                                                             cache, &mut warnings)?;
+        log_warnings(&warnings);
+
+        // A cache left from when the option was enabled would become stale, so it is removed
+        if !config_set.default.cache_configs {
+            if cache_file.exists() {
+                if let Err(e) = fs::remove_file(&cache_file) {
+                    warn!("Unable to remove the config cache: {}", e);
+                }
+            }
+            return Ok(config_set);
+        }
+
+        let mut sources = sources;
+        sources.extend(modification_times(config_set.imported_files.clone()));
+        sources.sort();
+
+        let disk_cache = ConfigSetDiskCache {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            env_vars: referenced_env_vars(&sources),
+            sources,
+            warnings,
+            config_set,
+        };
+        match serde_json::to_string(&disk_cache) {
+            Ok(content) => {
+                if let Err(e) = fs::write(&cache_file, content) {
+                    warn!("Unable to write the config cache: {}", e);
+                }
+            },
+            Err(e) => warn!("Unable to serialize the config cache: {}", e),
+        }
+
+        Ok(disk_cache.config_set)
+    }
+
+    // List all the files that could affect the ConfigSet, with their modification time
+    fn list_source_files(config_dir: &Path, package_dir: &Path, default_file_name: Option<&str>) -> Vec<(PathBuf, SystemTime)> {
+        let mut paths = vec![default_config_path(config_dir, default_file_name)];
+
+        // The symlinks are always followed, listing more files than needed only makes the cache stricter
+        for dir in [config_dir.join(USER_CONFIGS_FOLDER_NAME), package_dir.to_owned()].iter() {
+            if dir.exists() {
+                paths.extend(WalkDir::new(dir).follow_links(true).into_iter()
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.file_type().is_file())
+                    .map(|entry| entry.path().to_owned()));
+            }
+        }
+
+        let mut sources = modification_times(paths);
+        sources.sort();
+
+        sources
+    }
+
+    pub fn load_default() -> Result<ConfigSet, ConfigLoadError> {
+        ConfigSet::load_default_with_cache(&mut ConfigCache::new())
+    }
+
+    /// Same as load_default, but the files that didn't change since they were
+    /// stored in the given cache are not parsed again.
+    pub fn load_default_with_cache(cache: &mut ConfigCache) -> Result<ConfigSet, ConfigLoadError> {
+        // Configuration related
+
+        let config_dir = crate::context::get_config_dir();
+
+        ConfigSet::check_config_dir(config_dir.as_path())?;
+
+        // A combined file, such as one shared as a single gist, replaces the config directory
+        if let Some(combined_file) = combined_config_file_from_env() {
+            return ConfigSet::load_from_file(combined_file.as_path());
+        }
+
+        // Load the profile selected in the previous session, if any
+        if let Some(profile) = runtime::load_active_profile() {
+            match ConfigSet::load_profile(&profile) {
+                Err(ConfigLoadError::ProfileNotFound(path)) => {
+                    warn!("Profile directory {:?} does not exist, using the base config", path);
+                },
+                result => return result,
+            }
+        }
+
+        let default_file_name = default_config_file_name_from_env();
+        let default_file_name = default_file_name.as_ref().map(String::as_str);
+        let default_file = default_config_path(config_dir.as_path(), default_file_name);
+
+        // If config file does not exist, create one from template
+        if !default_file.exists() {
+            let result = fs::write(&default_file, DEFAULT_CONFIG_FILE_CONTENT);
+            if result.is_err() {
+                return Err(ConfigLoadError::UnableToCreateDefaultConfig)
+            }
+        }
+
+        // Create auxiliary directories
+
+        let user_config_dir = config_dir.join(USER_CONFIGS_FOLDER_NAME);
+        if !user_config_dir.exists() {
+            let res = create_dir_all(user_config_dir.as_path());
+            if res.is_err() {
+                return Err(ConfigLoadError::UnableToCreateDefaultConfig)
+            }
+        }
+
+
+        // Packages
+
+        let package_dir = crate::context::get_package_dir();
+        let res = create_dir_all(package_dir.as_path());
+        if res.is_err() {
+            return Err(ConfigLoadError::UnableToCreateDefaultConfig)  // TODO: change error type
+        }
+
+        // The user files are only rewritten on request, see ConfigSet::migrate
+        for path in ConfigSet::deprecated_config_files(config_dir.as_path(), default_file_name) {
+            warn!("{:?} uses deprecated field names, run 'espanso migrate' to rename them", path);
+        }
+
+        return ConfigSet::load_cached_with(config_dir.as_path(), package_dir.as_path(), default_file_name, cache);
+    }
+
+    /// Load the profile with the given name, stored in the `profiles` directory
+    /// of the config directory, with its own default config and `user` directory.
+    pub fn load_profile(name: &str) -> Result<ConfigSet, ConfigLoadError> {
+        let config_dir = crate::context::get_config_dir();
+        let package_dir = crate::context::get_package_dir();
+
+        ConfigSet::load_profile_from_dir(config_dir.as_path(), package_dir.as_path(), name)
+    }
+
+    /// Load the profile with the given name from the given directories, or the base
+    /// config if None, regardless of the profile selected in the previous session.
+    pub fn load_profile_or_base(config_dir: &Path, package_dir: &Path, name: Option<&str>) -> Result<ConfigSet, ConfigLoadError> {
+        match name {
+            Some(name) => ConfigSet::load_profile_from_dir(config_dir, package_dir, name),
+            None => {
+                let default_file_name = default_config_file_name_from_env();
+                ConfigSet::load(config_dir, package_dir, default_file_name.as_ref().map(String::as_str))
+            },
+        }
+    }
+
+    fn load_profile_from_dir(config_dir: &Path, package_dir: &Path, name: &str) -> Result<ConfigSet, ConfigLoadError> {
+        let profile_dir = profile_path(config_dir, name);
+        if !profile_dir.is_dir() {
+            return Err(ConfigLoadError::ProfileNotFound(profile_dir));
+        }
+
+        ConfigSet::load(profile_dir.as_path(), package_dir, None)
+    }
+
+    /// Make sure the config directory exists and is writable, so that the default
+    /// config and the auxiliary directories can be created in it.
+    pub fn check_config_dir(config_dir: &Path) -> Result<(), ConfigLoadError> {
+        let metadata = match fs::metadata(config_dir) {
+            Ok(metadata) => metadata,
+            Err(_) => return Err(ConfigLoadError::ConfigDirMissing(config_dir.to_owned())),
+        };
+
+        if !metadata.is_dir() {
+            return Err(ConfigLoadError::ConfigDirNotADirectory(config_dir.to_owned()));
+        }
+
+        if metadata.permissions().readonly() {
+            return Err(ConfigLoadError::ConfigDirNotWritable(config_dir.to_owned()));
+        }
+
+        // The permission bits don't tell the whole story (for example, when the
+        // directory is owned by another user), so try to create a file
+        let probe_file = config_dir.join(".espanso_write_check");
+        if File::create(&probe_file).is_err() {
+            return Err(ConfigLoadError::ConfigDirNotWritable(config_dir.to_owned()));
+        }
+        if let Err(e) = fs::remove_file(&probe_file) {
+            warn!("Unable to remove the write check file: {}", e);
+        }
+
+        Ok(())
+    }
+
+    /// Same as migrate, for the config directory used by load_default.
+    pub fn migrate_default() -> std::io::Result<Vec<PathBuf>> {
+        let config_dir = crate::context::get_config_dir();
+        let default_file_name = default_config_file_name_from_env();
+
+        ConfigSet::migrate(config_dir.as_path(), default_file_name.as_ref().map(String::as_str))
+    }
+
+    /// Rename the deprecated fields used in the default and user config files,
+    /// saving a copy of each original file with the `.bak` extension appended.
+    /// Return the paths of the rewritten files.
+    pub fn migrate(config_dir: &Path, default_file_name: Option<&str>) -> std::io::Result<Vec<PathBuf>> {
+        let mut migrated = Vec::new();
+
+        for path in config_file_paths(config_dir, default_file_name) {
+            let content = fs::read_to_string(&path)?;
+            if let Some(new_content) = migrate_fields(&content) {
+                let mut backup_path = path.clone().into_os_string();
+                backup_path.push(".bak");
+                fs::copy(&path, &backup_path)?;
+
+                fs::write(&path, new_content)?;
+
+                warn!("Renamed deprecated fields in {:?}, the original file was saved in {:?}", path, backup_path);
+                migrated.push(path);
+            }
+        }
+
+        Ok(migrated)
+    }
+
+    // Config files that would be rewritten by migrate, without touching them
+    fn deprecated_config_files(config_dir: &Path, default_file_name: Option<&str>) -> Vec<PathBuf> {
+        config_file_paths(config_dir, default_file_name).into_iter()
+            .filter(|path| {
+                fs::read_to_string(path).ok()
+                    .map_or(false, |content| migrate_fields(&content).is_some())
+            })
+            .collect()
+    }
+
+    fn has_conflicts(default: &Configs, specific: &Vec<Configs>) -> bool {
+        !Self::trigger_conflicts(default, specific).is_empty()
+    }
+
+    /// Find the triggers starting with another trigger of the same config, returned
+    /// as (trigger, conflicting prefix) pairs.
+    fn trigger_conflicts(default: &Configs, specific: &[Configs]) -> Vec<(String, String)> {
+        let mut conflicts = Vec::new();
+
+        for config in std::iter::once(default).chain(specific.iter()) {
+            let mut sorted_triggers : Vec<String> = config.matches.iter().flat_map(|t| {
+                t.triggers().iter().cloned()
+            }).collect();
+            sorted_triggers.sort();
+            conflicts.extend(Self::list_conflicts(&sorted_triggers));
+        }
+
+        conflicts
+    }
+
+    /// Find the specific configs that define exactly the same filters,
+    /// making it ambiguous which one should be used for the matching applications.
+    /// Returns the pairs of conflicting config names.
+    pub fn detect_filter_conflicts(&self) -> Vec<(String, String)> {
+        let mut conflicts = Vec::new();
+
+        for (i, config) in self.specific.iter().enumerate() {
+            // Configs without filters are never activated, so they can't conflict
+            if config.filter_title.is_empty() && config.filter_class.is_empty() && config.filter_exec.is_empty() {
+                continue;
+            }
+
+            for other in self.specific.iter().skip(i + 1) {
+                if config.filter_title == other.filter_title &&
+                    config.filter_class == other.filter_class &&
+                    config.filter_exec == other.filter_exec {
+                    conflicts.push((config.name.clone(), other.name.clone()));
+                }
+            }
+        }
+
+        conflicts
+    }
+
+    fn list_has_conflicts(sorted_list: &Vec<String>) -> bool {
+        !Self::list_conflicts(sorted_list).is_empty()
+    }
+
+    fn list_conflicts(sorted_list: &[String]) -> Vec<(String, String)> {
+        let mut conflicts = Vec::new();
+
+        for (i, item) in sorted_list.iter().skip(1).enumerate() {
+            let previous = &sorted_list[i];
+            if item.starts_with(previous) {
+                conflicts.push((item.clone(), previous.clone()));
+            }
+        }
+
+        conflicts
+    }
+}
+
+pub trait ConfigManager<'a> {
+    // The returned configs stay valid even if the set is swapped in the meantime,
+    // so they can be kept until the current event is processed.
+    fn active_config(&'a self) -> LiveConfig;
+    fn default_config(&'a self) -> LiveConfig;
+    fn is_trigger_disabled(&self, trigger: &str) -> bool;
+
+    // Disable the match with the given trigger at runtime, or enable it again.
+    fn set_trigger_disabled(&self, _trigger: &str, _disabled: bool) {}
+
+    // Triggers of the 'once' matches that already fired in the current session.
+    fn is_trigger_spent(&self, _trigger: &str) -> bool {
+        false
+    }
+    fn mark_trigger_spent(&self, _trigger: &str) {}
+
+    // Persist the enabled/disabled state of espanso, so that it can be restored after a restart.
+    fn save_enabled_state(&self, _enabled: bool) {}
+
+    // Class and executable of the focused window, used to check the 'apps' of the matches.
+    fn current_app(&self) -> (Option<String>, Option<String>) {
+        (None, None)
+    }
+
+    // Replace the whole config set, including the settings, such as when the
+    // configuration is reloaded.
+    fn swap_config_set(&self, new_set: ConfigSet);
+
+    // Replace the matches and filters, along with the rest of the set, without
+    // restarting the keyboard listener or the IPC server. The new set is handed
+    // over behind a lock, and installed before the next config is returned.
+    fn swap_matches(&self, new_set: ConfigSet) {
+        self.swap_config_set(new_set);
+    }
+
+    // Copy of the config set currently in use, if the manager holds a whole set.
+    fn config_set(&self) -> Option<ConfigSet> {
+        None
+    }
+}
+
+/// Config returned by a `ConfigManager`, sharing the ownership of the set it belongs to.
+pub struct LiveConfig {
+    pub set: Rc<ConfigSet>,
+    pub position: Option<usize>, // Position of the active config in 'specific', if any
+    pub generation: usize,
+}
+
+impl LiveConfig {
+    /// Number of swaps performed before obtaining this config. Indexes of configs
+    /// with different generations can't be mixed, even if they have the same name.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+}
+
+impl Deref for LiveConfig {
+    type Target = Configs;
+
+    fn deref(&self) -> &Configs {
+        match self.position {
+            Some(position) => &self.set.specific[position],
+            None => &self.set.default,
+        }
+    }
+}
+
+/// Non-fatal problem found while loading the configs, see ConfigSet::load_with_warnings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConfigWarning {
+    UnknownField(PathBuf, String),
+    EmptyReplacement(PathBuf, String),
+    TriggerConflict(String, String),
+    FilterConflict(String, String),
+    TriggerExceedsBufferSize(String, usize),
+    DuplicateLabel(String, String),
+}
+
+impl fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigWarning::UnknownField(path, field) => write!(f, "Unknown field '{}' in {:?}, it will be ignored", field, path),
+            ConfigWarning::EmptyReplacement(path, trigger) => write!(f, "Match '{}' in {:?} has an empty replacement", trigger, path),
+            ConfigWarning::TriggerConflict(trigger, prefix) => write!(f, "trigger '{}' is conflicting with '{}' and may not behave as intended", trigger, prefix),
+            ConfigWarning::FilterConflict(first, second) => write!(f, "configs '{}' and '{}' have the same filters and may not behave as intended", first, second),
+            ConfigWarning::DuplicateLabel(config, label) => write!(f, "label '{}' is used by more than one match of config '{}', only the first one is kept", label, config),
+            ConfigWarning::TriggerExceedsBufferSize(trigger, size) => write!(f, "trigger '{}' is longer than the max_buffer_size of {} chars, the typed buffer will be truncated", trigger, size),
+        }
+    }
+}
+
+// The conflicts are printed on stderr, as they are usually found while editing the configs
+fn log_warnings(warnings: &[ConfigWarning]) {
+    let mut has_trigger_conflicts = false;
+    for warning in warnings {
+        match warning {
+            ConfigWarning::TriggerConflict(_, _) => {
+                has_trigger_conflicts = true;
+                eprintln!("Warning: {}", warning);
+            },
+            ConfigWarning::FilterConflict(_, _) => eprintln!("Warning: {}", warning),
+            _ => warn!("{}", warning),
+        }
+    }
+
+    if has_trigger_conflicts {
+        eprintln!("Warning: some triggers had conflicts and may not behave as intended");
+        eprintln!("To turn off this check, add \"conflict_check: false\" in the configuration");
+    }
+}
+
+// Error handling
+#[derive(Debug)]
+pub enum ConfigLoadError {
+    FileNotFound,
+    UnableToReadFile,
+    InvalidYAML(PathBuf, serde_yaml::Error),
+    InvalidConfigDirectory,
+    InvalidParameter(PathBuf, Vec<String>),
+    NameDuplicate(PathBuf),
+    UnableToCreateDefaultConfig,
+    IncompatibleVersion(PathBuf, String),
+    UnknownField(PathBuf, String),
+    ConfigDirMissing(PathBuf),
+    ConfigDirNotWritable(PathBuf),
+    ConfigDirNotADirectory(PathBuf),
+    ProfileNotFound(PathBuf),
+    EmptyTrigger(PathBuf),
+    ValidationFailed(PathBuf, Vec<String>),
+    UndefinedVariable(PathBuf, String),
+    DuplicateLabel(String, String),
+}
+
+impl fmt::Display for ConfigLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigLoadError::FileNotFound =>  write!(f, "File not found"),
+            ConfigLoadError::UnableToReadFile =>  write!(f, "Unable to read config file"),
+            ConfigLoadError::InvalidYAML(path, e) => {
+                if let Some(location) = e.location() {
+                    write!(f, "{}:{}:{}: {}", path.to_str().unwrap_or_default(), location.line(), location.column(), e)
+                }else{
+                    write!(f, "Error parsing YAML file '{}', invalid syntax: {}", path.to_str().unwrap_or_default(), e)
+                }
+            },
+            ConfigLoadError::InvalidConfigDirectory =>  write!(f, "Invalid config directory"),
+            ConfigLoadError::InvalidParameter(path, fields) =>  write!(f, "Invalid parameter in '{}', use of reserved parameters ({}) in user defined configs is not permitted, as they affect espanso globally and can be only used in the default.yml config file", path.to_str().unwrap_or_default(), fields.join(", ")),
+            ConfigLoadError::NameDuplicate(path) =>  write!(f, "Found duplicate 'name' in '{}', please use different names", path.to_str().unwrap_or_default()),
+            ConfigLoadError::UnableToCreateDefaultConfig =>  write!(f, "Could not generate default config file"),
+            ConfigLoadError::IncompatibleVersion(path, req) =>  write!(f, "Config '{}' requires espanso version '{}', but the current one is {}", path.to_str().unwrap_or_default(), req, env!("CARGO_PKG_VERSION")),
+            ConfigLoadError::UnknownField(path, field) =>  write!(f, "Unknown field '{}' in '{}', please check for typos", field, path.to_str().unwrap_or_default()),
+            ConfigLoadError::ConfigDirMissing(path) =>  write!(f, "Config directory '{}' does not exist, please create it", path.to_str().unwrap_or_default()),
+            ConfigLoadError::ConfigDirNotWritable(path) =>  write!(f, "Config directory '{}' is not writable, please check its permissions", path.to_str().unwrap_or_default()),
+            ConfigLoadError::ConfigDirNotADirectory(path) =>  write!(f, "Config path '{}' is not a directory, please move or remove the file", path.to_str().unwrap_or_default()),
+            ConfigLoadError::ProfileNotFound(path) =>  write!(f, "Profile directory '{}' does not exist", path.to_str().unwrap_or_default()),
+            ConfigLoadError::EmptyTrigger(path) =>  write!(f, "Found a match with an empty trigger in '{}', please specify a trigger", path.to_str().unwrap_or_default()),
+            ConfigLoadError::ValidationFailed(path, errors) =>  write!(f, "Invalid fields in '{}': {}", path.to_str().unwrap_or_default(), errors.join("; ")),
+            ConfigLoadError::DuplicateLabel(config, label) =>  write!(f, "Label '{}' is used by more than one match of config '{}', labels must be unique", label, config),
+            ConfigLoadError::UndefinedVariable(path, name) =>  write!(f, "Variable '{}' referenced in '{}' is not defined, set it or provide a default with ${{{}:-default}}", name, path.to_str().unwrap_or_default(), name),
+        }
+    }
+}
+
+impl Error for ConfigLoadError {
+    fn description(&self) -> &str {
+        match self {
+            ConfigLoadError::FileNotFound => "File not found",
+            ConfigLoadError::UnableToReadFile => "Unable to read config file",
+            ConfigLoadError::InvalidYAML(_, _) => "Error parsing YAML file, invalid syntax",
+            ConfigLoadError::InvalidConfigDirectory => "Invalid config directory",
+            ConfigLoadError::InvalidParameter(_, _) => "Invalid parameter, use of reserved parameters in user defined configs is not permitted",
+            ConfigLoadError::NameDuplicate(_) => "Found duplicate 'name' in some configurations, please use different names",
+            ConfigLoadError::UnableToCreateDefaultConfig => "Could not generate default config file",
+            ConfigLoadError::IncompatibleVersion(_, _) => "Config requires an incompatible espanso version",
+            ConfigLoadError::UnknownField(_, _) => "Config contains an unknown field",
+            ConfigLoadError::ConfigDirMissing(_) => "Config directory does not exist",
+            ConfigLoadError::ConfigDirNotWritable(_) => "Config directory is not writable",
+            ConfigLoadError::ConfigDirNotADirectory(_) => "Config path is not a directory",
+            ConfigLoadError::ProfileNotFound(_) => "Profile does not exist",
+            ConfigLoadError::EmptyTrigger(_) => "Found a match with an empty trigger",
+            ConfigLoadError::ValidationFailed(_, _) => "Config contains invalid fields",
+            ConfigLoadError::UndefinedVariable(_, _) => "Config references an undefined variable",
+            ConfigLoadError::DuplicateLabel(_, _) => "Config contains duplicate match labels",
+        }
+    }
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigLoadError::InvalidYAML(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl ConfigLoadError {
+    /// Stable identifier of the error, meant to be consumed by external tools.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConfigLoadError::FileNotFound => "FILE_NOT_FOUND",
+            ConfigLoadError::UnableToReadFile => "UNABLE_TO_READ_FILE",
+            ConfigLoadError::InvalidYAML(_, _) => "INVALID_YAML",
+            ConfigLoadError::InvalidConfigDirectory => "INVALID_CONFIG_DIRECTORY",
+            ConfigLoadError::InvalidParameter(_, _) => "INVALID_PARAMETER",
+            ConfigLoadError::NameDuplicate(_) => "NAME_DUPLICATE",
+            ConfigLoadError::UnableToCreateDefaultConfig => "UNABLE_TO_CREATE_DEFAULT_CONFIG",
+            ConfigLoadError::IncompatibleVersion(_, _) => "INCOMPATIBLE_VERSION",
+            ConfigLoadError::UnknownField(_, _) => "UNKNOWN_FIELD",
+            ConfigLoadError::ConfigDirMissing(_) => "CONFIG_DIR_MISSING",
+            ConfigLoadError::ConfigDirNotWritable(_) => "CONFIG_DIR_NOT_WRITABLE",
+            ConfigLoadError::ConfigDirNotADirectory(_) => "CONFIG_DIR_NOT_A_DIRECTORY",
+            ConfigLoadError::ProfileNotFound(_) => "PROFILE_NOT_FOUND",
+            ConfigLoadError::EmptyTrigger(_) => "EMPTY_TRIGGER",
+            ConfigLoadError::ValidationFailed(_, _) => "VALIDATION_FAILED",
+            ConfigLoadError::UndefinedVariable(_, _) => "UNDEFINED_VARIABLE",
+            ConfigLoadError::DuplicateLabel(_, _) => "DUPLICATE_LABEL",
+        }
+    }
+
+    /// Path of the config file that caused the error, if known.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ConfigLoadError::InvalidYAML(path, _) => Some(path),
+            ConfigLoadError::InvalidParameter(path, _) => Some(path),
+            ConfigLoadError::NameDuplicate(path) => Some(path),
+            ConfigLoadError::IncompatibleVersion(path, _) => Some(path),
+            ConfigLoadError::UnknownField(path, _) => Some(path),
+            ConfigLoadError::ConfigDirMissing(path) => Some(path),
+            ConfigLoadError::ConfigDirNotWritable(path) => Some(path),
+            ConfigLoadError::ConfigDirNotADirectory(path) => Some(path),
+            ConfigLoadError::ProfileNotFound(path) => Some(path),
+            ConfigLoadError::EmptyTrigger(path) => Some(path),
+            ConfigLoadError::ValidationFailed(path, _) => Some(path),
+            ConfigLoadError::UndefinedVariable(path, _) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+// serde_yaml::Error is not comparable, so the errors are compared by code, path and message
+impl PartialEq for ConfigLoadError {
+    fn eq(&self, other: &Self) -> bool {
+        self.code() == other.code() && self.path() == other.path() &&
+            self.to_string() == other.to_string()
+    }
+}
+
+// Machine-readable form of the error, such as:
+// {"code": "INVALID_YAML", "message": "Error parsing YAML file ...", "path": "/path/to/file.yml"}
+impl Serialize for ConfigLoadError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut state = serializer.serialize_struct("ConfigLoadError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("path", &self.path())?;
+        state.end()
+    }
+}
+
+
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::{NamedTempFile, TempDir};
+    use std::any::Any;
+    use crate::matcher::{TextContent, MatchContentType};
+
+    const TEST_WORKING_CONFIG_FILE : &str = include_str!("../res/test/working_config.yml");
+    const TEST_CONFIG_FILE_WITH_BAD_YAML : &str = include_str!("../res/test/config_with_bad_yaml.yml");
+
+    // Test Configs
+
+    fn create_tmp_file(string: &str) -> NamedTempFile {
+        let file = NamedTempFile::new().unwrap();
+        file.as_file().write_all(string.as_bytes());
+        file
+    }
+
+    fn variant_eq<T>(a: &T, b: &T) -> bool {
+        std::mem::discriminant(a) == std::mem::discriminant(b)
+    }
+
+    #[test]
+    fn test_config_file_not_found() {
+        let config = Configs::load_config(Path::new("invalid/path"));
+        assert_eq!(config.is_err(), true);
+        assert_eq!(config.unwrap_err(), ConfigLoadError::FileNotFound);
+    }
+
+    #[test]
+    fn test_config_file_with_bad_yaml_syntax() {
+        let broken_config_file = create_tmp_file(TEST_CONFIG_FILE_WITH_BAD_YAML);
+        let config = Configs::load_config(broken_config_file.path());
+        match config {
+            Ok(_) => {assert!(false)},
+            Err(e) => {
+                match e {
+                    ConfigLoadError::InvalidYAML(p, _) => assert_eq!(p, broken_config_file.path().to_owned()),
+                    _ => assert!(false),
+                }
+                assert!(true);
+            },
+        }
+
+    }
+
+    #[test]
+    fn test_validate_field_macro() {
+        let mut result: Vec<String> = Vec::new();
+
+        validate_field!(result, 3, 3);
+        assert!(result.is_empty());
+
+        validate_field!(result, 10, 3);
+        assert_eq!(result, vec!["10".to_owned()]);
+
+        validate_field!(result, 3, 3);
+        assert_eq!(result, vec!["10".to_owned()]);
+    }
+
+    #[test]
+    fn test_user_defined_config_does_not_have_reserved_fields() {
+        let working_config_file = create_tmp_file(r###"
+
+        backend: Clipboard
+
+        "###);
+        let config = Configs::load_config(working_config_file.path());
+        assert!(config.unwrap().validate_user_defined_config().is_empty());
+    }
+
+    #[test]
+    fn test_user_defined_config_has_reserved_fields_config_caching_interval() {
+        let working_config_file = create_tmp_file(r###"
+
+        # This should not happen in an app-specific config
+        config_caching_interval: 100
+
+        "###);
+        let config = Configs::load_config(working_config_file.path());
+        assert_eq!(config.unwrap().validate_user_defined_config(), vec!["config_caching_interval".to_owned()]);
+    }
+
+    #[test]
+    fn test_user_defined_config_has_reserved_fields_toggle_key() {
+        let working_config_file = create_tmp_file(r###"
+
+        # This should not happen in an app-specific config
+        toggle_key: CTRL
+
+        "###);
+        let config = Configs::load_config(working_config_file.path());
+        assert_eq!(config.unwrap().validate_user_defined_config(), vec!["toggle_key".to_owned()]);
+    }
+
+    #[test]
+    fn test_user_defined_config_has_reserved_fields_toggle_interval() {
+        let working_config_file = create_tmp_file(r###"
+
+        # This should not happen in an app-specific config
+        toggle_interval: 1000
+
+        "###);
+        let config = Configs::load_config(working_config_file.path());
+        assert_eq!(config.unwrap().validate_user_defined_config(), vec!["toggle_interval".to_owned()]);
+    }
+
+    #[test]
+    fn test_user_defined_config_has_reserved_fields_backspace_limit() {
+        let working_config_file = create_tmp_file(r###"
+
+        # This should not happen in an app-specific config
+        backspace_limit: 10
+
+        "###);
+        let config = Configs::load_config(working_config_file.path());
+        assert_eq!(config.unwrap().validate_user_defined_config(), vec!["backspace_limit".to_owned()]);
+    }
+
+    #[test]
+    fn test_user_defined_config_has_multiple_reserved_fields() {
+        let working_config_file = create_tmp_file(r###"
+
+        # This should not happen in an app-specific config
+        toggle_key: CTRL
+        backspace_limit: 10
+
+        "###);
+        let config = Configs::load_config(working_config_file.path());
+        assert_eq!(config.unwrap().validate_user_defined_config(), vec!["toggle_key".to_owned(), "backspace_limit".to_owned()]);
+    }
+
+    #[test]
+    fn test_config_load_error_invalid_parameter_lists_fields() {
+        let error = ConfigLoadError::InvalidParameter(PathBuf::from("/tmp/test.yml"),
+                                                     vec!["toggle_key".to_owned(), "backspace_limit".to_owned()]);
+        assert!(error.to_string().contains("toggle_key, backspace_limit"));
+    }
+
+    #[test]
+    fn test_user_defined_config_can_override_word_separators() {
+        let working_config_file = create_tmp_file(r###"
+
+        word_separators: [' ', ';']
+
+        "###);
+        let config = Configs::load_config(working_config_file.path());
+        assert!(config.unwrap().validate_user_defined_config().is_empty());
+    }
+
+    #[test]
+    fn test_config_set_specific_file_with_own_word_separators() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: editor
+        filter_title: "Editor"
+        word_separators: [' ', ';', '(']
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.specific[0].word_separators, vec![' ', ';', '(']);
+        assert_eq!(config_set.default.word_separators, default_word_separators());
+    }
+
+    #[test]
+    fn test_user_defined_config_can_raise_log_level() {
+        let working_config_file = create_tmp_file(r###"
+
+        log_level: 2
+
+        "###);
+        let config = Configs::load_config(working_config_file.path());
+        assert!(config.unwrap().validate_user_defined_config().is_empty());
+    }
+
+    #[test]
+    fn test_config_level_filter() {
+        let mut config: Configs = serde_yaml::from_str("log_level: 0").unwrap();
+        let expected = vec![
+            (-1, LevelFilter::Warn),
+            (0, LevelFilter::Warn),
+            (1, LevelFilter::Info),
+            (2, LevelFilter::Debug),
+            (3, LevelFilter::Trace),
+            (4, LevelFilter::Trace),
+        ];
+
+        for (log_level, level_filter) in expected {
+            config.log_level = log_level;
+            assert_eq!(config.level_filter(), level_filter);
+        }
+    }
+
+    #[test]
+    fn test_config_set_level_filter_is_raised_by_specific_configs() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.level_filter(), config_set.default.level_filter());
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        filter_class: Code
+        log_level: 2
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.level_filter(), LevelFilter::Debug);
+    }
+
+    #[test]
+    fn test_config_word_separators_parsed_correctly() {
+        let config: Configs = serde_yaml::from_str(r###"
+        word_separators: [' ', ';']
+        word_separator_strings: [", ", "\t "]
+        "###).unwrap();
+
+        assert_eq!(config.word_separators, vec![' ', ';']);
+        assert_eq!(config.word_separator_strings, vec![", ".to_owned(), "\t ".to_owned()]);
+    }
+
+    #[test]
+    fn test_config_ipc_transport_parsed_correctly() {
+        let config: Configs = serde_yaml::from_str("ipc_server_port: 1234").unwrap();
+        assert_eq!(config.ipc_transport, IPCTransport::default());
+
+        let config: Configs = serde_yaml::from_str("ipc_transport: tcp").unwrap();
+        assert_eq!(config.ipc_transport, IPCTransport::Tcp);
+
+        let config: Configs = serde_yaml::from_str("ipc_transport: pipe").unwrap();
+        assert_eq!(config.ipc_transport, IPCTransport::Pipe);
+
+        assert!(serde_yaml::from_str::<Configs>("ipc_transport: carrier_pigeon").is_err());
+    }
+
+    #[test]
+    fn test_config_toggle_mode_parsed_correctly() {
+        let config: Configs = serde_yaml::from_str("toggle_interval: 230").unwrap();
+        assert_eq!(config.toggle_mode, ToggleMode::DoublePress);
+
+        let config: Configs = serde_yaml::from_str("toggle_mode: SinglePress").unwrap();
+        assert_eq!(config.toggle_mode, ToggleMode::SinglePress);
+    }
+
+    #[test]
+    fn test_config_backspace_mode_parsed_correctly() {
+        let config: Configs = serde_yaml::from_str("log_level: 0").unwrap();
+        assert_eq!(config.backspace_mode, BackspaceMode::Codepoint);
+
+        let config: Configs = serde_yaml::from_str("backspace_mode: grapheme").unwrap();
+        assert_eq!(config.backspace_mode, BackspaceMode::Grapheme);
+    }
+
+    #[test]
+    fn test_config_word_separator_strings_default_empty() {
+        let config: Configs = serde_yaml::from_str("log_level: 0").unwrap();
+
+        assert_eq!(config.word_separators, default_word_separators());
+        assert!(config.word_separator_strings.is_empty());
+    }
+
+    #[test]
+    fn test_config_loaded_correctly() {
+        let working_config_file = create_tmp_file(TEST_WORKING_CONFIG_FILE);
+        let config = Configs::load_config(working_config_file.path());
+        assert_eq!(config.is_ok(), true);
+    }
+
+    // Test ConfigSet
+
+    pub fn create_temp_espanso_directories() -> (TempDir, TempDir) {
+        create_temp_espanso_directories_with_default_content(DEFAULT_CONFIG_FILE_CONTENT)
+    }
+
+    pub fn create_temp_espanso_directories_with_default_content(default_content: &str) -> (TempDir, TempDir) {
+        let data_dir = TempDir::new().expect("unable to create data directory");
+        let package_dir = TempDir::new().expect("unable to create package directory");
+
+        let default_path = data_dir.path().join(DEFAULT_CONFIG_FILE_NAME);
+        fs::write(default_path, default_content);
+
+        (data_dir, package_dir)
+    }
+
+    pub fn create_temp_file_in_dir(tmp_dir: &PathBuf, name: &str, content: &str) -> PathBuf {
+        let user_defined_path = tmp_dir.join(name);
+        let user_defined_path_copy = user_defined_path.clone();
+        fs::write(user_defined_path, content);
+
+        user_defined_path_copy
+    }
+
+    pub fn create_user_config_file(tmp_dir: &Path, name: &str, content: &str) -> PathBuf {
+        let user_config_dir = tmp_dir.join(USER_CONFIGS_FOLDER_NAME);
+        if !user_config_dir.exists() {
+            create_dir_all(&user_config_dir);
+        }
+
+        create_temp_file_in_dir(&user_config_dir, name, content)
+    }
+
+    pub fn create_package_file(package_data_dir: &Path, package_name: &str, filename: &str, content: &str) -> PathBuf {
+        let package_dir = package_data_dir.join(package_name);
+        if !package_dir.exists() {
+            create_dir_all(&package_dir);
+        }
+
+        create_temp_file_in_dir(&package_dir, filename, content)
+    }
+
+    #[test]
+    fn test_config_set_default_content_should_work_correctly() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        assert!(config_set.is_ok());
+    }
+
+    #[test]
+    fn test_config_set_load_fail_bad_directory() {
+        let config_set = ConfigSet::load(Path::new("invalid/path"), Path::new("invalid/path"), None);
+        assert_eq!(config_set.is_err(), true);
+        assert_eq!(config_set.unwrap_err(), ConfigLoadError::InvalidConfigDirectory);
+    }
+
+    #[test]
+    fn test_config_set_load_custom_default_file_name() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":home"
+              replace: "home"
+        "###);
+        create_temp_file_in_dir(&data_dir.path().to_owned(), "work.yml", r###"
+        matches:
+            - trigger: ":work"
+              replace: "work"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), Some("work.yml")).unwrap();
+        assert_eq!(config_set.default.matches.len(), 1);
+        assert_eq!(config_set.default.matches[0].trigger(), ":work");
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.matches.len(), 1);
+        assert_eq!(config_set.default.matches[0].trigger(), ":home");
+    }
+
+    #[test]
+    fn test_config_set_load_custom_default_file_missing() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), Some("work.yml"));
+        assert!(config_set.is_err());
+    }
+
+    #[test]
+    fn test_default_config_path_falls_back_to_default_file() {
+        let config_dir = Path::new("config");
+        assert_eq!(default_config_path(config_dir, None), config_dir.join(DEFAULT_CONFIG_FILE_NAME));
+        assert_eq!(default_config_path(config_dir, Some("work.yml")), config_dir.join("work.yml"));
+    }
+
+    #[test]
+    fn test_config_set_missing_default_file() {
+        let data_dir = TempDir::new().expect("unable to create temp directory");
+        let package_dir = TempDir::new().expect("unable to create package directory");
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        assert_eq!(config_set.is_err(), true);
+        assert_eq!(config_set.unwrap_err(), ConfigLoadError::FileNotFound);
+    }
+
+    #[test]
+    fn test_config_set_invalid_yaml_syntax() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(
+            TEST_CONFIG_FILE_WITH_BAD_YAML
+        );
+        let default_path = data_dir.path().join(DEFAULT_CONFIG_FILE_NAME);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        match config_set {
+            Ok(_) => {assert!(false)},
+            Err(e) => {
+                match e {
+                    ConfigLoadError::InvalidYAML(p, _) => assert_eq!(p, default_path),
+                    _ => assert!(false),
+                }
+                assert!(true);
+            },
+        }
+    }
+
+    #[cfg(unix)]
+    fn create_symlinked_shared_dir(data_dir: &Path) -> TempDir {
+        let shared_dir = TempDir::new().unwrap();
+        fs::write(shared_dir.path().join("shared.yml"), r###"
+        name: shared
+        filter_class: Code
+        matches:
+          - trigger: ":shared"
+            replace: "from the shared repo"
+        "###).unwrap();
+
+        let user_dir = data_dir.join(USER_CONFIGS_FOLDER_NAME);
+        create_dir_all(&user_dir).unwrap();
+        std::os::unix::fs::symlink(shared_dir.path(), user_dir.join("shared")).unwrap();
+
+        shared_dir
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_config_set_follows_symlinked_directories() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+        let _shared_dir = create_symlinked_shared_dir(data_dir.path());
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.specific.len(), 1);
+        assert!(config_set.specific[0].matches.iter().any(|m| m.trigger() == ":shared"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_config_set_symlinks_not_followed_when_disabled() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content("follow_symlinks: false");
+        let _shared_dir = create_symlinked_shared_dir(data_dir.path());
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert!(config_set.specific.is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_config_set_symlink_loop_is_skipped() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+        let user_dir = data_dir.path().join(USER_CONFIGS_FOLDER_NAME);
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        filter_class: Code
+        "###);
+        std::os::unix::fs::symlink(&user_dir, user_dir.join("loop")).unwrap();
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.specific.len(), 1);
+    }
+
+    #[test]
+    fn test_config_set_specific_file_with_reserved_fields() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        let user_defined_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
+        config_caching_interval: 10000
+        "###);
+        let user_defined_path_copy = user_defined_path.clone();
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        assert!(config_set.is_err());
+        assert_eq!(config_set.unwrap_err(), ConfigLoadError::InvalidParameter(user_defined_path_copy, vec!["config_caching_interval".to_owned()]))
+    }
+
+    #[test]
+    fn test_config_set_load_with_warnings_reports_prefix_triggers() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":ab"
+              replace: "first"
+            - trigger: ":abc"
+              replace: "second"
+        "###);
+
+        let (config_set, warnings) = ConfigSet::load_with_warnings(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.matches.len(), 2);
+        assert_eq!(warnings, vec![ConfigWarning::TriggerConflict(":abc".to_owned(), ":ab".to_owned())]);
+    }
+
+    #[test]
+    fn test_config_set_load_with_warnings_reports_lenient_problems() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        word_seperators: [" "]
+        matches:
+            - trigger: ":empty"
+              replace: ""
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        filter_class: Code
+        "###);
+        create_user_config_file(data_dir.path(), "specific2.yml", r###"
+        name: specific2
+        filter_class: Code
+        "###);
+
+        let (_, warnings) = ConfigSet::load_with_warnings(data_dir.path(), package_dir.path(), None).unwrap();
+        let default_path = data_dir.path().join(DEFAULT_CONFIG_FILE_NAME);
+        assert!(warnings.contains(&ConfigWarning::UnknownField(default_path.clone(), "word_seperators".to_owned())));
+        assert!(warnings.contains(&ConfigWarning::EmptyReplacement(default_path, ":empty".to_owned())));
+        assert!(warnings.iter().any(|w| variant_eq(w, &ConfigWarning::FilterConflict(String::new(), String::new()))));
+    }
+
+    #[test]
+    fn test_config_set_load_with_warnings_no_warnings() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":hi"
+              replace: "Hello"
+        "###);
+
+        let (_, warnings) = ConfigSet::load_with_warnings(data_dir.path(), package_dir.path(), None).unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_config_set_specific_file_with_bad_regex_fails() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        let user_defined_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
+        filter_title: "Chrome("
+        "###);
+
+        let error = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap_err();
+        match error {
+            ConfigLoadError::ValidationFailed(path, errors) => {
+                assert_eq!(path, user_defined_path);
+                assert_eq!(errors.len(), 1);
+                assert!(errors[0].starts_with("invalid regex in 'filter_title'"));
+            },
+            _ => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn test_config_set_bad_date_format_fails() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":now"
+              replace: "It's {{mytime}}"
+              vars:
+                - name: mytime
+                  type: date
+                  params:
+                    format: "%H:%Q"
+        "###);
+
+        let error = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap_err();
+        match error {
+            ConfigLoadError::ValidationFailed(path, errors) => {
+                assert_eq!(path, data_dir.path().join(DEFAULT_CONFIG_FILE_NAME));
+                assert_eq!(errors, vec!["match ':now': invalid date format '%H:%Q' in variable 'mytime'".to_owned()]);
+            },
+            _ => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn test_configs_retain_os_matches() {
+        let mut config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - trigger: ":sep"
+              replace: "\\"
+              os: [windows]
+            - trigger: ":sep"
+              replace: "/"
+              os: [macos, linux]
+            - trigger: ":hi"
+              replace: "Hello"
+        "###).unwrap();
+
+        config.retain_os_matches("windows");
+
+        let replaces: Vec<String> = config.matches.iter().map(|m| m.expand(m.trigger())).collect();
+        assert_eq!(replaces, vec!["\\".to_owned(), "Hello".to_owned()]);
+    }
+
+    #[test]
+    fn test_config_set_drops_matches_of_other_os() {
+        let other_os = if cfg!(target_os = "linux") { "windows" } else { "linux" };
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(&format!(r###"
+        matches:
+            - trigger: ":other"
+              replace: "other"
+              os: [{}]
+            - trigger: ":current"
+              replace: "current"
+              os: [{}]
+        "###, other_os, std::env::consts::OS));
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let triggers: Vec<&str> = config_set.default.matches.iter().map(|m| m.trigger()).collect();
+        assert_eq!(triggers, vec![":current"]);
+    }
+
+    #[test]
+    fn test_configs_validate_accumulates_errors() {
+        let config: Configs = serde_yaml::from_str(r###"
+        filter_class: "["
+        filter_exec: "("
+        global_vars:
+            - name: today
+              type: date
+              params:
+                format: "%"
+        "###).unwrap();
+
+        assert_eq!(config.validate().len(), 3);
+    }
+
+    #[test]
+    fn test_config_set_specific_file_with_empty_trigger_fails() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        let user_defined_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
+        matches:
+          - trigger: ""
+            replace: "hello"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        assert_eq!(config_set.unwrap_err(), ConfigLoadError::EmptyTrigger(user_defined_path));
+    }
+
+    #[test]
+    fn test_config_set_default_file_with_empty_trigger_fails() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+          - triggers: [":hello", ""]
+            replace: "hello"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        assert_eq!(config_set.unwrap_err(), ConfigLoadError::EmptyTrigger(data_dir.path().join(DEFAULT_CONFIG_FILE_NAME)));
+    }
+
+    #[test]
+    fn test_config_set_hotkey_match_with_empty_trigger_is_loaded() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+          - trigger: ""
+            replace: "hello"
+            hotkey: "CTRL+H"
+          - replace: "world"
+            hotkey: "CTRL+W"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.matches.len(), 2);
+        assert!(config_set.default.matches.iter().all(|m| m.triggers().is_empty()));
+    }
+
+    #[test]
+    fn test_config_set_empty_replacement_is_allowed() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+          - trigger: ":empty"
+            replace: ""
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        assert!(config_set.is_ok());
+    }
+
+    #[test]
+    fn test_config_set_specific_file_missing_name_auto_generated() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        let user_defined_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
+        backend: Clipboard
+        "###);
+        let user_defined_path_copy = user_defined_path.clone();
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        assert!(config_set.is_ok());
+        assert_eq!(config_set.unwrap().specific[0].name, user_defined_path_copy.to_str().unwrap_or_default())
+    }
+
+    #[test]
+    fn test_config_set_specific_file_duplicate_name() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        let user_defined_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific1
+        "###);
+
+        let user_defined_path2 = create_user_config_file(data_dir.path(), "specific2.yml", r###"
+        name: specific1
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        assert!(config_set.is_err());
+        assert!(variant_eq(&config_set.unwrap_err(), &ConfigLoadError::NameDuplicate(PathBuf::new())))
+    }
+
+    pub fn create_profile(config_dir: &Path, name: &str, default_content: &str) -> PathBuf {
+        let profile_dir = profile_path(config_dir, name);
+        create_dir_all(&profile_dir).unwrap();
+        fs::write(profile_dir.join(DEFAULT_CONFIG_FILE_NAME), default_content).unwrap();
+
+        profile_dir
+    }
+
+    #[test]
+    fn test_config_set_load_profiles() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+        create_profile(data_dir.path(), "work", r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Regards, ACME"
+        "###);
+        let personal_dir = create_profile(data_dir.path(), "personal", r###"
+        matches:
+            - trigger: ":cheers"
+              replace: "Cheers"
+        "###);
+        create_user_config_file(&personal_dir, "extra.yml", r###"
+        parent: default
+        matches:
+            - trigger: ":home"
+              replace: "Home sweet home"
+        "###);
+
+        let work = ConfigSet::load_profile_from_dir(data_dir.path(), package_dir.path(), "work").unwrap();
+        let personal = ConfigSet::load_profile_from_dir(data_dir.path(), package_dir.path(), "personal").unwrap();
+
+        assert_eq!(work.default.matches.len(), 1);
+        assert_eq!(personal.default.matches.len(), 2);
+        assert_eq!(work.default.matches[0].trigger(), ":sig");
+        assert!(personal.default.matches.iter().all(|m| m.trigger() != ":sig"));
+    }
+
+    #[test]
+    fn test_config_set_load_missing_profile() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        let config_set = ConfigSet::load_profile_from_dir(data_dir.path(), package_dir.path(), "work");
+        assert_eq!(config_set.unwrap_err(), ConfigLoadError::ProfileNotFound(profile_path(data_dir.path(), "work")));
+    }
+
+    #[test]
+    fn test_config_set_load_from_file_default_and_specifics() {
+        let data_dir = TempDir::new().expect("unable to create data directory");
+        let path = create_temp_file_in_dir(&data_dir.path().to_owned(), "combined.yml", r###"
+        configs:
+            - matches:
+                - trigger: ":hi"
+                  replace: "Hello"
+            - name: chrome
+              filter_class: Chrome
+              matches:
+                - trigger: ":web"
+                  replace: "Browser"
+            - name: term
+              filter_title: Terminal
+              exclude_default_entries: true
+              matches:
+                - trigger: ":cmd"
+                  replace: "Command"
+        "###);
+
+        let config_set = ConfigSet::load_from_file(&path).unwrap();
+        assert_eq!(config_set.default.matches.len(), 1);
+        assert_eq!(config_set.specific.len(), 2);
+
+        let chrome = config_set.specific.iter().find(|c| c.name == "chrome").unwrap();
+        assert!(chrome.matches.iter().any(|m| m.trigger() == ":web"));
+        assert!(chrome.matches.iter().any(|m| m.trigger() == ":hi"));
+
+        let term = config_set.specific.iter().find(|c| c.name == "term").unwrap();
+        assert_eq!(term.matches.len(), 1);
+        assert_eq!(term.matches[0].trigger(), ":cmd");
+    }
+
+    #[test]
+    fn test_config_set_load_from_file_merges_children() {
+        let data_dir = TempDir::new().expect("unable to create data directory");
+        let path = create_temp_file_in_dir(&data_dir.path().to_owned(), "combined.yml", r###"
+        configs:
+            - matches:
+                - trigger: ":hi"
+                  replace: "Hello"
+            - parent: default
+              matches:
+                - trigger: ":bye"
+                  replace: "Goodbye"
+        "###);
+
+        let config_set = ConfigSet::load_from_file(&path).unwrap();
+        assert!(config_set.specific.is_empty());
+        assert_eq!(config_set.default.matches.len(), 2);
+    }
+
+    #[test]
+    fn test_config_set_load_from_file_duplicate_name() {
+        let data_dir = TempDir::new().expect("unable to create data directory");
+        let path = create_temp_file_in_dir(&data_dir.path().to_owned(), "combined.yml", r###"
+        configs:
+            - name: default
+            - name: specific1
+            - name: specific1
+        "###);
+
+        let config_set = ConfigSet::load_from_file(&path);
+        assert!(variant_eq(&config_set.unwrap_err(), &ConfigLoadError::NameDuplicate(PathBuf::new())))
+    }
+
+    #[test]
+    fn test_config_set_load_from_file_reserved_fields() {
+        let data_dir = TempDir::new().expect("unable to create data directory");
+        let path = create_temp_file_in_dir(&data_dir.path().to_owned(), "combined.yml", r###"
+        configs:
+            - name: default
+            - name: specific1
+              config_caching_interval: 10000
+        "###);
+
+        let config_set = ConfigSet::load_from_file(&path);
+        assert!(variant_eq(&config_set.unwrap_err(), &ConfigLoadError::InvalidParameter(PathBuf::new(), Vec::new())))
+    }
+
+    #[test]
+    fn test_config_set_load_from_file_missing_configs_list() {
+        let data_dir = TempDir::new().expect("unable to create data directory");
+        let path = create_temp_file_in_dir(&data_dir.path().to_owned(), "combined.yml", r###"
+        matches:
+            - trigger: ":hi"
+              replace: "Hello"
+        "###);
+
+        let config_set = ConfigSet::load_from_file(&path);
+        assert!(variant_eq(&config_set.unwrap_err(), &ConfigLoadError::InvalidYAML(PathBuf::new(),
+            <serde_yaml::Error as serde::de::Error>::custom(""))))
+    }
+
+    fn replace_of<'a>(matches: &'a [Match], trigger: &str) -> &'a str {
+        match &matches.iter().find(|m| m.trigger() == trigger).unwrap().content {
+            MatchContentType::Text(content) => content.replace.as_str(),
+            _ => panic!("not a text match"),
+        }
+    }
+
+    #[test]
+    fn test_config_set_imports_later_import_wins() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        imports:
+            - "snippets/first.yml"
+            - "snippets/second.yml"
+        "###);
+        let snippets_dir = data_dir.path().join("snippets");
+        create_dir_all(&snippets_dir).unwrap();
+        create_temp_file_in_dir(&snippets_dir, "first.yml", r###"
+        matches:
+            - trigger: ":sig"
+              replace: "first"
+            - trigger: ":one"
+              replace: "one"
+        "###);
+        create_temp_file_in_dir(&snippets_dir, "second.yml", r###"
+        matches:
+            - trigger: ":sig"
+              replace: "second"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.matches.len(), 2);
+        assert_eq!(replace_of(&config_set.default.matches, ":sig"), "second");
+        assert_eq!(replace_of(&config_set.default.matches, ":one"), "one");
+    }
+
+    #[test]
+    fn test_config_set_imports_own_matches_win() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        imports:
+            - "first.yml"
+            - "second.yml"
+        matches:
+            - trigger: ":sig"
+              replace: "own"
+        "###);
+        create_temp_file_in_dir(&data_dir.path().to_owned(), "first.yml", r###"
+        matches:
+            - trigger: ":sig"
+              replace: "first"
+        "###);
+        create_temp_file_in_dir(&data_dir.path().to_owned(), "second.yml", r###"
+        matches:
+            - trigger: ":sig"
+              replace: "second"
+        global_vars:
+            - name: "name"
+              type: "dummy"
+              params:
+                echo: "John"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.matches.len(), 1);
+        assert_eq!(replace_of(&config_set.default.matches, ":sig"), "own");
+        assert_eq!(config_set.default.global_vars.len(), 1);
+    }
+
+    #[test]
+    fn test_config_set_imports_global_vars_are_rendered() {
+        use crate::render::{Renderer, RenderResult};
+        use crate::render::default::DefaultRenderer;
+
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        imports:
+            - "defines.yml"
+        matches:
+            - trigger: ":sig"
+              replace: "Regards, {{name}}"
+            - trigger: ":local"
+              replace: "Hi {{name}}"
+              vars:
+                - name: "name"
+                  type: "dummy"
+                  params:
+                    echo: "Jane"
+        "###);
+        create_temp_file_in_dir(&data_dir.path().to_owned(), "defines.yml", r###"
+        global_vars:
+            - name: "name"
+              type: "dummy"
+              params:
+                echo: "John"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let config = config_set.default.clone();
+        let renderer = DefaultRenderer::new(crate::extension::get_extensions(), config.clone());
+
+        let render = |trigger: &str| {
+            let m = config.matches.iter().find(|m| m.trigger() == trigger).unwrap();
+            match renderer.render_match(m, trigger, &config, vec![]) {
+                RenderResult::Text(text) => text,
+                _ => panic!("expected a text result for {}", trigger),
+            }
+        };
+        assert_eq!(render(":sig"), "Regards, John");
+        assert_eq!(render(":local"), "Hi Jane");
+    }
+
+    #[test]
+    fn test_config_set_imports_missing_file() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        imports:
+            - "missing.yml"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        assert!(config_set.is_err());
+    }
+
+    #[test]
+    fn test_config_set_load_cached_modified_import_invalidates() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        cache_configs: true
+        imports:
+            - "snippets.yml"
+        "###);
+        let import_path = create_temp_file_in_dir(&data_dir.path().to_owned(), "snippets.yml", r###"
+        matches:
+            - trigger: ":sig"
+              replace: "old"
+        "###);
+
+        ConfigSet::load_cached(data_dir.path(), package_dir.path(), None).unwrap();
+
+        fs::write(&import_path, r###"
+        matches:
+            - trigger: ":sig"
+              replace: "new"
+        "###).unwrap();
+
+        // Make sure the cached modification time differs, regardless of the file system resolution
+        let cache_file = data_dir.path().join(CONFIG_CACHE_FILE_NAME);
+        let mut disk_cache: ConfigSetDiskCache = serde_json::from_str(&fs::read_to_string(&cache_file).unwrap()).unwrap();
+        for source in disk_cache.sources.iter_mut() {
+            if source.0 == import_path {
+                source.1 = SystemTime::UNIX_EPOCH;
+            }
+        }
+        fs::write(&cache_file, serde_json::to_string(&disk_cache).unwrap()).unwrap();
+
+        let config_set = ConfigSet::load_cached(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(replace_of(&config_set.default.matches, ":sig"), "new");
+    }
+
+    #[test]
+    fn test_config_set_load_cached_modified_replace_file_invalidates() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        cache_configs: true
+        matches:
+            - trigger: ":sig"
+              replace_file: "signature.txt"
+              replace_file_load: startup
+        "###);
+        let replace_path = create_temp_file_in_dir(&data_dir.path().to_owned(), "signature.txt", "old");
+
+        ConfigSet::load_cached(data_dir.path(), package_dir.path(), None).unwrap();
+
+        fs::write(&replace_path, "new").unwrap();
+
+        // Make sure the cached modification time differs, regardless of the file system resolution
+        let cache_file = data_dir.path().join(CONFIG_CACHE_FILE_NAME);
+        let mut disk_cache: ConfigSetDiskCache = serde_json::from_str(&fs::read_to_string(&cache_file).unwrap()).unwrap();
+        assert!(disk_cache.sources.iter().any(|source| source.0 == replace_path));
+        for source in disk_cache.sources.iter_mut() {
+            if source.0 == replace_path {
+                source.1 = SystemTime::UNIX_EPOCH;
+            }
+        }
+        fs::write(&cache_file, serde_json::to_string(&disk_cache).unwrap()).unwrap();
+
+        let config_set = ConfigSet::load_cached(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(replace_of(&config_set.default.matches, ":sig"), "new");
+    }
+
+    #[test]
+    fn test_user_defined_config_set_merge_with_parent_matches() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":lol"
+              replace: "LOL"
+            - trigger: ":yess"
+              replace: "Bob"
+        "###);
+
+        let user_defined_path = create_user_config_file(data_dir.path(), "specific1.yml", r###"
+        name: specific1
+
+        matches:
+            - trigger: "hello"
+              replace: "newstring"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.matches.len(), 2);
+        assert_eq!(config_set.specific[0].matches.len(), 3);
+
+        assert!(config_set.specific[0].matches.iter().find(|x| x.trigger() == "hello").is_some());
+        assert!(config_set.specific[0].matches.iter().find(|x| x.trigger() == ":lol").is_some());
+        assert!(config_set.specific[0].matches.iter().find(|x| x.trigger() == ":yess").is_some());
+    }
+
+    #[test]
+    fn test_user_defined_config_set_merge_with_multiple_triggers() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - triggers: [":lol", ":haha"]
+              replace: "LOL"
+            - trigger: ":yess"
+              replace: "Bob"
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific1
+
+        matches:
+            - trigger: ":haha"
+              replace: "newstring"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.specific[0].matches.len(), 2);
+        assert!(config_set.specific[0].matches.iter().all(|m| m.trigger() != ":lol"));
+        assert!(config_set.specific[0].matches.iter().any(|m| m.trigger() == ":haha"));
+    }
+
+    #[test]
+    fn test_user_defined_config_set_merge_with_parent_matches_child_priority() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":lol"
+              replace: "LOL"
+            - trigger: ":yess"
+              replace: "Bob"
+        "###);
+
+        let user_defined_path2 = create_user_config_file(data_dir.path(), "specific2.yml", r###"
+        name: specific1
+
+        matches:
+            - trigger: ":lol"
+              replace: "newstring"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.matches.len(), 2);
+        assert_eq!(config_set.specific[0].matches.len(), 2);
+
+        assert!(config_set.specific[0].matches.iter().find(|x| {
+            if let MatchContentType::Text(content) = &x.content {
+                x.trigger() == ":lol" && content.replace == "newstring"
+            }else{
+                false
+            }
+        }).is_some());
+        assert!(config_set.specific[0].matches.iter().find(|x| x.trigger() == ":yess").is_some());
+    }
+
+    fn text_replace_of<'a>(matches: &'a [Match], trigger: &str) -> &'a str {
+        match &matches.iter().find(|m| m.trigger() == trigger).unwrap().content {
+            MatchContentType::Text(content) => &content.replace,
+            _ => panic!("not a text match"),
+        }
+    }
+
+    #[test]
+    fn test_user_defined_config_set_merge_append_to_parent_match() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards, Jon"
+        "###);
+
+        create_user_config_file(data_dir.path(), "child.yml", r###"
+        parent: default
+        matches:
+            - trigger: ":sig"
+              replace: "\nSent from {{device}}"
+              merge: append
+              vars:
+                - name: device
+                  type: dummy
+                  params:
+                    echo: "my phone"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.matches.len(), 1);
+        assert_eq!(text_replace_of(&config_set.default.matches, ":sig"), "Best regards, Jon\nSent from {{device}}");
+        assert_eq!(config_set.default.matches[0].expand(":sig"), "Best regards, Jon\nSent from my phone");
+    }
+
+    #[test]
+    fn test_user_defined_config_set_merge_prepend_to_default_match() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Jon"
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        filter_class: Thunderbird
+        matches:
+            - trigger: ":sig"
+              replace: "Cheers, "
+              merge: prepend
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(text_replace_of(&config_set.default.matches, ":sig"), "Jon");
+        assert_eq!(config_set.specific[0].matches.len(), 1);
+        assert_eq!(text_replace_of(&config_set.specific[0].matches, ":sig"), "Cheers, Jon");
+    }
+
+    #[test]
+    fn test_user_defined_config_set_merge_replace_child_is_not_appended_again() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "A"
+        "###);
+
+        create_user_config_file(data_dir.path(), "child.yml", r###"
+        name: child
+        parent: default
+        matches:
+            - trigger: ":sig"
+              replace: "B"
+        "###);
+
+        create_user_config_file(data_dir.path(), "grandchild.yml", r###"
+        parent: child
+        matches:
+            - trigger: ":sig"
+              replace: "C"
+              merge: append
+        "###);
+
+        // The child replaces the default match, after being extended by the grandchild
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(text_replace_of(&config_set.default.matches, ":sig"), "BC");
+    }
+
+    #[test]
+    fn test_user_defined_config_set_exclude_merge_with_parent_matches() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":lol"
+              replace: "LOL"
+            - trigger: ":yess"
+              replace: "Bob"
+        "###);
+
+        let user_defined_path2 = create_user_config_file(data_dir.path(), "specific2.yml", r###"
+        name: specific1
+
+        exclude_default_entries: true
+
+        matches:
+            - trigger: "hello"
+              replace: "newstring"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.matches.len(), 2);
+        assert_eq!(config_set.specific[0].matches.len(), 1);
+
+        assert!(config_set.specific[0].matches.iter().find(|x| {
+            if let MatchContentType::Text(content) = &x.content {
+                x.trigger() == "hello" && content.replace == "newstring"
+            }else{
+                false
+            }
+        }).is_some());
+    }
+
+    #[test]
+    fn test_only_yaml_files_are_loaded_from_config() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(
+            r###"
+            matches:
+                - trigger: ":lol"
+                  replace: "LOL"
+                - trigger: ":yess"
+                  replace: "Bob"
+            "###
+        );
+
+        let user_defined_path2 = create_user_config_file(data_dir.path(), "specific.zzz", r###"
+        name: specific1
+
+        exclude_default_entries: true
+
+        matches:
+            - trigger: "hello"
+              replace: "newstring"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.specific.len(), 0);
+    }
+
+    #[test]
+    fn test_config_set_exclude_pattern_skips_files() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        exclude:
+            - "*.disabled.yml"
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific1
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific2.disabled.yml", r###"
+        name: specific2
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.specific.len(), 1);
+        assert_eq!(config_set.specific[0].name, "specific1");
+    }
+
+    #[test]
+    fn test_config_set_include_pattern_selects_files() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        include:
+            - "*.yml"
+            - "*.yaml"
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific1
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific2.yaml", r###"
+        name: specific2
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific3.txt", r###"
+        name: specific3
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.specific.len(), 2);
+        assert!(config_set.specific.iter().any(|c| c.name == "specific1"));
+        assert!(config_set.specific.iter().any(|c| c.name == "specific2"));
+    }
+
+    #[test]
+    fn test_config_set_exclude_pattern_with_directory() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        exclude:
+            - "package1/**"
+        "###);
+
+        create_package_file(package_dir.path(), "package1", "package.yml", r###"
+        name: package1
+        "###);
+
+        create_package_file(package_dir.path(), "package2", "package.yml", r###"
+        name: package2
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.specific.len(), 1);
+        assert_eq!(config_set.specific[0].name, "package2");
+    }
+
+    #[test]
+    fn test_config_set_export_effective_contains_package_matches() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":hi"
+              replace: "Hello"
+        "###);
+
+        create_package_file(package_dir.path(), "package1", "package.yml", r###"
+        parent: default
+        matches:
+            - trigger: ":pkg"
+              replace: "From package"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let exported = config_set.export_effective();
+
+        let value: serde_yaml::Value = serde_yaml::from_str(&exported).unwrap();
+        let triggers: Vec<&str> = value["default"]["matches"].as_sequence().unwrap().iter()
+            .filter_map(|m| m["trigger"].as_str())
+            .collect();
+        assert!(triggers.contains(&":hi"));
+        assert!(triggers.contains(&":pkg"));
+        assert!(value["specific"].as_sequence().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_config_to_yaml_reload_yields_same_matches() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        global_vars:
+            - name: greeting
+              type: dummy
+              params:
+                echo: "Hello"
+        matches:
+            - trigger: ":hi"
+              replace: "{{greeting}}"
+            - triggers: [":sig", ":regards"]
+              replace: "Best regards"
+              word: true
+              hotkey: "CTRL+ALT+S"
+            - trigger: ":date"
+              replace: "{{today}}"
+              vars:
+                - name: today
+                  type: date
+                  params:
+                    format: "%Y-%m-%d"
+        "###);
+
+        create_package_file(package_dir.path(), "package1", "package.yml", r###"
+        parent: default
+        matches:
+            - trigger: ":pkg"
+              replace: "From package"
+              tags: [package]
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        filter_class: Code
+        matches:
+            - trigger: ":code"
+              replace: "fn $|$()"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+
+        for config in std::iter::once(&config_set.default).chain(config_set.specific.iter()) {
+            let value: serde_yaml::Value = serde_yaml::from_str(&config.to_yaml()).unwrap();
+            let reloaded = Configs::from_yaml_value(value, data_dir.path()).unwrap();
+
+            assert_eq!(reloaded.name, config.name);
+            assert_eq!(reloaded.matches, config.matches);
+            assert_eq!(reloaded.global_vars, config.global_vars);
+        }
+    }
+
+    #[test]
+    fn test_config_interpolation_uses_present_variable() {
+        std::env::set_var("ESPANSO_TEST_INTERPOLATION_PORT", "4000");
+        std::env::set_var("ESPANSO_TEST_INTERPOLATION_EDITOR", "Code");
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        ipc_server_port: ${ESPANSO_TEST_INTERPOLATION_PORT:-34982}
+        "###);
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        filter_class: "^${ESPANSO_TEST_INTERPOLATION_EDITOR}$"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.ipc_server_port, 4000);
+        assert_eq!(config_set.specific[0].filter_class, "^Code$");
+    }
+
+    #[test]
+    fn test_config_interpolation_uses_default_of_absent_variable() {
+        std::env::remove_var("ESPANSO_TEST_INTERPOLATION_ABSENT");
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        ipc_server_port: ${ESPANSO_TEST_INTERPOLATION_ABSENT:-5000}
+        word_separator_strings: ["${ESPANSO_TEST_INTERPOLATION_ABSENT:-->}"]
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.ipc_server_port, 5000);
+        assert_eq!(config_set.default.word_separator_strings, vec!["->".to_owned()]);
+    }
+
+    #[test]
+    fn test_config_interpolation_absent_variable_without_default() {
+        std::env::remove_var("ESPANSO_TEST_INTERPOLATION_UNDEFINED");
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        filter_title: "${ESPANSO_TEST_INTERPOLATION_UNDEFINED}"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        let default_path = data_dir.path().join(DEFAULT_CONFIG_FILE_NAME);
+        assert_eq!(config_set.unwrap_err(), ConfigLoadError::UndefinedVariable(default_path, "ESPANSO_TEST_INTERPOLATION_UNDEFINED".to_owned()));
+    }
+
+    #[test]
+    fn test_config_interpolation_skips_matches() {
+        std::env::remove_var("ESPANSO_TEST_INTERPOLATION_SHELL");
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":path"
+              replace: "echo ${ESPANSO_TEST_INTERPOLATION_SHELL}"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(text_replace_of(&config_set.default.matches, ":path"), "echo ${ESPANSO_TEST_INTERPOLATION_SHELL}");
+    }
+
+    #[test]
+    fn test_config_backend_auto_is_parsed() {
+        let config: Configs = serde_yaml::from_str("backend: Auto").unwrap();
+        assert_eq!(config.backend, BackendType::Auto);
+    }
+
+    #[test]
+    fn test_config_set_max_trigger_length() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":a"
+              replace: "first"
+            - triggers: [":ab", ":àèìòù"]
+              replace: "second"
+            - trigger: ":hello"
+              replace: "word"
+              word: true
+            - trigger: ":averylongpassive"
+              replace: "passive"
+              passive_only: true
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        filter_class: Code
+        exclude_default_entries: true
+        matches:
+            - trigger: ":code"
+              replace: "code"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.max_trigger_length(), 6);
+        assert_eq!(config_set.specific[0].max_trigger_length(), 5);
+    }
+
+    #[test]
+    fn test_config_set_trigger_exceeding_buffer_size_warns() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        max_buffer_size: 8
+        matches:
+            - trigger: ":short"
+              replace: "short"
+            - trigger: ":verylongtrigger"
+              replace: "long"
+        "###);
+
+        let (_, warnings) = ConfigSet::load_with_warnings(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(warnings, vec![ConfigWarning::TriggerExceedsBufferSize(":verylongtrigger".to_owned(), 8)]);
+    }
+
+    fn load_overlapping_configs() -> ConfigSet {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":hi"
+              replace: "Hello"
+            - trigger: ":sig"
+              replace: "Default signature"
+        "###);
+
+        create_user_config_file(data_dir.path(), "gmail.yml", r###"
+        name: gmail
+        filter_exec: "gmail"
+        matches:
+            - trigger: ":sig"
+              replace: "Gmail signature"
+        "###);
+
+        create_user_config_file(data_dir.path(), "browser.yml", r###"
+        name: browser
+        filter_title: "Firefox"
+        matches:
+            - trigger: ":sig"
+              replace: "Browser signature"
+            - trigger: ":search"
+              replace: "Browser search"
+        "###);
+
+        ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap()
+    }
+
+    fn resolved_replace(config: &Configs, trigger: &str) -> String {
+        let m = config.matches.iter().find(|m| m.trigger() == trigger).unwrap();
+        m.expand(trigger)
+    }
+
+    #[test]
+    fn test_config_set_specific_sorted_by_order_then_name() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        // The file names are chosen so that the discovery order differs from the expected one
+        for (file, name, order) in [("a.yml", "first_file", "order: 10"), ("b.yml", "beta", ""),
+                                    ("c.yml", "alpha", ""), ("d.yml", "last_file", "order: -1")].iter() {
+            create_user_config_file(data_dir.path(), file, &format!(r###"
+            name: {}
+            filter_class: Code
+            {}
+            "###, name, order));
+        }
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let names: Vec<&str> = config_set.specific.iter().map(|config| config.name.as_str()).collect();
+        assert_eq!(names, vec!["last_file", "alpha", "beta", "first_file"]);
+    }
+
+    #[test]
+    fn test_config_set_duplicate_labels_in_one_file() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+              label: signature
+            - trigger: ":sig2"
+              replace: "Kind regards"
+              label: signature
+            - trigger: ":hi"
+              replace: "Hello"
+        "###);
+
+        let (config_set, warnings) = ConfigSet::load_with_warnings(data_dir.path(), package_dir.path(), None).unwrap();
+        assert!(warnings.contains(&ConfigWarning::DuplicateLabel("default".to_owned(), "signature".to_owned())));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":sig"));
+        assert!(!config_set.default.matches.iter().any(|m| m.trigger() == ":sig2"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":hi"));
+    }
+
+    #[test]
+    fn test_config_set_duplicate_labels_across_two_files() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+              label: signature
+        "###);
+        create_user_config_file(data_dir.path(), "child.yml", r###"
+        parent: default
+        matches:
+            - trigger: ":work"
+              replace: "Kind regards"
+              label: signature
+        "###);
+
+        let (config_set, warnings) = ConfigSet::load_with_warnings(data_dir.path(), package_dir.path(), None).unwrap();
+        assert!(warnings.contains(&ConfigWarning::DuplicateLabel("default".to_owned(), "signature".to_owned())));
+
+        // The child match wins
+        let labeled: Vec<&str> = config_set.default.matches.iter()
+            .filter(|m| m.label.is_some())
+            .map(|m| m.trigger())
+            .collect();
+        assert_eq!(labeled, vec![":work"]);
+    }
+
+    #[test]
+    fn test_config_set_duplicate_labels_strict_mode() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        strict: true
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+              label: signature
+            - trigger: ":sig2"
+              replace: "Kind regards"
+              label: signature
+        "###);
+
+        let result = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        assert_eq!(result.unwrap_err(), ConfigLoadError::DuplicateLabel("default".to_owned(), "signature".to_owned()));
+    }
+
+    #[test]
+    fn test_config_set_load_is_reproducible() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+        for file in ["d.yml", "b.yml", "nested/c.yml", "a.yml"].iter() {
+            let path = data_dir.path().join(USER_CONFIGS_FOLDER_NAME).join(file);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "filter_class: Code\nmatches:\n  - trigger: \":hi\"\n    replace: \"hello\"\n").unwrap();
+        }
+
+        let first = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let second = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+
+        let names = |config_set: &ConfigSet| -> Vec<String> {
+            config_set.specific.iter().map(|config| config.name.clone()).collect()
+        };
+        assert_eq!(names(&first), names(&second));
+        assert_eq!(first.specific.len(), 4);
+    }
+
+    #[test]
+    fn test_config_set_duplicate_name_reports_last_file_by_path() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+        create_user_config_file(data_dir.path(), "b.yml", "name: duplicate");
+        create_user_config_file(data_dir.path(), "a.yml", "name: duplicate");
+
+        let expected = data_dir.path().join(USER_CONFIGS_FOLDER_NAME).join("b.yml");
+        for _ in 0..2 {
+            let result = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+            assert_eq!(result.unwrap_err(), ConfigLoadError::NameDuplicate(expected.clone()));
+        }
+    }
+
+    #[test]
+    fn test_config_set_resolve_for_window_lower_order_wins() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+        create_user_config_file(data_dir.path(), "a.yml", r###"
+        name: a
+        filter_class: Code
+        order: 2
+        matches:
+            - trigger: ":hi"
+              replace: "from a"
+        "###);
+        create_user_config_file(data_dir.path(), "b.yml", r###"
+        name: b
+        filter_class: Code
+        order: 1
+        matches:
+            - trigger: ":hi"
+              replace: "from b"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let resolved = config_set.resolve_for_window("", "Code", "");
+        assert_eq!(resolved_replace(&resolved, ":hi"), "from b");
+    }
+
+    #[test]
+    fn test_config_set_resolve_for_window_narrowest_wins() {
+        let config_set = load_overlapping_configs();
+
+        let resolved = config_set.resolve_for_window("Inbox - Firefox", "", "/opt/gmail/app");
+
+        assert_eq!(resolved.matches.len(), 3);
+        assert_eq!(resolved_replace(&resolved, ":sig"), "Gmail signature");
+        assert_eq!(resolved_replace(&resolved, ":search"), "Browser search");
+        assert_eq!(resolved_replace(&resolved, ":hi"), "Hello");
+    }
+
+    #[test]
+    fn test_config_set_resolve_for_window_inherited_default_does_not_override() {
+        let config_set = load_overlapping_configs();
+
+        // Only the broad config applies
+        let resolved = config_set.resolve_for_window("Docs - Firefox", "", "/usr/bin/firefox");
+        assert_eq!(resolved_replace(&resolved, ":sig"), "Browser signature");
+
+        // No specific config applies
+        let resolved = config_set.resolve_for_window("Terminal", "", "/usr/bin/bash");
+        assert_eq!(resolved_replace(&resolved, ":sig"), "Default signature");
+        assert_eq!(resolved.matches.len(), 2);
+    }
+
+    fn explained_file_name(explanation: &ExpansionExplanation) -> String {
+        explanation.source_file.as_ref().unwrap().file_name().unwrap().to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_config_set_explain_trigger_overridden_in_specific() {
+        let config_set = load_overlapping_configs();
+
+        let explanation = config_set.explain(":sig", "Inbox", "", "/opt/gmail/app").unwrap();
+        assert_eq!(explanation.config_name, "gmail");
+        assert_eq!(explained_file_name(&explanation), "gmail.yml");
+        assert_eq!(explanation.matched.expand(":sig"), "Gmail signature");
+
+        let explanation = config_set.explain(":sig", "Docs - Firefox", "", "/usr/bin/firefox").unwrap();
+        assert_eq!(explanation.config_name, "browser");
+        assert_eq!(explained_file_name(&explanation), "browser.yml");
+    }
+
+    #[test]
+    fn test_config_set_explain_uses_the_config_selected_at_runtime() {
+        let config_set = load_overlapping_configs();
+
+        // The title filter is checked before the exec one, so the browser config is selected
+        let explanation = config_set.explain(":sig", "Inbox - Firefox", "", "/opt/gmail/app").unwrap();
+        assert_eq!(explanation.config_name, "browser");
+        assert_eq!(explanation.matched.expand(":sig"), "Browser signature");
+    }
+
+    #[test]
+    fn test_config_set_explain_trigger_inherited_from_default() {
+        let config_set = load_overlapping_configs();
+
+        let explanation = config_set.explain(":hi", "Inbox", "", "/opt/gmail/app").unwrap();
+        assert_eq!(explanation.config_name, "default");
+        assert_eq!(explained_file_name(&explanation), DEFAULT_CONFIG_FILE_NAME);
+
+        let explanation = config_set.explain(":sig", "Terminal", "", "/usr/bin/bash").unwrap();
+        assert_eq!(explanation.config_name, "default");
+        assert_eq!(explanation.matched.expand(":sig"), "Default signature");
+    }
+
+    #[test]
+    fn test_config_set_explain_missing_trigger() {
+        let config_set = load_overlapping_configs();
+
+        assert!(config_set.explain(":search", "Terminal", "", "/usr/bin/bash").is_none());
+        assert!(config_set.explain(":missing", "Inbox - Firefox", "", "").is_none());
+    }
+
+    #[test]
+    fn test_config_set_explain_imported_match_reports_imported_file() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        imports:
+            - "snippets.yml"
+        "###);
+        let import_path = create_temp_file_in_dir(&data_dir.path().to_owned(), "snippets.yml", r###"
+        matches:
+            - trigger: ":sig"
+              replace: "imported"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let explanation = config_set.explain(":sig", "", "", "").unwrap();
+        assert_eq!(explanation.config_name, "default");
+        assert_eq!(explanation.source_file, Some(import_path));
     }
 
     #[test]
-    fn test_config_file_not_found() {
-        let config = Configs::load_config(Path::new("invalid/path"));
-        assert_eq!(config.is_err(), true);
-        assert_eq!(config.unwrap_err(), ConfigLoadError::FileNotFound);
+    fn test_configs_filter_specificity() {
+        let specificity = |yaml: &str| serde_yaml::from_str::<Configs>(yaml).unwrap().filter_specificity();
+        assert_eq!(specificity("name: none"), 0);
+        assert_eq!(specificity("filter_title: a"), 1);
+        assert_eq!(specificity("filter_title: a\nfilter_class: b"), 2);
+        assert_eq!(specificity("filter_class: b\nfilter_exec: c"), 3);
     }
 
     #[test]
-    fn test_config_file_with_bad_yaml_syntax() {
-        let broken_config_file = create_tmp_file(TEST_CONFIG_FILE_WITH_BAD_YAML);
-        let config = Configs::load_config(broken_config_file.path());
-        match config {
-            Ok(_) => {assert!(false)},
-            Err(e) => {
-                match e {
-                    ConfigLoadError::InvalidYAML(p, _) => assert_eq!(p, broken_config_file.path().to_owned()),
-                    _ => assert!(false),
-                }
-                assert!(true);
-            },
-        }
+    fn test_config_set_matches_with_tag_across_configs() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+              description: "Email signature"
+              tags: ["email"]
+            - trigger: ":hi"
+              replace: "Hello"
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        filter_class: Thunderbird
+        matches:
+            - trigger: ":unsub"
+              replace: "Please unsubscribe me"
+              tags: ["email", "work"]
+        "###);
 
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+
+        let triggers: Vec<&str> = config_set.matches_with_tag("email").iter().map(|m| m.trigger()).collect();
+        assert_eq!(triggers, vec![":sig", ":unsub"]);
+        assert_eq!(config_set.matches_with_tag("work").len(), 1);
+        assert!(config_set.matches_with_tag("personal").is_empty());
+        assert_eq!(config_set.all_matches().len(), 3);
     }
 
     #[test]
-    fn test_validate_field_macro() {
-        let mut result = true;
+    fn test_config_set_matches_with_tag_includes_overrides() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+            - trigger: ":hi"
+              replace: "Hello"
+        "###);
 
-        validate_field!(result, 3, 3);
-        assert_eq!(result, true);
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        filter_class: Thunderbird
+        matches:
+            - trigger: ":sig"
+              replace: "Kind regards"
+              tags: ["email"]
+        "###);
 
-        validate_field!(result, 10, 3);
-        assert_eq!(result, false);
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
 
-        validate_field!(result, 3, 3);
-        assert_eq!(result, false);
+        let email = config_set.matches_with_tag("email");
+        assert_eq!(email.len(), 1);
+        assert_eq!(email[0].expand(":sig"), "Kind regards");
+
+        // The override is listed along with the default match, which is listed only once
+        let sig_count = config_set.all_matches().iter().filter(|m| m.trigger() == ":sig").count();
+        assert_eq!(sig_count, 2);
+        assert_eq!(config_set.all_matches().len(), 3);
     }
 
     #[test]
-    fn test_user_defined_config_does_not_have_reserved_fields() {
-        let working_config_file = create_tmp_file(r###"
-
-        backend: Clipboard
+    fn test_config_set_package_espanso_version_satisfied() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
 
+        create_package_file(package_dir.path(), "package1", "package.yml", r###"
+        name: package1
+        espanso_version: ">=0.1.0"
         "###);
-        let config = Configs::load_config(working_config_file.path());
-        assert_eq!(config.unwrap().validate_user_defined_config(), true);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.specific.len(), 1);
     }
 
     #[test]
-    fn test_user_defined_config_has_reserved_fields_config_caching_interval() {
-        let working_config_file = create_tmp_file(r###"
+    fn test_config_set_package_espanso_version_unsatisfied() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
 
-        # This should not happen in an app-specific config
-        config_caching_interval: 100
+        let package_path = create_package_file(package_dir.path(), "package1", "package.yml", r###"
+        name: package1
+        espanso_version: ">=99.0.0"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        assert_eq!(config_set.unwrap_err(), ConfigLoadError::IncompatibleVersion(package_path, ">=99.0.0".to_owned()));
+    }
+
+    #[test]
+    fn test_config_set_package_manifest_parsed() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
 
+        create_package_file(package_dir.path(), "package1", "_manifest.yml", r###"
+        name: package1
+        version: 0.1.0
+        author: John Doe
+        description: A test package
         "###);
-        let config = Configs::load_config(working_config_file.path());
-        assert_eq!(config.unwrap().validate_user_defined_config(), false);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.packages(), vec![PackageManifest {
+            name: "package1".to_owned(),
+            version: "0.1.0".to_owned(),
+            author: "John Doe".to_owned(),
+            description: "A test package".to_owned(),
+        }]);
+        assert_eq!(config_set.specific.len(), 0);
     }
 
     #[test]
-    fn test_user_defined_config_has_reserved_fields_toggle_key() {
-        let working_config_file = create_tmp_file(r###"
+    fn test_config_set_package_invalid_manifest_has_default_metadata() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
 
-        # This should not happen in an app-specific config
-        toggle_key: CTRL
+        create_package_file(package_dir.path(), "package1", "_manifest.yml", r###"
+        version: [1.2.0
+        "###);
 
+        create_package_file(package_dir.path(), "package1", "package.yml", r###"
+        name: package1
+        matches:
+            - trigger: ":pkg"
+              replace: "package"
         "###);
-        let config = Configs::load_config(working_config_file.path());
-        assert_eq!(config.unwrap().validate_user_defined_config(), false);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.packages(), vec![
+            PackageManifest {
+                name: "package1".to_owned(),
+                version: "".to_owned(),
+                author: "".to_owned(),
+                description: "".to_owned(),
+            },
+        ]);
+        assert_eq!(config_set.specific.len(), 1);
     }
 
     #[test]
-    fn test_user_defined_config_has_reserved_fields_toggle_interval() {
-        let working_config_file = create_tmp_file(r###"
+    fn test_config_set_package_without_manifest_has_default_metadata() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
 
-        # This should not happen in an app-specific config
-        toggle_interval: 1000
+        create_package_file(package_dir.path(), "package2", "_manifest.yml", r###"
+        version: 1.2.0
+        "###);
 
+        create_package_file(package_dir.path(), "package1", "package.yml", r###"
+        name: package1
+        parent: default
         "###);
-        let config = Configs::load_config(working_config_file.path());
-        assert_eq!(config.unwrap().validate_user_defined_config(), false);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.packages(), vec![
+            PackageManifest {
+                name: "package1".to_owned(),
+                version: "".to_owned(),
+                author: "".to_owned(),
+                description: "".to_owned(),
+            },
+            PackageManifest {
+                name: "package2".to_owned(),
+                version: "1.2.0".to_owned(),
+                author: "".to_owned(),
+                description: "".to_owned(),
+            },
+        ]);
     }
 
     #[test]
-    fn test_user_defined_config_has_reserved_fields_backspace_limit() {
-        let working_config_file = create_tmp_file(r###"
+    fn test_config_set_package_manifest_does_not_break_matches() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
 
-        # This should not happen in an app-specific config
-        backspace_limit: 10
+        create_package_file(package_dir.path(), "package1", "_manifest.yml", r###"
+        name: package1
+        version: 0.1.0
+        "###);
 
+        create_package_file(package_dir.path(), "package1", "package.yml", r###"
+        name: package1
+        parent: default
+
+        matches:
+            - trigger: ":pkg"
+              replace: "package"
         "###);
-        let config = Configs::load_config(working_config_file.path());
-        assert_eq!(config.unwrap().validate_user_defined_config(), false);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.packages().len(), 1);
+        assert_eq!(config_set.specific.len(), 0);
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":pkg"));
     }
 
     #[test]
-    fn test_config_loaded_correctly() {
-        let working_config_file = create_tmp_file(TEST_WORKING_CONFIG_FILE);
-        let config = Configs::load_config(working_config_file.path());
-        assert_eq!(config.is_ok(), true);
-    }
+    fn test_config_set_clear_default_matches_keeps_package_matches() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        clear_default_matches: true
+        matches:
+            - trigger: ":espanso"
+              replace: "Hi there!"
+        "###);
 
-    // Test ConfigSet
+        create_package_file(package_dir.path(), "package1", "package.yml", r###"
+        name: package1
+        parent: default
 
-    pub fn create_temp_espanso_directories() -> (TempDir, TempDir) {
-        create_temp_espanso_directories_with_default_content(DEFAULT_CONFIG_FILE_CONTENT)
+        matches:
+            - trigger: ":pkg"
+              replace: "package"
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let triggers: Vec<&str> = config_set.default.matches.iter().map(|m| m.trigger()).collect();
+        assert_eq!(triggers, vec![":pkg"]);
     }
 
-    pub fn create_temp_espanso_directories_with_default_content(default_content: &str) -> (TempDir, TempDir) {
-        let data_dir = TempDir::new().expect("unable to create data directory");
-        let package_dir = TempDir::new().expect("unable to create package directory");
+    #[test]
+    fn test_config_set_clear_default_matches_is_reserved() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
 
-        let default_path = data_dir.path().join(DEFAULT_CONFIG_FILE_NAME);
-        fs::write(default_path, default_content);
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        clear_default_matches: true
+        "###);
 
-        (data_dir, package_dir)
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        assert!(config_set.is_err());
     }
 
-    pub fn create_temp_file_in_dir(tmp_dir: &PathBuf, name: &str, content: &str) -> PathBuf {
-        let user_defined_path = tmp_dir.join(name);
-        let user_defined_path_copy = user_defined_path.clone();
-        fs::write(user_defined_path, content);
+    #[test]
+    fn test_config_replace_file_loaded_at_startup() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
 
-        user_defined_path_copy
+        let config_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        matches:
+            - trigger: ":legal"
+              replace_file: "disclaimer.txt"
+              replace_file_load: startup
+        "###);
+        fs::write(config_path.parent().unwrap().join("disclaimer.txt"), "All rights reserved {{year}}").unwrap();
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let m = config_set.specific[0].matches.iter().find(|m| m.trigger() == ":legal").unwrap();
+        match &m.content {
+            MatchContentType::Text(content) => {
+                assert_eq!(content.replace, "All rights reserved {{year}}");
+                assert!(content._has_vars);
+                assert!(content.replace_file.is_none());
+            },
+            _ => assert!(false),
+        }
     }
 
-    pub fn create_user_config_file(tmp_dir: &Path, name: &str, content: &str) -> PathBuf {
-        let user_config_dir = tmp_dir.join(USER_CONFIGS_FOLDER_NAME);
-        if !user_config_dir.exists() {
-            create_dir_all(&user_config_dir);
-        }
+    #[test]
+    fn test_config_replace_file_resolved_against_config_dir() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
 
-        create_temp_file_in_dir(&user_config_dir, name, content)
-    }
+        let config_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        matches:
+            - trigger: ":legal"
+              replace_file: "disclaimer.txt"
+        "###);
 
-    pub fn create_package_file(package_data_dir: &Path, package_name: &str, filename: &str, content: &str) -> PathBuf {
-        let package_dir = package_data_dir.join(package_name);
-        if !package_dir.exists() {
-            create_dir_all(&package_dir);
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let m = config_set.specific[0].matches.iter().find(|m| m.trigger() == ":legal").unwrap();
+        match &m.content {
+            MatchContentType::Text(content) => {
+                assert_eq!(content.replace_file, Some(config_path.parent().unwrap().join("disclaimer.txt")));
+            },
+            _ => assert!(false),
         }
-
-        create_temp_file_in_dir(&package_dir, filename, content)
     }
 
     #[test]
-    fn test_config_set_default_content_should_work_correctly() {
+    fn test_config_replace_and_replace_file_fails_to_load() {
         let (data_dir, package_dir) = create_temp_espanso_directories();
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path());
-        assert!(config_set.is_ok());
-    }
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        matches:
+            - trigger: ":legal"
+              replace: "text"
+              replace_file: "disclaimer.txt"
+        "###);
 
-    #[test]
-    fn test_config_set_load_fail_bad_directory() {
-        let config_set = ConfigSet::load(Path::new("invalid/path"), Path::new("invalid/path"));
-        assert_eq!(config_set.is_err(), true);
-        assert_eq!(config_set.unwrap_err(), ConfigLoadError::InvalidConfigDirectory);
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        match config_set {
+            Err(ConfigLoadError::InvalidYAML(_, _)) => {},
+            _ => assert!(false),
+        }
     }
 
     #[test]
-    fn test_config_set_missing_default_file() {
-        let data_dir = TempDir::new().expect("unable to create temp directory");
-        let package_dir = TempDir::new().expect("unable to create package directory");
+    fn test_config_groups_are_flattened_into_matches() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":hi"
+              replace: "hello"
+
+        groups:
+            - word: true
+              matches:
+                  - trigger: "btw"
+                    replace: "by the way"
+                  - trigger: "afaik"
+                    replace: "as far as I know"
+                    word: false
+        "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path());
-        assert_eq!(config_set.is_err(), true);
-        assert_eq!(config_set.unwrap_err(), ConfigLoadError::FileNotFound);
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let matches = &config_set.default.matches;
+        assert_eq!(matches.len(), 3);
+        assert_eq!(matches[0].trigger(), ":hi");
+        assert!(!matches[0].word);
+        assert_eq!(matches[1].trigger(), "btw");
+        assert!(matches[1].word);
+        assert_eq!(matches[2].trigger(), "afaik");
+        assert!(!matches[2].word);
+        assert!(config_set.default.groups.is_empty());
     }
 
     #[test]
-    fn test_config_set_invalid_yaml_syntax() {
-        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(
-            TEST_CONFIG_FILE_WITH_BAD_YAML
-        );
-        let default_path = data_dir.path().join(DEFAULT_CONFIG_FILE_NAME);
+    fn test_config_groups_with_invalid_match_fail_to_load() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        groups:
+            - word: true
+              matches:
+                  - replace: "missing trigger"
+        "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path());
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
         match config_set {
-            Ok(_) => {assert!(false)},
-            Err(e) => {
-                match e {
-                    ConfigLoadError::InvalidYAML(p, _) => assert_eq!(p, default_path),
-                    _ => assert!(false),
-                }
-                assert!(true);
-            },
+            Err(ConfigLoadError::InvalidYAML(_, _)) => {},
+            _ => assert!(false),
         }
     }
 
     #[test]
-    fn test_config_set_specific_file_with_reserved_fields() {
-        let (data_dir, package_dir) = create_temp_espanso_directories();
+    fn test_config_set_load_cached_hit_skips_parsing() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content("cache_configs: true");
 
-        let user_defined_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
-        config_caching_interval: 10000
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        matches:
+            - trigger: ":hello"
+              replace: "Hello"
         "###);
-        let user_defined_path_copy = user_defined_path.clone();
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path());
-        assert!(config_set.is_err());
-        assert_eq!(config_set.unwrap_err(), ConfigLoadError::InvalidParameter(user_defined_path_copy))
+        let config_set = ConfigSet::load_cached(data_dir.path(), package_dir.path(), None).unwrap();
+        let cache_file = data_dir.path().join(CONFIG_CACHE_FILE_NAME);
+        assert!(cache_file.exists());
+
+        // Alter the cached set, which is only returned if the files are not parsed again
+        let mut disk_cache: ConfigSetDiskCache = serde_json::from_str(&fs::read_to_string(&cache_file).unwrap()).unwrap();
+        disk_cache.config_set.specific[0].name = "cached".to_owned();
+        fs::write(&cache_file, serde_json::to_string(&disk_cache).unwrap()).unwrap();
+
+        let cached_set = ConfigSet::load_cached(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(cached_set.specific.len(), config_set.specific.len());
+        assert_eq!(cached_set.specific[0].name, "cached");
+        assert!(cached_set.specific[0].matches.iter().any(|m| m.trigger() == ":hello"));
     }
 
     #[test]
-    fn test_config_set_specific_file_missing_name_auto_generated() {
-        let (data_dir, package_dir) = create_temp_espanso_directories();
+    fn test_config_set_load_cached_modified_file_invalidates() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content("cache_configs: true");
 
-        let user_defined_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
-        backend: Clipboard
+        let user_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
         "###);
-        let user_defined_path_copy = user_defined_path.clone();
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path());
-        assert!(config_set.is_ok());
-        assert_eq!(config_set.unwrap().specific[0].name, user_defined_path_copy.to_str().unwrap_or_default())
+        ConfigSet::load_cached(data_dir.path(), package_dir.path(), None).unwrap();
+
+        fs::write(&user_path, r###"
+        name: specific
+        matches:
+            - trigger: ":hello"
+              replace: "Hello"
+        "###).unwrap();
+
+        // Make sure the cached modification time differs, regardless of the file system resolution
+        let cache_file = data_dir.path().join(CONFIG_CACHE_FILE_NAME);
+        let mut disk_cache: ConfigSetDiskCache = serde_json::from_str(&fs::read_to_string(&cache_file).unwrap()).unwrap();
+        for source in disk_cache.sources.iter_mut() {
+            if source.0 == user_path {
+                source.1 = SystemTime::UNIX_EPOCH;
+            }
+        }
+        fs::write(&cache_file, serde_json::to_string(&disk_cache).unwrap()).unwrap();
+
+        let config_set = ConfigSet::load_cached(data_dir.path(), package_dir.path(), None).unwrap();
+        assert!(config_set.specific[0].matches.iter().any(|m| m.trigger() == ":hello"));
     }
 
     #[test]
-    fn test_config_set_specific_file_duplicate_name() {
-        let (data_dir, package_dir) = create_temp_espanso_directories();
+    fn test_config_set_load_cached_changed_variable_invalidates() {
+        std::env::set_var("ESPANSO_TEST_CACHED_EDITOR", "Code");
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content("cache_configs: true");
 
-        let user_defined_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
-        name: specific1
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        filter_class: "^${ESPANSO_TEST_CACHED_EDITOR}$"
         "###);
 
-        let user_defined_path2 = create_user_config_file(data_dir.path(), "specific2.yml", r###"
-        name: specific1
-        "###);
+        let config_set = ConfigSet::load_cached(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.specific[0].filter_class, "^Code$");
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path());
-        assert!(config_set.is_err());
-        assert!(variant_eq(&config_set.unwrap_err(), &ConfigLoadError::NameDuplicate(PathBuf::new())))
+        std::env::set_var("ESPANSO_TEST_CACHED_EDITOR", "Vim");
+        let config_set = ConfigSet::load_cached(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.specific[0].filter_class, "^Vim$");
     }
 
     #[test]
-    fn test_user_defined_config_set_merge_with_parent_matches() {
-        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
-        matches:
-            - trigger: ":lol"
-              replace: "LOL"
-            - trigger: ":yess"
-              replace: "Bob"
-        "###);
+    fn test_config_set_load_cached_disabled_by_default() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
 
-        let user_defined_path = create_user_config_file(data_dir.path(), "specific1.yml", r###"
-        name: specific1
+        ConfigSet::load_cached(data_dir.path(), package_dir.path(), None).unwrap();
+        assert!(!data_dir.path().join(CONFIG_CACHE_FILE_NAME).exists());
+    }
 
-        matches:
-            - trigger: "hello"
-              replace: "newstring"
+    #[test]
+    fn test_config_set_load_cached_other_version_is_ignored() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content("cache_configs: true");
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
-        assert_eq!(config_set.default.matches.len(), 2);
-        assert_eq!(config_set.specific[0].matches.len(), 3);
+        ConfigSet::load_cached(data_dir.path(), package_dir.path(), None).unwrap();
 
-        assert!(config_set.specific[0].matches.iter().find(|x| x.trigger == "hello").is_some());
-        assert!(config_set.specific[0].matches.iter().find(|x| x.trigger == ":lol").is_some());
-        assert!(config_set.specific[0].matches.iter().find(|x| x.trigger == ":yess").is_some());
+        let cache_file = data_dir.path().join(CONFIG_CACHE_FILE_NAME);
+        let mut disk_cache: ConfigSetDiskCache = serde_json::from_str(&fs::read_to_string(&cache_file).unwrap()).unwrap();
+        assert_eq!(disk_cache.version, env!("CARGO_PKG_VERSION"));
+        disk_cache.version = "0.0.1".to_owned();
+        disk_cache.config_set.specific[0].name = "cached".to_owned();
+        fs::write(&cache_file, serde_json::to_string(&disk_cache).unwrap()).unwrap();
+
+        let config_set = ConfigSet::load_cached(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.specific[0].name, "specific");
     }
 
     #[test]
-    fn test_user_defined_config_set_merge_with_parent_matches_child_priority() {
+    fn test_config_set_load_cached_keeps_warnings() {
         let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        cache_configs: true
         matches:
-            - trigger: ":lol"
-              replace: "LOL"
-            - trigger: ":yess"
-              replace: "Bob"
+            - trigger: ":empty"
+              replace: ""
         "###);
 
-        let user_defined_path2 = create_user_config_file(data_dir.path(), "specific2.yml", r###"
-        name: specific1
+        ConfigSet::load_cached(data_dir.path(), package_dir.path(), None).unwrap();
+
+        let cache_file = data_dir.path().join(CONFIG_CACHE_FILE_NAME);
+        let disk_cache: ConfigSetDiskCache = serde_json::from_str(&fs::read_to_string(&cache_file).unwrap()).unwrap();
+        assert_eq!(disk_cache.warnings, vec![ConfigWarning::EmptyReplacement(
+            data_dir.path().join(DEFAULT_CONFIG_FILE_NAME), ":empty".to_owned())]);
+    }
+
+    fn active_triggers(config_set: &ConfigSet, title: &str, class: &str, exec: &str) -> Vec<String> {
+        config_set.active_matches(title, class, exec).iter().map(|m| m.trigger().to_owned()).collect()
+    }
 
+    #[test]
+    fn test_config_set_active_matches_no_specific_match() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
         matches:
-            - trigger: ":lol"
-              replace: "newstring"
+            - trigger: ":hi"
+              replace: "hello"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
-        assert_eq!(config_set.default.matches.len(), 2);
-        assert_eq!(config_set.specific[0].matches.len(), 2);
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: chrome
+        filter_title: "Chrome"
+        matches:
+            - trigger: ":web"
+              replace: "website"
+        "###);
 
-        assert!(config_set.specific[0].matches.iter().find(|x| {
-            if let MatchContentType::Text(content) = &x.content {
-                x.trigger == ":lol" && content.replace == "newstring"
-            }else{
-                false
-            }
-        }).is_some());
-        assert!(config_set.specific[0].matches.iter().find(|x| x.trigger == ":yess").is_some());
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(active_triggers(&config_set, "Terminal", "Terminal", "/usr/bin/term"), vec![":hi"]);
     }
 
     #[test]
-    fn test_user_defined_config_set_exclude_merge_with_parent_matches() {
+    fn test_config_set_active_matches_single_specific_match() {
         let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
         matches:
-            - trigger: ":lol"
-              replace: "LOL"
-            - trigger: ":yess"
-              replace: "Bob"
+            - trigger: ":hi"
+              replace: "hello"
         "###);
 
-        let user_defined_path2 = create_user_config_file(data_dir.path(), "specific2.yml", r###"
-        name: specific1
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: chrome
+        filter_title: "Chrome"
+        matches:
+            - trigger: ":web"
+              replace: "website"
+        "###);
 
+        create_user_config_file(data_dir.path(), "specific2.yml", r###"
+        name: editor
+        filter_class: "Editor"
         exclude_default_entries: true
-
         matches:
-            - trigger: "hello"
-              replace: "newstring"
+            - trigger: ":fn"
+              replace: "function"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
-        assert_eq!(config_set.default.matches.len(), 2);
-        assert_eq!(config_set.specific[0].matches.len(), 1);
-
-        assert!(config_set.specific[0].matches.iter().find(|x| {
-            if let MatchContentType::Text(content) = &x.content {
-                x.trigger == "hello" && content.replace == "newstring"
-            }else{
-                false
-            }
-        }).is_some());
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(active_triggers(&config_set, "Google Chrome", "Browser", "/usr/bin/chrome"), vec![":web", ":hi"]);
+        assert_eq!(active_triggers(&config_set, "main.rs", "Editor", "/usr/bin/editor"), vec![":fn"]);
     }
 
     #[test]
-    fn test_only_yaml_files_are_loaded_from_config() {
-        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(
-            r###"
-            matches:
-                - trigger: ":lol"
-                  replace: "LOL"
-                - trigger: ":yess"
-                  replace: "Bob"
-            "###
-        );
+    fn test_config_set_active_matches_overlapping_filters() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":hi"
+              replace: "hello"
+        "###);
 
-        let user_defined_path2 = create_user_config_file(data_dir.path(), "specific.zzz", r###"
-        name: specific1
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: chrome
+        filter_title: "Chrome"
+        matches:
+            - trigger: ":web"
+              replace: "website"
+            - trigger: ":hi"
+              replace: "hello from chrome"
+        "###);
 
+        create_user_config_file(data_dir.path(), "specific2.yml", r###"
+        name: browsers
+        filter_class: "Browser"
         exclude_default_entries: true
-
         matches:
-            - trigger: "hello"
-              replace: "newstring"
+            - trigger: ":web"
+              replace: "browser website"
+            - trigger: ":tab"
+              replace: "new tab"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
-        assert_eq!(config_set.specific.len(), 0);
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let mut triggers = active_triggers(&config_set, "Google Chrome", "Browser", "/usr/bin/chrome");
+        triggers.sort();
+        assert_eq!(triggers, vec![":hi", ":web"]);
+
+        // As at runtime, only the config matching the title is used
+        assert_eq!(config_set.config_for_window("Google Chrome", "Browser", "/usr/bin/chrome").name, "chrome");
+        assert_eq!(config_set.config_for_window("Firefox", "Browser", "/usr/bin/firefox").name, "browsers");
+        let matches = config_set.active_matches("Google Chrome", "Browser", "/usr/bin/chrome");
+        let hi_match = matches.iter().find(|m| m.trigger() == ":hi").unwrap();
+        match &hi_match.content {
+            MatchContentType::Text(content) => assert_eq!(content.replace, "hello from chrome"),
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_glob_pattern_matching() {
+        assert!(GlobPattern::new("*.yml").is_match(Path::new("test.yml")));
+        assert!(GlobPattern::new("*.yml").is_match(Path::new("nested/test.yml")));
+        assert!(!GlobPattern::new("*.yml").is_match(Path::new("test.yaml")));
+        assert!(GlobPattern::new("test?.yml").is_match(Path::new("test1.yml")));
+        assert!(GlobPattern::new("nested/*.yml").is_match(Path::new("nested/test.yml")));
+        assert!(!GlobPattern::new("nested/*.yml").is_match(Path::new("nested/inner/test.yml")));
+        assert!(GlobPattern::new("nested/**/*.yml").is_match(Path::new("nested/inner/test.yml")));
+        assert!(GlobPattern::new("nested/**/*.yml").is_match(Path::new("nested/test.yml")));
     }
 
     #[test]
@@ -939,7 +4839,7 @@ mod tests {
         name: specific2
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(config_set.specific.len(), 2);
     }
 
@@ -959,11 +4859,11 @@ mod tests {
               replace: "world"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(config_set.specific.len(), 0);
         assert_eq!(config_set.default.matches.len(), 2);
-        assert!(config_set.default.matches.iter().any(|m| m.trigger == "hasta"));
-        assert!(config_set.default.matches.iter().any(|m| m.trigger == "hello"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == "hasta"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == "hello"));
     }
 
     #[test]
@@ -980,12 +4880,12 @@ mod tests {
               replace: "world"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(config_set.specific.len(), 1);
         assert_eq!(config_set.default.matches.len(), 1);
-        assert!(config_set.default.matches.iter().any(|m| m.trigger == "hasta"));
-        assert!(!config_set.default.matches.iter().any(|m| m.trigger == "hello"));
-        assert!(config_set.specific[0].matches.iter().any(|m| m.trigger == "hello"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == "hasta"));
+        assert!(!config_set.default.matches.iter().any(|m| m.trigger() == "hello"));
+        assert!(config_set.specific[0].matches.iter().any(|m| m.trigger() == "hello"));
     }
 
     #[test]
@@ -1013,12 +4913,12 @@ mod tests {
               replace: "mario"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(config_set.specific.len(), 0);
         assert_eq!(config_set.default.matches.len(), 3);
-        assert!(config_set.default.matches.iter().any(|m| m.trigger == "hasta"));
-        assert!(config_set.default.matches.iter().any(|m| m.trigger == "hello"));
-        assert!(config_set.default.matches.iter().any(|m| m.trigger == "super"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == "hasta"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == "hello"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == "super"));
     }
 
     #[test]
@@ -1037,12 +4937,12 @@ mod tests {
               replace: "world"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(config_set.specific.len(), 0);
         assert_eq!(config_set.default.matches.len(), 1);
         assert!(config_set.default.matches.iter().any(|m| {
             if let MatchContentType::Text(content) = &m.content {
-                m.trigger == "hasta" && content.replace == "world"
+                m.trigger() == "hasta" && content.replace == "world"
             }else{
                 false
             }
@@ -1065,11 +4965,88 @@ mod tests {
               replace: "potter"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(config_set.specific.len(), 0);
         assert_eq!(config_set.default.matches.len(), 2);
-        assert!(config_set.default.matches.iter().any(|m| m.trigger == "hasta"));
-        assert!(config_set.default.matches.iter().any(|m| m.trigger == "harry"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == "hasta"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == "harry"));
+    }
+
+    fn create_three_packages(package_dir: &Path) {
+        for (package, trigger) in [("emoji-basic", ":smile"), ("emoji-extra", ":party"), ("greek", ":alpha")].iter() {
+            create_package_file(package_dir, package, "package.yml", &format!(r###"
+            parent: default
+
+            matches:
+                - trigger: "{}"
+                  replace: "{}"
+            "###, trigger, package));
+        }
+    }
+
+    #[test]
+    fn test_config_set_enabled_packages_glob() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        enabled_packages: ["emoji-*"]
+        "###);
+        create_three_packages(package_dir.path());
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.matches.len(), 2);
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":smile"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":party"));
+        assert!(!config_set.default.matches.iter().any(|m| m.trigger() == ":alpha"));
+    }
+
+    #[test]
+    fn test_config_set_enabled_packages_multiple_patterns() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        enabled_packages: ["greek", "emoji-bas?c"]
+        "###);
+        create_three_packages(package_dir.path());
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.matches.len(), 2);
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":smile"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":alpha"));
+    }
+
+    #[test]
+    fn test_config_set_enabled_packages_empty_loads_all() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+        create_three_packages(package_dir.path());
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":smile"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":party"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":alpha"));
+    }
+
+    #[test]
+    fn test_config_set_disabled_packages_are_skipped() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        disabled_packages: ["greek"]
+        "###);
+        create_three_packages(package_dir.path());
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.matches.len(), 2);
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":smile"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":party"));
+        assert!(!config_set.default.matches.iter().any(|m| m.trigger() == ":alpha"));
+    }
+
+    #[test]
+    fn test_config_set_disabled_packages_win_over_enabled() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        enabled_packages: ["emoji-*"]
+        disabled_packages: ["emoji-extra"]
+        "###);
+        create_three_packages(package_dir.path());
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.default.matches.len(), 1);
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":smile"));
     }
 
     #[test]
@@ -1086,11 +5063,11 @@ mod tests {
               replace: "potter"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(config_set.specific.len(), 1);
         assert_eq!(config_set.default.matches.len(), 1);
-        assert!(config_set.default.matches.iter().any(|m| m.trigger == "hasta"));
-        assert!(config_set.specific[0].matches.iter().any(|m| m.trigger == "harry"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == "hasta"));
+        assert!(config_set.specific[0].matches.iter().any(|m| m.trigger() == "harry"));
     }
 
     #[test]
@@ -1117,12 +5094,12 @@ mod tests {
               replace: "weasley"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(config_set.specific.len(), 1);
         assert_eq!(config_set.default.matches.len(), 1);
-        assert!(config_set.default.matches.iter().any(|m| m.trigger == "hasta"));
-        assert!(config_set.specific[0].matches.iter().any(|m| m.trigger == "harry"));
-        assert!(config_set.specific[0].matches.iter().any(|m| m.trigger == "ron"));
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == "hasta"));
+        assert!(config_set.specific[0].matches.iter().any(|m| m.trigger() == "harry"));
+        assert!(config_set.specific[0].matches.iter().any(|m| m.trigger() == "ron"));
     }
 
     #[test]
@@ -1155,7 +5132,7 @@ mod tests {
               replace: "world"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(ConfigSet::has_conflicts(&config_set.default, &config_set.specific), false);
     }
 
@@ -1179,7 +5156,7 @@ mod tests {
               replace: "world"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(ConfigSet::has_conflicts(&config_set.default, &config_set.specific), true);
     }
 
@@ -1201,7 +5178,7 @@ mod tests {
               replace: "Conflict"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(ConfigSet::has_conflicts(&config_set.default, &config_set.specific), true);
     }
 
@@ -1230,10 +5207,63 @@ mod tests {
               replace: "Conflict"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(ConfigSet::has_conflicts(&config_set.default, &config_set.specific), false);
     }
 
+    #[test]
+    fn test_detect_filter_conflicts_same_class() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: chrome1
+        filter_class: Chrome
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific2.yml", r###"
+        name: chrome2
+        filter_class: Chrome
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific3.yml", r###"
+        name: firefox
+        filter_class: Firefox
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let conflicts = config_set.detect_filter_conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0] == ("chrome1".to_owned(), "chrome2".to_owned()) ||
+                conflicts[0] == ("chrome2".to_owned(), "chrome1".to_owned()));
+    }
+
+    #[test]
+    fn test_detect_filter_conflicts_no_conflict() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: chrome
+        filter_class: Chrome
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific2.yml", r###"
+        name: chrome_title
+        filter_class: Chrome
+        filter_title: Gmail
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific3.yml", r###"
+        name: nofilter
+        "###);
+
+        create_user_config_file(data_dir.path(), "specific4.yml", r###"
+        name: nofilter2
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert!(config_set.detect_filter_conflicts().is_empty());
+    }
+
     #[test]
     fn test_config_set_specific_inherits_default_global_vars() {
         let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
@@ -1252,7 +5282,7 @@ mod tests {
                 format: "%m"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(config_set.specific.len(), 1);
         assert_eq!(config_set.default.global_vars.len(), 1);
         assert_eq!(config_set.specific[0].global_vars.len(), 2);
@@ -1279,7 +5309,7 @@ mod tests {
                 format: "%m"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(config_set.specific.len(), 0);
         assert_eq!(config_set.default.global_vars.len(), 2);
         assert!(config_set.default.global_vars.iter().any(|m| m.name == "testvar"));
@@ -1306,10 +5336,359 @@ mod tests {
                 format: "%m"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path()).unwrap();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
         assert_eq!(config_set.specific.len(), 1);
         assert_eq!(config_set.default.global_vars.len(), 1);
         assert_eq!(config_set.specific[0].global_vars.len(), 1);
         assert!(config_set.specific[0].global_vars.iter().any(|m| m.name == "specificvar"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_config_cache_unchanged_files_are_not_parsed_again() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific1
+        "###);
+
+        let mut cache = ConfigCache::new();
+
+        ConfigSet::load_with_cache(data_dir.path(), package_dir.path(), None, &mut cache).unwrap();
+        assert_eq!(cache.parse_count, 2);
+
+        let config_set = ConfigSet::load_with_cache(data_dir.path(), package_dir.path(), None, &mut cache).unwrap();
+        assert_eq!(cache.parse_count, 2);
+        assert_eq!(config_set.specific.len(), 1);
+        assert_eq!(config_set.specific[0].name, "specific1");
+    }
+
+    #[test]
+    fn test_config_cache_modified_files_are_parsed_again() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        let user_defined_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific1
+        "###);
+
+        let mut cache = ConfigCache::new();
+
+        ConfigSet::load_with_cache(data_dir.path(), package_dir.path(), None, &mut cache).unwrap();
+        assert_eq!(cache.parse_count, 2);
+
+        // Simulate a change in the modification time of the file
+        cache.entries.get_mut(&user_defined_path).unwrap().0 = SystemTime::UNIX_EPOCH;
+
+        ConfigSet::load_with_cache(data_dir.path(), package_dir.path(), None, &mut cache).unwrap();
+        assert_eq!(cache.parse_count, 3);
+    }
+
+    #[test]
+    fn test_config_cache_removed_files_are_evicted() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        let user_defined_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific1
+        "###);
+
+        let mut cache = ConfigCache::new();
+
+        ConfigSet::load_with_cache(data_dir.path(), package_dir.path(), None, &mut cache).unwrap();
+        assert!(cache.entries.contains_key(&user_defined_path));
+
+        fs::remove_file(&user_defined_path).unwrap();
+
+        ConfigSet::load_with_cache(data_dir.path(), package_dir.path(), None, &mut cache).unwrap();
+        assert!(!cache.entries.contains_key(&user_defined_path));
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_config_load_error_serialized_with_code() {
+        let path = PathBuf::from("/tmp/test.yml");
+        let expected = vec![
+            (ConfigLoadError::FileNotFound, "FILE_NOT_FOUND", None),
+            (ConfigLoadError::UnableToReadFile, "UNABLE_TO_READ_FILE", None),
+            (ConfigLoadError::InvalidYAML(path.clone(), serde_yaml::from_str::<Configs>("[").unwrap_err()), "INVALID_YAML", Some("/tmp/test.yml")),
+            (ConfigLoadError::InvalidConfigDirectory, "INVALID_CONFIG_DIRECTORY", None),
+            (ConfigLoadError::InvalidParameter(path.clone(), vec!["toggle_key".to_owned()]), "INVALID_PARAMETER", Some("/tmp/test.yml")),
+            (ConfigLoadError::NameDuplicate(path.clone()), "NAME_DUPLICATE", Some("/tmp/test.yml")),
+            (ConfigLoadError::UnableToCreateDefaultConfig, "UNABLE_TO_CREATE_DEFAULT_CONFIG", None),
+            (ConfigLoadError::IncompatibleVersion(path.clone(), ">=1.0.0".to_owned()), "INCOMPATIBLE_VERSION", Some("/tmp/test.yml")),
+            (ConfigLoadError::UnknownField(path.clone(), "word_seperators".to_owned()), "UNKNOWN_FIELD", Some("/tmp/test.yml")),
+            (ConfigLoadError::ConfigDirMissing(path.clone()), "CONFIG_DIR_MISSING", Some("/tmp/test.yml")),
+            (ConfigLoadError::ConfigDirNotWritable(path.clone()), "CONFIG_DIR_NOT_WRITABLE", Some("/tmp/test.yml")),
+            (ConfigLoadError::ConfigDirNotADirectory(path.clone()), "CONFIG_DIR_NOT_A_DIRECTORY", Some("/tmp/test.yml")),
+            (ConfigLoadError::ProfileNotFound(path.clone()), "PROFILE_NOT_FOUND", Some("/tmp/test.yml")),
+            (ConfigLoadError::EmptyTrigger(path.clone()), "EMPTY_TRIGGER", Some("/tmp/test.yml")),
+            (ConfigLoadError::ValidationFailed(path.clone(), vec!["invalid regex".to_owned()]), "VALIDATION_FAILED", Some("/tmp/test.yml")),
+            (ConfigLoadError::UndefinedVariable(path.clone(), "ESPANSO_PORT".to_owned()), "UNDEFINED_VARIABLE", Some("/tmp/test.yml")),
+            (ConfigLoadError::DuplicateLabel("default".to_owned(), "signature".to_owned()), "DUPLICATE_LABEL", None),
+        ];
+
+        for (error, code, path) in expected {
+            assert_eq!(error.code(), code);
+
+            let json: serde_json::Value = serde_json::to_value(&error).unwrap();
+            assert_eq!(json["code"], code);
+            assert_eq!(json["message"], error.to_string());
+            assert_eq!(json["path"].as_str(), path);
+        }
+    }
+
+    #[test]
+    fn test_check_config_dir_valid() {
+        let config_dir = TempDir::new().unwrap();
+        assert!(ConfigSet::check_config_dir(config_dir.path()).is_ok());
+
+        // The probe file should be removed
+        assert_eq!(fs::read_dir(config_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_check_config_dir_missing() {
+        let parent = TempDir::new().unwrap();
+        let config_dir = parent.path().join("espanso");
+
+        let error = ConfigSet::check_config_dir(&config_dir).unwrap_err();
+        assert_eq!(error, ConfigLoadError::ConfigDirMissing(config_dir.clone()));
+        assert!(error.to_string().contains(config_dir.to_str().unwrap()));
+    }
+
+    #[test]
+    fn test_check_config_dir_is_a_file() {
+        let config_file = create_tmp_file("");
+
+        let error = ConfigSet::check_config_dir(config_file.path()).unwrap_err();
+        assert_eq!(error, ConfigLoadError::ConfigDirNotADirectory(config_file.path().to_owned()));
+    }
+
+    #[test]
+    fn test_check_config_dir_read_only() {
+        let config_dir = TempDir::new().unwrap();
+        let mut permissions = fs::metadata(config_dir.path()).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(config_dir.path(), permissions.clone()).unwrap();
+
+        let result = ConfigSet::check_config_dir(config_dir.path());
+
+        permissions.set_readonly(false);
+        fs::set_permissions(config_dir.path(), permissions).unwrap();
+
+        assert_eq!(result.unwrap_err(), ConfigLoadError::ConfigDirNotWritable(config_dir.path().to_owned()));
+    }
+
+    #[test]
+    fn test_config_load_error_yaml_source() {
+        let broken_config_file = create_tmp_file(TEST_CONFIG_FILE_WITH_BAD_YAML);
+        let error = Configs::load_config(broken_config_file.path()).unwrap_err();
+
+        let source = error.source();
+        assert!(source.is_some());
+
+        let yaml_error = source.unwrap().downcast_ref::<serde_yaml::Error>().unwrap();
+        assert!(yaml_error.location().is_some());
+    }
+
+    #[test]
+    fn test_config_load_error_source_none_for_other_variants() {
+        assert!(ConfigLoadError::FileNotFound.source().is_none());
+        assert!(ConfigLoadError::InvalidParameter(PathBuf::from("/tmp/test.yml"), Vec::new()).source().is_none());
+    }
+
+    #[test]
+    fn test_config_load_error_yaml_display_contains_location() {
+        let broken_config_file = create_tmp_file("name: test\nparent: self\n  filter_title: test\n");
+        let error = Configs::load_config(broken_config_file.path()).unwrap_err();
+
+        let expected_prefix = format!("{}:3:", broken_config_file.path().to_str().unwrap());
+        assert!(error.to_string().starts_with(&expected_prefix));
+    }
+
+    #[test]
+    fn test_config_filter_regex_compiled_lazily_once() {
+        let config: Configs = serde_yaml::from_str(r###"
+        filter_title: "Chrome"
+        filter_class: "Browser"
+        "###).unwrap();
+
+        assert_eq!(config.filter_regexps.compile_count.get(), 0);
+
+        assert!(config.matches_title("Google Chrome"));
+        assert!(!config.matches_title("Firefox"));
+        assert!(config.matches_window(Some("Google Chrome"), None, None));
+        assert_eq!(config.filter_regexps.compile_count.get(), 1);
+
+        assert!(config.matches_window(None, Some("Browser"), Some("/usr/bin/app")));
+        assert!(!config.matches_window(Some("Terminal"), Some("Terminal"), Some("/usr/bin/app")));
+        assert_eq!(config.filter_regexps.compile_count.get(), 2);
+    }
+
+    #[test]
+    fn test_config_is_active_for_without_filters() {
+        let config: Configs = serde_yaml::from_str("name: test").unwrap();
+
+        assert!(!config.has_filters());
+        assert!(config.is_active_for("Google Chrome", "Chrome", "/usr/bin/chrome"));
+        assert!(config.is_active_for("", "", ""));
+    }
+
+    #[test]
+    fn test_config_is_active_for_each_filter() {
+        let title_config: Configs = serde_yaml::from_str("filter_title: \"Chrome$\"").unwrap();
+        assert!(title_config.is_active_for("Google Chrome", "Browser", "/usr/bin/chrome"));
+        assert!(!title_config.is_active_for("Chrome Settings", "Chrome", "/usr/bin/chrome"));
+
+        let class_config: Configs = serde_yaml::from_str("filter_class: \"^Code$\"").unwrap();
+        assert!(class_config.is_active_for("main.rs", "Code", "/usr/bin/code"));
+        assert!(!class_config.is_active_for("Code", "VSCode", "/usr/bin/code"));
+
+        let exec_config: Configs = serde_yaml::from_str("filter_exec: \"terminal\"").unwrap();
+        assert!(exec_config.is_active_for("bash", "Terminal", "/usr/bin/gnome-terminal"));
+        assert!(!exec_config.is_active_for("terminal", "terminal", "/usr/bin/xterm"));
+    }
+
+    #[test]
+    fn test_config_is_active_for_invalid_filter() {
+        let config: Configs = serde_yaml::from_str("filter_title: \"[`-_]\"").unwrap();
+
+        assert!(config.has_filters());
+        assert!(!config.is_active_for("[`-_]", "", ""));
+    }
+
+    #[test]
+    fn test_config_invalid_filter_regex_never_matches() {
+        let config: Configs = serde_yaml::from_str(r###"
+        filter_title: "[`-_]"
+        "###).unwrap();
+
+        assert!(!config.matches_title("[`-_]"));
+        assert!(!config.matches_title("anything"));
+        assert_eq!(config.filter_regexps.compile_count.get(), 1);
+    }
+
+    // Benchmark loading a large default config merged into the specific ones. It is ignored as
+    // it only reports the load time, run it with:
+    // cargo test bench_config_set_load -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_config_set_load_large_default() {
+        let mut default_content = String::from("matches:\n");
+        for i in 0..1000 {
+            default_content.push_str(&format!("    - trigger: \":trigger{}\"\n      replace: \"replacement{}\"\n", i, i));
+        }
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(&default_content);
+
+        for i in 0..10 {
+            create_user_config_file(data_dir.path(), &format!("specific{}.yml", i), &format!(r###"
+            name: specific{}
+            filter_title: "App{}"
+            matches:
+                - trigger: ":trigger{}"
+                  replace: "overridden"
+            "###, i, i, i));
+        }
+
+        let start = std::time::Instant::now();
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let elapsed = start.elapsed();
+
+        assert_eq!(config_set.specific.len(), 10);
+        assert_eq!(config_set.specific[0].matches.len(), 1000);
+
+        println!("load: {:?}", elapsed);
+    }
+
+    #[test]
+    fn test_config_set_migrate_renames_deprecated_fields() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        let user_defined_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        # keep this comment
+        exclude_default_matches: true
+
+        matches:
+            - trigger: "hello"
+              replace: "newstring"
+        "###);
+
+        // Detecting the deprecated fields leaves the files untouched
+        assert_eq!(ConfigSet::deprecated_config_files(data_dir.path(), None), vec![user_defined_path.clone()]);
+        assert!(fs::read_to_string(&user_defined_path).unwrap().contains("exclude_default_matches: true"));
+
+        let migrated = ConfigSet::migrate(data_dir.path(), None).unwrap();
+        assert_eq!(migrated, vec![user_defined_path.clone()]);
+
+        let content = fs::read_to_string(&user_defined_path).unwrap();
+        assert!(content.contains("exclude_default_entries: true"));
+        assert!(content.contains("# keep this comment"));
+        assert!(!content.contains("exclude_default_matches"));
+
+        let backup = fs::read_to_string(data_dir.path().join(USER_CONFIGS_FOLDER_NAME).join("specific.yml.bak")).unwrap();
+        assert!(backup.contains("exclude_default_matches: true"));
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert!(config_set.specific[0].exclude_default_entries);
+        assert_eq!(config_set.specific[0].matches.len(), 1);
+
+        // Running it again has no effect
+        assert!(ConfigSet::migrate(data_dir.path(), None).unwrap().is_empty());
+        assert!(ConfigSet::deprecated_config_files(data_dir.path(), None).is_empty());
+    }
+
+    #[test]
+    fn test_migrate_fields_skips_nested_and_existing_fields() {
+        assert_eq!(migrate_fields("name: test\nmatches:\n  - exclude_default_matches: true\n"), None);
+        assert_eq!(migrate_fields("exclude_default_entries: true\nexclude_default_matches: false\n"), None);
+        assert_eq!(migrate_fields("exclude_default_matches : true\n"),
+                   Some("exclude_default_entries : true\n".to_owned()));
+    }
+
+    #[test]
+    fn test_config_set_unknown_field_accepted_in_lenient_mode() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        word_seperators: [' ']
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        assert_eq!(config_set.specific[0].word_separators, default_word_separators());
+    }
+
+    #[test]
+    fn test_config_set_unknown_field_rejected_in_strict_mode() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        strict: true
+        "###);
+
+        let user_defined_path = create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        word_seperators: [' ']
+        "###);
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        assert_eq!(config_set.unwrap_err(), ConfigLoadError::UnknownField(user_defined_path, "word_seperators".to_owned()));
+    }
+
+    #[test]
+    fn test_config_set_strict_mode_accepts_known_fields() {
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(&format!(
+            "{}\nstrict: true\n", DEFAULT_CONFIG_FILE_CONTENT));
+
+        create_user_config_file(data_dir.path(), "specific.yml", r###"
+        name: specific
+        parent: default
+        filter_title: "Chrome"
+        word_separators: [' ']
+        groups:
+            - word: true
+              matches:
+                - trigger: "hello"
+                  replace: "world"
+        "###);
+
+        assert!(ConfigSet::load(data_dir.path(), package_dir.path(), None).is_ok());
+    }
+}
@@ -17,201 +17,412 @@
  * along with espanso.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use regex::Regex;
 use crate::system::SystemManager;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::time::SystemTime;
-use log::{debug, warn};
-use super::{Configs, ConfigSet};
-use crate::matcher::Match;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use log::{debug, info, warn, LevelFilter};
+use super::{ConfigSet, ConfigCache, LiveConfig};
+use crate::protocol::{IPCCommand, ReloadResponse, RELOAD_COMMAND_ID, PROFILE_COMMAND_ID};
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::collections::HashSet;
+
+const ENABLED_STATE_FILE_NAME: &str = "enabled.state";
+const ENABLED_STATE_VALUE: &str = "enabled";
+const DISABLED_STATE_VALUE: &str = "disabled";
+
+/// Load the enabled/disabled state saved in the previous session.
+/// If the state file is missing or corrupted, espanso defaults to enabled.
+pub fn load_enabled_state() -> bool {
+    let config_dir = crate::context::get_config_dir();
+    load_enabled_state_from_dir(&config_dir)
+}
 
-pub struct RuntimeConfigManager<'a, S: SystemManager> {
-    set: ConfigSet,
+/// Save the enabled/disabled state, so that it's restored by the next session.
+pub fn save_enabled_state(enabled: bool) -> std::io::Result<()> {
+    let config_dir = crate::context::get_config_dir();
+    save_enabled_state_in_dir(&config_dir, enabled)
+}
 
-    // Filter regexps
-    title_regexps: Vec<Option<Regex>>,
-    class_regexps: Vec<Option<Regex>>,
-    exec_regexps: Vec<Option<Regex>>,
+/// Load the name of the profile activated in the previous session, if any.
+pub fn load_active_profile() -> Option<String> {
+    let config_dir = crate::context::get_config_dir();
+    load_active_profile_from_dir(&config_dir)
+}
 
-    system_manager: S,
+/// Ask the running daemon to reload the configuration, using the IPC transport
+/// configured in the given set. If the new configuration can't be loaded, the
+/// daemon keeps the current one and the error is returned.
+pub fn send_reload_command(config_set: ConfigSet) -> Result<(), String> {
+    send_reload_request(config_set, RELOAD_COMMAND_ID, "")
+}
 
-    // Cache
-    last_config_update: RefCell<SystemTime>,
-    last_config: RefCell<Option<&'a Configs>>
+/// Ask the running daemon to switch to the given profile, or to the base config if None.
+/// The choice is persisted, so that the profile is loaded again after a restart.
+pub fn send_profile_command(config_set: ConfigSet, name: Option<&str>) -> Result<(), String> {
+    send_reload_request(config_set, PROFILE_COMMAND_ID, name.unwrap_or(""))
 }
 
-impl <'a, S: SystemManager> RuntimeConfigManager<'a, S> {
-    pub fn new<'b>(set: ConfigSet, system_manager: S) -> RuntimeConfigManager<'b, S> {
-        // Compile all the regexps
-        let title_regexps = set.specific.iter().map(
-            |config| {
-                if config.filter_title.is_empty() {
-                    None
-                }else{
-                    let res = Regex::new(&config.filter_title);
-                    if let Ok(regex) = res {
-                        Some(regex)
-                    }else{
-                        warn!("Invalid regex in 'filter_title' field of configuration {}, ignoring it...", config.name);
-                        None
-                    }
-                }
-            }
-        ).collect();
-
-        let class_regexps = set.specific.iter().map(
-            |config| {
-                if config.filter_class.is_empty() {
-                    None
-                }else{
-                    let res = Regex::new(&config.filter_class);
-                    if let Ok(regex) = res {
-                        Some(regex)
-                    }else{
-                        warn!("Invalid regex in 'filter_class' field of configuration {}, ignoring it...", config.name);
-                        None
-                    }
-                }
-            }
-        ).collect();
-
-        let exec_regexps = set.specific.iter().map(
-            |config| {
-                if config.filter_exec.is_empty() {
-                    None
-                }else{
-                    let res = Regex::new(&config.filter_exec);
-                    if let Ok(regex) = res {
-                        Some(regex)
-                    }else{
-                        warn!("Invalid regex in 'filter_exec' field of configuration {}, ignoring it...", config.name);
-                        None
-                    }
-                }
+fn send_reload_request(config_set: ConfigSet, id: &str, payload: &str) -> Result<(), String> {
+    let command = IPCCommand {
+        id: id.to_owned(),
+        payload: payload.to_owned(),
+        secret: config_set.default.ipc_secret.clone(),
+    };
+
+    let ipc_client = crate::protocol::get_ipc_client(config_set)?;
+    let response = ipc_client.send_request(command)?;
+    let response: ReloadResponse = serde_json::from_str(&response)
+        .map_err(|e| format!("Invalid response from the daemon: {}", e))?;
+
+    if response.success {
+        Ok(())
+    }else{
+        Err(response.error.unwrap_or_else(|| "Unable to reload the configuration".to_owned()))
+    }
+}
+
+fn save_enabled_state_in_dir(dir: &Path, enabled: bool) -> std::io::Result<()> {
+    let value = if enabled {
+        ENABLED_STATE_VALUE
+    }else{
+        DISABLED_STATE_VALUE
+    };
+
+    fs::write(dir.join(ENABLED_STATE_FILE_NAME), value)
+}
+
+const ACTIVE_PROFILE_FILE_NAME: &str = "profile.state";
+
+pub(crate) fn save_active_profile_in_dir(dir: &Path, name: Option<&str>) -> std::io::Result<()> {
+    let state_file = dir.join(ACTIVE_PROFILE_FILE_NAME);
+    match name {
+        Some(name) => fs::write(state_file, name),
+        None => {
+            if state_file.exists() {
+                fs::remove_file(state_file)
+            }else{
+                Ok(())
             }
-        ).collect();
+        },
+    }
+}
 
-        let last_config_update = RefCell::new(SystemTime::now());
-        let last_config = RefCell::new(None);
+pub(crate) fn load_active_profile_from_dir(dir: &Path) -> Option<String> {
+    let content = fs::read_to_string(dir.join(ACTIVE_PROFILE_FILE_NAME)).ok()?;
+    let name = content.trim();
+    if name.is_empty() {
+        None
+    }else{
+        Some(name.to_owned())
+    }
+}
 
-        RuntimeConfigManager {
-            set,
-            title_regexps,
-            class_regexps,
-            exec_regexps,
-            system_manager,
-            last_config_update,
-            last_config
+const DISABLED_TRIGGERS_FILE_NAME: &str = "disabled_triggers.json";
+
+fn save_disabled_triggers_in_dir(dir: &Path, triggers: &HashSet<String>) -> std::io::Result<()> {
+    let mut sorted_triggers: Vec<&String> = triggers.iter().collect();
+    sorted_triggers.sort();
+
+    let json_str = serde_json::to_string(&sorted_triggers)?;
+    fs::write(dir.join(DISABLED_TRIGGERS_FILE_NAME), json_str)
+}
+
+fn load_disabled_triggers_from_dir(dir: &Path) -> HashSet<String> {
+    let content = fs::read_to_string(dir.join(DISABLED_TRIGGERS_FILE_NAME));
+    if let Ok(content) = content {
+        let triggers: Result<Vec<String>, serde_json::Error> = serde_json::from_str(&content);
+        match triggers {
+            Ok(triggers) => {
+                return triggers.into_iter().collect()
+            },
+            Err(e) => {
+                warn!("Unable to parse the disabled triggers file, ignoring it: {}", e);
+            },
         }
     }
 
-    fn calculate_active_config(&'a self) -> &'a Configs {
-        // TODO: optimize performance by avoiding some of these checks if no Configs use the filters
+    HashSet::new()
+}
 
-        debug!("Requested config for window:");
+fn load_enabled_state_from_dir(dir: &Path) -> bool {
+    let content = fs::read_to_string(dir.join(ENABLED_STATE_FILE_NAME));
+    match content {
+        Ok(content) => {
+            content.trim() != DISABLED_STATE_VALUE
+        },
+        Err(_) => {
+            true
+        },
+    }
+}
 
-        let active_title = self.system_manager.get_current_window_title();
+/*
+ * Handle used to replace the config set of a RuntimeConfigManager from another thread,
+ * for example by a config watcher. The new set is only stored here, behind a lock, and
+ * then installed by the manager on the input thread the next time a config is requested,
+ * so that the matcher never observes a partially updated set.
+ */
+#[derive(Clone)]
+pub struct MatchSwapper {
+    pending: Arc<Mutex<Option<ConfigSet>>>,
+}
 
-        if let Some(title) = active_title {
-            debug!("=> Title: '{}'", title);
+impl MatchSwapper {
+    fn new() -> MatchSwapper {
+        MatchSwapper {
+            pending: Arc::new(Mutex::new(None)),
+        }
+    }
 
-            for (i, regex) in self.title_regexps.iter().enumerate() {
-                if let Some(regex) = regex {
-                    if regex.is_match(&title) {
-                        debug!("Matched 'filter_title' for '{}' config, using custom settings.",
-                               self.set.specific[i].name);
+    /// Schedule the given set to replace the current one.
+    /// If a previous set was not installed yet, it's discarded.
+    pub fn swap(&self, new_set: ConfigSet) {
+        *self.pending.lock().unwrap() = Some(new_set);
+    }
 
-                        return &self.set.specific[i]
-                    }
-                }
-            }
+    fn take(&self) -> Option<ConfigSet> {
+        // Never block the input thread, a pending set will be picked up on the next call
+        match self.pending.try_lock() {
+            Ok(mut pending) => pending.take(),
+            Err(_) => None,
         }
+    }
+}
 
-        let active_executable = self.system_manager.get_current_window_executable();
+pub struct RuntimeConfigManager<S: SystemManager> {
+    // Shared with the configs handed out by the manager, so that the whole set
+    // can be replaced while they are still in use
+    set: RefCell<Rc<ConfigSet>>,
 
-        if let Some(executable) = active_executable {
-            debug!("=> Executable: '{}'", executable);
+    // Number of sets installed after the initial one
+    generation: Cell<usize>,
 
-            for (i, regex) in self.exec_regexps.iter().enumerate() {
-                if let Some(regex) = regex {
-                    if regex.is_match(&executable) {
-                        debug!("Matched 'filter_exec' for '{}' config, using custom settings.",
-                               self.set.specific[i].name);
+    system_manager: S,
 
-                        return &self.set.specific[i]
-                    }
-                }
-            }
+    // Parsed config files, used to speed up reloads. The configs are parsed on
+    // the IPC thread, so that the input thread is not blocked
+    config_cache: Arc<Mutex<ConfigCache>>,
+
+    // Triggers disabled at runtime, skipped by the matcher
+    disabled_triggers: RefCell<HashSet<String>>,
+
+    // Triggers of the 'once' matches already fired, only kept in memory until the next reload
+    spent_triggers: RefCell<HashSet<String>>,
+
+    // Directory in which the runtime state is persisted, if any
+    state_dir: Option<PathBuf>,
+
+    // Sets scheduled with swap_matches, installed the next time a config is requested
+    swapper: MatchSwapper,
+
+    // Cache, containing the position of the last active config in 'specific', if any
+    last_config_update: RefCell<SystemTime>,
+    last_position: Cell<Option<Option<usize>>>,
+}
+
+impl <S: SystemManager> RuntimeConfigManager<S> {
+    pub fn new(set: ConfigSet, system_manager: S) -> RuntimeConfigManager<S> {
+        let last_config_update = RefCell::new(SystemTime::now());
+
+        RuntimeConfigManager {
+            set: RefCell::new(Rc::new(set)),
+            generation: Cell::new(0),
+            system_manager,
+            config_cache: Arc::new(Mutex::new(ConfigCache::new())),
+            disabled_triggers: RefCell::new(HashSet::new()),
+            spent_triggers: RefCell::new(HashSet::new()),
+            state_dir: None,
+            swapper: MatchSwapper::new(),
+            last_config_update,
+            last_position: Cell::new(None),
         }
+    }
 
-        let active_class = self.system_manager.get_current_window_class();
+    /// Return the cache used to skip the parsing of the config files that
+    /// didn't change since the last reload, shared with the threads loading them.
+    pub fn config_cache(&self) -> Arc<Mutex<ConfigCache>> {
+        Arc::clone(&self.config_cache)
+    }
 
-        if let Some(class) = active_class {
-            debug!("=> Class: '{}'", class);
+    /// Return a handle that can be used to swap the config set from other threads,
+    /// while the keyboard listener and the IPC server keep running.
+    #[cfg(test)]
+    pub fn match_swapper(&self) -> MatchSwapper {
+        self.swapper.clone()
+    }
 
-            for (i, regex) in self.class_regexps.iter().enumerate() {
-                if let Some(regex) = regex {
-                    if regex.is_match(&class) {
-                        debug!("Matched 'filter_class' for '{}' config, using custom settings.",
-                               self.set.specific[i].name);
+    fn install_pending_matches(&self) {
+        if let Some(new_set) = self.swapper.take() {
+            super::ConfigManager::swap_config_set(self, new_set);
+        }
+    }
 
-                        return &self.set.specific[i]
-                    }
-                }
+    /// Persist the runtime state (such as the disabled triggers) in the given
+    /// directory, restoring the one saved in the previous session.
+    pub fn set_state_dir(&mut self, state_dir: &Path) {
+        *self.disabled_triggers.borrow_mut() = load_disabled_triggers_from_dir(state_dir);
+        self.state_dir = Some(state_dir.to_owned());
+
+        // Otherwise it wouldn't be obvious why these triggers don't expand
+        let mut disabled_triggers: Vec<String> = self.disabled_triggers().into_iter().collect();
+        if !disabled_triggers.is_empty() {
+            disabled_triggers.sort();
+            info!("Triggers disabled in a previous session: {}", disabled_triggers.join(", "));
+        }
+    }
+
+    /// Disable the match with the given trigger, until it's enabled again.
+    pub fn disable_trigger(&self, trigger: &str) {
+        self.disabled_triggers.borrow_mut().insert(trigger.to_owned());
+        self.save_disabled_triggers();
+    }
+
+    /// Enable again a match previously disabled with `disable_trigger`.
+    pub fn enable_trigger(&self, trigger: &str) {
+        self.disabled_triggers.borrow_mut().remove(trigger);
+        self.save_disabled_triggers();
+    }
+
+    pub fn disabled_triggers(&self) -> HashSet<String> {
+        self.disabled_triggers.borrow().clone()
+    }
+
+    fn save_disabled_triggers(&self) {
+        if let Some(state_dir) = &self.state_dir {
+            let res = save_disabled_triggers_in_dir(state_dir, &self.disabled_triggers.borrow());
+            if let Err(e) = res {
+                warn!("Unable to save the disabled triggers: {}", e);
             }
         }
+    }
+
+    fn calculate_active_config(&self) -> LiveConfig {
+        let position = self.active_position(&self.set.borrow());
+        self.config_at(position)
+    }
+
+    // Return the config at the given position of the current set, or the default one if None
+    fn config_at(&self, position: Option<usize>) -> LiveConfig {
+        LiveConfig {
+            set: Rc::clone(&self.set.borrow()),
+            position,
+            generation: self.generation.get(),
+        }
+    }
 
-        // No matches, return the default mapping
-        debug!("No matches for custom configs, using default settings.");
-        &self.set.default
+    // Return the position of the specific config matching the current window, if any
+    fn active_position(&self, set: &ConfigSet) -> Option<usize> {
+        set.active_position(&self.system_manager)
     }
 }
 
-impl <'a, S: SystemManager> super::ConfigManager<'a> for RuntimeConfigManager<'a, S> {
-    fn active_config(&'a self) -> &'a Configs {
+impl <'a, S: SystemManager> super::ConfigManager<'a> for RuntimeConfigManager<S> {
+    fn active_config(&'a self) -> LiveConfig {
+        self.install_pending_matches();
+
         let mut last_config_update = self.last_config_update.borrow_mut();
         if let Ok(elapsed) = (*last_config_update).elapsed() {
             *last_config_update = SystemTime::now();
 
-            if elapsed.as_millis() < self.set.default.config_caching_interval as u128 {
-                let last_config = self.last_config.borrow();
-                if let Some(cached_config) = *last_config {
+            if elapsed.as_millis() < self.set.borrow().default.config_caching_interval as u128 {
+                if let Some(position) = self.last_position.get() {
                     debug!("Using cached config");
-                    return cached_config;
+                    return self.config_at(position);
                 }
             }
         }
 
         let config = self.calculate_active_config();
-
-        let mut last_config = self.last_config.borrow_mut();
-        *last_config = Some(config);
+        self.last_position.set(Some(config.position));
 
         config
     }
 
-    fn default_config(&'a self) -> &'a Configs {
-        &self.set.default
+    fn default_config(&'a self) -> LiveConfig {
+        self.install_pending_matches();
+        self.config_at(None)
+    }
+
+    fn is_trigger_disabled(&self, trigger: &str) -> bool {
+        self.disabled_triggers.borrow().contains(trigger)
+    }
+
+    fn set_trigger_disabled(&self, trigger: &str, disabled: bool) {
+        if disabled {
+            self.disable_trigger(trigger);
+        }else{
+            self.enable_trigger(trigger);
+        }
+    }
+
+    fn is_trigger_spent(&self, trigger: &str) -> bool {
+        // A pending set resets the spent triggers once installed
+        self.install_pending_matches();
+        self.spent_triggers.borrow().contains(trigger)
+    }
+
+    fn mark_trigger_spent(&self, trigger: &str) {
+        self.spent_triggers.borrow_mut().insert(trigger.to_owned());
     }
 
-    fn matches(&'a self) -> &'a Vec<Match> {
-        &self.active_config().matches
+    fn current_app(&self) -> (Option<String>, Option<String>) {
+        (self.system_manager.get_current_window_class(), self.system_manager.get_current_window_executable())
+    }
+
+    fn save_enabled_state(&self, enabled: bool) {
+        if let Some(state_dir) = &self.state_dir {
+            if let Err(e) = save_enabled_state_in_dir(state_dir, enabled) {
+                warn!("Unable to save the enabled state: {}", e);
+            }
+        }
+    }
+
+    fn swap_config_set(&self, new_set: ConfigSet) {
+        info!("Installing the new configuration");
+
+        // The log level is only updated on reload, as the log file always receives
+        // the info messages, it can't be lowered below that
+        log::set_max_level(std::cmp::max(new_set.level_filter(), LevelFilter::Info));
+
+        *self.set.borrow_mut() = Rc::new(new_set);
+        self.generation.set(self.generation.get() + 1);
+
+        // The active config must be calculated again with the new filters
+        self.last_position.set(None);
+
+        // The 'once' matches can fire again after a reload
+        self.spent_triggers.borrow_mut().clear();
+    }
+
+    fn swap_matches(&self, new_set: ConfigSet) {
+        self.swapper.swap(new_set);
+    }
+
+    fn config_set(&self) -> Option<ConfigSet> {
+        self.install_pending_matches();
+        Some((**self.set.borrow()).clone())
     }
 }
 
 // TESTS
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use tempfile::{NamedTempFile, TempDir};
     use crate::config::{DEFAULT_CONFIG_FILE_NAME, DEFAULT_CONFIG_FILE_CONTENT};
     use std::fs;
     use std::path::PathBuf;
     use crate::config::ConfigManager;
-    use crate::config::tests::{create_temp_espanso_directories, create_temp_file_in_dir, create_user_config_file};
+    use crate::matcher::TriggerEntry;
+    use crate::matcher::index::ROOT_NODE;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use crate::config::tests::{create_temp_espanso_directories, create_temp_espanso_directories_with_default_content, create_temp_file_in_dir, create_user_config_file, create_profile};
 
-    struct DummySystemManager {
+    pub struct DummySystemManager {
         title: RefCell<String>,
         class: RefCell<String>,
         exec: RefCell<String>,
@@ -267,49 +478,48 @@ mod tests {
         filter_title: "Nice"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path());
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
         assert!(config_set.is_ok());
 
         let dummy_system_manager = DummySystemManager::new();
 
         let config_manager = RuntimeConfigManager::new(config_set.unwrap(), dummy_system_manager);
 
-        let sp1index = config_manager.set.specific
+        let sp1index = config_manager.set.borrow().specific
             .iter().position(|x| x.name == "myname1").unwrap();
-        let sp2index = config_manager.set.specific
+        let sp2index = config_manager.set.borrow().specific
             .iter().position(|x| x.name == "myname2").unwrap();
-        let sp3index = config_manager.set.specific
+        let sp3index = config_manager.set.borrow().specific
             .iter().position(|x| x.name == "myname3").unwrap();
 
-        assert_eq!(config_manager.exec_regexps.len(), 3);
-        assert_eq!(config_manager.title_regexps.len(), 3);
-        assert_eq!(config_manager.class_regexps.len(), 3);
+        assert_eq!(config_manager.set.borrow().specific.len(), 3);
 
-        assert!(config_manager.class_regexps[sp1index].is_none());
-        assert!(config_manager.class_regexps[sp2index].is_some());
-        assert!(config_manager.class_regexps[sp3index].is_none());
+        assert!(config_manager.set.borrow().specific[sp1index].class_regex().is_none());
+        assert!(config_manager.set.borrow().specific[sp2index].class_regex().is_some());
+        assert!(config_manager.set.borrow().specific[sp3index].class_regex().is_none());
 
-        assert!(config_manager.title_regexps[sp1index].is_none());
-        assert!(config_manager.title_regexps[sp2index].is_some());
-        assert!(config_manager.title_regexps[sp3index].is_some());
+        assert!(config_manager.set.borrow().specific[sp1index].title_regex().is_none());
+        assert!(config_manager.set.borrow().specific[sp2index].title_regex().is_some());
+        assert!(config_manager.set.borrow().specific[sp3index].title_regex().is_some());
 
-        assert!(config_manager.exec_regexps[sp1index].is_some());
-        assert!(config_manager.exec_regexps[sp2index].is_none());
-        assert!(config_manager.exec_regexps[sp3index].is_none());
+        assert!(config_manager.set.borrow().specific[sp1index].exec_regex().is_some());
+        assert!(config_manager.set.borrow().specific[sp2index].exec_regex().is_none());
+        assert!(config_manager.set.borrow().specific[sp3index].exec_regex().is_none());
     }
 
     #[test]
     fn test_runtime_constructor_malformed_regexes_are_ignored() {
         let (data_dir, package_dir) = create_temp_espanso_directories();
 
+        // Malformed regexes are rejected by ConfigSet::load, so they are set afterwards
         let specific_path = create_user_config_file(&data_dir.path(), "specific.yml", r###"
         name: myname1
-        filter_exec: "[`-_]"
+        filter_exec: "Placeholder"
         "###);
 
         let specific_path2 = create_user_config_file(&data_dir.path(), "specific2.yml", r###"
         name: myname2
-        filter_title: "[`-_]"
+        filter_title: "Placeholder"
         filter_class: "Car"
         "###);
 
@@ -318,35 +528,42 @@ mod tests {
         filter_title: "Nice"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path());
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
         assert!(config_set.is_ok());
 
+        let mut config_set = config_set.unwrap();
+        for config in config_set.specific.iter_mut() {
+            if config.name == "myname1" {
+                config.filter_exec = "[`-_]".to_owned();
+            }else if config.name == "myname2" {
+                config.filter_title = "[`-_]".to_owned();
+            }
+        }
+
         let dummy_system_manager = DummySystemManager::new();
 
-        let config_manager = RuntimeConfigManager::new(config_set.unwrap(), dummy_system_manager);
+        let config_manager = RuntimeConfigManager::new(config_set, dummy_system_manager);
 
-        let sp1index = config_manager.set.specific
+        let sp1index = config_manager.set.borrow().specific
             .iter().position(|x| x.name == "myname1").unwrap();
-        let sp2index = config_manager.set.specific
+        let sp2index = config_manager.set.borrow().specific
             .iter().position(|x| x.name == "myname2").unwrap();
-        let sp3index = config_manager.set.specific
+        let sp3index = config_manager.set.borrow().specific
             .iter().position(|x| x.name == "myname3").unwrap();
 
-        assert_eq!(config_manager.exec_regexps.len(), 3);
-        assert_eq!(config_manager.title_regexps.len(), 3);
-        assert_eq!(config_manager.class_regexps.len(), 3);
+        assert_eq!(config_manager.set.borrow().specific.len(), 3);
 
-        assert!(config_manager.class_regexps[sp1index].is_none());
-        assert!(config_manager.class_regexps[sp2index].is_some());
-        assert!(config_manager.class_regexps[sp3index].is_none());
+        assert!(config_manager.set.borrow().specific[sp1index].class_regex().is_none());
+        assert!(config_manager.set.borrow().specific[sp2index].class_regex().is_some());
+        assert!(config_manager.set.borrow().specific[sp3index].class_regex().is_none());
 
-        assert!(config_manager.title_regexps[sp1index].is_none());
-        assert!(config_manager.title_regexps[sp2index].is_none());
-        assert!(config_manager.title_regexps[sp3index].is_some());
+        assert!(config_manager.set.borrow().specific[sp1index].title_regex().is_none());
+        assert!(config_manager.set.borrow().specific[sp2index].title_regex().is_none());
+        assert!(config_manager.set.borrow().specific[sp3index].title_regex().is_some());
 
-        assert!(config_manager.exec_regexps[sp1index].is_none());
-        assert!(config_manager.exec_regexps[sp2index].is_none());
-        assert!(config_manager.exec_regexps[sp3index].is_none());
+        assert!(config_manager.set.borrow().specific[sp1index].exec_regex().is_none());
+        assert!(config_manager.set.borrow().specific[sp2index].exec_regex().is_none());
+        assert!(config_manager.set.borrow().specific[sp3index].exec_regex().is_none());
     }
 
     #[test]
@@ -358,7 +575,7 @@ mod tests {
         filter_title: "Chrome"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path());
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
         assert!(config_set.is_ok());
 
         let dummy_system_manager = DummySystemManager::new_custom("Google Chrome", "Chrome", "C:\\Path\\chrome.exe");
@@ -376,7 +593,7 @@ mod tests {
         filter_class: "Chrome"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path());
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
         assert!(config_set.is_ok());
 
         let dummy_system_manager = DummySystemManager::new_custom("Google Chrome", "Chrome", "C:\\Path\\chrome.exe");
@@ -394,7 +611,7 @@ mod tests {
         filter_exec: "chrome.exe"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path());
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
         assert!(config_set.is_ok());
 
         let dummy_system_manager = DummySystemManager::new_custom("Google Chrome", "Chrome", "C:\\Path\\chrome.exe");
@@ -413,7 +630,7 @@ mod tests {
         filter_exec: "firefox.exe"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path());
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
         assert!(config_set.is_ok());
 
         let dummy_system_manager = DummySystemManager::new_custom("Google Chrome", "Browser", "C:\\Path\\chrome.exe");
@@ -432,7 +649,7 @@ mod tests {
         filter_title: "Firefox"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path());
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
         assert!(config_set.is_ok());
 
         let dummy_system_manager = DummySystemManager::new_custom("Google Chrome", "Chrome", "C:\\Path\\chrome.exe");
@@ -451,7 +668,7 @@ mod tests {
         filter_title: "Firefox"
         "###);
 
-        let config_set = ConfigSet::load(data_dir.path(), package_dir.path());
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
         assert!(config_set.is_ok());
 
         let dummy_system_manager = DummySystemManager::new_custom("Google Chrome", "Chrome", "C:\\Path\\chrome.exe");
@@ -467,4 +684,262 @@ mod tests {
         assert_eq!(config_manager.calculate_active_config().name, "firefox");
         assert_eq!(config_manager.active_config().name, "default");
     }
+
+    fn swap_test_set(config_str: &str) -> ConfigSet {
+        let mut set: ConfigSet = serde_yaml::from_str(config_str).unwrap();
+        set.build_match_indexes();
+        set
+    }
+
+    #[test]
+    fn test_runtime_swap_config_set_replaces_the_whole_set() {
+        let initial_set = swap_test_set(r###"
+        default:
+            backspace_limit: 3
+            matches:
+                - trigger: ":a"
+                  replace: "first"
+        specific:
+            - name: chrome
+              filter_title: "Chrome"
+              matches:
+                - trigger: ":c"
+                  replace: "chrome"
+        "###);
+
+        let dummy_system_manager = DummySystemManager::new_custom("Google Chrome", "Chrome", "C:\\Path\\chrome.exe");
+        let config_manager = RuntimeConfigManager::new(initial_set, dummy_system_manager);
+
+        assert_eq!(config_manager.active_config().name, "chrome");
+        assert_eq!(config_manager.active_config().generation(), 0);
+
+        config_manager.swap_config_set(swap_test_set(r###"
+        default:
+            backspace_limit: 10
+            matches:
+                - trigger: ":b"
+                  replace: "second"
+        specific:
+            - name: chrome
+              filter_title: "Firefox"
+              matches:
+                - trigger: ":c"
+                  replace: "chrome"
+        "###));
+
+        let live_config = config_manager.active_config();
+        assert_eq!(live_config.name, "default");
+        assert_eq!(live_config.generation(), 1);
+        assert_eq!(live_config.matches[0].trigger(), ":b");
+
+        // The other settings are swapped too
+        assert_eq!(live_config.backspace_limit, 10);
+        assert_eq!(config_manager.default_config().matches[0].trigger(), ":b");
+        assert_eq!(config_manager.config_set().unwrap().default.matches[0].trigger(), ":b");
+    }
+
+    #[test]
+    fn test_runtime_swap_config_set_uses_settings_of_new_configs() {
+        let dummy_system_manager = DummySystemManager::new_custom("Google Chrome", "Chrome", "C:\\Path\\chrome.exe");
+        let config_manager = RuntimeConfigManager::new(swap_test_set(r###"
+        default:
+            backspace_limit: 3
+        specific: []
+        "###), dummy_system_manager);
+
+        config_manager.swap_config_set(swap_test_set(r###"
+        default:
+            backspace_limit: 3
+        specific:
+            - name: chrome
+              filter_title: "Chrome"
+              backspace_limit: 7
+              matches:
+                - trigger: ":c"
+                  replace: "chrome"
+        "###));
+
+        let active_config = config_manager.active_config();
+        assert_eq!(active_config.name, "chrome");
+        assert_eq!(active_config.backspace_limit, 7);
+        assert_eq!(active_config.matches[0].trigger(), ":c");
+    }
+
+    #[test]
+    fn test_runtime_swap_matches_is_installed_on_next_request() {
+        let config_manager = RuntimeConfigManager::new(swap_test_set(r###"
+        default:
+            matches:
+                - trigger: ":a"
+                  replace: "first"
+        specific: []
+        "###), DummySystemManager::new());
+
+        config_manager.swap_matches(swap_test_set(r###"
+        default:
+            matches:
+                - trigger: ":b"
+                  replace: "second"
+        specific: []
+        "###));
+
+        // The set is only installed when a config is requested
+        assert_eq!(config_manager.generation.get(), 0);
+
+        let live_config = config_manager.active_config();
+        assert_eq!(live_config.generation(), 1);
+        assert_eq!(live_config.matches[0].trigger(), ":b");
+    }
+
+    #[test]
+    fn test_runtime_swap_matches_from_another_thread() {
+        let swap_count = 200;
+        let config_manager = RuntimeConfigManager::new(swap_test_set(r###"
+        default:
+            matches:
+                - trigger: ":v"
+                  replace: "initial"
+        specific: []
+        "###), DummySystemManager::new());
+
+        let swapper = config_manager.match_swapper();
+        let done = Arc::new(AtomicBool::new(false));
+        let writer_done = Arc::clone(&done);
+        let writer = std::thread::spawn(move || {
+            for i in 0..swap_count {
+                swapper.swap(swap_test_set(&format!(r###"
+                default:
+                    matches:
+                        - trigger: ":v{}"
+                          replace: "version {}"
+                specific: []
+                "###, i, i)));
+            }
+            writer_done.store(true, Ordering::SeqCst);
+        });
+
+        // Every observed config must be consistent, with an index built for its own matches
+        let check_live_config = || {
+            let live_config = config_manager.active_config();
+            assert_eq!(live_config.matches.len(), 1);
+
+            let mut node = ROOT_NODE;
+            for c in live_config.matches[0].trigger().chars() {
+                node = live_config.match_index.next(node, &TriggerEntry::Char(c)).unwrap();
+            }
+            assert_eq!(live_config.match_index.matches_at(node), &[(0, 0)]);
+
+            live_config.matches[0].trigger().to_owned()
+        };
+
+        while !done.load(Ordering::SeqCst) {
+            check_live_config();
+        }
+        writer.join().unwrap();
+
+        assert_eq!(check_live_config(), format!(":v{}", swap_count - 1));
+    }
+
+    #[test]
+    fn test_active_profile_round_trip() {
+        let data_dir = TempDir::new().unwrap();
+        assert_eq!(load_active_profile_from_dir(data_dir.path()), None);
+
+        save_active_profile_in_dir(data_dir.path(), Some("work")).unwrap();
+        assert_eq!(load_active_profile_from_dir(data_dir.path()), Some("work".to_owned()));
+
+        save_active_profile_in_dir(data_dir.path(), None).unwrap();
+        assert_eq!(load_active_profile_from_dir(data_dir.path()), None);
+    }
+
+    #[test]
+    fn test_enabled_state_round_trip() {
+        let data_dir = TempDir::new().unwrap();
+
+        save_enabled_state_in_dir(data_dir.path(), false).unwrap();
+        assert_eq!(load_enabled_state_from_dir(data_dir.path()), false);
+
+        save_enabled_state_in_dir(data_dir.path(), true).unwrap();
+        assert_eq!(load_enabled_state_from_dir(data_dir.path()), true);
+    }
+
+    #[test]
+    fn test_enabled_state_missing_file_defaults_to_enabled() {
+        let data_dir = TempDir::new().unwrap();
+
+        assert_eq!(load_enabled_state_from_dir(data_dir.path()), true);
+    }
+
+    #[test]
+    fn test_enabled_state_corrupted_file_defaults_to_enabled() {
+        let data_dir = TempDir::new().unwrap();
+        fs::write(data_dir.path().join(ENABLED_STATE_FILE_NAME), "corrupted").unwrap();
+
+        assert_eq!(load_enabled_state_from_dir(data_dir.path()), true);
+    }
+
+    #[test]
+    fn test_runtime_disable_and_enable_trigger() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        let config_manager = RuntimeConfigManager::new(config_set.unwrap(), DummySystemManager::new());
+
+        assert!(!config_manager.is_trigger_disabled(":espanso"));
+
+        config_manager.disable_trigger(":espanso");
+        assert!(config_manager.is_trigger_disabled(":espanso"));
+        assert!(config_manager.disabled_triggers().contains(":espanso"));
+
+        config_manager.enable_trigger(":espanso");
+        assert!(!config_manager.is_trigger_disabled(":espanso"));
+        assert!(config_manager.disabled_triggers().is_empty());
+    }
+
+    #[test]
+    fn test_runtime_disabled_triggers_are_persisted() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+        let state_dir = TempDir::new().unwrap();
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        let mut config_manager = RuntimeConfigManager::new(config_set.unwrap(), DummySystemManager::new());
+        config_manager.set_state_dir(state_dir.path());
+        config_manager.disable_trigger(":espanso");
+        config_manager.disable_trigger(":date");
+        config_manager.enable_trigger(":date");
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        let mut config_manager = RuntimeConfigManager::new(config_set.unwrap(), DummySystemManager::new());
+        config_manager.set_state_dir(state_dir.path());
+
+        assert!(config_manager.is_trigger_disabled(":espanso"));
+        assert!(!config_manager.is_trigger_disabled(":date"));
+    }
+
+    #[test]
+    fn test_runtime_spent_triggers_are_reset_on_swap() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None).unwrap();
+        let config_manager = RuntimeConfigManager::new(config_set.clone(), DummySystemManager::new());
+        config_manager.mark_trigger_spent(":espanso");
+        assert!(config_manager.is_trigger_spent(":espanso"));
+        assert!(!config_manager.is_trigger_spent(":date"));
+
+        config_manager.swap_config_set(config_set);
+        assert!(!config_manager.is_trigger_spent(":espanso"));
+    }
+
+    #[test]
+    fn test_runtime_corrupted_disabled_triggers_file_is_ignored() {
+        let (data_dir, package_dir) = create_temp_espanso_directories();
+        let state_dir = TempDir::new().unwrap();
+        fs::write(state_dir.path().join(DISABLED_TRIGGERS_FILE_NAME), "corrupted").unwrap();
+
+        let config_set = ConfigSet::load(data_dir.path(), package_dir.path(), None);
+        let mut config_manager = RuntimeConfigManager::new(config_set.unwrap(), DummySystemManager::new());
+        config_manager.set_state_dir(state_dir.path());
+
+        assert!(config_manager.disabled_triggers().is_empty());
+    }
 }
\ No newline at end of file
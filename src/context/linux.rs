@@ -81,7 +81,7 @@ impl Drop for LinuxContext {
 // Native bridge code
 
 extern fn keypress_callback(_self: *mut c_void, raw_buffer: *const u8, len: i32,
-                            is_modifier: i32, key_code: i32) {
+                            is_modifier: i32, key_code: i32, modifier_state: i32) {
     unsafe {
         let _self = _self as *mut LinuxContext;
 
@@ -93,7 +93,7 @@ extern fn keypress_callback(_self: *mut c_void, raw_buffer: *const u8, len: i32,
             // Send the char through the channel
             match char_str {
                 Ok(char_str) => {
-                    let event = Event::Key(KeyEvent::Char(char_str.to_owned()));
+                    let event = Event::Key(KeyEvent::Char(char_str.to_owned(), KeyModifierSet::from_native_state(modifier_state)));
                     (*_self).send_channel.send(event).unwrap();
                 },
                 Err(e) => {
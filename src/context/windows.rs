@@ -19,7 +19,7 @@
 
 use std::sync::mpsc::Sender;
 use crate::bridge::windows::*;
-use crate::event::{Event, KeyEvent, KeyModifier, ActionType};
+use crate::event::{Event, KeyEvent, KeyModifier, KeyModifierSet, ActionType};
 use crate::event::KeyModifier::*;
 use std::ffi::c_void;
 use std::{fs};
@@ -103,7 +103,7 @@ impl super::Context for WindowsContext {
 // Native bridge code
 
 extern fn keypress_callback(_self: *mut c_void, raw_buffer: *const u16, len: i32,
-                            is_modifier: i32, key_code: i32, is_key_down: i32) {
+                            is_modifier: i32, key_code: i32, is_key_down: i32, modifier_state: i32) {
     unsafe {
         let _self = _self as *mut WindowsContext;
         if is_key_down != 0 {  // KEY DOWN EVENT
@@ -118,7 +118,7 @@ extern fn keypress_callback(_self: *mut c_void, raw_buffer: *const u16, len: i32
                     // Send the char through the channel
                     match string {
                         Ok(string) => {
-                            let event = Event::Key(KeyEvent::Char(string));
+                            let event = Event::Key(KeyEvent::Char(string, KeyModifierSet::from_native_state(modifier_state)));
                             (*_self).send_channel.send(event).unwrap();
                         },
                         Err(e) => {
@@ -17,14 +17,17 @@
  * along with espanso.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::matcher::{Match, MatchReceiver, MatchContentType};
-use crate::keyboard::KeyboardManager;
-use crate::config::ConfigManager;
-use crate::config::BackendType;
+use crate::matcher::{Match, MatchReceiver, MatchContentType, CursorPosition, KEY_TOKEN_REGEX};
+use crate::keyboard::{KeyboardManager, KeyCode, PasteShortcut};
+use crate::config::{ConfigManager, ConfigSet, Configs, ConfigLoadError};
+use crate::config::{BackendType, BackspaceMode};
 use crate::clipboard::ClipboardManager;
 use log::{info, warn, error};
 use crate::ui::{UIManager, MenuItem, MenuItemType};
-use crate::event::{ActionEventReceiver, ActionType};
+use crate::ui::notifier::{Notifier, NoopNotifier};
+use crate::event::{ActionEventReceiver, ActionType, ExpandRequestReceiver, StatsRequestReceiver, ReloadRequestReceiver,
+                   TriggerStateRequestReceiver, ConfigSetRequestReceiver};
+use crate::stats::{ExpansionStats, StatsSnapshot, UsageStats};
 use crate::extension::Extension;
 use crate::render::{Renderer, RenderResult};
 use std::cell::RefCell;
@@ -33,6 +36,10 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use regex::{Regex, Captures};
 use std::time::SystemTime;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Number of most used triggers reported along with the expansion stats
+const MOST_USED_STATS_COUNT: usize = 10;
 
 pub struct Engine<'a, S: KeyboardManager, C: ClipboardManager, M: ConfigManager<'a>,
                   U: UIManager, R: Renderer> {
@@ -41,10 +48,12 @@ pub struct Engine<'a, S: KeyboardManager, C: ClipboardManager, M: ConfigManager<
     config_manager: &'a M,
     ui_manager: &'a U,
     renderer: &'a R,
+    notifier: &'a dyn Notifier,  // The NoopNotifier, unless one is installed with set_notifier
 
     enabled: RefCell<bool>,
     last_action_time: RefCell<SystemTime>,  // Used to block espanso from re-interpreting it's own inputs
-    action_noop_interval: u128,
+    stats: ExpansionStats,
+    usage_stats: UsageStats,
 }
 
 impl <'a, S: KeyboardManager, C: ClipboardManager, M: ConfigManager<'a>, U: UIManager, R: Renderer>
@@ -54,17 +63,71 @@ impl <'a, S: KeyboardManager, C: ClipboardManager, M: ConfigManager<'a>, U: UIMa
                renderer: &'a R) -> Engine<'a, S, C, M, U, R> {
         let enabled = RefCell::new(true);
         let last_action_time = RefCell::new(SystemTime::now());
-        let action_noop_interval = config_manager.default_config().action_noop_interval;
 
         Engine{keyboard_manager,
             clipboard_manager,
             config_manager,
             ui_manager,
             renderer,
+            notifier: &NoopNotifier {},
             enabled,
             last_action_time,
-            action_noop_interval,
+            stats: ExpansionStats::new(),
+            usage_stats: UsageStats::new(),
+        }
+    }
+
+    /// Replace the usage stats, which are only kept in memory by default.
+    pub fn set_usage_stats(&mut self, usage_stats: UsageStats) {
+        self.usage_stats = usage_stats;
+    }
+
+    /// Return at most `n` matches, sorted from the most expanded one.
+    pub fn most_used(&self, n: usize) -> Vec<(Match, u64)> {
+        let config = self.config_manager.active_config();
+        self.usage_stats.most_used(&config.matches, n).into_iter()
+            .map(|(m, count)| (m.clone(), count))
+            .collect()
+    }
+
+    /// Replace the notifier used to report the state changes of espanso.
+    pub fn set_notifier(&mut self, notifier: &'a dyn Notifier) {
+        self.notifier = notifier;
+    }
+
+    // Update the enabled state, returning false if the change was a no-op, such as when
+    // restoring the state at startup, in which case the user is not notified.
+    fn set_enabled(&self, status: bool) -> bool {
+        let mut enabled_ref = self.enabled.borrow_mut();
+        if *enabled_ref == status {
+            return false;
+        }
+        *enabled_ref = status;
+
+        // avoid espanso reinterpreting its own actions
+        if self.check_last_action_and_set(self.config_manager.default_config().action_noop_interval) {
+            return false;
         }
+
+        let message = if status {
+            "espanso enabled"
+        }else{
+            "espanso disabled"
+        };
+
+        info!("Toggled: {}", message);
+
+        self.notify(|notifier| notifier.on_toggle(status));
+        true
+    }
+
+    // Report a state change through the notifier, unless the notifications are disabled
+    fn notify<F: FnOnce(&dyn Notifier)>(&self, action: F) {
+        if !self.config_manager.default_config().show_notifications {
+            return;
+        }
+
+        action(self.notifier);
     }
 
     fn build_menu(&self) -> Vec<MenuItem> {
@@ -100,16 +163,22 @@ impl <'a, S: KeyboardManager, C: ClipboardManager, M: ConfigManager<'a>, U: UIMa
     fn return_content_if_preserve_clipboard_is_enabled(&self) -> Option<String> {
         // If the preserve_clipboard option is enabled, first save the current
         // clipboard content in order to restore it later.
+        // Non-text content can't be read, so it's not restored
         if self.config_manager.default_config().preserve_clipboard {
-            match self.clipboard_manager.get_clipboard() {
-                Some(clipboard) => {Some(clipboard)},
-                None => {None},
-            }
+            self.clipboard_manager.get_clipboard()
         }else {
             None
         }
     }
 
+    fn trigger_paste(&self, shortcut: &PasteShortcut) {
+        // Custom combinations are sent as they are, the others are handled by the platform
+        match shortcut {
+            PasteShortcut::Custom(keys) => self.keyboard_manager.send_chord(keys),
+            _ => self.keyboard_manager.trigger_paste(shortcut),
+        }
+    }
+
     /// Used to check if the last action has been executed within a specified interval.
     /// If so, return true (blocking the action), otherwise false.
     fn check_last_action_and_set(&self, interval: u128) -> bool {
@@ -123,117 +192,144 @@ impl <'a, S: KeyboardManager, C: ClipboardManager, M: ConfigManager<'a>, U: UIMa
         (*last_action_time) = SystemTime::now();
         return false;
     }
-}
 
-lazy_static! {
-    static ref VAR_REGEX: Regex = Regex::new("\\{\\{\\s*(?P<name>\\w+)\\s*\\}\\}").unwrap();
-}
+    /// Type the given text. If the injection fails, the following lines of the text
+    /// are not typed and the byte offset of the text that was not typed is returned.
+    /// Failures are only detected on Wayland, through the exit status of wtype.
+    fn send_text(&self, text: &str) -> Result<(), usize> {
+        if cfg!(target_os = "linux") {
+            if self.keyboard_manager.try_send_string(text) {
+                Ok(())
+            }else{
+                Err(0)
+            }
+        }else{
+            self.send_text_lines(text)
+        }
+    }
 
-impl <'a, S: KeyboardManager, C: ClipboardManager, M: ConfigManager<'a>, U: UIManager, R: Renderer>
-    MatchReceiver for Engine<'a, S, C, M, U, R>{
+    // On windows and macos, newlines are not managed by send_string, so
+    // each "\n" char is substituted with an Enter key press.
+    fn send_text_lines(&self, text: &str) -> Result<(), usize> {
+        let mut offset = 0;
 
-    fn on_match(&self, m: &Match, trailing_separator: Option<char>) {
-        let config = self.config_manager.active_config();
+        for (i, split) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.keyboard_manager.send_enter();
+            }
 
-        if !config.enable_active {
-            return;
+            if !self.keyboard_manager.try_send_string(split) {
+                return Err(offset);
+            }
+
+            offset += split.len() + 1;
         }
 
-        // avoid espanso reinterpreting its own actions
-        if self.check_last_action_and_set(self.action_noop_interval) {
-            return;
+        Ok(())
+    }
+
+    /// Paste the given text through the clipboard, returning the previous clipboard
+    /// content if it has to be restored.
+    fn paste_text(&self, text: &str, config: &Configs) -> Option<String> {
+        // If the preserve_clipboard option is enabled, save the current
+        // clipboard content to restore it later.
+        let previous_clipboard_content = self.return_content_if_preserve_clipboard_is_enabled();
+
+        self.clipboard_manager.set_clipboard(text);
+        self.trigger_paste(&config.paste_shortcut);
+
+        previous_clipboard_content
+    }
+
+    // Check if the expansions must be suppressed because a password field is focused
+    fn is_in_password_field(&self) -> bool {
+        if self.config_manager.default_config().disable_in_password_fields &&
+            self.keyboard_manager.is_secure_input_active() {
+            info!("Password field detected, skipping the expansion");
+            return true;
+        }
+
+        false
+    }
+
+    /// Compute what the expansion of the given match would insert, going through the same
+    /// steps as the injection but without typing anything. Return None if the match
+    /// doesn't expand to text.
+    pub fn preview_match(&self, m: &Match, typed_trigger: &str, trailing_separator: Option<char>) -> Option<ExpansionPreview> {
+        self.inject_match(m, trailing_separator, Some(typed_trigger), true)
+    }
+
+    /// Expand the given match. If `typed_trigger` is None, the trigger was
+    /// not typed by the user (for example, when requested through IPC or with
+    /// a hotkey), so there is nothing to delete. In preview mode, nothing is typed
+    /// and the text expansion that would be sent is returned instead.
+    fn inject_match(&self, m: &Match, trailing_separator: Option<char>, typed_trigger: Option<&str>,
+                    preview: bool) -> Option<ExpansionPreview> {
+        let config = self.config_manager.active_config();
+
+        if !preview {
+            if !config.enable_active || self.is_in_password_field() {
+                return None;
+            }
+
+            // avoid espanso reinterpreting its own actions
+            if self.check_last_action_and_set(self.config_manager.default_config().action_noop_interval) {
+                return None;
+            }
         }
 
-        let char_count = if trailing_separator.is_none() {
-            m.trigger.chars().count() as i32
+        // If the trigger is kept, the trailing separator is kept as well,
+        // so there is no need to type it again after the expansion.
+        let trailing_separator = if m.keep_trigger {
+            None
         }else{
-            m.trigger.chars().count() as i32 + 1 // Count also the separator
+            trailing_separator
         };
 
-        self.keyboard_manager.delete_string(char_count);
+        // The trigger is deleted once the match is rendered, so that it's left
+        // untouched if the rendering fails
+        let deleted = match typed_trigger {
+            Some(typed_trigger) if !m.keep_trigger => {
+                trigger_deletion_count(typed_trigger, trailing_separator, &config.backspace_mode) as usize
+            },
+            _ => 0,
+        };
 
         let mut previous_clipboard_content : Option<String> = None;
 
-        let rendered = self.renderer.render_match(m, config, vec![]);
+        let rendered = self.renderer.render_match(m, typed_trigger.unwrap_or(m.trigger()), &config, vec![]);
 
         match rendered {
-            RenderResult::Text(mut target_string) => {
-                // If a trailing separator was counted in the match, add it back to the target string
-                if let Some(trailing_separator) = trailing_separator {
-                    if trailing_separator == '\r' {   // If the trailing separator is a carriage return,
-                        target_string.push('\n');   // convert it to new line
-                    }else{
-                        target_string.push(trailing_separator);
-                    }
+            RenderResult::Text(target_string) => {
+                let result = compute_expansion(m, target_string, deleted, trailing_separator, &config);
+                if preview {
+                    return Some(ExpansionPreview::from_result(&result));
                 }
 
-                // Convert Windows style newlines into unix styles
-                target_string = target_string.replace("\r\n", "\n");
-
-                // Calculate cursor rewind moves if a Cursor Hint is present
-                let index = target_string.find("$|$");
-                let cursor_rewind = if let Some(index) = index {
-                    // Convert the byte index to a char index
-                    let char_str = &target_string[0..index];
-                    let char_index = char_str.chars().count();
-                    let total_size = target_string.chars().count();
-
-                    // Remove the $|$ placeholder
-                    target_string = target_string.replace("$|$", "");
-
-                    // Calculate the amount of rewind moves needed (LEFT ARROW).
-                    // Subtract also 3, equal to the number of chars of the placeholder "$|$"
-                    let moves = (total_size - char_index - 3) as i32;
-                    Some(moves)
-                }else{
-                    None
-                };
-
-                match config.backend {
-                    BackendType::Inject => {
-                        // Send the expected string. On linux, newlines are managed automatically
-                        // while on windows and macos, we need to emulate a Enter key press.
-
-                        if cfg!(target_os = "linux") {
-                            self.keyboard_manager.send_string(&target_string);
-                        }else{
-                            // To handle newlines, substitute each "\n" char with an Enter key press.
-                            let splits = target_string.split('\n');
-
-                            for (i, split) in splits.enumerate() {
-                                if i > 0 {
-                                    self.keyboard_manager.send_enter();
-                                }
-
-                                self.keyboard_manager.send_string(split);
-                            }
-                        }
-                    },
-                    BackendType::Clipboard => {
-                        // If the preserve_clipboard option is enabled, save the current
-                        // clipboard content to restore it later.
-                        previous_clipboard_content = self.return_content_if_preserve_clipboard_is_enabled();
-
-                        self.clipboard_manager.set_clipboard(&target_string);
-                        self.keyboard_manager.trigger_paste(&config.paste_shortcut);
-                    },
-                }
+                previous_clipboard_content = self.apply_expansion(m, &result, trailing_separator, &config);
 
-                if let Some(moves) = cursor_rewind {
-                    // Simulate left arrow key presses to bring the cursor into the desired position
-                    self.keyboard_manager.move_cursor_left(moves);
-                }
+                self.stats.record(&m.fire_key());
+                self.usage_stats.increment(&m.fire_key());
             },
+            RenderResult::Image(_) if preview => return None,
             RenderResult::Image(image_path) => {
+                if deleted > 0 {
+                    self.keyboard_manager.delete_string(deleted as i32);
+                }
+
                 // If the preserve_clipboard option is enabled, save the current
                 // clipboard content to restore it later.
                 previous_clipboard_content = self.return_content_if_preserve_clipboard_is_enabled();
 
                 self.clipboard_manager.set_clipboard_image(&image_path);
-                self.keyboard_manager.trigger_paste(&config.paste_shortcut);
+                self.trigger_paste(&config.paste_shortcut);
+                self.press_enter_if_needed(m);
+
+                self.stats.record(&m.fire_key());
+                self.usage_stats.increment(&m.fire_key());
             },
             RenderResult::Error => {
-                error!("Could not render match: {}", m.trigger);
+                error!("Could not render match: {}", m.fire_key());
             },
         }
 
@@ -245,37 +341,321 @@ impl <'a, S: KeyboardManager, C: ClipboardManager, M: ConfigManager<'a>, U: UIMa
 
             self.clipboard_manager.set_clipboard(&previous_clipboard_content);
         }
+
+        None
     }
 
-    fn on_enable_update(&self, status: bool) {
-        // avoid espanso reinterpreting its own actions
-        if self.check_last_action_and_set(self.action_noop_interval) {
-            return;
+    // Send the keys of the given expansion, returning the previous clipboard content if it
+    // has to be restored.
+    fn apply_expansion(&self, m: &Match, result: &ExpansionResult, trailing_separator: Option<char>,
+                       config: &Configs) -> Option<String> {
+        let mut previous_clipboard_content = None;
+
+        if result.deleted > 0 {
+            self.keyboard_manager.delete_string(result.deleted as i32);
         }
 
-        let message = if status {
-            "espanso enabled"
+        let parts = split_key_tokens(&result.inserted);
+        match result.used_backend {
+            BackendType::Inject => {
+                for part in parts.iter() {
+                    match part {
+                        ReplacementPart::Text(text) => {
+                            let _ = self.send_text(text);
+                        },
+                        ReplacementPart::Key(key) => self.keyboard_manager.send_key(*key, 1),
+                    }
+                }
+            },
+            BackendType::Clipboard => {
+                previous_clipboard_content = self.paste_text(&result.inserted, config);
+            },
+            BackendType::Auto => {
+                // Replacements with key presses are always injected, so this is plain text.
+                // Only the lines that were not typed are pasted, to avoid duplicating them.
+                if let Err(offset) = self.send_text(&result.inserted) {
+                    warn!("Unable to inject the replacement, falling back to the clipboard");
+                    previous_clipboard_content = self.paste_text(&result.inserted[offset..], config);
+                }
+            },
+        }
+
+        if m.select_after {
+            // Select the inserted text, so that it can be immediately overwritten.
+            // The trailing separator is left out of the selection and, as the
+            // cursor is moved by the selection, the cursor hint is ignored.
+            let mut selection_len = Match::rendered_length(&result.inserted) as i32;
+            if trailing_separator.is_some() {
+                self.keyboard_manager.move_cursor_left(1);
+                selection_len -= 1;
+            }
+
+            self.keyboard_manager.select_left(selection_len);
         }else{
-            "espanso disabled"
-        };
+            match m.cursor {
+                Some(CursorPosition::Start) => self.keyboard_manager.send_key(KeyCode::Home, 1),
+                Some(CursorPosition::End) => self.keyboard_manager.send_key(KeyCode::End, 1),
+                Some(CursorPosition::Marker) | None => {
+                    if let Some(moves) = result.cursor_offset {
+                        // Simulate left arrow key presses to bring the cursor into the desired position
+                        self.keyboard_manager.move_cursor_left(moves as i32);
+                    }
+                },
+            }
+        }
 
-        info!("Toggled: {}", message);
+        if !m.select_after {
+            self.press_enter_if_needed(m);
+        }
 
-        let mut enabled_ref = self.enabled.borrow_mut();
-        *enabled_ref = status;
+        previous_clipboard_content
+    }
+
+    fn press_enter_if_needed(&self, m: &Match) {
+        // send_enter is a NOOP on the platforms typing the newlines with send_string,
+        // so the key is pressed explicitly
+        if m.press_enter_after {
+            self.keyboard_manager.send_key(KeyCode::Enter, 1);
+        }
+    }
+}
+
+lazy_static! {
+    static ref VAR_REGEX: Regex = Regex::new("\\{\\{\\s*(?P<name>\\w+)\\s*\\}\\}").unwrap();
+}
+
+/// Piece of a replacement, either text to type or a key to press.
+#[derive(Debug, PartialEq)]
+enum ReplacementPart {
+    Text(String),
+    Key(KeyCode),
+}
+
+/// Split the replacement at each {{key:NAME}} token. Tokens with an unknown
+/// key name are kept as text.
+fn split_key_tokens(text: &str) -> Vec<ReplacementPart> {
+    let mut parts = Vec::new();
+    let mut last_end = 0;
+
+    for caps in KEY_TOKEN_REGEX.captures_iter(text) {
+        let token = caps.get(0).unwrap();
+        let name = caps.name("name").unwrap().as_str();
+
+        match KeyCode::from_name(name) {
+            Some(key) => {
+                if token.start() > last_end {
+                    parts.push(ReplacementPart::Text(text[last_end..token.start()].to_owned()));
+                }
+                parts.push(ReplacementPart::Key(key));
+                last_end = token.end();
+            },
+            None => {
+                warn!("Unknown key name in replacement: {}", name);
+            },
+        }
+    }
+
+    if last_end < text.len() {
+        parts.push(ReplacementPart::Text(text[last_end..].to_owned()));
+    }
+
+    parts
+}
 
-        self.ui_manager.notify(message);
+/*
+ * Calculate the number of backspaces needed to delete the given trigger.
+ * By default, the count is made of Unicode scalar values (not bytes), as each
+ * keystroke received by the matcher corresponds to a single char. In grapheme
+ * mode, each grapheme cluster is counted once instead.
+ */
+fn trigger_deletion_count(trigger: &str, trailing_separator: Option<char>, mode: &BackspaceMode) -> i32 {
+    let count = match mode {
+        BackspaceMode::Codepoint => trigger.chars().count() as i32,
+        BackspaceMode::Grapheme => trigger.graphemes(true).count() as i32,
+    };
+
+    if trailing_separator.is_some() {
+        count + 1 // Count also the separator
+    }else{
+        count
+    }
+}
+
+/// Outcome of the expansion of a text match, computed before anything is typed, so
+/// that the expansion logic can be tested without a keyboard.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpansionResult {
+    // Number of chars deleted before the insertion, to remove the typed trigger
+    pub deleted: usize,
+
+    // Replacement, including the trailing separator. The {{key:NAME}} tokens it contains
+    // are pressed as keys, so they always lead to the Inject backend.
+    pub inserted: String,
+
+    // Number of Left presses that bring the cursor on the $|$ hint, if present
+    pub cursor_offset: Option<usize>,
+
+    pub used_backend: BackendType,
+}
+
+/// Compute the expansion of a text match from its rendered replacement.
+fn compute_expansion(m: &Match, rendered: String, deleted: usize, trailing_separator: Option<char>,
+                     config: &Configs) -> ExpansionResult {
+    let rendered = m.adjust_trailing_newline(rendered);
+    let (inserted, cursor_rewind) = prepare_target_string(rendered, trailing_separator);
+
+    let parts = split_key_tokens(&inserted);
+    let has_keys = parts.iter().any(|part| match part {
+        ReplacementPart::Key(_) => true,
+        ReplacementPart::Text(_) => false,
+    });
+
+    // Key presses can't be pasted, so replacements containing them are always injected.
+    // Otherwise the match backend wins, then long replacements are pasted.
+    let used_backend = if has_keys {
+        BackendType::Inject
+    }else if let Some(backend) = &m.backend {
+        backend.clone()
+    }else if config.clipboard_threshold > 0 && Match::rendered_length(&inserted) > config.clipboard_threshold {
+        BackendType::Clipboard
+    }else{
+        config.backend.clone()
+    };
+
+    ExpansionResult {
+        deleted,
+        inserted,
+        cursor_offset: cursor_rewind.map(|moves| moves as usize),
+        used_backend,
+    }
+}
+
+/// Add the trailing separator back to the rendered replacement, normalize the newlines
+/// and remove the $|$ cursor hint, returning the number of Left presses needed to
+/// bring the cursor back to the hint, if present.
+fn prepare_target_string(mut target_string: String, trailing_separator: Option<char>) -> (String, Option<i32>) {
+    // If a trailing separator was counted in the match, add it back to the target string
+    if let Some(trailing_separator) = trailing_separator {
+        if trailing_separator == '\r' {   // If the trailing separator is a carriage return,
+            target_string.push('\n');   // convert it to new line
+        }else{
+            target_string.push(trailing_separator);
+        }
+    }
+
+    // Convert Windows style newlines into unix styles
+    target_string = target_string.replace("\r\n", "\n");
+
+    // Calculate cursor rewind moves if a Cursor Hint is present
+    let index = target_string.find("$|$");
+    let cursor_rewind = if let Some(index) = index {
+        // Calculate the amount of rewind moves needed (LEFT ARROW), equal
+        // to the number of chars typed after the $|$ placeholder
+        let moves = Match::rendered_length(&target_string[index+3..]) as i32;
+
+        // Remove the $|$ placeholder
+        target_string = target_string.replace("$|$", "");
+
+        Some(moves)
+    }else{
+        None
+    };
+
+    (target_string, cursor_rewind)
+}
+
+/// What an expansion would insert, computed without typing anything, see Engine::preview_match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpansionPreview {
+    // Text that would be typed or pasted, without the {{key:NAME}} tokens
+    pub text: String,
+
+    // Number of Left presses that would bring the cursor on the $|$ hint, if present
+    pub cursor_offset: Option<usize>,
+
+    // Keys that would be pressed, in order
+    pub keys: Vec<KeyCode>,
+
+    // Number of chars that would be deleted before the insertion
+    pub deleted: usize,
+
+    pub used_backend: BackendType,
+}
+
+impl ExpansionPreview {
+    fn from_result(result: &ExpansionResult) -> ExpansionPreview {
+        let mut text = String::new();
+        let mut keys = Vec::new();
+        for part in split_key_tokens(&result.inserted) {
+            match part {
+                ReplacementPart::Text(part_text) => text.push_str(&part_text),
+                ReplacementPart::Key(key) => keys.push(key),
+            }
+        }
+
+        ExpansionPreview {
+            text,
+            cursor_offset: result.cursor_offset,
+            keys,
+            deleted: result.deleted,
+            used_backend: result.used_backend.clone(),
+        }
+    }
+}
+
+impl <'a, S: KeyboardManager, C: ClipboardManager, M: ConfigManager<'a>, U: UIManager, R: Renderer>
+    MatchReceiver for Engine<'a, S, C, M, U, R>{
+
+    fn on_match(&self, m: &Match, trailing_separator: Option<char>, trigger_offset: usize) {
+        let typed_trigger = m.triggers().get(trigger_offset).map_or(m.trigger(), String::as_str);
+        self.inject_match(m, trailing_separator, Some(typed_trigger), false);
+    }
+
+    fn on_hotkey_match(&self, m: &Match) {
+        self.inject_match(m, None, None, false);
+    }
+
+    fn on_escaped_match(&self, m: &Match, trailing_separator: Option<char>, trigger_offset: usize) {
+        let config = self.config_manager.active_config();
+
+        if !config.enable_active || self.is_in_password_field() {
+            return;
+        }
+
+        // avoid espanso reinterpreting its own actions
+        if self.check_last_action_and_set(self.config_manager.default_config().action_noop_interval) {
+            return;
+        }
+
+        // Remove the escape char along with the trigger, then type the trigger back
+        let typed_trigger = m.triggers().get(trigger_offset).map_or(m.trigger(), String::as_str);
+        let char_count = trigger_deletion_count(typed_trigger, trailing_separator, &config.backspace_mode) + 1;
+        self.keyboard_manager.delete_string(char_count);
+        self.keyboard_manager.send_string(typed_trigger);
+
+        match trailing_separator {
+            Some('\r') | Some('\n') => self.keyboard_manager.send_enter(),
+            Some(separator) => self.keyboard_manager.send_string(&separator.to_string()),
+            None => {},
+        }
+    }
+
+    fn on_enable_update(&self, status: bool) {
+        // Persist the state, so that it can be restored after a restart
+        if self.set_enabled(status) {
+            self.config_manager.save_enabled_state(status);
+        }
     }
 
     fn on_passive(&self) {
         // avoid espanso reinterpreting its own actions
-        if self.check_last_action_and_set(self.action_noop_interval) {
+        if self.check_last_action_and_set(self.config_manager.default_config().action_noop_interval) {
             return;
         }
 
         let config = self.config_manager.active_config();
 
-        if !config.enable_passive {
+        if !config.enable_passive || self.is_in_password_field() {
             return;
         }
 
@@ -300,7 +680,7 @@ impl <'a, S: KeyboardManager, C: ClipboardManager, M: ConfigManager<'a>, U: UIMa
                     self.clipboard_manager.set_clipboard(&payload);
 
                     std::thread::sleep(std::time::Duration::from_millis(100)); // TODO: avoid hardcoding
-                    self.keyboard_manager.trigger_paste(&config.paste_shortcut);
+                    self.trigger_paste(&config.paste_shortcut);
                 },
                 _ => {
                     warn!("Cannot expand passive match")
@@ -308,6 +688,10 @@ impl <'a, S: KeyboardManager, C: ClipboardManager, M: ConfigManager<'a>, U: UIMa
             }
         }
     }
+
+    fn is_password_field_focused(&self) -> bool {
+        self.keyboard_manager.is_secure_input_active()
+    }
 }
 
 impl <'a, S: KeyboardManager, C: ClipboardManager,
@@ -326,4 +710,1396 @@ impl <'a, S: KeyboardManager, C: ClipboardManager,
             _ => {}
         }
     }
-}
\ No newline at end of file
+}
+
+impl <'a, S: KeyboardManager, C: ClipboardManager,
+    M: ConfigManager<'a>, U: UIManager, R: Renderer> ExpandRequestReceiver for Engine<'a, S, C, M, U, R>{
+
+    fn on_expand_request(&self, trigger: &str) -> Result<(), String> {
+        // The matches typed by the user are ignored too while espanso is disabled
+        if !*self.enabled.borrow() {
+            return Err("espanso is disabled".to_owned());
+        }
+
+        // Only the matches of the config of the focused application are considered
+        let config = self.config_manager.active_config();
+
+        let m = config.matches.iter().find(|m| m.triggers().iter().any(|t| t == trigger));
+        match m {
+            Some(m) => {
+                self.inject_match(m, None, None, false);
+                Ok(())
+            },
+            None => {
+                Err(format!("No match found for trigger '{}'", trigger))
+            },
+        }
+    }
+
+    fn on_preview_request(&self, trigger: &str) -> Result<ExpansionPreview, String> {
+        // Nothing is typed, so the preview is available even while espanso is disabled
+        let config = self.config_manager.active_config();
+
+        let m = config.matches.iter().find(|m| m.triggers().iter().any(|t| t == trigger))
+            .ok_or_else(|| format!("No match found for trigger '{}'", trigger))?;
+
+        self.preview_match(m, trigger, None)
+            .ok_or_else(|| format!("The match of trigger '{}' doesn't expand to text", trigger))
+    }
+}
+
+impl <'a, S: KeyboardManager, C: ClipboardManager,
+    M: ConfigManager<'a>, U: UIManager, R: Renderer> StatsRequestReceiver for Engine<'a, S, C, M, U, R>{
+
+    fn on_stats_request(&self) -> StatsSnapshot {
+        let config = self.config_manager.active_config();
+        let mut snapshot = self.stats.snapshot(&config.name);
+        snapshot.most_used = self.most_used(MOST_USED_STATS_COUNT).into_iter()
+            .map(|(m, count)| (m.fire_key(), count))
+            .collect();
+        snapshot
+    }
+}
+
+impl <'a, S: KeyboardManager, C: ClipboardManager,
+    M: ConfigManager<'a>, U: UIManager, R: Renderer> ReloadRequestReceiver for Engine<'a, S, C, M, U, R>{
+
+    fn on_reload_request(&self, config_set: &Result<ConfigSet, ConfigLoadError>) -> Result<(), String> {
+        match config_set {
+            Ok(config_set) => {
+                self.config_manager.swap_matches(config_set.clone());
+                info!("Configuration reloaded");
+                Ok(())
+            },
+            Err(e) => {
+                // Keep using the current configuration
+                error!("Unable to reload the configuration: {}", e);
+                self.notify(|notifier| notifier.on_reload_error(e));
+                Err(e.to_string())
+            },
+        }
+    }
+}
+
+impl <'a, S: KeyboardManager, C: ClipboardManager,
+    M: ConfigManager<'a>, U: UIManager, R: Renderer> ConfigSetRequestReceiver for Engine<'a, S, C, M, U, R>{
+
+    fn on_config_set_request(&self) -> Result<ConfigSet, String> {
+        self.config_manager.config_set().ok_or_else(|| "The configuration is not available".to_owned())
+    }
+}
+
+impl <'a, S: KeyboardManager, C: ClipboardManager,
+    M: ConfigManager<'a>, U: UIManager, R: Renderer> TriggerStateRequestReceiver for Engine<'a, S, C, M, U, R>{
+
+    fn on_trigger_state_request(&self, trigger: &str, disabled: bool) {
+        info!("{} trigger '{}'", if disabled { "Disabling" } else { "Enabling" }, trigger);
+        self.config_manager.set_trigger_disabled(trigger, disabled);
+    }
+}
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Configs, ConfigSet, LiveConfig};
+    use std::rc::Rc;
+    use crate::keyboard::mock::{MockKeyboardManager, KeyboardEvent};
+    use crate::matcher::Matcher;
+    use crate::matcher::scrolling::ScrollingMatcher;
+    use crate::render::default::DefaultRenderer;
+    use crate::config::runtime::RuntimeConfigManager;
+    use crate::config::runtime::tests::DummySystemManager;
+    use std::path::Path;
+
+    struct TestClipboardManager {}
+
+    impl ClipboardManager for TestClipboardManager {
+        fn get_clipboard(&self) -> Option<String> {
+            None
+        }
+
+        fn set_clipboard(&self, _payload: &str) {}
+
+        fn set_clipboard_image(&self, _image_path: &Path) {}
+    }
+
+    struct TestConfigManager {
+        set: Rc<ConfigSet>,
+        saved_states: RefCell<Vec<bool>>,
+    }
+
+    impl <'a> ConfigManager<'a> for TestConfigManager {
+        fn active_config(&'a self) -> LiveConfig {
+            self.default_config()
+        }
+
+        fn default_config(&'a self) -> LiveConfig {
+            LiveConfig { set: Rc::clone(&self.set), position: None, generation: 0 }
+        }
+
+        fn is_trigger_disabled(&self, _trigger: &str) -> bool {
+            false
+        }
+
+        fn save_enabled_state(&self, enabled: bool) {
+            self.saved_states.borrow_mut().push(enabled);
+        }
+
+        fn swap_config_set(&self, _new_set: ConfigSet) {}
+    }
+
+    struct TestUIManager {}
+
+    impl UIManager for TestUIManager {
+        fn notify(&self, _message: &str) {}
+
+        fn show_menu(&self, _menu: Vec<MenuItem>) {}
+
+        fn cleanup(&self) {}
+    }
+
+    struct TestNotifier<'a> {
+        toggles: &'a RefCell<Vec<bool>>,
+    }
+
+    impl <'a> Notifier for TestNotifier<'a> {
+        fn on_toggle(&self, enabled: bool) {
+            self.toggles.borrow_mut().push(enabled);
+        }
+
+        fn on_reload_error(&self, _error: &crate::config::ConfigLoadError) {}
+    }
+
+    // Managers used by the engine under test, which borrows them
+    struct EngineFixture<C: ClipboardManager = TestClipboardManager> {
+        keyboard_manager: MockKeyboardManager,
+        clipboard_manager: C,
+        ui_manager: TestUIManager,
+        renderer: DefaultRenderer,
+        config_manager: TestConfigManager,
+    }
+
+    impl EngineFixture {
+        fn new(config: Configs) -> EngineFixture {
+            EngineFixture::with_clipboard(config, TestClipboardManager {})
+        }
+    }
+
+    impl <C: ClipboardManager> EngineFixture<C> {
+        fn with_clipboard(config: Configs, clipboard_manager: C) -> EngineFixture<C> {
+            EngineFixture {
+                keyboard_manager: MockKeyboardManager::new(),
+                clipboard_manager,
+                ui_manager: TestUIManager {},
+                renderer: DefaultRenderer::new(crate::extension::get_extensions(), config.clone()),
+                config_manager: TestConfigManager {
+                    set: Rc::new(ConfigSet::from_default(config)),
+                    saved_states: RefCell::new(Vec::new()),
+                },
+            }
+        }
+
+        fn engine(&self) -> Engine<MockKeyboardManager, C, TestConfigManager, TestUIManager, DefaultRenderer> {
+            Engine::new(&self.keyboard_manager, &self.clipboard_manager, &self.config_manager,
+                        &self.ui_manager, &self.renderer)
+        }
+    }
+
+    // Expand the given match, returning the events received by the keyboard manager
+    fn expand_match(config_str: &str, trigger: &str, trailing_separator: Option<char>) -> Vec<KeyboardEvent> {
+        let mut config: Configs = serde_yaml::from_str(config_str).unwrap();
+        config.backend = BackendType::Inject;
+        config.action_noop_interval = 0;
+
+        let fixture = EngineFixture::new(config);
+        let engine = fixture.engine();
+
+        let m = fixture.config_manager.set.default.matches.iter().find(|m| m.trigger() == trigger).unwrap();
+        engine.on_match(m, trailing_separator, 0);
+
+        fixture.keyboard_manager.events()
+    }
+
+    #[test]
+    fn test_engine_toggle_invokes_notifier_with_new_state() {
+        let mut config: Configs = serde_yaml::from_str("name: default").unwrap();
+        config.action_noop_interval = 0;
+
+        let fixture = EngineFixture::new(config);
+        let toggles = RefCell::new(Vec::new());
+        let notifier = TestNotifier { toggles: &toggles };
+        let mut engine = fixture.engine();
+        engine.set_notifier(&notifier);
+
+        engine.on_enable_update(false);
+        engine.on_enable_update(true);
+
+        assert_eq!(*toggles.borrow(), vec![false, true]);
+    }
+
+    #[test]
+    fn test_engine_enable_update_saves_state() {
+        let mut config: Configs = serde_yaml::from_str("name: default").unwrap();
+        config.action_noop_interval = 0;
+
+        let fixture = EngineFixture::new(config);
+        let engine = fixture.engine();
+
+        engine.on_enable_update(false);
+        engine.on_enable_update(false);
+        engine.on_enable_update(true);
+
+        assert_eq!(*fixture.config_manager.saved_states.borrow(), vec![false, true]);
+    }
+
+    #[test]
+    fn test_engine_enable_update_at_startup_is_not_saved() {
+        // The state restored at startup is applied within the action_noop_interval
+        let fixture = EngineFixture::new(serde_yaml::from_str("name: default").unwrap());
+        let engine = fixture.engine();
+
+        engine.on_enable_update(false);
+
+        assert!(!*engine.enabled.borrow());
+        assert!(fixture.config_manager.saved_states.borrow().is_empty());
+    }
+
+    // Expand a match while a password field is focused
+    fn expand_match_in_password_field(disable_in_password_fields: bool) -> Vec<KeyboardEvent> {
+        let mut config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+        "###).unwrap();
+        config.backend = BackendType::Inject;
+        config.action_noop_interval = 0;
+        config.disable_in_password_fields = disable_in_password_fields;
+
+        let fixture = EngineFixture::new(config);
+        fixture.keyboard_manager.set_secure_input_active(true);
+        let engine = fixture.engine();
+        engine.on_match(&fixture.config_manager.set.default.matches[0], None, 0);
+
+        fixture.keyboard_manager.events()
+    }
+
+    #[test]
+    fn test_engine_suppresses_expansion_in_password_field() {
+        assert!(expand_match_in_password_field(true).is_empty());
+    }
+
+    #[test]
+    fn test_engine_expands_in_password_field_if_not_disabled() {
+        let calls = expand_match_in_password_field(false);
+        assert!(calls.contains(&KeyboardEvent::SendString("Best regards".to_owned())));
+    }
+
+    #[test]
+    fn test_engine_deletes_trigger() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+        "###, ":sig", None);
+
+        assert_eq!(calls[0], KeyboardEvent::DeleteString(4));
+        assert!(calls.contains(&KeyboardEvent::SendString("Best regards".to_owned())));
+    }
+
+    #[test]
+    fn test_engine_deletes_typed_trigger_of_multiple_triggers() {
+        let mut config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - triggers: [":sig", ":regards"]
+              replace: "Best regards"
+        "###).unwrap();
+        config.backend = BackendType::Inject;
+        config.action_noop_interval = 0;
+
+        let fixture = EngineFixture::new(config);
+        let engine = fixture.engine();
+        engine.on_match(&fixture.config_manager.set.default.matches[0], None, 1);
+
+        let calls = fixture.keyboard_manager.events();
+        assert_eq!(calls[0], KeyboardEvent::DeleteString(8));
+        assert!(calls.contains(&KeyboardEvent::SendString("Best regards".to_owned())));
+    }
+
+    #[test]
+    fn test_engine_trigger_var_is_the_typed_trigger() {
+        let mut config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - triggers: [":sig", ":regards"]
+              replace: "typed {{trigger}}"
+        "###).unwrap();
+        config.backend = BackendType::Inject;
+        config.action_noop_interval = 0;
+
+        let fixture = EngineFixture::new(config);
+        let engine = fixture.engine();
+        engine.on_match(&fixture.config_manager.set.default.matches[0], None, 1);
+
+        assert!(fixture.keyboard_manager.events().contains(&KeyboardEvent::SendString("typed :regards".to_owned())));
+    }
+
+    #[test]
+    fn test_typed_trigger_injection_sequence() {
+        let mut config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards,\n$|$Jon"
+        "###).unwrap();
+        config.backend = BackendType::Inject;
+        config.action_noop_interval = 0;
+        config.match_index = config.build_match_index();
+
+        let fixture = EngineFixture::new(config);
+        let engine = fixture.engine();
+        let matcher = ScrollingMatcher::new(&fixture.config_manager, &engine);
+
+        for c in ":sig".chars() {
+            matcher.handle_char(&c.to_string());
+        }
+
+        let mut expected = vec![KeyboardEvent::DeleteString(4)];
+        if cfg!(target_os = "linux") {
+            expected.push(KeyboardEvent::SendString("Best regards,\nJon".to_owned()));
+        }else{
+            expected.push(KeyboardEvent::SendString("Best regards,".to_owned()));
+            expected.push(KeyboardEvent::SendEnter);
+            expected.push(KeyboardEvent::SendString("Jon".to_owned()));
+        }
+        expected.push(KeyboardEvent::MoveCursorLeft(3));
+
+        assert_eq!(fixture.keyboard_manager.events(), expected);
+    }
+
+    #[test]
+    fn test_typed_escaped_trigger_is_typed_literally() {
+        let mut config: Configs = serde_yaml::from_str(r###"
+        trigger_escape_char: "`"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+            - trigger: "name"
+              replace: "Jon"
+              word: true
+        "###).unwrap();
+        config.action_noop_interval = 0;
+        config.match_index = config.build_match_index();
+
+        let fixture = EngineFixture::new(config);
+        let engine = fixture.engine();
+        let matcher = ScrollingMatcher::new(&fixture.config_manager, &engine);
+
+        for c in "`:sig `name ".chars() {
+            matcher.handle_char(&c.to_string());
+        }
+
+        assert_eq!(fixture.keyboard_manager.events(), vec![
+            KeyboardEvent::DeleteString(5),
+            KeyboardEvent::SendString(":sig".to_owned()),
+            KeyboardEvent::DeleteString(6),
+            KeyboardEvent::SendString("name".to_owned()),
+            KeyboardEvent::SendString(" ".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn test_typed_expansions_are_counted_in_stats() {
+        let mut config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+            - trigger: ":hi"
+              replace: "Hello"
+        "###).unwrap();
+        config.backend = BackendType::Inject;
+        config.action_noop_interval = 0;
+        config.match_index = config.build_match_index();
+
+        let fixture = EngineFixture::new(config);
+        let engine = fixture.engine();
+        let matcher = ScrollingMatcher::new(&fixture.config_manager, &engine);
+
+        for c in ":sig :hi".chars() {
+            matcher.handle_char(&c.to_string());
+        }
+
+        let stats = engine.on_stats_request();
+        assert_eq!(stats.total_expansions, 2);
+        assert_eq!(stats.trigger_counts.get(":sig"), Some(&1));
+        assert_eq!(stats.trigger_counts.get(":hi"), Some(&1));
+        assert_eq!(stats.most_used, vec![(":hi".to_owned(), 1), (":sig".to_owned(), 1)]);
+        assert_eq!(stats.active_config, "default");
+    }
+
+    #[test]
+    fn test_expansions_update_most_used_matches() {
+        let mut config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+            - trigger: ":hi"
+              replace: "Hello"
+        "###).unwrap();
+        config.backend = BackendType::Inject;
+        config.action_noop_interval = 0;
+        config.match_index = config.build_match_index();
+
+        let fixture = EngineFixture::new(config);
+        let engine = fixture.engine();
+        let matcher = ScrollingMatcher::new(&fixture.config_manager, &engine);
+
+        for c in ":hi :sig :hi ".chars() {
+            matcher.handle_char(&c.to_string());
+        }
+
+        let most_used: Vec<(String, u64)> = engine.most_used(10).into_iter()
+            .map(|(m, count)| (m.trigger().to_owned(), count))
+            .collect();
+        assert_eq!(most_used, vec![(":hi".to_owned(), 2), (":sig".to_owned(), 1)]);
+    }
+
+    #[test]
+    fn test_engine_keep_trigger_does_not_delete_trigger() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+              keep_trigger: true
+        "###, ":sig", None);
+
+        assert!(!calls.iter().any(|c| match c { KeyboardEvent::DeleteString(_) => true, _ => false }));
+        assert!(calls.contains(&KeyboardEvent::SendString("Best regards".to_owned())));
+    }
+
+    #[test]
+    fn test_engine_keep_trigger_does_not_type_trailing_separator_again() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: "sig"
+              replace: "Best regards"
+              keep_trigger: true
+              word: true
+        "###, "sig", Some(' '));
+
+        // The separator typed by the user is kept with the trigger, so it's not sent again
+        assert_eq!(calls, vec![KeyboardEvent::SendString("Best regards".to_owned())]);
+    }
+
+    #[test]
+    fn test_engine_select_after_selects_inserted_text() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":name"
+              replace: "Frédéric 😀"
+              select_after: true
+        "###, ":name", None);
+
+        assert_eq!(calls.last().unwrap(), &KeyboardEvent::SelectLeft(10));
+    }
+
+    #[test]
+    fn test_engine_select_after_excludes_trailing_separator() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: "name"
+              replace: "Jon"
+              select_after: true
+              word: true
+        "###, "name", Some(' '));
+
+        let len = calls.len();
+        assert_eq!(calls[len-2], KeyboardEvent::MoveCursorLeft(1));
+        assert_eq!(calls[len-1], KeyboardEvent::SelectLeft(3));
+    }
+
+    #[test]
+    fn test_engine_select_after_counts_rendered_variables() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":name"
+              replace: "Hi {{name}}"
+              select_after: true
+              vars:
+                - name: name
+                  type: dummy
+                  params:
+                    echo: "Frédéric"
+        "###, ":name", None);
+
+        assert_eq!(calls.last().unwrap(), &KeyboardEvent::SelectLeft(11));
+    }
+
+    #[test]
+    fn test_engine_cursor_default_moves_to_marker() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":div"
+              replace: "<div>$|$</div>"
+        "###, ":div", None);
+
+        assert_eq!(calls.last().unwrap(), &KeyboardEvent::MoveCursorLeft(6));
+    }
+
+    #[test]
+    fn test_engine_cursor_default_without_marker_stays_at_end() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+        "###, ":sig", None);
+
+        assert_eq!(calls.last().unwrap(), &KeyboardEvent::SendString("Best regards".to_owned()));
+    }
+
+    #[test]
+    fn test_engine_cursor_marker() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":div"
+              replace: "<div>$|$</div>"
+              cursor: marker
+        "###, ":div", None);
+
+        assert_eq!(calls.last().unwrap(), &KeyboardEvent::MoveCursorLeft(6));
+    }
+
+    #[test]
+    fn test_engine_cursor_start_sends_home() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":div"
+              replace: "<div>$|$</div>"
+              cursor: start
+        "###, ":div", None);
+
+        assert!(!calls.contains(&KeyboardEvent::MoveCursorLeft(6)));
+        assert_eq!(calls.last().unwrap(), &KeyboardEvent::SendKey(KeyCode::Home, 1));
+    }
+
+    #[test]
+    fn test_engine_cursor_end_sends_end() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":div"
+              replace: "<div>$|$</div>"
+              cursor: end
+        "###, ":div", None);
+
+        assert!(!calls.contains(&KeyboardEvent::MoveCursorLeft(6)));
+        assert_eq!(calls.last().unwrap(), &KeyboardEvent::SendKey(KeyCode::End, 1));
+    }
+
+    fn paste_match(config_str: &str, trigger: &str) -> Vec<KeyboardEvent> {
+        let mut config: Configs = serde_yaml::from_str(config_str).unwrap();
+        config.backend = BackendType::Clipboard;
+        config.action_noop_interval = 0;
+
+        let fixture = EngineFixture::new(config);
+        let engine = fixture.engine();
+
+        let m = fixture.config_manager.set.default.matches.iter().find(|m| m.trigger() == trigger).unwrap();
+        engine.on_match(m, None, 0);
+
+        fixture.keyboard_manager.events()
+    }
+
+    // Clipboard keeping track of the contents it's set to
+    struct RecordingClipboardManager {
+        content: RefCell<Option<String>>,
+        history: RefCell<Vec<String>>,
+    }
+
+    impl ClipboardManager for RecordingClipboardManager {
+        fn get_clipboard(&self) -> Option<String> {
+            self.content.borrow().clone()
+        }
+
+        fn set_clipboard(&self, payload: &str) {
+            *self.content.borrow_mut() = Some(payload.to_owned());
+            self.history.borrow_mut().push(payload.to_owned());
+        }
+
+        fn set_clipboard_image(&self, _image_path: &Path) {}
+    }
+
+    fn paste_with_clipboard(preserve_clipboard: bool, original: Option<&str>) -> RecordingClipboardManager {
+        let mut config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+        "###).unwrap();
+        config.backend = BackendType::Clipboard;
+        config.action_noop_interval = 0;
+        config.restore_clipboard_delay = 0;
+        config.preserve_clipboard = preserve_clipboard;
+
+        let clipboard_manager = RecordingClipboardManager {
+            content: RefCell::new(original.map(str::to_owned)),
+            history: RefCell::new(Vec::new()),
+        };
+        let fixture = EngineFixture::with_clipboard(config, clipboard_manager);
+
+        {
+            let engine = fixture.engine();
+            engine.on_match(&fixture.config_manager.set.default.matches[0], None, 0);
+        }
+
+        fixture.clipboard_manager
+    }
+
+    #[test]
+    fn test_engine_paste_restores_clipboard() {
+        let clipboard = paste_with_clipboard(true, Some("original"));
+        assert_eq!(*clipboard.history.borrow(), vec!["Best regards".to_owned(), "original".to_owned()]);
+        assert_eq!(clipboard.get_clipboard(), Some("original".to_owned()));
+    }
+
+    #[test]
+    fn test_engine_paste_without_preserve_clipboard_keeps_replacement() {
+        let clipboard = paste_with_clipboard(false, Some("original"));
+        assert_eq!(clipboard.get_clipboard(), Some("Best regards".to_owned()));
+    }
+
+    #[test]
+    fn test_engine_paste_non_text_clipboard_is_not_restored() {
+        let clipboard = paste_with_clipboard(true, None);
+        assert_eq!(*clipboard.history.borrow(), vec!["Best regards".to_owned()]);
+    }
+
+    #[test]
+    fn test_preserve_clipboard_enabled_by_default() {
+        let config: Configs = serde_yaml::from_str("name: default").unwrap();
+        assert!(config.preserve_clipboard);
+    }
+
+    #[test]
+    fn test_engine_custom_paste_shortcut_sends_chord() {
+        let calls = paste_match(r###"
+        paste_shortcut: "CTRL+SHIFT+V"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+        "###, ":sig");
+
+        assert_eq!(calls, vec![
+            KeyboardEvent::DeleteString(4),
+            KeyboardEvent::SendChord(vec![KeyCode::Ctrl, KeyCode::Shift, KeyCode::Char('v')]),
+        ]);
+    }
+
+    #[test]
+    fn test_engine_predefined_paste_shortcut_is_unchanged() {
+        let calls = paste_match(r###"
+        paste_shortcut: CtrlShiftV
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+        "###, ":sig");
+
+        assert_eq!(calls.last().unwrap(), &KeyboardEvent::TriggerPaste(PasteShortcut::CtrlShiftV));
+    }
+
+    #[test]
+    fn test_engine_replacement_at_clipboard_threshold_is_injected() {
+        let calls = expand_match(r###"
+        clipboard_threshold: 12
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+        "###, ":sig", None);
+
+        assert_eq!(calls, vec![
+            KeyboardEvent::DeleteString(4),
+            KeyboardEvent::SendString("Best regards".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn test_engine_replacement_over_clipboard_threshold_is_pasted() {
+        let calls = expand_match(r###"
+        clipboard_threshold: 11
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+        "###, ":sig", None);
+
+        assert_eq!(calls, vec![
+            KeyboardEvent::DeleteString(4),
+            KeyboardEvent::TriggerPaste(PasteShortcut::Default),
+        ]);
+    }
+
+    #[test]
+    fn test_engine_clipboard_threshold_counts_trailing_separator() {
+        let calls = expand_match(r###"
+        clipboard_threshold: 12
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+              word: true
+        "###, ":sig", Some(' '));
+
+        assert_eq!(calls.last().unwrap(), &KeyboardEvent::TriggerPaste(PasteShortcut::Default));
+    }
+
+    #[test]
+    fn test_engine_match_backend_overrides_clipboard_threshold() {
+        let calls = expand_match(r###"
+        clipboard_threshold: 1
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+              backend: Inject
+        "###, ":sig", None);
+
+        assert_eq!(calls.last().unwrap(), &KeyboardEvent::SendString("Best regards".to_owned()));
+    }
+
+    // Expand a match with the Auto backend, returning the events received by the keyboard manager
+    fn expand_match_with_auto_backend(injection_fails: bool) -> Vec<KeyboardEvent> {
+        let mut config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+        "###).unwrap();
+        config.backend = BackendType::Auto;
+        config.action_noop_interval = 0;
+
+        let fixture = EngineFixture::new(config);
+        fixture.keyboard_manager.set_injection_fails(injection_fails);
+        let engine = fixture.engine();
+
+        let m = fixture.config_manager.set.default.matches.iter().find(|m| m.trigger() == ":sig").unwrap();
+        engine.on_match(m, None, 0);
+
+        fixture.keyboard_manager.events()
+    }
+
+    #[test]
+    fn test_engine_auto_backend_injects_when_possible() {
+        let calls = expand_match_with_auto_backend(false);
+
+        assert_eq!(calls, vec![
+            KeyboardEvent::DeleteString(4),
+            KeyboardEvent::SendString("Best regards".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn test_engine_auto_backend_falls_back_to_clipboard() {
+        let calls = expand_match_with_auto_backend(true);
+
+        assert_eq!(calls, vec![
+            KeyboardEvent::DeleteString(4),
+            KeyboardEvent::SendString("Best regards".to_owned()),
+            KeyboardEvent::TriggerPaste(PasteShortcut::Default),
+        ]);
+    }
+
+    #[test]
+    fn test_send_text_lines_returns_offset_of_text_not_typed() {
+        let config: Configs = serde_yaml::from_str("backend: Auto").unwrap();
+        let fixture = EngineFixture::new(config);
+        fixture.keyboard_manager.set_injection_fails_after(1);
+        let engine = fixture.engine();
+
+        let text = "first\nsecond\nthird";
+        let offset = engine.send_text_lines(text).unwrap_err();
+
+        // The failed line and the following ones are left for the clipboard fallback
+        assert_eq!(&text[offset..], "second\nthird");
+        assert_eq!(fixture.keyboard_manager.events(), vec![
+            KeyboardEvent::SendString("first".to_owned()),
+            KeyboardEvent::SendEnter,
+            KeyboardEvent::SendString("second".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn test_engine_match_backend_overrides_config_backend() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+              backend: Clipboard
+        "###, ":sig", None);
+
+        assert_eq!(calls.last().unwrap(), &KeyboardEvent::TriggerPaste(PasteShortcut::Default));
+    }
+
+    // Text and keys actually sent by the injection, to be compared with the preview
+    fn sent_text_and_keys(calls: &[KeyboardEvent]) -> (String, Vec<KeyCode>) {
+        let mut text = String::new();
+        let mut keys = Vec::new();
+        for call in calls {
+            match call {
+                KeyboardEvent::SendString(s) => text.push_str(s),
+                KeyboardEvent::SendKey(key, 1) => keys.push(*key),
+                _ => {},
+            }
+        }
+        (text, keys)
+    }
+
+    // Preview the expansion of the given match, then expand it for real with the same engine
+    fn preview_and_expand_match(config_str: &str, trigger: &str, trailing_separator: Option<char>) -> (ExpansionPreview, Vec<KeyboardEvent>) {
+        let mut config: Configs = serde_yaml::from_str(config_str).unwrap();
+        config.backend = BackendType::Inject;
+        config.action_noop_interval = 0;
+
+        let fixture = EngineFixture::new(config);
+        let engine = fixture.engine();
+        let m = &fixture.config_manager.set.default.matches[0];
+
+        let preview = engine.preview_match(m, trigger, trailing_separator).unwrap();
+        assert!(fixture.keyboard_manager.events().is_empty());
+
+        engine.on_match(m, trailing_separator, 0);
+        (preview, fixture.keyboard_manager.events())
+    }
+
+    #[test]
+    fn test_preview_match_matches_injected_text() {
+        let (preview, calls) = preview_and_expand_match(r###"
+        global_vars:
+            - name: name
+              type: dummy
+              params:
+                echo: "Jon"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards, {{name}}"
+        "###, ":sig", Some(' '));
+        let (text, keys) = sent_text_and_keys(&calls);
+
+        assert_eq!(preview.text, "Best regards, Jon ");
+        assert_eq!(preview.text, text);
+        assert_eq!(preview.keys, keys);
+        assert_eq!(preview.cursor_offset, None);
+        assert_eq!(calls[0], KeyboardEvent::DeleteString(preview.deleted as i32));
+    }
+
+    #[test]
+    fn test_preview_match_with_keys_and_cursor_hint() {
+        let (preview, calls) = preview_and_expand_match(r###"
+        matches:
+            - trigger: ":form"
+              replace: "Jon{{key:TAB}}$|$Snow"
+        "###, ":form", None);
+        let (text, keys) = sent_text_and_keys(&calls);
+
+        assert_eq!(preview, ExpansionPreview {
+            text: "JonSnow".to_owned(),
+            cursor_offset: Some(4),
+            keys: vec![KeyCode::Tab],
+            deleted: 5,
+            used_backend: BackendType::Inject,
+        });
+        assert_eq!(preview.text, text);
+        assert_eq!(preview.keys, keys);
+        assert!(calls.contains(&KeyboardEvent::MoveCursorLeft(4)));
+    }
+
+    #[test]
+    fn test_preview_match_keep_trigger_drops_separator() {
+        let (preview, _) = preview_and_expand_match(r###"
+        matches:
+            - trigger: "kg"
+              replace: " (kilograms)"
+              keep_trigger: true
+        "###, "kg", Some(' '));
+
+        assert_eq!(preview.text, " (kilograms)");
+        assert_eq!(preview.deleted, 0);
+    }
+
+    #[test]
+    fn test_preview_match_uses_clipboard_threshold() {
+        let (preview, calls) = preview_and_expand_match(r###"
+        clipboard_threshold: 5
+        matches:
+            - trigger: ":long"
+              replace: "a long replacement"
+        "###, ":long", None);
+
+        assert_eq!(preview.used_backend, BackendType::Clipboard);
+        assert_eq!(preview.text, "a long replacement");
+        assert!(calls.iter().any(|e| match e { KeyboardEvent::TriggerPaste(_) => true, _ => false }));
+    }
+
+    #[test]
+    fn test_preview_match_does_not_change_state() {
+        let mut config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+        "###).unwrap();
+        config.backend = BackendType::Inject;
+
+        // With the default noop interval, an expansion right after the preview would be blocked
+        // if the preview counted as an action
+        let fixture = EngineFixture::new(config);
+        let engine = fixture.engine();
+        let m = &fixture.config_manager.set.default.matches[0];
+        *engine.last_action_time.borrow_mut() = SystemTime::UNIX_EPOCH;
+
+        assert!(engine.preview_match(m, ":sig", None).is_some());
+        engine.on_match(m, None, 0);
+
+        assert!(fixture.keyboard_manager.events().contains(&KeyboardEvent::SendString("Best regards".to_owned())));
+        assert_eq!(engine.stats.snapshot("default").total_expansions, 1);
+    }
+
+    fn compute_expansion_for(config_str: &str, deleted: usize, trailing_separator: Option<char>) -> ExpansionResult {
+        let mut config: Configs = serde_yaml::from_str(config_str).unwrap();
+        config.backend = BackendType::Inject;
+        let m = &config.matches[0];
+        let rendered = m.expand_with_cursor_hint(m.trigger());
+        compute_expansion(m, rendered, deleted, trailing_separator, &config)
+    }
+
+    #[test]
+    fn test_compute_expansion_result_fields() {
+        let result = compute_expansion_for(r###"
+        matches:
+            - trigger: ":div"
+              replace: "<div>$|$</div>"
+        "###, 4, Some(' '));
+
+        assert_eq!(result, ExpansionResult {
+            deleted: 4,
+            inserted: "<div></div> ".to_owned(),
+            cursor_offset: Some(7),
+            used_backend: BackendType::Inject,
+        });
+    }
+
+    #[test]
+    fn test_compute_expansion_clipboard_threshold_selects_backend() {
+        let result = compute_expansion_for(r###"
+        clipboard_threshold: 3
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+        "###, 4, None);
+
+        assert_eq!(result.used_backend, BackendType::Clipboard);
+        assert_eq!(result.cursor_offset, None);
+    }
+
+    #[test]
+    fn test_compute_expansion_keys_force_inject() {
+        let result = compute_expansion_for(r###"
+        matches:
+            - trigger: ":form"
+              replace: "Jon{{key:TAB}}Snow"
+              backend: Clipboard
+        "###, 0, None);
+
+        assert_eq!(result.used_backend, BackendType::Inject);
+        assert_eq!(result.inserted, "Jon{{key:TAB}}Snow");
+    }
+
+    #[test]
+    fn test_engine_render_error_keeps_trigger() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":bad"
+              replace_file: "this_file_does_not_exist.txt"
+        "###, ":bad", None);
+
+        assert!(calls.iter().all(|c| match c { KeyboardEvent::DeleteString(_) => false, _ => true }));
+    }
+
+    #[test]
+    fn test_engine_press_enter_after_expansion() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":brb"
+              replace: "be right back"
+              press_enter_after: true
+        "###, ":brb", None);
+
+        assert_eq!(calls, vec![
+            KeyboardEvent::DeleteString(4),
+            KeyboardEvent::SendString("be right back".to_owned()),
+            KeyboardEvent::SendKey(KeyCode::Enter, 1),
+        ]);
+    }
+
+    #[test]
+    fn test_engine_press_enter_after_comes_after_cursor_hint() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":quote"
+              replace: "\"$|$\""
+              press_enter_after: true
+        "###, ":quote", None);
+
+        assert_eq!(&calls[calls.len() - 2..], &[
+            KeyboardEvent::MoveCursorLeft(1),
+            KeyboardEvent::SendKey(KeyCode::Enter, 1),
+        ]);
+    }
+
+    #[test]
+    fn test_engine_press_enter_after_is_skipped_with_select_after() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":brb"
+              replace: "be right back"
+              select_after: true
+              press_enter_after: true
+        "###, ":brb", None);
+
+        assert_eq!(calls.last().unwrap(), &KeyboardEvent::SelectLeft(13));
+        assert!(!calls.contains(&KeyboardEvent::SendKey(KeyCode::Enter, 1)));
+    }
+
+    #[test]
+    fn test_engine_press_enter_after_image_paste() {
+        let image = tempfile::NamedTempFile::new().unwrap();
+        let calls = paste_match(&format!(r###"
+        matches:
+            - trigger: ":img"
+              image_path: "{}"
+              press_enter_after: true
+        "###, image.path().to_string_lossy()), ":img");
+
+        assert_eq!(calls, vec![
+            KeyboardEvent::DeleteString(4),
+            KeyboardEvent::TriggerPaste(PasteShortcut::Default),
+            KeyboardEvent::SendKey(KeyCode::Enter, 1),
+        ]);
+    }
+
+    #[test]
+    fn test_engine_trim_trailing_newline() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":addr"
+              replace: |
+                Main Street
+              trim_trailing_newline: true
+        "###, ":addr", None);
+
+        assert_eq!(calls.last().unwrap(), &KeyboardEvent::SendString("Main Street".to_owned()));
+    }
+
+    #[test]
+    fn test_engine_ensure_trailing_newline() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":addr"
+              replace: "Main Street"
+              ensure_trailing_newline: true
+        "###, ":addr", None);
+
+        assert_eq!(sent_text_and_keys(&calls).0, "Main Street\n");
+    }
+
+    #[test]
+    fn test_engine_key_tokens_are_interleaved_with_text() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":form"
+              replace: "Jon{{key:TAB}}Snow{{ key:enter }}"
+        "###, ":form", None);
+
+        assert_eq!(calls, vec![
+            KeyboardEvent::DeleteString(5),
+            KeyboardEvent::SendString("Jon".to_owned()),
+            KeyboardEvent::SendKey(KeyCode::Tab, 1),
+            KeyboardEvent::SendString("Snow".to_owned()),
+            KeyboardEvent::SendKey(KeyCode::Enter, 1),
+        ]);
+    }
+
+    #[test]
+    fn test_engine_cursor_hint_ignores_key_tokens() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":form"
+              replace: "Jon{{key:TAB}}$|$Sn{{key:LEFT}}ow"
+        "###, ":form", None);
+
+        assert_eq!(calls.last().unwrap(), &KeyboardEvent::MoveCursorLeft(4));
+    }
+
+    #[test]
+    fn test_rendered_length_counts_chars_not_bytes() {
+        assert_eq!(Match::rendered_length("Hello world"), 11);
+        assert_eq!(Match::rendered_length("Ciao è 😀"), 8);
+        assert_eq!(Match::rendered_length(""), 0);
+    }
+
+    #[test]
+    fn test_rendered_length_excludes_key_tokens() {
+        assert_eq!(Match::rendered_length("Name{{key:TAB}}Surname{{ key:ENTER }}"), 11);
+
+        // Unknown keys are typed as they are
+        assert_eq!(Match::rendered_length("a{{key:FOO}}"), 12);
+    }
+
+    #[test]
+    fn test_split_key_tokens_keeps_unknown_keys_as_text() {
+        assert_eq!(split_key_tokens("a{{key:F99}}b{{key:tab}}"), vec![
+            ReplacementPart::Text("a{{key:F99}}b".to_owned()),
+            ReplacementPart::Key(KeyCode::Tab),
+        ]);
+        assert_eq!(split_key_tokens("plain"), vec![ReplacementPart::Text("plain".to_owned())]);
+        assert!(split_key_tokens("").is_empty());
+    }
+
+    #[test]
+    fn test_trigger_deletion_count_ascii() {
+        assert_eq!(trigger_deletion_count(":hi", None, &BackspaceMode::Codepoint), 3);
+        assert_eq!(trigger_deletion_count(":hi", Some(' '), &BackspaceMode::Codepoint), 4);
+    }
+
+    #[test]
+    fn test_trigger_deletion_count_accented() {
+        assert_eq!(trigger_deletion_count("café:", None, &BackspaceMode::Codepoint), 5);
+        assert_eq!(trigger_deletion_count("café:", Some(' '), &BackspaceMode::Codepoint), 6);
+    }
+
+    #[test]
+    fn test_trigger_deletion_count_emoji() {
+        assert_eq!(trigger_deletion_count(":👍", None, &BackspaceMode::Codepoint), 2);
+    }
+
+    #[test]
+    fn test_engine_deletes_accented_trigger() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: "café:"
+              replace: "coffee"
+        "###, "café:", None);
+
+        assert_eq!(calls[0], KeyboardEvent::DeleteString(5));
+    }
+
+    #[test]
+    fn test_engine_deletes_emoji_trigger() {
+        let calls = expand_match(r###"
+        matches:
+            - trigger: ":👍"
+              replace: "thumbs up"
+        "###, ":👍", None);
+
+        assert_eq!(calls[0], KeyboardEvent::DeleteString(2));
+    }
+
+    #[test]
+    fn test_trigger_deletion_count_zwj_emoji_codepoint_mode() {
+        // The family emoji is made of 3 emojis joined by 2 ZWJ chars
+        assert_eq!(trigger_deletion_count(":👨\u{200D}👩\u{200D}👧", None, &BackspaceMode::Codepoint), 6);
+    }
+
+    #[test]
+    fn test_trigger_deletion_count_zwj_emoji_grapheme_mode() {
+        assert_eq!(trigger_deletion_count(":👨\u{200D}👩\u{200D}👧", None, &BackspaceMode::Grapheme), 2);
+        assert_eq!(trigger_deletion_count(":👨\u{200D}👩\u{200D}👧", Some(' '), &BackspaceMode::Grapheme), 3);
+    }
+
+    #[test]
+    fn test_engine_backspace_mode_grapheme() {
+        let calls = expand_match(r###"
+        backspace_mode: grapheme
+        matches:
+            - trigger: ":👨‍👩‍👧"
+              replace: "family"
+        "###, ":👨‍👩‍👧", None);
+
+        assert_eq!(calls[0], KeyboardEvent::DeleteString(2));
+    }
+
+    #[test]
+    fn test_engine_expand_request_sends_replacement() {
+        let mut config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+        "###).unwrap();
+        config.backend = BackendType::Inject;
+        config.action_noop_interval = 0;
+
+        let fixture = EngineFixture::new(config);
+        let engine = fixture.engine();
+
+        assert!(engine.on_expand_request(":sig").is_ok());
+        assert_eq!(fixture.keyboard_manager.events(), vec![KeyboardEvent::SendString("Best regards".to_owned())]);
+
+        assert_eq!(engine.on_expand_request(":missing"), Err("No match found for trigger ':missing'".to_owned()));
+        assert_eq!(fixture.keyboard_manager.events().len(), 1);
+    }
+
+    #[test]
+    fn test_engine_preview_request_types_nothing() {
+        let mut config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best $|$regards"
+        "###).unwrap();
+        config.backend = BackendType::Inject;
+        config.action_noop_interval = 0;
+
+        let fixture = EngineFixture::new(config);
+        let engine = fixture.engine();
+
+        let preview = engine.on_preview_request(":sig").unwrap();
+        assert_eq!(preview.text, "Best regards");
+        assert_eq!(preview.cursor_offset, Some(7));
+        assert_eq!(preview.deleted, 4);
+
+        assert_eq!(engine.on_preview_request(":missing").unwrap_err(), "No match found for trigger ':missing'");
+        assert!(fixture.keyboard_manager.events().is_empty());
+    }
+
+    #[test]
+    fn test_engine_expand_request_ignored_while_disabled() {
+        let mut config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+        "###).unwrap();
+        config.backend = BackendType::Inject;
+        config.action_noop_interval = 0;
+
+        let fixture = EngineFixture::new(config);
+        let engine = fixture.engine();
+
+        engine.on_enable_update(false);
+        assert_eq!(engine.on_expand_request(":sig"), Err("espanso is disabled".to_owned()));
+        assert!(fixture.keyboard_manager.events().is_empty());
+
+        engine.on_enable_update(true);
+        assert!(engine.on_expand_request(":sig").is_ok());
+        assert_eq!(fixture.keyboard_manager.events(), vec![KeyboardEvent::SendString("Best regards".to_owned())]);
+    }
+
+    #[test]
+    fn test_engine_reload_request_applies_new_settings() {
+        let load_set = |config_str: &str| {
+            let mut set: ConfigSet = serde_yaml::from_str(config_str).unwrap();
+            set.build_match_indexes();
+            set
+        };
+
+        let keyboard_manager = MockKeyboardManager::new();
+        let clipboard_manager = RecordingClipboardManager {
+            content: RefCell::new(None),
+            history: RefCell::new(Vec::new()),
+        };
+        let ui_manager = TestUIManager {};
+        let config_manager = RuntimeConfigManager::new(load_set(r###"
+        default:
+            backend: Inject
+            backspace_limit: 3
+            action_noop_interval: 0
+            matches:
+                - trigger: ":sig"
+                  replace: "Best regards"
+        specific: []
+        "###), DummySystemManager::new());
+        let renderer = DefaultRenderer::new(crate::extension::get_extensions(), (*config_manager.default_config()).clone());
+        let engine = Engine::new(&keyboard_manager, &clipboard_manager, &config_manager, &ui_manager, &renderer);
+
+        let result = engine.on_reload_request(&Ok(load_set(r###"
+        default:
+            backend: Clipboard
+            backspace_limit: 5
+            action_noop_interval: 0
+            matches:
+                - trigger: ":sig"
+                  replace: "Kind regards"
+        specific: []
+        "###)));
+        assert_eq!(result, Ok(()));
+        assert_eq!(config_manager.active_config().backspace_limit, 5);
+
+        assert!(engine.on_expand_request(":sig").is_ok());
+        assert!(clipboard_manager.history.borrow().contains(&"Kind regards".to_owned()));
+        assert!(keyboard_manager.events().iter().any(|e| match e { KeyboardEvent::TriggerPaste(_) => true, _ => false }));
+        assert!(!keyboard_manager.events().iter().any(|e| match e { KeyboardEvent::SendString(_) => true, _ => false }));
+    }
+
+    #[test]
+    fn test_engine_reload_request_resets_spent_triggers() {
+        let mut config_set: ConfigSet = serde_yaml::from_str(r###"
+        default:
+            backend: Inject
+            action_noop_interval: 0
+            matches:
+                - trigger: ":sig"
+                  replace: "Best regards"
+                  once: true
+        specific: []
+        "###).unwrap();
+        config_set.build_match_indexes();
+
+        let keyboard_manager = MockKeyboardManager::new();
+        let clipboard_manager = TestClipboardManager {};
+        let ui_manager = TestUIManager {};
+        let config_manager = RuntimeConfigManager::new(config_set.clone(), DummySystemManager::new());
+        let renderer = DefaultRenderer::new(crate::extension::get_extensions(), config_set.default.clone());
+        let engine = Engine::new(&keyboard_manager, &clipboard_manager, &config_manager, &ui_manager, &renderer);
+        let matcher = ScrollingMatcher::new(&config_manager, &engine);
+
+        for c in ":sig :sig ".chars() {
+            matcher.handle_char(&c.to_string());
+        }
+        assert!(config_manager.is_trigger_spent(":sig"));
+
+        assert_eq!(engine.on_reload_request(&Ok(config_set)), Ok(()));
+        assert!(!config_manager.is_trigger_spent(":sig"));
+
+        for c in ":sig ".chars() {
+            matcher.handle_char(&c.to_string());
+        }
+
+        let expansions = keyboard_manager.events().into_iter()
+            .filter(|e| e == &KeyboardEvent::SendString("Best regards".to_owned()))
+            .count();
+        assert_eq!(expansions, 2);
+    }
+
+    #[test]
+    fn test_engine_trigger_state_request_disables_the_match() {
+        let mut config_set: ConfigSet = serde_yaml::from_str(r###"
+        default:
+            backend: Inject
+            action_noop_interval: 0
+            matches:
+                - trigger: ":sig"
+                  replace: "Best regards"
+        specific: []
+        "###).unwrap();
+        config_set.build_match_indexes();
+
+        let keyboard_manager = MockKeyboardManager::new();
+        let clipboard_manager = TestClipboardManager {};
+        let ui_manager = TestUIManager {};
+        let config_manager = RuntimeConfigManager::new(config_set.clone(), DummySystemManager::new());
+        let renderer = DefaultRenderer::new(crate::extension::get_extensions(), config_set.default.clone());
+        let engine = Engine::new(&keyboard_manager, &clipboard_manager, &config_manager, &ui_manager, &renderer);
+        let matcher = ScrollingMatcher::new(&config_manager, &engine);
+
+        engine.on_trigger_state_request(":sig", true);
+        for c in ":sig ".chars() {
+            matcher.handle_char(&c.to_string());
+        }
+        assert!(keyboard_manager.events().is_empty());
+
+        engine.on_trigger_state_request(":sig", false);
+        for c in ":sig ".chars() {
+            matcher.handle_char(&c.to_string());
+        }
+        assert!(keyboard_manager.events().contains(&KeyboardEvent::SendString("Best regards".to_owned())));
+    }
+}
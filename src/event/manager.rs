@@ -17,8 +17,10 @@
  * along with espanso.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::event::{KeyEventReceiver, ActionEventReceiver, Event};
+use crate::event::{KeyEventReceiver, ActionEventReceiver, ExpandRequestReceiver, StatsRequestReceiver,
+                   ReloadRequestReceiver, TriggerStateRequestReceiver, ConfigSetRequestReceiver, Event};
 use std::sync::mpsc::Receiver;
+use log::warn;
 
 pub trait EventManager {
     fn eventloop(&self);
@@ -28,15 +30,30 @@ pub struct DefaultEventManager<'a> {
     receive_channel: Receiver<Event>,
     key_receivers: Vec<&'a dyn KeyEventReceiver>,
     action_receivers: Vec<&'a dyn ActionEventReceiver>,
+    expand_receivers: Vec<&'a dyn ExpandRequestReceiver>,
+    stats_receivers: Vec<&'a dyn StatsRequestReceiver>,
+    reload_receivers: Vec<&'a dyn ReloadRequestReceiver>,
+    trigger_state_receivers: Vec<&'a dyn TriggerStateRequestReceiver>,
+    config_set_receivers: Vec<&'a dyn ConfigSetRequestReceiver>,
 }
 
 impl<'a> DefaultEventManager<'a> {
     pub fn new(receive_channel: Receiver<Event>, key_receivers: Vec<&'a dyn KeyEventReceiver>,
-               action_receivers: Vec<&'a dyn ActionEventReceiver>) -> DefaultEventManager<'a> {
+               action_receivers: Vec<&'a dyn ActionEventReceiver>,
+               expand_receivers: Vec<&'a dyn ExpandRequestReceiver>,
+               stats_receivers: Vec<&'a dyn StatsRequestReceiver>,
+               reload_receivers: Vec<&'a dyn ReloadRequestReceiver>,
+               trigger_state_receivers: Vec<&'a dyn TriggerStateRequestReceiver>,
+               config_set_receivers: Vec<&'a dyn ConfigSetRequestReceiver>) -> DefaultEventManager<'a> {
         DefaultEventManager {
             receive_channel,
             key_receivers,
             action_receivers,
+            expand_receivers,
+            stats_receivers,
+            reload_receivers,
+            trigger_state_receivers,
+            config_set_receivers,
         }
     }
 }
@@ -52,7 +69,49 @@ impl <'a> EventManager for DefaultEventManager<'a> {
                         },
                         Event::Action(action_event) => {
                             self.action_receivers.iter().for_each(|&receiver| receiver.on_action_event(action_event.clone()));
-                        }
+                        },
+                        Event::Expand(request) => {
+                            self.expand_receivers.iter().for_each(|&receiver| {
+                                let result = receiver.on_expand_request(&request.trigger);
+                                request.reply.send(result).unwrap_or_else(|e| {
+                                    warn!("Unable to reply to the expand request: {}", e);
+                                });
+                            });
+                        },
+                        Event::Preview(request) => {
+                            self.expand_receivers.iter().for_each(|&receiver| {
+                                let result = receiver.on_preview_request(&request.trigger);
+                                request.reply.send(result).unwrap_or_else(|e| {
+                                    warn!("Unable to reply to the preview request: {}", e);
+                                });
+                            });
+                        },
+                        Event::Stats(request) => {
+                            self.stats_receivers.iter().for_each(|&receiver| {
+                                request.reply.send(receiver.on_stats_request()).unwrap_or_else(|e| {
+                                    warn!("Unable to reply to the stats request: {}", e);
+                                });
+                            });
+                        },
+                        Event::Reload(request) => {
+                            self.reload_receivers.iter().for_each(|&receiver| {
+                                request.reply.send(receiver.on_reload_request(&request.config_set)).unwrap_or_else(|e| {
+                                    warn!("Unable to reply to the reload request: {}", e);
+                                });
+                            });
+                        },
+                        Event::TriggerState(request) => {
+                            self.trigger_state_receivers.iter().for_each(|&receiver| {
+                                receiver.on_trigger_state_request(&request.trigger, request.disabled);
+                            });
+                        },
+                        Event::ConfigSet(request) => {
+                            self.config_set_receivers.iter().for_each(|&receiver| {
+                                request.reply.send(receiver.on_config_set_request()).unwrap_or_else(|e| {
+                                    warn!("Unable to reply to the config set request: {}", e);
+                                });
+                            });
+                        },
                     }
                 },
                 Err(e) => panic!("Broken event channel {}", e),
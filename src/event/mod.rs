@@ -19,12 +19,67 @@
 
 pub(crate) mod manager;
 
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+use std::sync::mpsc::Sender;
+use crate::stats::StatsSnapshot;
+use crate::config::{ConfigSet, ConfigLoadError};
+use crate::engine::ExpansionPreview;
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub enum Event {
     Action(ActionType),
-    Key(KeyEvent)
+    Key(KeyEvent),
+    Expand(ExpandRequest),
+    Preview(PreviewRequest),
+    Stats(StatsRequest),
+    Reload(ReloadRequest),
+    TriggerState(TriggerStateRequest),
+    ConfigSet(ConfigSetRequest),
+}
+
+// Request to expand the match with the given trigger, as if it was typed by the user.
+// The outcome is sent back through the reply channel.
+#[derive(Debug, Clone)]
+pub struct ExpandRequest {
+    pub trigger: String,
+    pub reply: Sender<Result<(), String>>,
+}
+
+// Request to compute what the expansion of the match with the given trigger would insert,
+// without typing anything. The preview is sent back through the reply channel.
+#[derive(Debug, Clone)]
+pub struct PreviewRequest {
+    pub trigger: String,
+    pub reply: Sender<Result<ExpansionPreview, String>>,
+}
+
+// Request for the expansion stats, sent back through the reply channel.
+#[derive(Debug, Clone)]
+pub struct StatsRequest {
+    pub reply: Sender<StatsSnapshot>,
+}
+
+// Request to replace the configuration with the one loaded from disk, which is parsed
+// before sending the request to avoid blocking the input thread. If the new configuration
+// couldn't be loaded, the current one is kept and the error is sent back through the reply channel.
+#[derive(Debug)]
+pub struct ReloadRequest {
+    pub config_set: Result<ConfigSet, ConfigLoadError>,
+    pub reply: Sender<Result<(), String>>,
+}
+
+// Request to disable the match with the given trigger at runtime, or to enable it again.
+#[derive(Debug, Clone)]
+pub struct TriggerStateRequest {
+    pub trigger: String,
+    pub disabled: bool,
+}
+
+// Request for the configuration currently used by the daemon, which is sent back through the
+// reply channel, or an error if it's not available.
+#[derive(Debug, Clone)]
+pub struct ConfigSetRequest {
+    pub reply: Sender<Result<ConfigSet, String>>,
 }
 
 #[derive(Debug, Clone)]
@@ -52,7 +107,7 @@ impl From<i32> for ActionType {
 
 #[derive(Debug, Clone)]
 pub enum KeyEvent {
-    Char(String),
+    Char(String, KeyModifierSet),  // The typed chars, with the modifiers held at the time
     Modifier(KeyModifier)
 }
 
@@ -66,6 +121,100 @@ pub enum KeyModifier {
     OFF,
 }
 
+/// Combination of modifiers pressed together. In the config files, it can be written
+/// either as a single modifier, such as `ALT`, or as a list, such as `[CTRL, SHIFT]`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct KeyModifierSet {
+    modifiers: Vec<KeyModifier>,
+}
+
+impl KeyModifierSet {
+    pub fn new(modifiers: Vec<KeyModifier>) -> KeyModifierSet {
+        let mut set = KeyModifierSet::default();
+        for modifier in modifiers {
+            set.insert(modifier);
+        }
+        set
+    }
+
+    pub fn insert(&mut self, modifier: KeyModifier) {
+        if !self.contains(&modifier) {
+            self.modifiers.push(modifier);
+        }
+    }
+
+    pub fn contains(&self, modifier: &KeyModifier) -> bool {
+        self.modifiers.contains(modifier)
+    }
+
+    pub fn is_subset_of(&self, other: &KeyModifierSet) -> bool {
+        self.modifiers.iter().all(|modifier| other.contains(modifier))
+    }
+
+    /// Check if all the modifiers of a non-empty set are among the pressed ones.
+    pub fn all_pressed(&self, pressed: &KeyModifierSet) -> bool {
+        !self.is_empty() && self.is_subset_of(pressed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modifiers.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.modifiers.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<KeyModifier> {
+        self.modifiers.iter()
+    }
+
+    /// Build the set of modifiers held during a keypress, from the MODIFIER_* bits
+    /// reported by the native bridges.
+    pub fn from_native_state(state: i32) -> KeyModifierSet {
+        let bits = [(1, KeyModifier::CTRL), (2, KeyModifier::SHIFT), (4, KeyModifier::ALT), (8, KeyModifier::META)];
+        KeyModifierSet::new(bits.iter()
+            .filter(|(bit, _)| state & bit != 0)
+            .map(|(_, modifier)| modifier.clone())
+            .collect())
+    }
+}
+
+impl From<KeyModifier> for KeyModifierSet {
+    fn from(modifier: KeyModifier) -> Self {
+        KeyModifierSet::new(vec![modifier])
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum KeyModifierSetRepr {
+    Single(KeyModifier),
+    Multiple(Vec<KeyModifier>),
+}
+
+impl Serialize for KeyModifierSet {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+
+        if self.len() == 1 {
+            KeyModifierSetRepr::Single(self.modifiers[0].clone()).serialize(serializer)
+        }else{
+            KeyModifierSetRepr::Multiple(self.modifiers.clone()).serialize(serializer)
+        }
+    }
+}
+
+impl <'de> Deserialize<'de> for KeyModifierSet {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where
+        D: Deserializer<'de> {
+
+        match KeyModifierSetRepr::deserialize(deserializer)? {
+            KeyModifierSetRepr::Single(modifier) => Ok(KeyModifierSet::from(modifier)),
+            KeyModifierSetRepr::Multiple(modifiers) => Ok(KeyModifierSet::new(modifiers)),
+        }
+    }
+}
+
 // Receivers
 
 pub trait KeyEventReceiver {
@@ -74,4 +223,72 @@ pub trait KeyEventReceiver {
 
 pub trait ActionEventReceiver {
     fn on_action_event(&self, e: ActionType);
-}
\ No newline at end of file
+}
+
+pub trait ExpandRequestReceiver {
+    fn on_expand_request(&self, trigger: &str) -> Result<(), String>;
+    fn on_preview_request(&self, trigger: &str) -> Result<ExpansionPreview, String>;
+}
+
+pub trait StatsRequestReceiver {
+    fn on_stats_request(&self) -> StatsSnapshot;
+}
+
+pub trait TriggerStateRequestReceiver {
+    fn on_trigger_state_request(&self, trigger: &str, disabled: bool);
+}
+
+pub trait ReloadRequestReceiver {
+    fn on_reload_request(&self, config_set: &Result<ConfigSet, ConfigLoadError>) -> Result<(), String>;
+}
+
+pub trait ConfigSetRequestReceiver {
+    fn on_config_set_request(&self) -> Result<ConfigSet, String>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_modifier_set_single_yaml() {
+        let set: KeyModifierSet = serde_yaml::from_str("ALT").unwrap();
+        assert_eq!(set, KeyModifierSet::from(KeyModifier::ALT));
+        assert_eq!(serde_yaml::from_str::<KeyModifierSet>(&serde_yaml::to_string(&set).unwrap()).unwrap(), set);
+    }
+
+    #[test]
+    fn test_key_modifier_set_multiple_yaml() {
+        let set: KeyModifierSet = serde_yaml::from_str("[CTRL, SHIFT, CTRL]").unwrap();
+        assert_eq!(set, KeyModifierSet::new(vec![KeyModifier::CTRL, KeyModifier::SHIFT]));
+        assert_eq!(set.len(), 2);
+        assert_eq!(serde_yaml::from_str::<KeyModifierSet>(&serde_yaml::to_string(&set).unwrap()).unwrap(), set);
+    }
+
+    #[test]
+    fn test_key_modifier_set_invalid_yaml() {
+        assert!(serde_yaml::from_str::<KeyModifierSet>("HYPER").is_err());
+        assert!(serde_yaml::from_str::<KeyModifierSet>("[CTRL, HYPER]").is_err());
+    }
+
+    #[test]
+    fn test_key_modifier_set_all_pressed() {
+        let combo = KeyModifierSet::new(vec![KeyModifier::CTRL, KeyModifier::ALT]);
+        let pressed = KeyModifierSet::new(vec![KeyModifier::ALT, KeyModifier::SHIFT, KeyModifier::CTRL]);
+
+        assert!(combo.contains(&KeyModifier::ALT));
+        assert!(!combo.contains(&KeyModifier::SHIFT));
+        assert!(combo.is_subset_of(&pressed));
+        assert!(!pressed.is_subset_of(&combo));
+        assert!(combo.all_pressed(&pressed));
+        assert!(!combo.all_pressed(&KeyModifierSet::from(KeyModifier::CTRL)));
+        assert!(!KeyModifierSet::default().all_pressed(&pressed));
+    }
+
+    #[test]
+    fn test_key_modifier_set_from_native_state() {
+        assert!(KeyModifierSet::from_native_state(0).is_empty());
+        assert_eq!(KeyModifierSet::from_native_state(1 | 2), KeyModifierSet::new(vec![KeyModifier::CTRL, KeyModifier::SHIFT]));
+        assert_eq!(KeyModifierSet::from_native_state(4 | 8), KeyModifierSet::new(vec![KeyModifier::ALT, KeyModifier::META]));
+    }
+}
@@ -19,9 +19,19 @@
 
 use serde_yaml::{Mapping, Value};
 use chrono::{DateTime, Local};
+use chrono::format::{StrftimeItems, Item};
 
 pub struct DateExtension {}
 
+/// Check if the given strftime format can be used to format a date, as formatting
+/// a date with an invalid one panics.
+pub fn is_valid_format(format: &str) -> bool {
+    !StrftimeItems::new(format).any(|item| match item {
+        Item::Error => true,
+        _ => false,
+    })
+}
+
 impl DateExtension {
     pub fn new() -> DateExtension {
         DateExtension{}
@@ -46,4 +56,16 @@ impl super::Extension for DateExtension {
 
         Some(date)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_format() {
+        assert!(is_valid_format("%H:%M"));
+        assert!(is_valid_format("no specifiers"));
+        assert!(!is_valid_format("%Q"));
+        assert!(!is_valid_format("%H:%"));
+    }
+}
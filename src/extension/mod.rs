@@ -19,11 +19,11 @@
 
 use serde_yaml::Mapping;
 
-mod date;
+pub(crate) mod date;
 mod shell;
 mod script;
 mod random;
-mod dummy;
+pub(crate) mod dummy;
 
 pub trait Extension {
     fn name(&self) -> String;
@@ -36,6 +36,7 @@ pub fn get_extensions() -> Vec<Box<dyn Extension>> {
         Box::new(shell::ShellExtension::new()),
         Box::new(script::ScriptExtension::new()),
         Box::new(random::RandomExtension::new()),
+        Box::new(random::RandomExtension::new_choice()),
         Box::new(dummy::DummyExtension::new()),
     ]
 }
\ No newline at end of file
@@ -19,23 +19,51 @@
 
 use serde_yaml::{Mapping, Value};
 use rand::seq::SliceRandom;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::cell::RefCell;
 use log::{warn, error};
 
-pub struct RandomExtension {}
+pub struct RandomExtension {
+    name: &'static str,
+    rng: RefCell<StdRng>,
+}
 
 impl RandomExtension {
     pub fn new() -> RandomExtension {
-        RandomExtension{}
+        RandomExtension{
+            name: "random",
+            rng: RefCell::new(StdRng::from_entropy()),
+        }
+    }
+
+    // The 'choice' variables are random variables too, usually listing their 'values'
+    pub fn new_choice() -> RandomExtension {
+        RandomExtension{
+            name: "choice",
+            rng: RefCell::new(StdRng::from_entropy()),
+        }
+    }
+
+    // Used to obtain a predictable sequence of choices
+    #[cfg(test)]
+    pub fn with_seed(seed: u64) -> RandomExtension {
+        RandomExtension{
+            name: "random",
+            rng: RefCell::new(StdRng::seed_from_u64(seed)),
+        }
     }
 }
 
 impl super::Extension for RandomExtension {
     fn name(&self) -> String {
-        String::from("random")
+        String::from(self.name)
     }
 
     fn calculate(&self, params: &Mapping, args: &Vec<String>) -> Option<String> {
-        let choices = params.get(&Value::from("choices"));
+        // The choices can be listed as 'values' too
+        let choices = params.get(&Value::from("choices"))
+            .or_else(|| params.get(&Value::from("values")));
         if choices.is_none() {
             warn!("No 'choices' parameter specified for random variable");
             return None
@@ -46,8 +74,8 @@ impl super::Extension for RandomExtension {
                 arg.as_str().unwrap_or_default().to_string()
             }).collect::<Vec<String>>();
 
-            // Select a random choice between the possibilities
-            let choice = str_choices.choose(&mut rand::thread_rng());
+            // Select a random choice between the possibilities, a different one at each expansion
+            let choice = str_choices.choose(&mut *self.rng.borrow_mut());
 
             match choice {
                 Some(output) => {
@@ -73,6 +101,7 @@ impl super::Extension for RandomExtension {
 mod tests {
     use super::*;
     use crate::extension::Extension;
+    use std::collections::HashSet;
 
     #[test]
     fn test_random_basic() {
@@ -119,4 +148,31 @@ mod tests {
 
         assert!(rendered_choices.iter().any(|x| x == &output));
     }
+
+    #[test]
+    fn test_random_all_choices_appear() {
+        let mut params = Mapping::new();
+        let values = vec!("Hello", "Hi", "Good morning");
+        params.insert(Value::from("values"), Value::from(values.clone()));
+
+        let extension = RandomExtension::with_seed(42);
+        let outputs: HashSet<String> = (0..100).map(|_| {
+            extension.calculate(&params, &vec![]).unwrap()
+        }).collect();
+
+        let expected: HashSet<String> = values.iter().map(|value| value.to_string()).collect();
+        assert_eq!(outputs, expected);
+    }
+
+    #[test]
+    fn test_random_missing_or_empty_choices() {
+        let extension = RandomExtension::new_choice();
+        assert_eq!(extension.name(), "choice");
+
+        let mut params = Mapping::new();
+        assert!(extension.calculate(&params, &vec![]).is_none());
+
+        params.insert(Value::from("values"), Value::from(Vec::<String>::new()));
+        assert!(extension.calculate(&params, &vec![]).is_none());
+    }
 }
\ No newline at end of file
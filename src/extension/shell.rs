@@ -18,7 +18,7 @@
  */
 
 use serde_yaml::{Mapping, Value};
-use std::process::Command;
+use std::process::{Command, Output};
 use log::{warn, error};
 use regex::{Regex, Captures};
 
@@ -30,6 +30,63 @@ lazy_static! {
     };
 }
 
+/// Interpreter used to run the command of a shell variable, selected with the
+/// `shell` param. When missing, `cmd` is used on Windows and `sh` elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Cmd,
+    Powershell,
+    Bash,
+    Sh,
+}
+
+impl Shell {
+    fn from_string(shell: &str) -> Option<Shell> {
+        match shell {
+            "cmd" => Some(Shell::Cmd),
+            "powershell" => Some(Shell::Powershell),
+            "bash" => Some(Shell::Bash),
+            "sh" => Some(Shell::Sh),
+            _ => None,
+        }
+    }
+
+    fn execute(&self, cmd: &str) -> std::io::Result<Output> {
+        match self {
+            Shell::Cmd => {
+                Command::new("cmd")
+                    .args(&["/C", cmd])
+                    .output()
+            },
+            Shell::Powershell => {
+                Command::new("powershell")
+                    .args(&["-Command", cmd])
+                    .output()
+            },
+            Shell::Bash => {
+                Command::new("bash")
+                    .args(&["-c", cmd])
+                    .output()
+            },
+            Shell::Sh => {
+                Command::new("sh")
+                    .args(&["-c", cmd])
+                    .output()
+            },
+        }
+    }
+}
+
+impl Default for Shell {
+    fn default() -> Shell {
+        if cfg!(target_os = "windows") {
+            Shell::Cmd
+        }else{
+            Shell::Sh
+        }
+    }
+}
+
 pub struct ShellExtension {}
 
 impl ShellExtension {
@@ -62,31 +119,31 @@ impl super::Extension for ShellExtension {
             }
         }).to_string();
 
-        let output = if cfg!(target_os = "windows") {
-            Command::new("cmd")
-                .args(&["/C", &cmd])
-                .output()
-        } else {
-            Command::new("sh")
-                .arg("-c")
-                .arg(&cmd)
-                .output()
+        let shell = match params.get(&Value::from("shell")) {
+            Some(value) => {
+                let shell = value.as_str().and_then(Shell::from_string);
+                if shell.is_none() {
+                    error!("Invalid 'shell' parameter for shell variable: {:?}", value);
+                    return None
+                }
+                shell.unwrap()
+            },
+            None => Shell::default(),
         };
 
+        let output = shell.execute(&cmd);
+
         match output {
             Ok(output) => {
                 let output_str = String::from_utf8_lossy(output.stdout.as_slice());
                 let mut output_str = output_str.into_owned();
 
-                // If specified, trim the output
-                let trim_opt = params.get(&Value::from("trim"));
-                if let Some(value) = trim_opt {
-                    let val = value.as_bool();
-                    if let Some(val) = val {
-                        if val {
-                            output_str = output_str.trim().to_owned()
-                        }
-                    }
+                // Trim the output, unless disabled with `trim: false`
+                let trim = params.get(&Value::from("trim"))
+                    .and_then(|value| value.as_bool())
+                    .unwrap_or(true);
+                if trim {
+                    output_str = output_str.trim().to_owned()
                 }
 
                 Some(output_str)
@@ -112,6 +169,19 @@ mod tests {
         let extension = ShellExtension::new();
         let output = extension.calculate(&params, &vec![]);
 
+        assert!(output.is_some());
+        assert_eq!(output.unwrap(), "hello world");
+    }
+
+    #[test]
+    fn test_shell_untrimmed() {
+        let mut params = Mapping::new();
+        params.insert(Value::from("cmd"), Value::from("echo hello world"));
+        params.insert(Value::from("trim"), Value::from(false));
+
+        let extension = ShellExtension::new();
+        let output = extension.calculate(&params, &vec![]);
+
         assert!(output.is_some());
 
         if cfg!(target_os = "windows") {
@@ -162,11 +232,33 @@ mod tests {
         let output = extension.calculate(&params, &vec![]);
 
         assert!(output.is_some());
-        if cfg!(target_os = "windows") {
-            assert_eq!(output.unwrap(), "hello world\r\n");
-        }else{
-            assert_eq!(output.unwrap(), "hello world\n");
-        }
+        assert_eq!(output.unwrap(), "hello world");
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_shell_bash_selector() {
+        let mut params = Mapping::new();
+        params.insert(Value::from("cmd"), Value::from("echo $BASH_VERSION | cut -c1"));
+        params.insert(Value::from("shell"), Value::from("bash"));
+
+        let extension = ShellExtension::new();
+        let output = extension.calculate(&params, &vec![]);
+
+        assert!(output.is_some());
+        assert!(!output.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_shell_invalid_selector() {
+        let mut params = Mapping::new();
+        params.insert(Value::from("cmd"), Value::from("echo hello world"));
+        params.insert(Value::from("shell"), Value::from("fish"));
+
+        let extension = ShellExtension::new();
+        let output = extension.calculate(&params, &vec![]);
+
+        assert!(output.is_none());
     }
 
     #[test]
@@ -194,7 +286,7 @@ mod tests {
 
         assert!(output.is_some());
 
-        assert_eq!(output.unwrap(), "hello\n");
+        assert_eq!(output.unwrap(), "hello");
     }
 
     #[test]
@@ -208,6 +300,6 @@ mod tests {
 
         assert!(output.is_some());
 
-        assert_eq!(output.unwrap(), "hello\r\n");
+        assert_eq!(output.unwrap(), "hello");
     }
 }
\ No newline at end of file
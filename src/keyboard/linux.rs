@@ -19,7 +19,8 @@
 
 use std::ffi::CString;
 use crate::bridge::linux::*;
-use super::PasteShortcut;
+use super::{PasteShortcut, KeyCode};
+use crate::config::LinuxInputBackend;
 use log::error;
 
 pub struct LinuxKeyboardManager {
@@ -76,9 +77,9 @@ impl super::KeyboardManager for LinuxKeyboardManager {
         unsafe {delete_string(count)}
     }
 
-    fn move_cursor_left(&self, count: i32) {
+    fn select_left(&self, count: i32) {
         unsafe {
-            left_arrow(count);
+            select_left(count);
         }
     }
 
@@ -87,4 +88,95 @@ impl super::KeyboardManager for LinuxKeyboardManager {
             trigger_copy();
         }
     }
-}
\ No newline at end of file
+
+    fn send_key(&self, key: KeyCode, count: i32) {
+        let cstr = CString::new(key_name(key)).unwrap();
+        unsafe {
+            send_key(cstr.as_ptr(), count);
+        }
+    }
+
+    fn send_chord(&self, keys: &[KeyCode]) {
+        // xdo accepts the combinations written as "Control_L+Shift_L+v"
+        let names: Vec<String> = keys.iter().map(|key| key_name(*key)).collect();
+        let cstr = CString::new(names.join("+")).unwrap();
+        unsafe {
+            send_key(cstr.as_ptr(), 1);
+        }
+    }
+}
+
+/// Choose the input backend to use, given the configured one and the values of the
+/// XDG_SESSION_TYPE and WAYLAND_DISPLAY environment variables.
+pub fn resolve_input_backend(backend: LinuxInputBackend, session_type: Option<&str>,
+                             wayland_display: Option<&str>) -> LinuxInputBackend {
+    if backend != LinuxInputBackend::Auto {
+        return backend;
+    }
+
+    let is_wayland = match session_type {
+        Some(session_type) if !session_type.is_empty() => session_type.eq_ignore_ascii_case("wayland"),
+        // Some display managers don't set the session type
+        _ => wayland_display.map_or(false, |display| !display.is_empty()),
+    };
+
+    if is_wayland {
+        LinuxInputBackend::Wayland
+    }else{
+        LinuxInputBackend::X11
+    }
+}
+
+// X11 keysym names, also used by the Wayland backend
+pub(super) fn key_name(key: KeyCode) -> String {
+    let name = match key {
+        KeyCode::Tab => "Tab",
+        KeyCode::Enter => "Return",
+        KeyCode::Escape => "Escape",
+        KeyCode::Backspace => "BackSpace",
+        KeyCode::Delete => "Delete",
+        KeyCode::Up => "Up",
+        KeyCode::Down => "Down",
+        KeyCode::Left => "Left",
+        KeyCode::Right => "Right",
+        KeyCode::Home => "Home",
+        KeyCode::End => "End",
+        KeyCode::PageUp => "Prior",
+        KeyCode::PageDown => "Next",
+        KeyCode::Insert => "Insert",
+        KeyCode::Ctrl => "Control_L",
+        KeyCode::Shift => "Shift_L",
+        KeyCode::Alt => "Alt_L",
+        KeyCode::Meta => "Super_L",
+        KeyCode::Char(c) => return c.to_string(),
+    };
+
+    name.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_input_backend_explicit() {
+        assert_eq!(resolve_input_backend(LinuxInputBackend::X11, Some("wayland"), Some("wayland-0")), LinuxInputBackend::X11);
+        assert_eq!(resolve_input_backend(LinuxInputBackend::Wayland, Some("x11"), None), LinuxInputBackend::Wayland);
+    }
+
+    #[test]
+    fn test_resolve_input_backend_session_type() {
+        assert_eq!(resolve_input_backend(LinuxInputBackend::Auto, Some("wayland"), None), LinuxInputBackend::Wayland);
+        assert_eq!(resolve_input_backend(LinuxInputBackend::Auto, Some("Wayland"), None), LinuxInputBackend::Wayland);
+        assert_eq!(resolve_input_backend(LinuxInputBackend::Auto, Some("x11"), Some("wayland-0")), LinuxInputBackend::X11);
+        assert_eq!(resolve_input_backend(LinuxInputBackend::Auto, Some("tty"), None), LinuxInputBackend::X11);
+    }
+
+    #[test]
+    fn test_resolve_input_backend_fallback_on_wayland_display() {
+        assert_eq!(resolve_input_backend(LinuxInputBackend::Auto, None, Some("wayland-0")), LinuxInputBackend::Wayland);
+        assert_eq!(resolve_input_backend(LinuxInputBackend::Auto, Some(""), Some("wayland-0")), LinuxInputBackend::Wayland);
+        assert_eq!(resolve_input_backend(LinuxInputBackend::Auto, None, None), LinuxInputBackend::X11);
+        assert_eq!(resolve_input_backend(LinuxInputBackend::Auto, None, Some("")), LinuxInputBackend::X11);
+    }
+}
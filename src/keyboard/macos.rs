@@ -19,8 +19,8 @@
 
 use std::ffi::CString;
 use crate::bridge::macos::*;
-use super::PasteShortcut;
-use log::error;
+use super::{PasteShortcut, KeyCode};
+use log::{error, warn};
 
 pub struct MacKeyboardManager {
 }
@@ -34,13 +34,6 @@ impl super::KeyboardManager for MacKeyboardManager {
         }
     }
 
-    fn send_enter(&self) {
-        unsafe {
-            // Send the kVK_Return key press
-            send_vkey(0x24);
-        }
-    }
-
     fn trigger_paste(&self, shortcut: &PasteShortcut) {
         unsafe {
             match shortcut {
@@ -66,10 +59,106 @@ impl super::KeyboardManager for MacKeyboardManager {
         unsafe {delete_string(count)}
     }
 
-    fn move_cursor_left(&self, count: i32) {
+    fn select_left(&self, count: i32) {
         unsafe {
-            // Simulate the Left arrow count times
-            send_multi_vkey(0x7B, count);
+            // Simulate the Shift+Left arrow count times
+            select_left(count);
+        }
+    }
+
+    fn send_key(&self, key: KeyCode, count: i32) {
+        match virtual_key_code(key) {
+            Some(vk) => unsafe { send_multi_vkey(vk, count); },
+            None => warn!("Key {:?} is not supported on macOS", key),
         }
     }
-}
\ No newline at end of file
+
+    fn send_chord(&self, keys: &[KeyCode]) {
+        let vks: Option<Vec<i32>> = keys.iter().map(|key| virtual_key_code(*key)).collect();
+        match vks {
+            Some(vks) => unsafe { send_chord(vks.as_ptr(), vks.len() as i32); },
+            None => warn!("Key combination {:?} is not supported on macOS", keys),
+        }
+    }
+
+    fn is_secure_input_active(&self) -> bool {
+        unsafe { is_secure_input_active() != 0 }
+    }
+}
+
+// macOS virtual key codes (kVK_*)
+fn virtual_key_code(key: KeyCode) -> Option<i32> {
+    let vk = match key {
+        KeyCode::Tab => 0x30,
+        KeyCode::Enter => 0x24,
+        KeyCode::Escape => 0x35,
+        KeyCode::Backspace => 0x33,
+        KeyCode::Delete => 0x75,
+        KeyCode::Up => 0x7E,
+        KeyCode::Down => 0x7D,
+        KeyCode::Left => 0x7B,
+        KeyCode::Right => 0x7C,
+        KeyCode::Home => 0x73,
+        KeyCode::End => 0x77,
+        KeyCode::PageUp => 0x74,
+        KeyCode::PageDown => 0x79,
+        KeyCode::Insert => 0x72,  // kVK_Help, in place of Insert on Apple keyboards
+        KeyCode::Ctrl => 0x3B,
+        KeyCode::Shift => 0x38,
+        KeyCode::Alt => 0x3A,
+        KeyCode::Meta => 0x37,
+        KeyCode::Char(c) => return ansi_key_code(c),
+    };
+
+    Some(vk)
+}
+
+// Codes of the letters and digits on the ANSI layout (kVK_ANSI_*)
+fn ansi_key_code(c: char) -> Option<i32> {
+    let vk = match c {
+        'a' => 0x00, 's' => 0x01, 'd' => 0x02, 'f' => 0x03, 'h' => 0x04,
+        'g' => 0x05, 'z' => 0x06, 'x' => 0x07, 'c' => 0x08, 'v' => 0x09,
+        'b' => 0x0B, 'q' => 0x0C, 'w' => 0x0D, 'e' => 0x0E, 'r' => 0x0F,
+        'y' => 0x10, 't' => 0x11, '1' => 0x12, '2' => 0x13, '3' => 0x14,
+        '4' => 0x15, '6' => 0x16, '5' => 0x17, '9' => 0x19, '7' => 0x1A,
+        '8' => 0x1C, '0' => 0x1D, 'o' => 0x1F, 'u' => 0x20, 'i' => 0x22,
+        'p' => 0x23, 'l' => 0x25, 'j' => 0x26, 'k' => 0x28, 'n' => 0x2D,
+        'm' => 0x2E,
+        _ => return None,
+    };
+
+    Some(vk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtual_key_code_mapping() {
+        let expected = [
+            (KeyCode::Tab, 0x30),
+            (KeyCode::Enter, 0x24),
+            (KeyCode::Escape, 0x35),
+            (KeyCode::Backspace, 0x33),
+            (KeyCode::Delete, 0x75),
+            (KeyCode::Up, 0x7E),
+            (KeyCode::Down, 0x7D),
+            (KeyCode::Left, 0x7B),
+            (KeyCode::Right, 0x7C),
+            (KeyCode::Home, 0x73),
+            (KeyCode::End, 0x77),
+            (KeyCode::PageUp, 0x74),
+            (KeyCode::PageDown, 0x79),
+            (KeyCode::Ctrl, 0x3B),
+            (KeyCode::Shift, 0x38),
+            (KeyCode::Alt, 0x3A),
+            (KeyCode::Meta, 0x37),
+            (KeyCode::Char('v'), 0x09),
+        ];
+
+        for (key, vk) in expected.iter() {
+            assert_eq!(virtual_key_code(*key), Some(*vk), "wrong virtual key for {:?}", key);
+        }
+    }
+}
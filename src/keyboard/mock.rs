@@ -0,0 +1,134 @@
+/*
+ * This file is part of espanso.
+ *
+ * Copyright (C) 2019 Federico Terzi
+ *
+ * espanso is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * espanso is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with espanso.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::cell::{Cell, RefCell};
+use super::{PasteShortcut, KeyCode};
+
+/// Action requested to a KeyboardManager.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyboardEvent {
+    SendString(String),
+    SendEnter,
+    TriggerPaste(PasteShortcut),
+    DeleteString(i32),
+    MoveCursorLeft(i32),
+    SelectLeft(i32),
+    TriggerCopy,
+    SendKey(KeyCode, i32),
+    SendChord(Vec<KeyCode>),
+}
+
+/// KeyboardManager that doesn't interact with the system, but records
+/// every request so that tests can check the injected sequence.
+pub struct MockKeyboardManager {
+    events: RefCell<Vec<KeyboardEvent>>,
+    secure_input_active: Cell<bool>,
+    injection_fails: Cell<bool>,
+    injected_before_failure: Cell<Option<usize>>,
+}
+
+impl MockKeyboardManager {
+    pub fn new() -> MockKeyboardManager {
+        MockKeyboardManager {
+            events: RefCell::new(Vec::new()),
+            secure_input_active: Cell::new(false),
+            injection_fails: Cell::new(false),
+            injected_before_failure: Cell::new(None),
+        }
+    }
+
+    /// Simulate the focus of a password field.
+    pub fn set_secure_input_active(&self, active: bool) {
+        self.secure_input_active.set(active);
+    }
+
+    /// Simulate an application in which the injected strings don't appear.
+    pub fn set_injection_fails(&self, fails: bool) {
+        self.injection_fails.set(fails);
+    }
+
+    /// Simulate an application in which only the first `count` injected strings appear.
+    pub fn set_injection_fails_after(&self, count: usize) {
+        self.injected_before_failure.set(Some(count));
+    }
+
+    /// Return the events recorded so far, in the order they were received.
+    pub fn events(&self) -> Vec<KeyboardEvent> {
+        self.events.borrow().clone()
+    }
+
+    fn record(&self, event: KeyboardEvent) {
+        self.events.borrow_mut().push(event);
+    }
+}
+
+impl super::KeyboardManager for MockKeyboardManager {
+    fn send_string(&self, s: &str) {
+        self.record(KeyboardEvent::SendString(s.to_owned()));
+    }
+
+    fn try_send_string(&self, s: &str) -> bool {
+        self.send_string(s);
+
+        if let Some(count) = self.injected_before_failure.get() {
+            if count == 0 {
+                return false;
+            }
+            self.injected_before_failure.set(Some(count - 1));
+        }
+
+        !self.injection_fails.get()
+    }
+
+    fn send_enter(&self) {
+        self.record(KeyboardEvent::SendEnter);
+    }
+
+    fn trigger_paste(&self, shortcut: &PasteShortcut) {
+        self.record(KeyboardEvent::TriggerPaste(shortcut.clone()));
+    }
+
+    fn delete_string(&self, count: i32) {
+        self.record(KeyboardEvent::DeleteString(count));
+    }
+
+    fn move_cursor_left(&self, count: i32) {
+        self.record(KeyboardEvent::MoveCursorLeft(count));
+    }
+
+    fn select_left(&self, count: i32) {
+        self.record(KeyboardEvent::SelectLeft(count));
+    }
+
+    fn trigger_copy(&self) {
+        self.record(KeyboardEvent::TriggerCopy);
+    }
+
+    fn send_key(&self, key: KeyCode, count: i32) {
+        self.record(KeyboardEvent::SendKey(key, count));
+    }
+
+    fn send_chord(&self, keys: &[KeyCode]) {
+        self.record(KeyboardEvent::SendChord(keys.to_vec()));
+    }
+
+    fn is_secure_input_active(&self) -> bool {
+        self.secure_input_active.get()
+    }
+}
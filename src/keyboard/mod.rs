@@ -17,7 +17,8 @@
  * along with espanso.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use serde::{Serialize, Deserialize, Deserializer};
+use serde::{Serialize, Deserialize, Deserializer, Serializer};
+use crate::config::Configs;
 
 #[cfg(target_os = "windows")]
 mod windows;
@@ -25,25 +26,185 @@ mod windows;
 #[cfg(target_os = "linux")]
 mod linux;
 
+#[cfg(target_os = "linux")]
+mod wayland;
+
 #[cfg(target_os = "macos")]
 mod macos;
 
+#[cfg(test)]
+pub(crate) mod mock;
+
 pub trait KeyboardManager {
     fn send_string(&self, s: &str);
-    fn send_enter(&self);
+
+    // Same as send_string, but returns false if the string couldn't be injected.
+    // Platforms that can't detect a failed injection always return true.
+    fn try_send_string(&self, s: &str) -> bool {
+        self.send_string(s);
+        true
+    }
     fn trigger_paste(&self, shortcut: &PasteShortcut);
     fn delete_string(&self, count: i32);
-    fn move_cursor_left(&self, count: i32);
+    fn select_left(&self, count: i32);
     fn trigger_copy(&self);
+
+    // Press the given key, *count* times
+    fn send_key(&self, key: KeyCode, count: i32);
+
+    // Press the given keys together, for example CTRL+SHIFT+V, releasing them in reverse order
+    fn send_chord(&self, keys: &[KeyCode]);
+
+    fn send_enter(&self) {
+        self.send_key(KeyCode::Enter, 1);
+    }
+
+    fn move_cursor_left(&self, count: i32) {
+        self.send_key(KeyCode::Left, count);
+    }
+
+    // Check if a secure text field (such as a password input) is focused.
+    // Platforms that can't detect it always return false.
+    fn is_secure_input_active(&self) -> bool {
+        false
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Platform independent key, mapped to the right virtual key by each KeyboardManager.
+/// It can also be pressed in the middle of a replacement, with the {{key:NAME}} token.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyCode {
+    Tab,
+    Enter,
+    Escape,
+    Backspace,
+    Delete,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Ctrl,
+    Shift,
+    Alt,
+    Meta,     // Win key on Windows, CMD on macOS and Super on Linux
+    Char(char),   // Letter or digit, always lowercase
+}
+
+impl KeyCode {
+    /// Parse the name used in the {{key:NAME}} token, ignoring the case.
+    pub fn from_name(name: &str) -> Option<KeyCode> {
+        match name.to_uppercase().as_str() {
+            "TAB" => Some(KeyCode::Tab),
+            "ENTER" | "RETURN" => Some(KeyCode::Enter),
+            "ESC" | "ESCAPE" => Some(KeyCode::Escape),
+            "BACKSPACE" => Some(KeyCode::Backspace),
+            "DEL" | "DELETE" => Some(KeyCode::Delete),
+            "UP" => Some(KeyCode::Up),
+            "DOWN" => Some(KeyCode::Down),
+            "LEFT" => Some(KeyCode::Left),
+            "RIGHT" => Some(KeyCode::Right),
+            "HOME" => Some(KeyCode::Home),
+            "END" => Some(KeyCode::End),
+            "PAGEUP" => Some(KeyCode::PageUp),
+            "PAGEDOWN" => Some(KeyCode::PageDown),
+            "INS" | "INSERT" => Some(KeyCode::Insert),
+            "CTRL" | "CONTROL" => Some(KeyCode::Ctrl),
+            "SHIFT" => Some(KeyCode::Shift),
+            "ALT" | "OPTION" => Some(KeyCode::Alt),
+            "META" | "CMD" | "SUPER" | "WIN" => Some(KeyCode::Meta),
+            _ => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if c.is_ascii_alphanumeric() => Some(KeyCode::Char(c.to_ascii_lowercase())),
+                    _ => None,
+                }
+            },
+        }
+    }
+
+    /// Canonical name of the key, accepted by `from_name`.
+    pub fn name(&self) -> String {
+        match self {
+            KeyCode::Tab => "TAB".to_owned(),
+            KeyCode::Enter => "ENTER".to_owned(),
+            KeyCode::Escape => "ESC".to_owned(),
+            KeyCode::Backspace => "BACKSPACE".to_owned(),
+            KeyCode::Delete => "DELETE".to_owned(),
+            KeyCode::Up => "UP".to_owned(),
+            KeyCode::Down => "DOWN".to_owned(),
+            KeyCode::Left => "LEFT".to_owned(),
+            KeyCode::Right => "RIGHT".to_owned(),
+            KeyCode::Home => "HOME".to_owned(),
+            KeyCode::End => "END".to_owned(),
+            KeyCode::PageUp => "PAGEUP".to_owned(),
+            KeyCode::PageDown => "PAGEDOWN".to_owned(),
+            KeyCode::Insert => "INSERT".to_owned(),
+            KeyCode::Ctrl => "CTRL".to_owned(),
+            KeyCode::Shift => "SHIFT".to_owned(),
+            KeyCode::Alt => "ALT".to_owned(),
+            KeyCode::Meta => "META".to_owned(),
+            KeyCode::Char(c) => c.to_ascii_uppercase().to_string(),
+        }
+    }
+
+    /// Parse a key combination, such as "CTRL+SHIFT+V".
+    pub fn parse_chord(chord: &str) -> Option<Vec<KeyCode>> {
+        chord.split('+').map(|name| KeyCode::from_name(name.trim())).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum PasteShortcut {
     Default,        // Default one for the current system
     CtrlV,          // Classic Ctrl+V shortcut
     CtrlShiftV,     // Could be used to paste without formatting in many applications
     ShiftInsert,    // Often used in Linux systems
     MetaV,          // Corresponding to Win+V on Windows and Linux, CMD+V on macOS
+    Custom(Vec<KeyCode>),   // Any other combination, written as "CTRL+ALT+V"
+}
+
+// Serialized as a string, either the name of a predefined shortcut or a custom combination
+impl Serialize for PasteShortcut {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+
+        let name = match self {
+            PasteShortcut::Default => "Default".to_owned(),
+            PasteShortcut::CtrlV => "CtrlV".to_owned(),
+            PasteShortcut::CtrlShiftV => "CtrlShiftV".to_owned(),
+            PasteShortcut::ShiftInsert => "ShiftInsert".to_owned(),
+            PasteShortcut::MetaV => "MetaV".to_owned(),
+            PasteShortcut::Custom(keys) => {
+                keys.iter().map(KeyCode::name).collect::<Vec<String>>().join("+")
+            },
+        };
+
+        serializer.serialize_str(&name)
+    }
+}
+
+impl <'de> Deserialize<'de> for PasteShortcut {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where
+        D: Deserializer<'de> {
+
+        let name = String::deserialize(deserializer)?;
+        match name.as_str() {
+            "Default" => Ok(PasteShortcut::Default),
+            "CtrlV" => Ok(PasteShortcut::CtrlV),
+            "CtrlShiftV" => Ok(PasteShortcut::CtrlShiftV),
+            "ShiftInsert" => Ok(PasteShortcut::ShiftInsert),
+            "MetaV" => Ok(PasteShortcut::MetaV),
+            _ => match KeyCode::parse_chord(&name) {
+                Some(keys) => Ok(PasteShortcut::Custom(keys)),
+                None => Err(serde::de::Error::custom(format!("invalid paste shortcut: '{}'", name))),
+            },
+        }
+    }
 }
 
 impl Default for PasteShortcut{
@@ -54,18 +215,117 @@ impl Default for PasteShortcut{
 
 // WINDOWS IMPLEMENTATION
 #[cfg(target_os = "windows")]
-pub fn get_manager() -> impl KeyboardManager {
+pub fn get_manager(_config: &Configs) -> impl KeyboardManager {
     windows::WindowsKeyboardManager{}
 }
 
 // LINUX IMPLEMENTATION
 #[cfg(target_os = "linux")]
-pub fn get_manager() -> impl KeyboardManager {
-    linux::LinuxKeyboardManager{}
+pub fn get_manager(config: &Configs) -> impl KeyboardManager {
+    use crate::config::LinuxInputBackend;
+    use log::info;
+
+    let session_type = std::env::var("XDG_SESSION_TYPE").ok();
+    let wayland_display = std::env::var("WAYLAND_DISPLAY").ok();
+    let backend = linux::resolve_input_backend(config.linux_input_backend,
+                                               session_type.as_ref().map(String::as_str),
+                                               wayland_display.as_ref().map(String::as_str));
+
+    info!("Using the {:?} input backend", backend);
+
+    let manager: Box<dyn KeyboardManager> = match backend {
+        LinuxInputBackend::Wayland => Box::new(wayland::WaylandKeyboardManager{}),
+        _ => Box::new(linux::LinuxKeyboardManager{}),
+    };
+    manager
 }
 
 // MAC IMPLEMENTATION
 #[cfg(target_os = "macos")]
-pub fn get_manager() -> impl KeyboardManager {
+pub fn get_manager(_config: &Configs) -> impl KeyboardManager {
     macos::MacKeyboardManager{}
-}
\ No newline at end of file
+}
+
+// Used to choose the KeyboardManager at runtime
+impl <K: KeyboardManager + ?Sized> KeyboardManager for Box<K> {
+    fn send_string(&self, s: &str) {
+        (**self).send_string(s)
+    }
+
+    fn trigger_paste(&self, shortcut: &PasteShortcut) {
+        (**self).trigger_paste(shortcut)
+    }
+
+    fn delete_string(&self, count: i32) {
+        (**self).delete_string(count)
+    }
+
+    fn select_left(&self, count: i32) {
+        (**self).select_left(count)
+    }
+
+    fn trigger_copy(&self) {
+        (**self).trigger_copy()
+    }
+
+    fn send_key(&self, key: KeyCode, count: i32) {
+        (**self).send_key(key, count)
+    }
+
+    fn send_chord(&self, keys: &[KeyCode]) {
+        (**self).send_chord(keys)
+    }
+
+    fn send_enter(&self) {
+        (**self).send_enter()
+    }
+
+    fn move_cursor_left(&self, count: i32) {
+        (**self).move_cursor_left(count)
+    }
+
+    fn is_secure_input_active(&self) -> bool {
+        (**self).is_secure_input_active()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_from_name() {
+        assert_eq!(KeyCode::from_name("TAB"), Some(KeyCode::Tab));
+        assert_eq!(KeyCode::from_name("tab"), Some(KeyCode::Tab));
+        assert_eq!(KeyCode::from_name("Return"), Some(KeyCode::Enter));
+        assert_eq!(KeyCode::from_name("PageDown"), Some(KeyCode::PageDown));
+        assert_eq!(KeyCode::from_name("F13"), None);
+        assert_eq!(KeyCode::from_name("v"), Some(KeyCode::Char('v')));
+        assert_eq!(KeyCode::from_name("V"), Some(KeyCode::Char('v')));
+        assert_eq!(KeyCode::from_name("é"), None);
+    }
+
+    #[test]
+    fn test_key_parse_chord() {
+        assert_eq!(KeyCode::parse_chord("CTRL+SHIFT+V"), Some(vec![KeyCode::Ctrl, KeyCode::Shift, KeyCode::Char('v')]));
+        assert_eq!(KeyCode::parse_chord("ctrl + v"), Some(vec![KeyCode::Ctrl, KeyCode::Char('v')]));
+        assert_eq!(KeyCode::parse_chord("CTRL+"), None);
+        assert_eq!(KeyCode::parse_chord("CTRL+HYPER"), None);
+    }
+
+    #[test]
+    fn test_paste_shortcut_deserialize() {
+        assert_eq!(serde_yaml::from_str::<PasteShortcut>("CtrlShiftV").unwrap(), PasteShortcut::CtrlShiftV);
+        assert_eq!(serde_yaml::from_str::<PasteShortcut>("\"CTRL+ALT+V\"").unwrap(),
+                   PasteShortcut::Custom(vec![KeyCode::Ctrl, KeyCode::Alt, KeyCode::Char('v')]));
+        assert!(serde_yaml::from_str::<PasteShortcut>("CtrlQ+").is_err());
+    }
+
+    #[test]
+    fn test_paste_shortcut_serialize_round_trip() {
+        for shortcut in vec![PasteShortcut::MetaV, PasteShortcut::Custom(vec![KeyCode::Shift, KeyCode::Insert])] {
+            let serialized = serde_json::to_string(&shortcut).unwrap();
+            assert_eq!(serde_json::from_str::<PasteShortcut>(&serialized).unwrap(), shortcut);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+/*
+ * This file is part of espanso.
+ *
+ * Copyright (C) 2019 Federico Terzi
+ *
+ * espanso is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * espanso is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with espanso.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::process::Command;
+use super::{PasteShortcut, KeyCode};
+use super::linux::key_name;
+use log::error;
+
+/// KeyboardManager for Wayland sessions, sending the keystrokes through the
+/// 'wtype' tool, which uses the virtual keyboard protocol.
+pub struct WaylandKeyboardManager {
+}
+
+impl WaylandKeyboardManager {
+    // Run wtype with the given arguments, returning true if it succeeded
+    fn wtype(&self, args: &[String]) -> bool {
+        if args.is_empty() {
+            return true;
+        }
+
+        match Command::new("wtype").args(args).status() {
+            Ok(status) if !status.success() => {
+                error!("wtype exited with {}", status);
+                false
+            },
+            Err(e) => {
+                error!("Unable to run wtype, make sure it's installed: {}", e);
+                false
+            },
+            _ => true,
+        }
+    }
+}
+
+// Build the wtype arguments to press the given keys together
+fn chord_args(keys: &[KeyCode]) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut modifiers = Vec::new();
+
+    for key in keys.iter() {
+        match modifier_name(*key) {
+            Some(modifier) => {
+                args.push("-M".to_owned());
+                args.push(modifier.to_owned());
+                modifiers.push(modifier);
+            },
+            None => {
+                args.push("-k".to_owned());
+                args.push(key_name(*key));
+            },
+        }
+    }
+
+    for modifier in modifiers.iter().rev() {
+        args.push("-m".to_owned());
+        args.push((*modifier).to_owned());
+    }
+
+    args
+}
+
+fn modifier_name(key: KeyCode) -> Option<&'static str> {
+    match key {
+        KeyCode::Ctrl => Some("ctrl"),
+        KeyCode::Shift => Some("shift"),
+        KeyCode::Alt => Some("alt"),
+        KeyCode::Meta => Some("logo"),
+        _ => None,
+    }
+}
+
+fn key_args(key: KeyCode, count: i32) -> Vec<String> {
+    (0..count).flat_map(|_| vec!["-k".to_owned(), key_name(key)]).collect()
+}
+
+impl super::KeyboardManager for WaylandKeyboardManager {
+    fn send_string(&self, s: &str) {
+        self.try_send_string(s);
+    }
+
+    fn try_send_string(&self, s: &str) -> bool {
+        // Newlines are typed as Enter key presses
+        let mut args = Vec::new();
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                args.extend(key_args(KeyCode::Enter, 1));
+            }
+            if !line.is_empty() {
+                args.push("--".to_owned());
+                args.push(line.to_owned());
+            }
+        }
+
+        self.wtype(&args)
+    }
+
+    fn send_enter(&self) {
+        // Newlines are already handled by send_string, so NOOP
+    }
+
+    fn trigger_paste(&self, shortcut: &PasteShortcut) {
+        let keys = match shortcut {
+            PasteShortcut::Default | PasteShortcut::CtrlV => vec![KeyCode::Ctrl, KeyCode::Char('v')],
+            PasteShortcut::CtrlShiftV => vec![KeyCode::Ctrl, KeyCode::Shift, KeyCode::Char('v')],
+            PasteShortcut::ShiftInsert => vec![KeyCode::Shift, KeyCode::Insert],
+            PasteShortcut::MetaV => vec![KeyCode::Meta, KeyCode::Char('v')],
+            PasteShortcut::Custom(keys) => keys.clone(),
+        };
+
+        self.send_chord(&keys);
+    }
+
+    fn delete_string(&self, count: i32) {
+        self.send_key(KeyCode::Backspace, count);
+    }
+
+    fn select_left(&self, count: i32) {
+        let mut args = vec!["-M".to_owned(), "shift".to_owned()];
+        args.extend(key_args(KeyCode::Left, count));
+        args.push("-m".to_owned());
+        args.push("shift".to_owned());
+
+        self.wtype(&args);
+    }
+
+    fn trigger_copy(&self) {
+        self.send_chord(&[KeyCode::Ctrl, KeyCode::Char('c')]);
+    }
+
+    fn send_key(&self, key: KeyCode, count: i32) {
+        self.wtype(&key_args(key, count));
+    }
+
+    fn send_chord(&self, keys: &[KeyCode]) {
+        self.wtype(&chord_args(keys));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chord_args() {
+        assert_eq!(chord_args(&[KeyCode::Ctrl, KeyCode::Shift, KeyCode::Char('v')]),
+                   vec!["-M", "ctrl", "-M", "shift", "-k", "v", "-m", "shift", "-m", "ctrl"]);
+    }
+
+    #[test]
+    fn test_key_args() {
+        assert_eq!(key_args(KeyCode::Backspace, 2), vec!["-k", "BackSpace", "-k", "BackSpace"]);
+        assert!(key_args(KeyCode::Tab, 0).is_empty());
+    }
+}
@@ -19,7 +19,7 @@
 
 use widestring::{U16CString};
 use crate::bridge::windows::*;
-use super::PasteShortcut;
+use super::{PasteShortcut, KeyCode};
 use log::error;
 
 pub struct WindowsKeyboardManager {
@@ -39,13 +39,6 @@ impl super::KeyboardManager for WindowsKeyboardManager {
 
     }
 
-    fn send_enter(&self) {
-        unsafe {
-            // Send the VK_RETURN key press
-            send_vkey(0x0D);
-        }
-    }
-
     fn trigger_paste(&self, shortcut: &PasteShortcut) {
         unsafe {
             match shortcut {
@@ -67,10 +60,9 @@ impl super::KeyboardManager for WindowsKeyboardManager {
         }
     }
 
-    fn move_cursor_left(&self, count: i32) {
+    fn select_left(&self, count: i32) {
         unsafe {
-            // Send the left arrow key multiple times
-            send_multi_vkey(0x25, count)
+            select_left(count)
         }
     }
 
@@ -79,4 +71,75 @@ impl super::KeyboardManager for WindowsKeyboardManager {
             trigger_copy();
         }
     }
-}
\ No newline at end of file
+
+    fn send_key(&self, key: KeyCode, count: i32) {
+        unsafe {
+            send_multi_vkey(virtual_key_code(key), count);
+        }
+    }
+
+    fn send_chord(&self, keys: &[KeyCode]) {
+        let vks: Vec<i32> = keys.iter().map(|key| virtual_key_code(*key)).collect();
+        unsafe {
+            send_chord(vks.as_ptr(), vks.len() as i32);
+        }
+    }
+
+    fn send_enter(&self) {
+        unsafe {
+            send_multi_vkey(virtual_key_code(KeyCode::Enter), 1);
+        }
+    }
+
+    fn move_cursor_left(&self, count: i32) {
+        // Simulate the Left arrow count times, used to position the cursor on the $|$ hint
+        unsafe {
+            send_multi_vkey(virtual_key_code(KeyCode::Left), count);
+        }
+    }
+}
+
+// Windows Virtual-Key codes
+fn virtual_key_code(key: KeyCode) -> i32 {
+    match key {
+        KeyCode::Tab => 0x09,
+        KeyCode::Enter => 0x0D,
+        KeyCode::Escape => 0x1B,
+        KeyCode::Backspace => 0x08,
+        KeyCode::Delete => 0x2E,
+        KeyCode::Up => 0x26,
+        KeyCode::Down => 0x28,
+        KeyCode::Left => 0x25,
+        KeyCode::Right => 0x27,
+        KeyCode::Home => 0x24,
+        KeyCode::End => 0x23,
+        KeyCode::PageUp => 0x21,
+        KeyCode::PageDown => 0x22,
+        KeyCode::Insert => 0x2D,
+        KeyCode::Ctrl => 0x11,
+        KeyCode::Shift => 0x10,
+        KeyCode::Alt => 0x12,
+        KeyCode::Meta => 0x5B,
+        KeyCode::Char(c) => c.to_ascii_uppercase() as i32,  // Same as the ASCII code for letters and digits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyboard::KeyboardManager;
+
+    // Fails to compile if some method of the trait is missing on Windows
+    fn assert_keyboard_manager<K: KeyboardManager>(_manager: &K) {}
+
+    #[test]
+    fn test_windows_keyboard_manager_implements_trait() {
+        assert_keyboard_manager(&WindowsKeyboardManager {});
+    }
+
+    #[test]
+    fn test_virtual_key_code_cursor_keys() {
+        assert_eq!(virtual_key_code(KeyCode::Left), 0x25);
+        assert_eq!(virtual_key_code(KeyCode::Enter), 0x0D);
+    }
+}
@@ -24,12 +24,12 @@ use std::thread;
 use std::fs::{File, OpenOptions};
 use std::process::exit;
 use std::sync::mpsc;
-use std::sync::mpsc::Receiver;
+use std::sync::mpsc::{Receiver, Sender};
 use std::time::Duration;
 
 use clap::{App, Arg, SubCommand, ArgMatches};
 use fs2::FileExt;
-use log::{info, warn, LevelFilter};
+use log::{info, warn, error, LevelFilter};
 use simplelog::{CombinedLogger, SharedLogger, TerminalMode, TermLogger, WriteLogger};
 
 use crate::config::{ConfigSet, ConfigManager};
@@ -62,6 +62,7 @@ mod protocol;
 mod clipboard;
 mod extension;
 mod sysdaemon;
+mod stats;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const LOG_FILE: &str = "espanso.log";
@@ -95,9 +96,24 @@ fn main() {
                 .about("Disable the espanso replacement engine."))
             .subcommand(SubCommand::with_name("toggle")
                 .about("Toggle the status of the espanso replacement engine."))
+            .subcommand(SubCommand::with_name("reload")
+                .about("Reload the configuration, keeping the current one if the new one is invalid."))
+            .subcommand(SubCommand::with_name("disable-trigger")
+                .about("Disable the match with the given trigger, until it's enabled again.")
+                .arg(Arg::with_name("trigger").required(true)))
+            .subcommand(SubCommand::with_name("enable-trigger")
+                .about("Enable again a match disabled with disable-trigger.")
+                .arg(Arg::with_name("trigger").required(true)))
+            .subcommand(SubCommand::with_name("profile")
+                .about("Switch to the given profile, or to the base config if none is given.")
+                .arg(Arg::with_name("name")
+                    .help("Name of the profile, stored in the 'profiles' folder of the config directory.")
+                    .required(false)))
         )
+        .subcommand(SubCommand::with_name("migrate")
+            .about("Rename the deprecated fields used in the config files."))
         .subcommand(SubCommand::with_name("dump")
-            .about("Prints all current configuration options."))
+            .about("Prints the effective configuration as YAML, after all the configs and packages are merged."))
         .subcommand(SubCommand::with_name("detect")
             .about("Tool to detect current window properties, to simplify filters creation."))
         .subcommand(SubCommand::with_name("daemon")
@@ -148,13 +164,23 @@ fn main() {
 
     let log_level = matches.occurrences_of("v") as i32;
 
+    // The migration runs before loading the configuration, as the deprecated fields
+    // could prevent it from loading
+    if matches.subcommand_matches("migrate").is_some() {
+        migrate_main();
+        return;
+    }
+
     // Load the configuration
     let mut config_set = ConfigSet::load_default().unwrap_or_else(|e| {
         println!("{}", e);
         exit(1);
     });
 
-    config_set.default.log_level = log_level;
+    // The verbosity flag can only raise the log level specified in the config
+    if log_level > config_set.default.log_level {
+        config_set.default.log_level = log_level;
+    }
 
     // Match the correct subcommand
 
@@ -164,7 +190,7 @@ fn main() {
     }
 
     if matches.subcommand_matches("dump").is_some() {
-        println!("{:#?}", config_set);
+        print!("{}", config_set.export_effective());
         return;
     }
 
@@ -263,12 +289,8 @@ fn daemon_main(config_set: ConfigSet) {
 
     precheck_guard();
 
-    // Initialize log
-    let log_level = match config_set.default.log_level {
-        0 => LevelFilter::Warn,
-        1 => LevelFilter::Info,
-        2 | _ => LevelFilter::Debug,
-    };
+    // Initialize log, the specific configs can raise the verbosity of the default one
+    let log_level = config_set.level_filter();
 
     let mut log_outputs: Vec<Box<dyn SharedLogger>> = Vec::new();
 
@@ -296,6 +318,9 @@ fn daemon_main(config_set: ConfigSet) {
         log_outputs
     ).expect("Error opening log destination");
 
+    // The log file always receives the info messages
+    log::set_max_level(std::cmp::max(log_level, LevelFilter::Info));
+
     // Activate logging for panics
     log_panics::init();
 
@@ -308,47 +333,74 @@ fn daemon_main(config_set: ConfigSet) {
 
     let context = context::new(send_channel.clone());
 
-    let config_set_copy = config_set.clone();
+    let send_channel_copy = send_channel.clone();
     thread::Builder::new().name("daemon_background".to_string()).spawn(move || {
-        daemon_background(receive_channel, config_set_copy);
+        daemon_background(receive_channel, send_channel_copy, config_set);
     }).expect("Unable to spawn daemon background thread");
 
-    let ipc_server = protocol::get_ipc_server(config_set, send_channel.clone());
-    ipc_server.start();
-
     context.eventloop();
 }
 
 /// Background thread worker for the daemon
-fn daemon_background(receive_channel: Receiver<Event>, config_set: ConfigSet) {
+fn daemon_background(receive_channel: Receiver<Event>, send_channel: Sender<Event>, config_set: ConfigSet) {
     let system_manager = system::get_manager();
-    let config_manager = RuntimeConfigManager::new(config_set, system_manager);
+    let mut config_manager = RuntimeConfigManager::new(config_set.clone(), system_manager);
+    config_manager.set_state_dir(&context::get_config_dir());
+
+    // The IPC server reloads the configuration with the parse cache of the manager
+    let ipc_server = protocol::get_ipc_server(config_set, send_channel, config_manager.config_cache()).unwrap_or_else(|e| {
+        error!("{}", e);
+        println!("{}", e);
+        exit(1);
+    });
+    ipc_server.start().unwrap_or_else(|e| {
+        error!("{}", e);
+        println!("{}", e);
+        exit(1);
+    });
 
     let ui_manager = ui::get_uimanager();
     ui_manager.notify("espanso is running!");
 
     let clipboard_manager = clipboard::get_manager();
 
-    let keyboard_manager = keyboard::get_manager();
+    let keyboard_manager = keyboard::get_manager(&config_manager.default_config());
 
     let extensions = extension::get_extensions();
 
-    let renderer = render::default::DefaultRenderer::new(extensions,
-                                                          config_manager.default_config().clone());
+    let mut renderer = render::default::DefaultRenderer::new(extensions,
+                                                              (*config_manager.default_config()).clone());
+    renderer.set_clipboard_manager(Box::new(clipboard::get_manager()));
+
+    let notifier = ui::notifier::UINotifier::new(&ui_manager);
 
-    let engine = Engine::new(&keyboard_manager,
+    let mut engine = Engine::new(&keyboard_manager,
                              &clipboard_manager,
                              &config_manager,
                              &ui_manager,
                              &renderer,
     );
+    engine.set_notifier(&notifier);
+    if config_manager.default_config().collect_stats {
+        engine.set_usage_stats(stats::UsageStats::load(&context::get_config_dir()));
+    }
 
     let matcher = ScrollingMatcher::new(&config_manager, &engine);
 
+    // Restore the enabled state of the previous session
+    if !config::runtime::load_enabled_state() {
+        matcher.on_action_event(ActionType::Disable);
+    }
+
     let event_manager = DefaultEventManager::new(
         receive_channel,
         vec!(&matcher),
         vec!(&engine, &matcher),
+        vec!(&engine),
+        vec!(&engine),
+        vec!(&engine),
+        vec!(&engine),
+        vec!(&engine),
     );
 
     info!("espanso is running!");
@@ -529,6 +581,7 @@ fn stop_main(config_set: ConfigSet) {
     let res = send_command(config_set, IPCCommand{
         id: "exit".to_owned(),
         payload: "".to_owned(),
+        secret: None,
     });
 
     if let Err(e) = res {
@@ -548,6 +601,7 @@ fn restart_main(config_set: ConfigSet) {
         send_command(config_set.clone(), IPCCommand{
             id: "exit".to_owned(),
             payload: "".to_owned(),
+            secret: None,
         }).unwrap_or_else(|e| warn!("Unable to send IPC command to daemon: {}", e));
     }else{
         release_lock(lock_file.unwrap());
@@ -642,31 +696,81 @@ fn detect_main() {
 
 /// Send the given command to the espanso daemon
 fn cmd_main(config_set: ConfigSet, matches: &ArgMatches) {
+    // The reload command waits for the outcome of the reload
+    if matches.subcommand_matches("reload").is_some() {
+        match config::runtime::send_reload_command(config_set) {
+            Ok(_) => {
+                println!("Configuration reloaded");
+                exit(0);
+            },
+            Err(e) => {
+                println!("{}", e);
+                exit(1);
+            },
+        }
+    }
+
+    if let Some(matches) = matches.subcommand_matches("profile") {
+        let name = matches.value_of("name");
+        match config::runtime::send_profile_command(config_set, name) {
+            Ok(_) => {
+                println!("Switched to the {} profile", name.unwrap_or("base"));
+                exit(0);
+            },
+            Err(e) => {
+                println!("{}", e);
+                exit(1);
+            },
+        }
+    }
+
     let command = if matches.subcommand_matches("exit").is_some() {
         Some(IPCCommand {
             id: String::from("exit"),
             payload: String::from(""),
+            secret: None,
         })
     }else if matches.subcommand_matches("toggle").is_some() {
         Some(IPCCommand {
             id: String::from("toggle"),
             payload: String::from(""),
+            secret: None,
         })
     }else if matches.subcommand_matches("enable").is_some() {
         Some(IPCCommand {
             id: String::from("enable"),
             payload: String::from(""),
+            secret: None,
         })
     }else if matches.subcommand_matches("disable").is_some() {
         Some(IPCCommand {
             id: String::from("disable"),
             payload: String::from(""),
+            secret: None,
+        })
+    }else if let Some(matches) = matches.subcommand_matches("disable-trigger") {
+        Some(IPCCommand {
+            id: String::from("disable_trigger"),
+            payload: matches.value_of("trigger").unwrap_or_default().to_owned(),
+            secret: None,
+        })
+    }else if let Some(matches) = matches.subcommand_matches("enable-trigger") {
+        Some(IPCCommand {
+            id: String::from("enable_trigger"),
+            payload: matches.value_of("trigger").unwrap_or_default().to_owned(),
+            secret: None,
         })
     }else{
         None
     };
 
     if let Some(command) = command {
+        let enabled = match command.id.as_str() {
+            "enable" => Some(true),
+            "disable" => Some(false),
+            _ => None,
+        };
+
         let res = send_command(config_set, command);
 
         if res.is_ok() {
@@ -674,16 +778,46 @@ fn cmd_main(config_set: ConfigSet, matches: &ArgMatches) {
         }else{
             println!("{}", res.unwrap_err());
         }
+
+        // If espanso is not running, the state is saved for the next start
+        if let Some(enabled) = enabled {
+            match config::runtime::save_enabled_state(enabled) {
+                Ok(_) => {
+                    println!("espanso will start {}", if enabled { "enabled" } else { "disabled" });
+                    exit(0);
+                },
+                Err(e) => println!("Unable to save the enabled state: {}", e),
+            }
+        }
     }
 
     exit(1);
 }
 
-fn send_command(config_set: ConfigSet, command: IPCCommand) -> Result<(), String> {
-    let ipc_client = protocol::get_ipc_client(config_set);
+fn send_command(config_set: ConfigSet, mut command: IPCCommand) -> Result<(), String> {
+    command.secret = config_set.default.ipc_secret.clone();
+
+    let ipc_client = protocol::get_ipc_client(config_set)?;
     ipc_client.send_command(command)
 }
 
+fn migrate_main() {
+    match ConfigSet::migrate_default() {
+        Ok(migrated) => {
+            if migrated.is_empty() {
+                println!("No config file needs to be migrated");
+            }
+            for path in migrated {
+                println!("Migrated {:?}, the original file was saved with the .bak extension", path);
+            }
+        },
+        Err(e) => {
+            println!("Unable to migrate the config files: {}", e);
+            exit(1);
+        },
+    }
+}
+
 fn log_main() {
     let espanso_dir = context::get_data_dir();
     let log_file_path = espanso_dir.join(LOG_FILE);
@@ -864,7 +998,8 @@ fn path_main(_config_set: ConfigSet, matches: &ArgMatches) {
     }else if matches.subcommand_matches("data").is_some() {
         println!("{}", data.to_string_lossy());
     }else if matches.subcommand_matches("default").is_some() {
-        let default_file = config.join(crate::config::DEFAULT_CONFIG_FILE_NAME);
+        let default_file_name = crate::config::default_config_file_name_from_env();
+        let default_file = crate::config::default_config_path(&config, default_file_name.as_ref().map(String::as_str));
         println!("{}", default_file.to_string_lossy());
     }else{
         println!("Config: {}", config.to_string_lossy());
@@ -0,0 +1,220 @@
+/*
+ * This file is part of espanso.
+ *
+ * Copyright (C) 2019 Federico Terzi
+ *
+ * espanso is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * espanso is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with espanso.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::matcher::{Match, TriggerEntry};
+use std::collections::HashMap;
+
+/*
+ * Trie built from the trigger sequences of a list of matches.
+ * The matcher walks it one keystroke at a time, so that the cost of processing
+ * a key press is proportional to the number of partial matches in progress
+ * instead of the total number of matches.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct MatchIndex {
+    nodes: Vec<IndexNode>,
+
+    // Number of chars of the longest indexed trigger
+    max_trigger_length: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct IndexNode {
+    children: HashMap<TriggerEntry, usize>,
+
+    // Positions (in the original match list) of the matches whose trigger ends in this node,
+    // paired with the offset of that trigger in the triggers of the match
+    matches: Vec<(usize, usize)>,
+}
+
+pub const ROOT_NODE: usize = 0;
+
+impl MatchIndex {
+    /// Build the index of the given matches. Passive-only matches are not
+    /// included, as they can't be expanded while typing.
+    pub fn new(matches: &[Match]) -> MatchIndex {
+        let mut nodes = vec![IndexNode::default()];
+        let mut max_trigger_length = 0;
+
+        for (position, m) in matches.iter().enumerate() {
+            if m.passive_only {
+                continue;
+            }
+
+            max_trigger_length = m.triggers().iter().map(|trigger| trigger.chars().count())
+                .fold(max_trigger_length, usize::max);
+
+            for (trigger_offset, trigger_sequence) in m._trigger_sequences.iter().enumerate() {
+                if trigger_sequence.is_empty() {
+                    continue;
+                }
+
+                let mut current = ROOT_NODE;
+                for entry in trigger_sequence.iter() {
+                    current = match nodes[current].children.get(entry) {
+                        Some(&child) => child,
+                        None => {
+                            nodes.push(IndexNode::default());
+                            let child = nodes.len() - 1;
+                            nodes[current].children.insert(entry.clone(), child);
+                            child
+                        },
+                    };
+                }
+
+                nodes[current].matches.push((position, trigger_offset));
+            }
+        }
+
+        MatchIndex {
+            nodes,
+            max_trigger_length,
+        }
+    }
+
+    /// Return the number of chars of the longest indexed trigger.
+    pub fn max_trigger_length(&self) -> usize {
+        self.max_trigger_length
+    }
+
+    /// Return the node reached from the given one through the entry, if any.
+    pub fn next(&self, node: usize, entry: &TriggerEntry) -> Option<usize> {
+        self.nodes.get(node).and_then(|n| n.children.get(entry).cloned())
+    }
+
+    /// Return the positions of the matches whose trigger ends in the given node, in the
+    /// same order they were defined, along with the offset of the trigger in Match::triggers.
+    pub fn matches_at(&self, node: usize) -> &[(usize, usize)] {
+        match self.nodes.get(node) {
+            Some(n) => &n.matches,
+            None => &[],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Configs;
+    use std::time::Instant;
+
+    fn lookup(index: &MatchIndex, trigger: &str) -> Vec<usize> {
+        let mut node = ROOT_NODE;
+        for c in trigger.chars() {
+            match index.next(node, &TriggerEntry::Char(c)) {
+                Some(next) => node = next,
+                None => return Vec::new(),
+            }
+        }
+
+        index.matches_at(node).iter().map(|(position, _)| *position).collect()
+    }
+
+    #[test]
+    fn test_match_index_lookup() {
+        let config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - trigger: ":a"
+              replace: "first"
+            - trigger: ":ab"
+              replace: "second"
+            - trigger: ":a"
+              replace: "third"
+            - trigger: ":p"
+              replace: "passive"
+              passive_only: true
+        "###).unwrap();
+
+        let index = config.build_match_index();
+
+        assert_eq!(lookup(&index, ":a"), vec![0, 2]);
+        assert_eq!(lookup(&index, ":ab"), vec![1]);
+        assert!(lookup(&index, ":abc").is_empty());
+        assert!(lookup(&index, ":p").is_empty());
+    }
+
+    #[test]
+    fn test_match_index_word_trigger_ends_with_separator() {
+        let config: Configs = serde_yaml::from_str(r###"
+        matches:
+            - trigger: "hi"
+              replace: "hello"
+              word: true
+        "###).unwrap();
+
+        let index = config.build_match_index();
+
+        assert!(lookup(&index, "hi").is_empty());
+
+        let node = index.next(ROOT_NODE, &TriggerEntry::Char('h')).unwrap();
+        let node = index.next(node, &TriggerEntry::Char('i')).unwrap();
+        let node = index.next(node, &TriggerEntry::WordSeparator).unwrap();
+        assert_eq!(index.matches_at(node), &[(0, 0)]);
+    }
+
+    // Benchmark comparing the trigger lookup of the index with a linear scan. The wall-clock
+    // timings are only reported, the check is on the number of candidates visited. Run it with:
+    // cargo test bench_match_index -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_match_index_vs_linear_scan() {
+        let mut config_str = String::from("matches:\n");
+        for i in 0..5000 {
+            config_str.push_str(&format!("    - trigger: \":trigger{}\"\n      replace: \"replacement{}\"\n", i, i));
+        }
+        let config: Configs = serde_yaml::from_str(&config_str).unwrap();
+        let index = config.build_match_index();
+
+        let triggers: Vec<String> = (0..5000).step_by(50).map(|i| format!(":trigger{}", i)).collect();
+        let iterations = 100;
+
+        // The linear scan checks every match on each keystroke, as the matcher used to do
+        let mut linear_visited = 0;
+        let mut candidates = 0;
+        let start = Instant::now();
+        for _ in 0..iterations {
+            for trigger in triggers.iter() {
+                for c in trigger.chars() {
+                    let entry = TriggerEntry::Char(c);
+                    linear_visited += config.matches.len();
+                    candidates += config.matches.iter()
+                        .filter(|m| m._trigger_sequences[0][0] == entry)
+                        .count();
+                }
+            }
+        }
+        let linear_elapsed = start.elapsed();
+        assert!(candidates > 0);
+
+        // The index visits a single node on each keystroke
+        let mut index_visited = 0;
+        let start = Instant::now();
+        for _ in 0..iterations {
+            for trigger in triggers.iter() {
+                index_visited += trigger.chars().count();
+                let found = lookup(&index, trigger);
+                assert!(!found.is_empty());
+            }
+        }
+        let index_elapsed = start.elapsed();
+
+        assert!(index_visited * 1000 < linear_visited);
+        println!("linear scan: {:?}, index: {:?}", linear_elapsed, index_elapsed);
+    }
+}
@@ -17,44 +17,235 @@
  * along with espanso.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use serde::{Serialize, Deserialize, Deserializer};
-use crate::event::{KeyEvent, KeyModifier};
+use serde::{Serialize, Deserialize, Deserializer, Serializer};
+use crate::event::{KeyEvent, KeyModifier, KeyModifierSet};
 use crate::event::KeyEventReceiver;
-use serde_yaml::Mapping;
+use serde_yaml::{Mapping, Value};
 use regex::Regex;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
+use log::warn;
+use regex::Captures;
+use crate::extension::Extension;
+use crate::extension::dummy::DummyExtension;
+use crate::render::default::{VAR_REGEX, TRIGGER_VAR_NAME};
+use crate::render::filter::{apply_filters, find_unknown_filters};
+use crate::render::conditional::has_conditionals;
+use crate::keyboard::KeyCode;
+use crate::config::BackendType;
+use std::fmt;
+use std::time::SystemTime;
+use std::collections::HashSet;
+
+lazy_static! {
+    pub(crate) static ref KEY_TOKEN_REGEX: Regex = Regex::new("\\{\\{\\s*key:(?P<name>\\w+)\\s*\\}\\}").unwrap();
+}
 
 pub(crate) mod scrolling;
+pub(crate) mod index;
 
-#[derive(Debug, Serialize, Clone)]
+// Values accepted by the 'os' field of the matches, as returned by std::env::consts::OS
+pub const SUPPORTED_OS: &[&str] = &["macos", "windows", "linux"];
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Match {
-    pub trigger: String,
+    // All the triggers of the match, whether defined with 'trigger' or 'triggers'
+    triggers: Vec<String>,
+
     pub content: MatchContentType,
     pub word: bool,
     pub passive_only: bool,
+    pub keep_trigger: bool,
+    pub select_after: bool,
 
-    // Automatically calculated from the trigger, used by the matcher to check for correspondences.
-    #[serde(skip_serializing)]
-    pub _trigger_sequence: Vec<TriggerEntry>,
+    // If true, Enter is pressed once the expansion is complete, for example to send a chat
+    // message. It's the last action, so it comes after the cursor is moved or the image pasted.
+    // It's ignored when 'select_after' is set, as Enter would replace the selected text.
+    pub press_enter_after: bool,
+
+    // If true, the match only fires when the trigger is typed at the start of a line
+    // (or as the first thing after espanso started). When combined with 'word', both
+    // conditions must hold, and as newlines are word separators by default, a trigger
+    // typed at the start of a line also satisfies the 'word' requirement.
+    pub at_line_start: bool,
+
+    // Window classes or executables in which the match is active. If empty, the match
+    // is active everywhere. When the match is defined in a specific config, both the
+    // config filters and this list must match the focused window.
+    pub apps: Vec<String>,
+
+    // Final position of the cursor after the expansion, see CursorPosition
+    pub cursor: Option<CursorPosition>,
+
+    // Key combination that fires the match, without deleting anything as no trigger
+    // was typed. The trigger keeps working as usual, independently of the hotkey.
+    // The focused application still receives the combination, so it's better to pick
+    // one that the applications don't use.
+    pub hotkey: Option<Hotkey>,
+
+    // Minimum time between two expansions of the match, in milliseconds. If 0, there is no limit.
+    pub cooldown_ms: u64,
+
+    // If true, the match fires only once per session, until espanso restarts or the configuration is reloaded
+    pub once: bool,
+
+    // Minimum time between two consecutive chars of the trigger, in milliseconds. If the trigger
+    // is typed faster, for example by an autocorrect or a paste, the match doesn't fire. If 0, there is no limit.
+    pub min_char_interval_ms: u64,
+
+    // Backend used to expand the match, overriding both the config one and the clipboard_threshold
+    pub backend: Option<BackendType>,
+
+    // Control the newline at the end of the replacement, as YAML block scalars make it easy
+    // to add or lose one by mistake. They are mutually exclusive.
+    pub trim_trailing_newline: bool,
+    pub ensure_trailing_newline: bool,
+
+    // Metadata used to document and search the matches, they never affect the matching
+    pub description: String,
+    pub tags: Vec<String>,
+
+    // Name used by tools to address the match, unique within its config
+    pub label: Option<String>,
+
+    // Config file defining the match (or the imported file, if it comes from an import),
+    // set when the config is loaded and used to explain where an expansion comes from
+    pub source_file: Option<PathBuf>,
+
+    // Strategy used when the match overrides a parent one, see MergeStrategy
+    pub merge: MergeStrategy,
+
+    // Platforms on which the match is available, see SUPPORTED_OS. If empty, the match is
+    // available everywhere. Matches not available on the current one are dropped at load time.
+    pub os: Vec<String>,
+
+    // Automatically calculated from the triggers, used by the matcher to check for correspondences.
+    pub _trigger_sequences: Vec<Vec<TriggerEntry>>,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone, PartialEq)]
 pub enum MatchContentType {
     Text(TextContent),
     Image(ImageContent),
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone, PartialEq)]
 pub struct TextContent {
     pub replace: String,
     pub vars: Vec<MatchVariable>,
 
+    // If present, the replacement is read from this file instead of 'replace'
+    pub replace_file: Option<PathBuf>,
+    pub replace_file_load: FileLoadMode,
+
     #[serde(skip_serializing)]
     pub _has_vars: bool,
 }
 
-#[derive(Debug, Serialize, Clone)]
+// Determines when the file of a 'replace_file' match is read. With Startup, the
+// contents are read once when the config is loaded, so changes to the file are
+// only picked up after the config file itself changes or espanso restarts.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileLoadMode {
+    Trigger,
+    Startup,
+}
+impl Default for FileLoadMode {
+    fn default() -> Self {
+        FileLoadMode::Trigger
+    }
+}
+
+// Where the cursor is placed after the expansion. If not specified, the cursor is moved
+// to the $|$ marker when the replacement contains one, and left after the inserted text
+// otherwise. Start and End send the Home and End keys, ignoring the marker.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorPosition {
+    Start,
+    End,
+    Marker,
+}
+
+// How a match is combined with the parent one sharing a trigger, when a child config (or
+// an importing file, or a specific config inheriting the default matches) is merged into
+// its parent. As the child always has the priority, Replace discards the parent match,
+// while Append and Prepend add the child replacement after or before the parent one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeStrategy {
+    Replace,
+    Append,
+    Prepend,
+}
+impl Default for MergeStrategy {
+    fn default() -> Self {
+        MergeStrategy::Replace
+    }
+}
+
+/// Combination of modifiers and a letter or digit, such as "CTRL+SHIFT+S".
+/// The hotkey fires when its key is typed while holding exactly its modifiers,
+/// as reported by the platform layers along with each char. The keys are not
+/// grabbed, so the focused application receives the combination as well.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hotkey {
+    pub modifiers: KeyModifierSet,
+    pub key: char,  // Always lowercase
+}
+
+impl Hotkey {
+    pub fn parse(hotkey: &str) -> Result<Hotkey, String> {
+        let invalid = || format!("invalid hotkey '{}', it must be made of one or more modifiers \
+                                  (CTRL, SHIFT, ALT, META), not only SHIFT, and a single letter or digit", hotkey);
+
+        let keys = KeyCode::parse_chord(hotkey).ok_or_else(invalid)?;
+
+        let mut modifiers = KeyModifierSet::default();
+        let mut key = None;
+        for code in keys {
+            let modifier = match code {
+                KeyCode::Ctrl => KeyModifier::CTRL,
+                KeyCode::Shift => KeyModifier::SHIFT,
+                KeyCode::Alt => KeyModifier::ALT,
+                KeyCode::Meta => KeyModifier::META,
+                KeyCode::Char(c) if key.is_none() => {
+                    key = Some(c);
+                    continue;
+                },
+                _ => return Err(invalid()),
+            };
+
+            modifiers.insert(modifier);
+        }
+
+        // SHIFT alone just types the uppercase letters, which can't be told apart from a hotkey
+        match key {
+            Some(key) if !modifiers.is_empty() && modifiers != KeyModifierSet::from(KeyModifier::SHIFT) => {
+                Ok(Hotkey { modifiers, key })
+            },
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Check if the given key, typed while holding the given modifiers, fires the hotkey.
+    pub fn is_fired_by(&self, modifiers: &KeyModifierSet, key: char) -> bool {
+        self.key == key.to_ascii_lowercase() && self.modifiers.all_pressed(modifiers) &&
+            modifiers.is_subset_of(&self.modifiers)
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for modifier in self.modifiers.iter() {
+            write!(f, "{:?}+", modifier)?;
+        }
+        write!(f, "{}", self.key.to_ascii_uppercase())
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
 pub struct ImageContent {
     pub path: PathBuf,
 }
@@ -64,40 +255,157 @@ impl <'de> serde::Deserialize<'de> for Match {
         D: Deserializer<'de> {
 
         let auto_match = AutoMatch::deserialize(deserializer)?;
+        match (&auto_match.trigger, auto_match.triggers.is_empty()) {
+            (Some(trigger), false) => return Err(serde::de::Error::custom(format!(
+                "match '{}' can't define both 'trigger' and 'triggers'", trigger))),
+            // The hotkey-only matches don't need a trigger, they are fired by the hotkey
+            (None, true) if auto_match.hotkey.is_none() => return Err(serde::de::Error::custom(
+                "match must define either 'trigger' or 'triggers'")),
+            _ => {},
+        }
+        if let Some(hotkey) = &auto_match.hotkey {
+            Hotkey::parse(hotkey).map_err(serde::de::Error::custom)?;
+        }
+        if let Some(replace) = &auto_match.replace {
+            if let Some(filter) = find_unknown_filters(replace).first() {
+                return Err(serde::de::Error::custom(format!(
+                    "match '{}' uses the unknown filter '{}'", auto_match.first_trigger(), filter)));
+            }
+        }
+        if let Some(os) = auto_match.os.iter().find(|os| !SUPPORTED_OS.contains(&os.as_str())) {
+            return Err(serde::de::Error::custom(format!(
+                "match '{}' has an unknown os '{}', expected one of: {}", auto_match.first_trigger(), os, SUPPORTED_OS.join(", "))));
+        }
+        if auto_match.trim_trailing_newline && auto_match.ensure_trailing_newline {
+            return Err(serde::de::Error::custom(format!(
+                "match '{}' can't define both 'trim_trailing_newline' and 'ensure_trailing_newline'", auto_match.first_trigger())));
+        }
+        if auto_match.replace.is_some() && auto_match.replace_file.is_some() {
+            return Err(serde::de::Error::custom(format!(
+                "match '{}' can't define both 'replace' and 'replace_file'", auto_match.first_trigger())));
+        }
+
         Ok(Match::from(&auto_match))
     }
 }
 
-impl<'a> From<&'a AutoMatch> for Match{
-    fn from(other: &'a AutoMatch) -> Self {
-        lazy_static! {
-            static ref VAR_REGEX: Regex = Regex::new("\\{\\{\\s*(\\w+)\\s*\\}\\}").unwrap();
+// A Match is serialized in the same format it is defined in the config files,
+// so that it can be deserialized back.
+impl Serialize for Match {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer {
+
+        AutoMatch::from(self).serialize(serializer)
+    }
+}
+
+impl<'a> From<&'a Match> for AutoMatch {
+    fn from(other: &'a Match) -> Self {
+        let (replace, replace_file, replace_file_load, image_path, vars) = match &other.content {
+            MatchContentType::Text(content) => {
+                match &content.replace_file {
+                    Some(replace_file) => (None, Some(replace_file.to_string_lossy().into_owned()),
+                                           content.replace_file_load, None, content.vars.clone()),
+                    None => (Some(content.replace.clone()), None, FileLoadMode::default(), None, content.vars.clone()),
+                }
+            },
+            MatchContentType::Image(content) => {
+                (None, None, FileLoadMode::default(), Some(content.path.to_string_lossy().into_owned()), Vec::new())
+            },
+        };
+
+        // A single trigger is serialized as 'trigger', as most matches define it that way
+        let (trigger, triggers) = if other.triggers.len() > 1 {
+            (None, other.triggers.clone())
+        }else if other.triggers.is_empty() {
+            (None, Vec::new())
+        }else{
+            (Some(other.trigger().to_owned()), Vec::new())
         };
 
+        Self {
+            trigger,
+            triggers,
+            replace,
+            replace_file,
+            replace_file_load,
+            image_path,
+            vars,
+            word: other.word,
+            passive_only: other.passive_only,
+            keep_trigger: other.keep_trigger,
+            select_after: other.select_after,
+            press_enter_after: other.press_enter_after,
+            at_line_start: other.at_line_start,
+            apps: other.apps.clone(),
+            cursor: other.cursor,
+            hotkey: other.hotkey.as_ref().map(Hotkey::to_string),
+            cooldown_ms: other.cooldown_ms,
+            once: other.once,
+            min_char_interval_ms: other.min_char_interval_ms,
+            backend: other.backend.clone(),
+            trim_trailing_newline: other.trim_trailing_newline,
+            ensure_trailing_newline: other.ensure_trailing_newline,
+            description: other.description.clone(),
+            label: other.label.clone(),
+            source_file: other.source_file.clone(),
+            tags: other.tags.clone(),
+            os: other.os.clone(),
+            merge: other.merge,
+        }
+    }
+}
+
+impl<'a> From<&'a AutoMatch> for Match{
+    fn from(other: &'a AutoMatch) -> Self {
         // TODO: may need to replace windows newline (\r\n) with newline only (\n)
 
-        // Calculate the trigger sequence
-        let mut trigger_sequence = Vec::new();
-        let trigger_chars : Vec<char> = other.trigger.chars().collect();
-        trigger_sequence.extend(trigger_chars.into_iter().map(|c| {
-            TriggerEntry::Char(c)
-        }));
-        if other.word {  // If it's a word match, end with a word separator
-            trigger_sequence.push(TriggerEntry::WordSeparator);
+        let mut triggers = if other.triggers.is_empty() {
+            other.trigger.iter().cloned().collect()
+        }else{
+            other.triggers.clone()
+        };
+
+        // An empty trigger is allowed on the hotkey matches, meaning they are fired only by the hotkey.
+        // On the other matches it's kept, so that it's rejected when checking the config.
+        if other.hotkey.is_some() {
+            triggers.retain(|trigger| !trigger.is_empty());
         }
 
+        // Calculate the trigger sequences
+        let trigger_sequences = triggers.iter().map(|trigger| {
+            let mut trigger_sequence: Vec<TriggerEntry> = trigger.chars().map(TriggerEntry::Char).collect();
+            if other.word {  // If it's a word match, end with a word separator
+                trigger_sequence.push(TriggerEntry::WordSeparator);
+            }
+            trigger_sequence
+        }).collect();
+
         let content = if let Some(replace) = &other.replace {  // Text match
             let new_replace = replace.clone();
 
             // Check if the match contains variables
-            let has_vars = VAR_REGEX.is_match(replace);
+            let has_vars = VAR_REGEX.is_match(replace) || has_conditionals(replace);
 
             let content = TextContent {
                 replace: new_replace,
                 vars: other.vars.clone(),
+                replace_file: None,
+                replace_file_load: FileLoadMode::default(),
                 _has_vars: has_vars,
             };
 
+            MatchContentType::Text(content)
+        }else if let Some(replace_file) = &other.replace_file {  // Text match read from a file
+            // The variables are checked once the file is read
+            let content = TextContent {
+                replace: String::new(),
+                vars: other.vars.clone(),
+                replace_file: Some(PathBuf::from(replace_file)),
+                replace_file_load: other.replace_file_load,
+                _has_vars: false,
+            };
+
             MatchContentType::Text(content)
         }else if let Some(image_path) = &other.image_path {  // Image match
             // On Windows, we have to replace the forward / with the backslash \ in the path
@@ -127,28 +435,309 @@ impl<'a> From<&'a AutoMatch> for Match{
 
             MatchContentType::Image(content)
         }else {
-            eprintln!("ERROR: no action specified for match {}, please specify either 'replace' or 'image_path'", other.trigger);
+            eprintln!("ERROR: no action specified for match {}, please specify either 'replace', 'replace_file' or 'image_path'", other.first_trigger());
             std::process::exit(2);
         };
 
         Self {
-            trigger: other.trigger.clone(),
+            triggers,
             content,
             word: other.word,
             passive_only: other.passive_only,
-            _trigger_sequence: trigger_sequence,
+            keep_trigger: other.keep_trigger,
+            select_after: other.select_after,
+            press_enter_after: other.press_enter_after,
+            at_line_start: other.at_line_start,
+            apps: other.apps.clone(),
+            cursor: other.cursor,
+            hotkey: other.hotkey.as_ref().and_then(|hotkey| Hotkey::parse(hotkey).ok()),
+            cooldown_ms: other.cooldown_ms,
+            once: other.once,
+            min_char_interval_ms: other.min_char_interval_ms,
+            backend: other.backend.clone(),
+            trim_trailing_newline: other.trim_trailing_newline,
+            ensure_trailing_newline: other.ensure_trailing_newline,
+            description: other.description.clone(),
+            label: other.label.clone(),
+            source_file: other.source_file.clone(),
+            tags: other.tags.clone(),
+            os: other.os.clone(),
+            merge: other.merge,
+            _trigger_sequences: trigger_sequences,
         }
     }
 }
 
+impl Match {
+    /// Return all the triggers of the match, regardless of whether they were
+    /// defined with 'trigger' or 'triggers'. It's empty for the hotkey-only matches.
+    pub fn triggers(&self) -> &[String] {
+        &self.triggers
+    }
+
+    /// Return the first of the triggers, used to identify the match (for example, in the
+    /// stats or when disabling it). Code dealing with the typed text must use triggers().
+    pub fn trigger(&self) -> &str {
+        self.triggers.first().map_or("", String::as_str)
+    }
+
+    /// Return the key identifying the match when tracking its fires (cooldown, once and stats),
+    /// that is the first trigger, or the hotkey for the hotkey-only matches.
+    pub fn fire_key(&self) -> String {
+        match (self.triggers.first(), &self.hotkey) {
+            (None, Some(hotkey)) => hotkey.to_string(),
+            _ => self.trigger().to_owned(),
+        }
+    }
+
+    /// Check if the match is available on the given platform, named as in std::env::consts::OS.
+    pub fn is_available_on(&self, os: &str) -> bool {
+        self.os.is_empty() || self.os.iter().any(|o| o == os)
+    }
+
+    /// Combine the replacement with the one of the given parent match, which shares a trigger,
+    /// according to the merge strategy. The variables of the parent are kept unless redefined.
+    /// Afterwards the match has the parent strategy, as it takes its place.
+    pub fn merge_with_parent(&mut self, parent: &Match) {
+        let strategy = std::mem::replace(&mut self.merge, parent.merge);
+        if strategy == MergeStrategy::Replace {
+            return;
+        }
+
+        match (&mut self.content, &parent.content) {
+            (MatchContentType::Text(content), MatchContentType::Text(parent_content))
+                if content.replace_file.is_none() && parent_content.replace_file.is_none() => {
+                content.replace = if strategy == MergeStrategy::Append {
+                    format!("{}{}", parent_content.replace, content.replace)
+                }else{
+                    format!("{}{}", content.replace, parent_content.replace)
+                };
+
+                let parent_vars: Vec<MatchVariable> = parent_content.vars.iter()
+                    .filter(|var| !content.vars.iter().any(|v| v.name == var.name))
+                    .cloned().collect();
+                content.vars.extend(parent_vars);
+                content._has_vars = VAR_REGEX.is_match(&content.replace) || has_conditionals(&content.replace);
+            },
+            _ => {
+                warn!("Match '{}' can't be merged with its parent, only text replacements support the '{:?}' strategy. \
+                       The parent one will be replaced", self.trigger(), strategy);
+            },
+        }
+    }
+
+    /// Check if the match is annotated with the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Check if the match shares at least a trigger with the given set.
+    pub fn has_any_trigger(&self, triggers: &HashSet<&str>) -> bool {
+        self.triggers.iter().any(|trigger| triggers.contains(trigger.as_str()))
+    }
+
+    /// Check if the match is active in the focused app. An entry of 'apps' matches
+    /// if it's equal to the window class or to the file name of the executable, with
+    /// or without its extension, ignoring the case in both.
+    pub fn is_active_in_app(&self, class: Option<&str>, exec: Option<&str>) -> bool {
+        if self.apps.is_empty() {
+            return true;
+        }
+
+        let class = class.map(str::to_lowercase);
+
+        // The path separators are split by hand, as the Windows ones are not recognized elsewhere
+        let exec_name = exec.and_then(|exec| exec.rsplit(|c| c == '/' || c == '\\').next())
+            .map(str::to_lowercase);
+        let exec_stem = exec_name.as_ref()
+            .and_then(|name| Path::new(name).file_stem().and_then(|stem| stem.to_str()))
+            .map(str::to_owned);
+
+        self.apps.iter().map(|app| app.to_lowercase()).any(|app| {
+            class.as_ref().map_or(false, |class| *class == app) ||
+                exec_name.as_ref().map_or(false, |name| *name == app) ||
+                exec_stem.as_ref().map_or(false, |stem| *stem == app)
+        })
+    }
+
+    /// Compute the text that replaces the given typed trigger, without any side effect.
+    /// Only the variables that don't depend on the environment (such as the `dummy` ones
+    /// and the typed trigger) are substituted, the others are left untouched. The cursor hint is removed and, if the
+    /// typed trigger is capitalized or uppercase, the same case is applied to the result.
+    /// Image matches don't produce any text, so an empty string is returned.
+    pub fn expand(&self, typed_trigger: &str) -> String {
+        self.expand_with_cursor_hint(typed_trigger).replace("$|$", "")
+    }
+
+    /// Same as expand, but the $|$ cursor hint is kept in the result.
+    pub fn expand_with_cursor_hint(&self, typed_trigger: &str) -> String {
+        let content = match &self.content {
+            MatchContentType::Text(content) => content,
+            MatchContentType::Image(_) => return String::new(),
+        };
+
+        let expanded = if content._has_vars {
+            let dummy = DummyExtension::new();
+            VAR_REGEX.replace_all(&content.replace, |caps: &Captures| {
+                let var_name = caps.name("name").unwrap().as_str();
+                let var = content.vars.iter().find(|var| var.name == var_name);
+                let output = match var {
+                    Some(var) if var.var_type == dummy.name() => dummy.calculate(&var.params, &vec![]),
+                    None if var_name == TRIGGER_VAR_NAME => Some(typed_trigger.to_owned()),
+                    _ => None,
+                };
+                match output {
+                    Some(output) => apply_filters(&output, &caps["filters"]),
+                    None => caps.get(0).unwrap().as_str().to_owned(),
+                }
+            }).to_string()
+        }else{
+            content.replace.clone()
+        };
+
+        propagate_case(self.trigger(), typed_trigger, &expanded)
+    }
+
+    /// Count the characters actually inserted by the replacement, excluding the $|$ cursor
+    /// hint, the {{key:NAME}} tokens (pressed instead of typed) and the variable tokens,
+    /// whose value is only known when the match is expanded. Image matches insert no text.
+    pub fn visible_length(&self) -> usize {
+        let content = match &self.content {
+            MatchContentType::Text(content) => content,
+            MatchContentType::Image(_) => return 0,
+        };
+
+        Match::rendered_length(&VAR_REGEX.replace_all(&content.replace, ""))
+    }
+
+    /// Count the characters inserted by an already rendered replacement, with the same rules
+    /// as visible_length. The engine uses it for all the cursor, selection and clipboard
+    /// threshold math, as there the variables count with their rendered value.
+    pub fn rendered_length(rendered: &str) -> usize {
+        let text = rendered.replace("$|$", "");
+        let text = KEY_TOKEN_REGEX.replace_all(&text, |caps: &Captures| {
+            match KeyCode::from_name(&caps["name"]) {
+                Some(_) => String::new(),
+                None => caps[0].to_owned(),  // Unknown keys are typed as text
+            }
+        });
+
+        text.chars().count()
+    }
+
+    /// Check the fields that would otherwise only fail when the match is expanded,
+    /// returning a description of each invalid one.
+    pub fn validate(&self) -> Vec<String> {
+        let vars = match &self.content {
+            MatchContentType::Text(content) => content.vars.as_slice(),
+            MatchContentType::Image(_) => &[],
+        };
+
+        vars.iter().flat_map(MatchVariable::validate)
+            .map(|error| format!("match '{}': {}", self.trigger(), error))
+            .collect()
+    }
+
+    /// Apply the trim_trailing_newline and ensure_trailing_newline options to the rendered replacement.
+    pub fn adjust_trailing_newline(&self, mut text: String) -> String {
+        if self.trim_trailing_newline {
+            if text.ends_with("\r\n") {
+                text.truncate(text.len() - 2);
+            }else if text.ends_with('\n') {
+                text.pop();
+            }
+        }else if self.ensure_trailing_newline && !text.ends_with('\n') {
+            text.push('\n');
+        }
+
+        text
+    }
+
+    /// Resolve the 'replace_file' path, if any, against the directory of the config
+    /// file defining the match. If the file has to be read at startup, its contents
+    /// become the replacement text. The resolved path is returned in both cases.
+    pub fn load_replace_file(&mut self, config_dir: &Path) -> Option<PathBuf> {
+        if let MatchContentType::Text(content) = &mut self.content {
+            if let Some(replace_file) = content.replace_file.take() {
+                let path = config_dir.join(replace_file);
+
+                if content.replace_file_load == FileLoadMode::Startup {
+                    match fs::read_to_string(&path) {
+                        Ok(replace) => {
+                            content.set_replace(replace);
+                            return Some(path);
+                        },
+                        Err(e) => {
+                            warn!("Unable to read replace file {:?} of match '{}', it will be read when triggered: {}",
+                                  path, self.triggers.first().map_or("", String::as_str), e);
+                        },
+                    }
+                }
+
+                content.replace_file = Some(path.clone());
+                return Some(path);
+            }
+        }
+
+        None
+    }
+}
+
+impl TextContent {
+    pub fn set_replace(&mut self, replace: String) {
+        self._has_vars = VAR_REGEX.is_match(&replace) || has_conditionals(&replace);
+        self.replace = replace;
+    }
+}
+
+/// Apply the case of the typed trigger to the expansion, if it differs from the original trigger
+/// only by case. An uppercase trigger makes the whole expansion uppercase, while a capitalized
+/// one only capitalizes its first letter.
+fn propagate_case(trigger: &str, typed_trigger: &str, expanded: &str) -> String {
+    if trigger == typed_trigger || trigger.to_lowercase() != typed_trigger.to_lowercase() {
+        return expanded.to_owned();
+    }
+
+    let typed_letters: Vec<char> = typed_trigger.chars().filter(|c| c.is_alphabetic()).collect();
+    let trigger_letters: Vec<char> = trigger.chars().filter(|c| c.is_alphabetic()).collect();
+
+    if typed_letters.len() > 1 && typed_letters.iter().all(|c| c.is_uppercase()) {
+        expanded.to_uppercase()
+    }else if typed_letters.first().map_or(false, |c| c.is_uppercase())
+        && trigger_letters.first().map_or(false, |c| c.is_lowercase()) {
+        match expanded.char_indices().find(|(_, c)| c.is_alphabetic()) {
+            Some((index, c)) => {
+                let mut result = expanded[..index].to_owned();
+                result.extend(c.to_uppercase());
+                result.push_str(&expanded[index + c.len_utf8()..]);
+                result
+            },
+            None => expanded.to_owned(),
+        }
+    }else{
+        expanded.to_owned()
+    }
+}
+
 /// Used to deserialize the Match struct before applying some custom elaboration.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct AutoMatch {
-    pub trigger: String,
+    #[serde(default = "default_trigger", skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<String>,
+
+    // Alternative to 'trigger', used when the match can be fired by many triggers
+    #[serde(default = "default_triggers", skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<String>,
 
     #[serde(default = "default_replace")]
     pub replace: Option<String>,
 
+    #[serde(default = "default_replace_file")]
+    pub replace_file: Option<String>,
+
+    #[serde(default)]
+    pub replace_file_load: FileLoadMode,
+
     #[serde(default = "default_image_path")]
     pub image_path: Option<String>,
 
@@ -160,15 +749,151 @@ struct AutoMatch {
 
     #[serde(default = "default_passive_only")]
     pub passive_only: bool,
+
+    #[serde(default = "default_keep_trigger")]
+    pub keep_trigger: bool,
+
+    #[serde(default = "default_select_after")]
+    pub select_after: bool,
+
+    #[serde(default = "default_press_enter_after")]
+    pub press_enter_after: bool,
+
+    #[serde(default = "default_at_line_start")]
+    pub at_line_start: bool,
+
+    #[serde(default = "default_apps")]
+    pub apps: Vec<String>,
+
+    #[serde(default = "default_cursor")]
+    pub cursor: Option<CursorPosition>,
+
+    #[serde(default = "default_hotkey")]
+    pub hotkey: Option<String>,
+
+    #[serde(default = "default_cooldown_ms")]
+    pub cooldown_ms: u64,
+
+    #[serde(default = "default_once")]
+    pub once: bool,
+
+    #[serde(default = "default_min_char_interval_ms")]
+    pub min_char_interval_ms: u64,
+
+    #[serde(default = "default_backend", skip_serializing_if = "Option::is_none")]
+    pub backend: Option<BackendType>,
+
+    #[serde(default = "default_trim_trailing_newline")]
+    pub trim_trailing_newline: bool,
+
+    #[serde(default = "default_ensure_trailing_newline")]
+    pub ensure_trailing_newline: bool,
+
+    #[serde(default = "default_description", skip_serializing_if = "String::is_empty")]
+    pub description: String,
+
+    #[serde(default = "default_tags", skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    #[serde(default = "default_label", skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    #[serde(default = "default_source_file", skip_serializing_if = "Option::is_none")]
+    pub source_file: Option<PathBuf>,
+
+    #[serde(default = "default_os", skip_serializing_if = "Vec::is_empty")]
+    pub os: Vec<String>,
+
+    #[serde(default)]
+    pub merge: MergeStrategy,
 }
 
+fn default_trigger() -> Option<String> {None}
+fn default_triggers() -> Vec<String> {Vec::new()}
 fn default_vars() -> Vec<MatchVariable> {Vec::new()}
 fn default_word() -> bool {false}
 fn default_passive_only() -> bool {false}
+fn default_keep_trigger() -> bool {false}
+fn default_select_after() -> bool {false}
+fn default_press_enter_after() -> bool {false}
+fn default_at_line_start() -> bool {false}
+fn default_apps() -> Vec<String> {Vec::new()}
+fn default_cursor() -> Option<CursorPosition> {None}
+fn default_hotkey() -> Option<String> {None}
+fn default_cooldown_ms() -> u64 {0}
+fn default_once() -> bool {false}
+fn default_min_char_interval_ms() -> u64 {0}
+fn default_backend() -> Option<BackendType> {None}
+fn default_trim_trailing_newline() -> bool {false}
+fn default_ensure_trailing_newline() -> bool {false}
+fn default_description() -> String {String::new()}
+fn default_tags() -> Vec<String> {Vec::new()}
+fn default_label() -> Option<String> {None}
+fn default_source_file() -> Option<PathBuf> {None}
+fn default_os() -> Vec<String> {Vec::new()}
 fn default_replace() -> Option<String> {None}
 fn default_image_path() -> Option<String> {None}
+fn default_replace_file() -> Option<String> {None}
+
+impl AutoMatch {
+    fn first_trigger(&self) -> &str {
+        match self.triggers.first() {
+            Some(trigger) => trigger,
+            None => self.trigger.as_ref().map(String::as_str).unwrap_or_default(),
+        }
+    }
+}
 
+/// Set of matches sharing the same options. Each option defined in the group
+/// is inherited by all its matches, unless they define it themselves.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MatchGroup {
+    #[serde(default)]
+    pub word: Option<bool>,
+
+    #[serde(default)]
+    pub passive_only: Option<bool>,
+
+    #[serde(default)]
+    pub keep_trigger: Option<bool>,
+
+    #[serde(default)]
+    pub select_after: Option<bool>,
+
+    #[serde(default)]
+    pub at_line_start: Option<bool>,
+
+    #[serde(default)]
+    pub matches: Vec<Mapping>,
+}
+
+impl MatchGroup {
+    /// Build the matches of the group, applying the group options to them.
+    pub fn into_matches(self) -> Result<Vec<Match>, serde_yaml::Error> {
+        let options = [
+            ("word", self.word),
+            ("passive_only", self.passive_only),
+            ("keep_trigger", self.keep_trigger),
+            ("select_after", self.select_after),
+            ("at_line_start", self.at_line_start),
+        ];
+
+        self.matches.into_iter().map(|mut match_map| {
+            for (name, value) in options.iter() {
+                if let Some(value) = value {
+                    let key = Value::from(*name);
+                    if !match_map.contains_key(&key) {
+                        match_map.insert(key, Value::from(*value));
+                    }
+                }
+            }
+
+            serde_yaml::from_value(Value::Mapping(match_map))
+        }).collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct MatchVariable {
     pub name: String,
 
@@ -178,28 +903,80 @@ pub struct MatchVariable {
     pub params: Mapping,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+impl MatchVariable {
+    /// Check the params that can be validated without evaluating the variable,
+    /// returning a description of each invalid one.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.var_type == "date" {
+            if let Some(format) = self.params.get(&Value::from("format")) {
+                match format.as_str() {
+                    Some(format) if crate::extension::date::is_valid_format(format) => {},
+                    Some(format) => errors.push(format!("invalid date format '{}' in variable '{}'", format, self.name)),
+                    None => errors.push(format!("the date format in variable '{}' must be a string", self.name)),
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub enum TriggerEntry {
     Char(char),
     WordSeparator
 }
 
+/// Source of the current time, replaced in tests to simulate the passing of time.
+pub trait Clock {
+    fn now(&self) -> SystemTime;
+}
+
+pub struct SystemClock {}
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
 pub trait MatchReceiver {
-    fn on_match(&self, m: &Match, trailing_separator: Option<char>);
+    // The trigger offset is the position of the typed trigger in m.triggers()
+    fn on_match(&self, m: &Match, trailing_separator: Option<char>, trigger_offset: usize);
+
+    // Called instead of on_match when the trigger was preceded by the escape char
+    fn on_escaped_match(&self, m: &Match, trailing_separator: Option<char>, trigger_offset: usize);
+
+    // Called when the hotkey of the match is pressed, there is no trigger to delete
+    fn on_hotkey_match(&self, m: &Match);
+
     fn on_enable_update(&self, status: bool);
     fn on_passive(&self);
+
+    // True if a password field is focused, in which case the typed text must not be logged
+    fn is_password_field_focused(&self) -> bool {
+        false
+    }
 }
 
 pub trait Matcher : KeyEventReceiver {
     fn handle_char(&self, c: &str);
     fn handle_modifier(&self, m: KeyModifier);
+
+    // Check if the given char, typed while holding the given modifiers, fires the hotkey
+    // of a match. In that case, the char is not part of a trigger.
+    fn handle_hotkey(&self, c: &str, modifiers: &KeyModifierSet) -> bool;
 }
 
 impl <M: Matcher> KeyEventReceiver for M {
     fn on_key_event(&self, e: KeyEvent) {
         match e {
-            KeyEvent::Char(c) => {
-                self.handle_char(&c);
+            KeyEvent::Char(c, modifiers) => {
+                if !self.handle_hotkey(&c, &modifiers) {
+                    self.handle_char(&c);
+                }
             },
             KeyEvent::Modifier(m) => {
                 self.handle_modifier(m);
@@ -215,6 +992,201 @@ impl <M: Matcher> KeyEventReceiver for M {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_hotkey_parse() {
+        let hotkey = Hotkey::parse("CTRL+SHIFT+S").unwrap();
+        assert_eq!(hotkey.modifiers, KeyModifierSet::new(vec![KeyModifier::CTRL, KeyModifier::SHIFT]));
+        assert_eq!(hotkey.key, 's');
+
+        let hotkey = Hotkey::parse("cmd + alt + 1").unwrap();
+        assert_eq!(hotkey.modifiers, KeyModifierSet::new(vec![KeyModifier::META, KeyModifier::ALT]));
+        assert_eq!(hotkey.key, '1');
+    }
+
+    #[test]
+    fn test_hotkey_parse_invalid() {
+        assert!(Hotkey::parse("S").is_err());
+        assert!(Hotkey::parse("CTRL+SHIFT").is_err());
+        assert!(Hotkey::parse("CTRL+S+T").is_err());
+        assert!(Hotkey::parse("CTRL+ENTER").is_err());
+        assert!(Hotkey::parse("CTRL+FOO").is_err());
+        assert!(Hotkey::parse("SHIFT+S").is_err());
+    }
+
+    #[test]
+    fn test_hotkey_is_fired_by_same_modifiers_only() {
+        let hotkey = Hotkey::parse("CTRL+SHIFT+S").unwrap();
+        let pressed = |modifiers| KeyModifierSet::new(modifiers);
+        assert!(hotkey.is_fired_by(&pressed(vec![KeyModifier::SHIFT, KeyModifier::CTRL]), 'S'));
+        assert!(!hotkey.is_fired_by(&pressed(vec![KeyModifier::CTRL]), 's'));
+        assert!(!hotkey.is_fired_by(&pressed(vec![KeyModifier::CTRL, KeyModifier::SHIFT, KeyModifier::ALT]), 's'));
+        assert!(!hotkey.is_fired_by(&pressed(vec![KeyModifier::CTRL, KeyModifier::SHIFT]), 'd'));
+    }
+
+    #[test]
+    fn test_match_hotkey_round_trip() {
+        let m: Match = serde_yaml::from_str(r###"
+        trigger: ":sig"
+        replace: "Best regards"
+        hotkey: "ctrl+alt+s"
+        "###).unwrap();
+        assert_eq!(m.hotkey, Some(Hotkey::parse("CTRL+ALT+S").unwrap()));
+
+        let deserialized: Match = serde_yaml::from_str(&serde_yaml::to_string(&m).unwrap()).unwrap();
+        assert_eq!(deserialized.hotkey, m.hotkey);
+    }
+
+    #[test]
+    fn test_match_invalid_hotkey_is_rejected() {
+        let result = serde_yaml::from_str::<Match>(r###"
+        trigger: ":sig"
+        replace: "Best regards"
+        hotkey: "ctrl"
+        "###);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_match_hotkey_only() {
+        let missing: Match = serde_yaml::from_str(r###"
+        replace: "Best regards"
+        hotkey: "ctrl+alt+s"
+        "###).unwrap();
+        assert!(missing.triggers().is_empty());
+        assert!(missing._trigger_sequences.is_empty());
+        assert_eq!(missing.trigger(), "");
+        assert_eq!(missing.fire_key(), "CTRL+ALT+S");
+
+        let empty: Match = serde_yaml::from_str(r###"
+        trigger: ""
+        word: true
+        replace: "Best regards"
+        hotkey: "ctrl+alt+s"
+        "###).unwrap();
+        assert!(empty.triggers().is_empty());
+        assert!(empty._trigger_sequences.is_empty());
+
+        let deserialized: Match = serde_yaml::from_str(&serde_yaml::to_string(&missing).unwrap()).unwrap();
+        assert!(deserialized.triggers().is_empty());
+        assert_eq!(deserialized.hotkey, missing.hotkey);
+    }
+
+    #[test]
+    fn test_match_without_trigger_and_hotkey_fails() {
+        let result = serde_yaml::from_str::<Match>(r###"
+        replace: "Best regards"
+        "###);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_match_is_available_on() {
+        let any_os: Match = serde_yaml::from_str("trigger: \":a\"\nreplace: \"a\"").unwrap();
+        assert!(any_os.is_available_on("linux"));
+        assert!(any_os.is_available_on("windows"));
+
+        let desktop: Match = serde_yaml::from_str(r###"
+        trigger: ":path"
+        replace: "C:\\Users"
+        os: [windows, macos]
+        "###).unwrap();
+        assert!(desktop.is_available_on("windows"));
+        assert!(desktop.is_available_on("macos"));
+        assert!(!desktop.is_available_on("linux"));
+    }
+
+    #[test]
+    fn test_match_unknown_os_is_rejected() {
+        let result = serde_yaml::from_str::<Match>(r###"
+        trigger: ":path"
+        replace: "/home"
+        os: [bsd]
+        "###);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_match_metadata_round_trip() {
+        let _match: Match = serde_yaml::from_str(r###"
+        trigger: ":sig"
+        replace: "Best regards"
+        description: "Email signature"
+        tags: ["email", "work"]
+        label: signature
+        "###).unwrap();
+        assert_eq!(_match.description, "Email signature");
+        assert_eq!(_match.label, Some("signature".to_owned()));
+        assert!(_match.has_tag("work"));
+
+        let serialized = serde_yaml::to_string(&_match).unwrap();
+        let deserialized: Match = serde_yaml::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.description, "Email signature");
+        assert_eq!(deserialized.tags, vec!["email".to_owned(), "work".to_owned()]);
+        assert_eq!(deserialized.label, Some("signature".to_owned()));
+
+        // Matches without metadata don't serialize it
+        let plain: Match = serde_yaml::from_str("trigger: \":hi\"\nreplace: \"Hello\"").unwrap();
+        let serialized = serde_yaml::to_string(&plain).unwrap();
+        assert!(!serialized.contains("description") && !serialized.contains("tags") && !serialized.contains("label"));
+    }
+
+    #[test]
+    fn test_match_trailing_newline_options_conflict() {
+        let result = serde_yaml::from_str::<Match>(r###"
+        trigger: ":test"
+        replace: "hello"
+        trim_trailing_newline: true
+        ensure_trailing_newline: true
+        "###);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_match_adjust_trailing_newline() {
+        let trim: Match = serde_yaml::from_str(r###"
+        trigger: ":test"
+        replace: "hello"
+        trim_trailing_newline: true
+        "###).unwrap();
+        assert_eq!(trim.adjust_trailing_newline("hello\n\n".to_owned()), "hello\n");
+        assert_eq!(trim.adjust_trailing_newline("hello\r\n".to_owned()), "hello");
+        assert_eq!(trim.adjust_trailing_newline("hello".to_owned()), "hello");
+
+        let ensure: Match = serde_yaml::from_str(r###"
+        trigger: ":test"
+        replace: "hello"
+        ensure_trailing_newline: true
+        "###).unwrap();
+        assert_eq!(ensure.adjust_trailing_newline("hello".to_owned()), "hello\n");
+        assert_eq!(ensure.adjust_trailing_newline("hello\n".to_owned()), "hello\n");
+    }
+
+    #[test]
+    fn test_match_unknown_filter_is_rejected() {
+        let result = serde_yaml::from_str::<Match>(r###"
+        trigger: ":test"
+        replace: "{{clipboard|shout}}"
+        "###);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_match_has_vars_with_filters_should_be_true() {
+        let _match : Match = serde_yaml::from_str(r###"
+        trigger: ":test"
+        replace: "{{clipboard | trim | upper}}"
+        "###).unwrap();
+
+        match _match.content {
+            MatchContentType::Text(content) => {
+                assert_eq!(content._has_vars, true);
+            },
+            _ => {
+                assert!(false);
+            },
+        }
+    }
+
     #[test]
     fn test_match_has_vars_should_be_false() {
         let match_str = r###"
@@ -281,10 +1253,10 @@ mod tests {
 
         let _match : Match = serde_yaml::from_str(match_str).unwrap();
 
-        assert_eq!(_match._trigger_sequence[0], TriggerEntry::Char('t'));
-        assert_eq!(_match._trigger_sequence[1], TriggerEntry::Char('e'));
-        assert_eq!(_match._trigger_sequence[2], TriggerEntry::Char('s'));
-        assert_eq!(_match._trigger_sequence[3], TriggerEntry::Char('t'));
+        assert_eq!(_match._trigger_sequences[0][0], TriggerEntry::Char('t'));
+        assert_eq!(_match._trigger_sequences[0][1], TriggerEntry::Char('e'));
+        assert_eq!(_match._trigger_sequences[0][2], TriggerEntry::Char('s'));
+        assert_eq!(_match._trigger_sequences[0][3], TriggerEntry::Char('t'));
     }
 
     #[test]
@@ -297,11 +1269,64 @@ mod tests {
 
         let _match : Match = serde_yaml::from_str(match_str).unwrap();
 
-        assert_eq!(_match._trigger_sequence[0], TriggerEntry::Char('t'));
-        assert_eq!(_match._trigger_sequence[1], TriggerEntry::Char('e'));
-        assert_eq!(_match._trigger_sequence[2], TriggerEntry::Char('s'));
-        assert_eq!(_match._trigger_sequence[3], TriggerEntry::Char('t'));
-        assert_eq!(_match._trigger_sequence[4], TriggerEntry::WordSeparator);
+        assert_eq!(_match._trigger_sequences[0][0], TriggerEntry::Char('t'));
+        assert_eq!(_match._trigger_sequences[0][1], TriggerEntry::Char('e'));
+        assert_eq!(_match._trigger_sequences[0][2], TriggerEntry::Char('s'));
+        assert_eq!(_match._trigger_sequences[0][3], TriggerEntry::Char('t'));
+        assert_eq!(_match._trigger_sequences[0][4], TriggerEntry::WordSeparator);
+    }
+
+    #[test]
+    fn test_match_trigger_and_triggers_forms_are_equivalent() {
+        let single: Match = serde_yaml::from_str(r###"
+        trigger: ":sig"
+        replace: "Best regards"
+        "###).unwrap();
+        let list: Match = serde_yaml::from_str(r###"
+        triggers: [":sig"]
+        replace: "Best regards"
+        "###).unwrap();
+
+        assert_eq!(single.triggers(), list.triggers());
+        assert_eq!(single.triggers(), &[":sig".to_owned()]);
+        assert_eq!(single._trigger_sequences, list._trigger_sequences);
+    }
+
+    #[test]
+    fn test_match_multiple_triggers() {
+        let _match: Match = serde_yaml::from_str(r###"
+        triggers: [":sig", ":regards"]
+        replace: "Best regards"
+        word: true
+        "###).unwrap();
+
+        assert_eq!(_match.trigger(), ":sig");
+        assert_eq!(_match.triggers(), &[":sig".to_owned(), ":regards".to_owned()]);
+        assert_eq!(_match._trigger_sequences.len(), 2);
+        assert_eq!(_match._trigger_sequences[1][1], TriggerEntry::Char('r'));
+        assert_eq!(_match._trigger_sequences[1].last(), Some(&TriggerEntry::WordSeparator));
+    }
+
+    #[test]
+    fn test_match_trigger_and_triggers_both_defined_fail() {
+        let result = serde_yaml::from_str::<Match>(r###"
+        trigger: ":sig"
+        triggers: [":regards"]
+        replace: "Best regards"
+        "###);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_match_multiple_triggers_serialization_round_trip() {
+        let _match: Match = serde_yaml::from_str(r###"
+        triggers: [":sig", ":regards"]
+        replace: "Best regards"
+        "###).unwrap();
+
+        let deserialized: Match = serde_yaml::from_str(&serde_yaml::to_string(&_match).unwrap()).unwrap();
+        assert_eq!(deserialized.triggers(), _match.triggers());
     }
 
     #[test]
@@ -322,4 +1347,264 @@ mod tests {
             },
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_match_serialization_round_trip() {
+        let match_str = r###"
+        trigger: "test"
+        replace: "This is a {{var}}"
+        word: true
+        vars:
+            - name: var
+              type: date
+              params:
+                format: "%H:%M"
+        "###;
+
+        let _match : Match = serde_yaml::from_str(match_str).unwrap();
+        let serialized = serde_json::to_string(&_match).unwrap();
+        let deserialized : Match = serde_json::from_str(&serialized).unwrap();
+
+        assert_eq!(deserialized.trigger(), "test");
+        assert_eq!(deserialized.word, true);
+        assert_eq!(deserialized._trigger_sequences, _match._trigger_sequences);
+        match deserialized.content {
+            MatchContentType::Text(content) => {
+                assert_eq!(content.replace, "This is a {{var}}");
+                assert_eq!(content.vars.len(), 1);
+                assert_eq!(content._has_vars, true);
+            },
+            _ => {
+                assert!(false);
+            },
+        }
+    }
+
+    fn parse_match(match_str: &str) -> Match {
+        serde_yaml::from_str(match_str).unwrap()
+    }
+
+    #[test]
+    fn test_match_expand_plain_replacement() {
+        let _match = parse_match(r###"
+        trigger: ":hi"
+        replace: "Hello, $|$world"
+        "###);
+
+        assert_eq!(_match.expand(":hi"), "Hello, world");
+    }
+
+    #[test]
+    fn test_match_expand_propagates_case() {
+        let _match = parse_match(r###"
+        trigger: "alh"
+        replace: "although"
+        "###);
+
+        assert_eq!(_match.expand("alh"), "although");
+        assert_eq!(_match.expand("Alh"), "Although");
+        assert_eq!(_match.expand("ALH"), "ALTHOUGH");
+
+        // A typed trigger that doesn't correspond leaves the expansion untouched
+        assert_eq!(_match.expand("other"), "although");
+    }
+
+    #[test]
+    fn test_match_expand_substitutes_variables() {
+        let _match = parse_match(r###"
+        trigger: ":greet"
+        replace: "{{greeting}}, it's {{time}}"
+        vars:
+            - name: greeting
+              type: dummy
+              params:
+                echo: "Hello"
+            - name: time
+              type: date
+              params:
+                format: "%H:%M"
+        "###);
+
+        assert_eq!(_match.expand(":greet"), "Hello, it's {{time}}");
+    }
+
+    #[test]
+    fn test_match_expand_substitutes_typed_trigger() {
+        let _match = parse_match(r###"
+        trigger: ":todo"
+        replace: "TODO({{trigger}}): "
+        "###);
+
+        assert_eq!(_match.expand(":todo"), "TODO(:todo): ");
+    }
+
+    #[test]
+    fn test_match_visible_length_plain_text() {
+        let _match = parse_match(r###"
+        trigger: ":hi"
+        replace: "Hello world"
+        "###);
+
+        assert_eq!(_match.visible_length(), 11);
+    }
+
+    #[test]
+    fn test_match_visible_length_counts_chars_not_bytes() {
+        let _match = parse_match(r###"
+        trigger: ":hi"
+        replace: "Ciao è 😀"
+        "###);
+
+        assert_eq!(_match.visible_length(), 8);
+    }
+
+    #[test]
+    fn test_match_visible_length_excludes_cursor_hint() {
+        let _match = parse_match(r###"
+        trigger: ":hi"
+        replace: "Hello $|$world"
+        "###);
+
+        assert_eq!(_match.visible_length(), 11);
+    }
+
+    #[test]
+    fn test_match_visible_length_excludes_key_tokens() {
+        let _match = parse_match(r###"
+        trigger: ":hi"
+        replace: "Name{{key:TAB}}Surname{{ key:ENTER }}"
+        "###);
+
+        assert_eq!(_match.visible_length(), 11);
+    }
+
+    #[test]
+    fn test_match_visible_length_keeps_unknown_key_tokens() {
+        let _match = parse_match(r###"
+        trigger: ":hi"
+        replace: "a{{key:FOO}}"
+        "###);
+
+        assert_eq!(_match.visible_length(), 12);
+    }
+
+    #[test]
+    fn test_match_visible_length_excludes_variables() {
+        let _match = parse_match(r###"
+        trigger: ":hi"
+        replace: "Today is {{date}}, {{ name | upper }}!"
+        vars:
+            - name: date
+              type: date
+              params:
+                format: "%x"
+        "###);
+
+        assert_eq!(_match.visible_length(), 12);
+    }
+
+    #[test]
+    fn test_match_group_options_are_inherited() {
+        let group: MatchGroup = serde_yaml::from_str(r###"
+        word: true
+        keep_trigger: true
+        matches:
+            - trigger: "hi"
+              replace: "hello"
+            - trigger: "bye"
+              replace: "goodbye"
+        "###).unwrap();
+
+        let matches = group.into_matches().unwrap();
+        assert_eq!(matches.len(), 2);
+        assert!(matches.iter().all(|m| m.word && m.keep_trigger));
+        assert!(matches.iter().all(|m| !m.passive_only && !m.select_after));
+        assert_eq!(matches[0]._trigger_sequences[0].last(), Some(&TriggerEntry::WordSeparator));
+    }
+
+    #[test]
+    fn test_match_group_options_are_overridden_by_matches() {
+        let group: MatchGroup = serde_yaml::from_str(r###"
+        word: true
+        matches:
+            - trigger: "hi"
+              replace: "hello"
+              word: false
+            - trigger: "bye"
+              replace: "goodbye"
+        "###).unwrap();
+
+        let matches = group.into_matches().unwrap();
+        assert!(!matches[0].word);
+        assert!(matches[1].word);
+    }
+
+    #[test]
+    fn test_match_with_replace_and_replace_file_fails() {
+        let match_str = r###"
+        trigger: ":legal"
+        replace: "text"
+        replace_file: "disclaimer.txt"
+        "###;
+
+        assert!(serde_yaml::from_str::<Match>(match_str).is_err());
+    }
+
+    #[test]
+    fn test_match_replace_file_round_trip() {
+        let _match = parse_match(r###"
+        trigger: ":legal"
+        replace_file: "disclaimer.txt"
+        replace_file_load: startup
+        "###);
+
+        let deserialized: Match = serde_json::from_str(&serde_json::to_string(&_match).unwrap()).unwrap();
+        match deserialized.content {
+            MatchContentType::Text(content) => {
+                assert_eq!(content.replace_file, Some(PathBuf::from("disclaimer.txt")));
+                assert_eq!(content.replace_file_load, FileLoadMode::Startup);
+            },
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_match_is_active_in_app() {
+        let _match = parse_match(r###"
+        trigger: ":sig"
+        replace: "text"
+        apps: ["Code", "slack"]
+        "###);
+
+        assert!(_match.is_active_in_app(Some("code"), None));
+        assert!(_match.is_active_in_app(None, Some("C:\\Program Files\\Slack\\slack.exe")));
+        assert!(!_match.is_active_in_app(Some("Firefox"), Some("/usr/bin/firefox")));
+        assert!(!_match.is_active_in_app(None, None));
+    }
+
+    #[test]
+    fn test_match_is_active_in_app_compares_the_exec_name() {
+        let _match = parse_match(r###"
+        trigger: ":sig"
+        replace: "text"
+        apps: ["code", "slack.exe"]
+        "###);
+
+        assert!(_match.is_active_in_app(None, Some("/usr/share/code/code")));
+        assert!(_match.is_active_in_app(None, Some("C:\\Program Files\\Slack\\Slack.exe")));
+        assert!(!_match.is_active_in_app(None, Some("/usr/bin/vscode-helper")));
+        assert!(!_match.is_active_in_app(None, Some("/opt/code/bin/editor")));
+    }
+
+    #[test]
+    fn test_match_without_apps_is_global() {
+        let _match = parse_match(r###"
+        trigger: ":sig"
+        replace: "text"
+        "###);
+
+        assert!(_match.apps.is_empty());
+        assert!(_match.is_active_in_app(None, None));
+        assert!(_match.is_active_in_app(Some("Firefox"), Some("/usr/bin/firefox")));
+    }
+}
@@ -17,29 +17,43 @@
  * along with espanso.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::matcher::{Match, MatchReceiver, TriggerEntry};
+use crate::matcher::{Match, MatchReceiver, TriggerEntry, Clock, SystemClock};
+use crate::matcher::index::{MatchIndex, ROOT_NODE};
 use std::cell::{RefCell, Ref};
-use crate::event::{KeyModifier, ActionEventReceiver, ActionType};
-use crate::config::ConfigManager;
+use crate::event::{KeyModifier, KeyModifierSet, ActionEventReceiver, ActionType};
+use crate::config::{ConfigManager, Configs, ToggleMode};
 use crate::event::KeyModifier::BACKSPACE;
-use std::time::SystemTime;
-use std::collections::VecDeque;
+use std::time::{SystemTime, Duration};
+use log::{trace, log_enabled, Level};
+use std::collections::{VecDeque, HashMap};
 
 pub struct ScrollingMatcher<'a, R: MatchReceiver, M: ConfigManager<'a>> {
     config_manager: &'a M,
     receiver: &'a R,
-    current_set_queue: RefCell<VecDeque<Vec<MatchEntry<'a>>>>,
+    current_set_queue: RefCell<VecDeque<Vec<MatchEntry>>>,
+    current_config: RefCell<(String, usize)>,  // Name and generation of the config the partial matches refer to
     toggle_press_time: RefCell<SystemTime>,
     passive_press_time: RefCell<SystemTime>,
     is_enabled: RefCell<bool>,
     was_previous_char_word_separator: RefCell<bool>,
+    was_previous_char_newline: RefCell<bool>,
+    was_previous_char_escape: RefCell<bool>,
+    recent_chars: RefCell<String>,
+    typed_buffer: RefCell<String>,  // Chars typed since the last word separator, see current_buffer
+    last_fire_times: RefCell<HashMap<String, SystemTime>>,  // Only for the matches with a cooldown
+    clock: Box<dyn Clock>,
 }
 
+// Partial match in progress, represented by the reached node of the active config index
 #[derive(Clone)]
-struct MatchEntry<'a> {
-    start: usize,
-    count: usize,
-    _match: &'a Match
+struct MatchEntry {
+    node: usize,
+    depth: usize,
+    after_word_separator: bool,
+    at_line_start: bool,
+    after_escape: bool,
+    last_char_time: SystemTime,
+    min_char_interval: Option<Duration>,  // Shortest time between two chars of the partial match
 }
 
 impl <'a, R: MatchReceiver, M: ConfigManager<'a>> ScrollingMatcher<'a, R, M> {
@@ -52,10 +66,106 @@ impl <'a, R: MatchReceiver, M: ConfigManager<'a>> ScrollingMatcher<'a, R, M> {
             config_manager,
             receiver,
             current_set_queue,
+            current_config: RefCell::new((String::new(), 0)),
             toggle_press_time,
             passive_press_time,
             is_enabled: RefCell::new(true),
             was_previous_char_word_separator: RefCell::new(true),
+            was_previous_char_newline: RefCell::new(true),
+            was_previous_char_escape: RefCell::new(false),
+            recent_chars: RefCell::new(String::new()),
+            typed_buffer: RefCell::new(String::new()),
+            last_fire_times: RefCell::new(HashMap::new()),
+            clock: Box::new(SystemClock {}),
+        }
+    }
+
+    #[cfg(test)]
+    fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    // A match is disabled at runtime if any of its triggers is
+    fn is_disabled(&self, m: &Match) -> bool {
+        m.triggers().iter().any(|trigger| self.config_manager.is_trigger_disabled(trigger))
+    }
+
+    // Check if the match can only fire once and it already did
+    fn is_spent(&self, m: &Match) -> bool {
+        m.once && self.config_manager.is_trigger_spent(&m.fire_key())
+    }
+
+    // Check if the match fired less than its cooldown ago
+    fn is_in_cooldown(&self, m: &Match) -> bool {
+        if m.cooldown_ms == 0 {
+            return false;
+        }
+
+        match self.last_fire_times.borrow().get(&m.fire_key()) {
+            Some(last_fire_time) => {
+                self.clock.now().duration_since(*last_fire_time)
+                    .map_or(false, |elapsed| elapsed.as_millis() < u128::from(m.cooldown_ms))
+            },
+            None => false,
+        }
+    }
+
+    // Check if the trigger was typed faster than the minimum interval of the match
+    fn is_typed_too_fast(m: &Match, entry: &MatchEntry) -> bool {
+        if m.min_char_interval_ms == 0 {
+            return false;
+        }
+
+        entry.min_char_interval
+            .map_or(false, |interval| interval.as_millis() < u128::from(m.min_char_interval_ms))
+    }
+
+    fn record_fire(&self, m: &Match) {
+        if m.cooldown_ms > 0 {
+            self.last_fire_times.borrow_mut().insert(m.fire_key(), self.clock.now());
+        }
+
+        if m.once {
+            self.config_manager.mark_trigger_spent(&m.fire_key());
+        }
+    }
+
+    /// Return the chars typed since the last word separator, which the current partial matches
+    /// refer to. It's meant for debugging, for example to understand why a trigger didn't fire.
+    pub fn current_buffer(&self) -> String {
+        self.typed_buffer.borrow().clone()
+    }
+
+    /// Return the number of typed chars kept in the buffer, enough for both the longest
+    /// trigger and the 'backspace_limit', but never more than the 'max_buffer_size'.
+    pub fn buffer_size(&self) -> usize {
+        let active_config = self.config_manager.active_config();
+        compute_buffer_size(&active_config.match_index, &self.config_manager.default_config())
+    }
+
+    /*
+     * Keep track of the typed chars for debugging purposes. The buffer is reset on word separators
+     * and bounded by the buffer size, independently of how long the user types without separators.
+     */
+    fn update_buffer(&self, c: &str, is_word_separator: bool, max_len: usize) {
+        {
+            let mut typed_buffer = self.typed_buffer.borrow_mut();
+
+            if is_word_separator {
+                typed_buffer.clear();
+            }else{
+                typed_buffer.push_str(c);
+                let char_count = typed_buffer.chars().count();
+                if char_count > max_len {
+                    let trimmed: String = typed_buffer.chars().skip(char_count - max_len).collect();
+                    *typed_buffer = trimmed;
+                }
+            }
+        }
+
+        // Only the length is logged, as the typed text may contain passwords
+        if log_enabled!(Level::Trace) && !self.receiver.is_password_field_focused() {
+            trace!("typed buffer: {} chars of {}", self.current_buffer().chars().count(), self.buffer_size());
         }
     }
 
@@ -73,16 +183,31 @@ impl <'a, R: MatchReceiver, M: ConfigManager<'a>> ScrollingMatcher<'a, R, M> {
         self.receiver.on_enable_update(*is_enabled);
     }
 
-    fn is_matching(mtc: &Match, current_char: &str, start: usize, is_current_word_separator: bool) -> bool {
-        match mtc._trigger_sequence[start] {
-            TriggerEntry::Char(c) => {
-                current_char.starts_with(c)
-            },
-            TriggerEntry::WordSeparator => {
-                is_current_word_separator
-            },
+    /*
+     * Check if the last typed chars, ending with the current one, form one of the
+     * multi-character word separators. Only the minimum amount of chars needed to
+     * recognize the longest separator is kept in memory.
+     */
+    fn ends_with_separator_string(&self, c: &str, separator_strings: &[String]) -> bool {
+        let mut recent_chars = self.recent_chars.borrow_mut();
+
+        let max_len = separator_strings.iter().map(|s| s.chars().count()).max().unwrap_or(0);
+        if max_len == 0 {
+            recent_chars.clear();
+            return false;
+        }
+
+        recent_chars.push_str(c);
+        let char_count = recent_chars.chars().count();
+        if char_count > max_len {
+            let trimmed: String = recent_chars.chars().skip(char_count - max_len).collect();
+            *recent_chars = trimmed;
         }
+
+        separator_strings.iter().any(|s| !s.is_empty() && recent_chars.ends_with(s.as_str()))
     }
+
+
 }
 
 impl <'a, R: MatchReceiver, M: ConfigManager<'a>> super::Matcher for ScrollingMatcher<'a, R, M> {
@@ -101,6 +226,12 @@ impl <'a, R: MatchReceiver, M: ConfigManager<'a>> super::Matcher for ScrollingMa
             &c.chars().nth(0).unwrap_or_default()
         );
 
+        // Multi-character separators are consulted only if the char is not a separator by itself
+        let ends_with_separator_string = self.ends_with_separator_string(c, &active_config.word_separator_strings);
+        if !is_current_word_separator {
+            is_current_word_separator = ends_with_separator_string;
+        }
+
         // Workaround needed on macos to consider espanso replacement key presses as separators.
         if cfg!(target_os = "macos") {
             if c.len() > 1 {
@@ -109,72 +240,146 @@ impl <'a, R: MatchReceiver, M: ConfigManager<'a>> super::Matcher for ScrollingMa
         }
 
         let mut was_previous_word_separator = self.was_previous_char_word_separator.borrow_mut();
+        let mut was_previous_newline = self.was_previous_char_newline.borrow_mut();
+        let mut was_previous_escape = self.was_previous_char_escape.borrow_mut();
 
         let mut current_set_queue = self.current_set_queue.borrow_mut();
 
-        let new_matches: Vec<MatchEntry> = active_config.matches.iter()
-            .filter(|&x| {
-                // only active-enabled matches are considered
-                if x.passive_only {
-                    return false;
-                }
+        // The partial matches refer to the index of a specific config, so they
+        // must be discarded when the active config changes or is swapped
+        let mut current_config = self.current_config.borrow_mut();
+        if current_config.0 != active_config.name || current_config.1 != active_config.generation() {
+            current_set_queue.clear();
+            *current_config = (active_config.name.clone(), active_config.generation());
+        }
 
-                let mut result = Self::is_matching(x, c, 0, is_current_word_separator);
+        let default_config = self.config_manager.default_config();
+        let index = &active_config.match_index;
+        let current_char = c.chars().nth(0);
 
-                if x.word {
-                    result = result && *was_previous_word_separator
-                }
+        self.update_buffer(c, is_current_word_separator, compute_buffer_size(index, &default_config));
+        let now = self.clock.now();
 
-                result
-            })
-            .map(|x | MatchEntry{
-                start: 1,
-                count: x._trigger_sequence.len(),
-                _match: &x
-            })
-            .collect();
-        // TODO: use an associative structure to improve the efficiency of this first "new_matches" lookup.
-
-        let combined_matches: Vec<MatchEntry> = match current_set_queue.back_mut() {
-            Some(last_matches) => {
-                let mut updated: Vec<MatchEntry> = last_matches.iter()
-                    .filter(|&x| {
-                        Self::is_matching(x._match, c, x.start, is_current_word_separator)
-                    })
-                    .map(|x | MatchEntry{
-                        start: x.start+1,
-                        count: x.count,
-                        _match: &x._match
-                    })
-                    .collect();
-
-                updated.extend(new_matches);
-                updated
-            },
-            None => {new_matches},
+        // Advance the partial matches with the current char, also starting a new one from the root
+        let root_entry = MatchEntry {
+            node: ROOT_NODE,
+            depth: 0,
+            after_word_separator: *was_previous_word_separator,
+            at_line_start: *was_previous_newline,
+            after_escape: *was_previous_escape,
+            last_char_time: now,
+            min_char_interval: None,
         };
+        let mut combined_matches: Vec<MatchEntry> = Vec::new();
+        {
+            let last_matches = current_set_queue.back();
+            let previous_entries = last_matches.into_iter().flatten()
+                .chain(std::iter::once(&root_entry));
+
+            for entry in previous_entries {
+                let mut next_nodes = Vec::new();
+                if let Some(current_char) = current_char {
+                    next_nodes.extend(index.next(entry.node, &TriggerEntry::Char(current_char)));
+                }
+                if is_current_word_separator {
+                    next_nodes.extend(index.next(entry.node, &TriggerEntry::WordSeparator));
+                }
+
+                // The first char of a partial match has no previous one to be compared with
+                let min_char_interval = if entry.depth == 0 {
+                    None
+                }else{
+                    let interval = now.duration_since(entry.last_char_time).unwrap_or_default();
+                    Some(entry.min_char_interval.map_or(interval, |min| min.min(interval)))
+                };
+
+                combined_matches.extend(next_nodes.into_iter().map(|node| MatchEntry {
+                    node,
+                    depth: entry.depth + 1,
+                    after_word_separator: entry.after_word_separator,
+                    at_line_start: entry.at_line_start,
+                    after_escape: entry.after_escape,
+                    last_char_time: now,
+                    min_char_interval,
+                }));
+            }
+        }
 
-        let mut found_match = None;
+        // The longest trigger wins, and between triggers of the same length the first defined one.
+        let mut found_match: Option<(&Match, usize)> = None;
+        let mut found_rank = (0, 0);
+        let mut found_escaped = false;
+        let mut focused_app: Option<(Option<String>, Option<String>)> = None;
 
         for entry in combined_matches.iter() {
-            if entry.start == entry.count {
-                found_match = Some(entry._match);
+            for &(position, trigger_offset) in index.matches_at(entry.node) {
+                let m = &active_config.matches[position];
+
+                // skip the matches disabled at runtime
+                if self.is_disabled(m) {
+                    continue;
+                }
+
+                // skip the matches fired too recently, or that can't fire anymore
+                if self.is_in_cooldown(m) || self.is_spent(m) {
+                    continue;
+                }
+
+                // skip the matches typed faster than allowed, for example by an autocorrect
+                if Self::is_typed_too_fast(m, entry) {
+                    continue;
+                }
+
+                // word matches must start after a word separator
+                if m.word && !entry.after_word_separator {
+                    continue;
+                }
+
+                // anchored matches must start at the beginning of a line
+                if m.at_line_start && !entry.at_line_start {
+                    continue;
+                }
+
+                // app scoped matches only fire while one of their apps is focused.
+                // The focused app is queried once, and only if needed.
+                if !m.apps.is_empty() {
+                    let (class, exec) = focused_app.get_or_insert_with(|| self.config_manager.current_app());
+                    if !m.is_active_in_app(class.as_ref().map(String::as_str), exec.as_ref().map(String::as_str)) {
+                        continue;
+                    }
+                }
+
+                let rank = (entry.depth, usize::max_value() - position);
+                if found_match.is_none() || rank > found_rank {
+                    found_match = Some((m, trigger_offset));
+                    found_rank = rank;
+                    found_escaped = entry.after_escape;
+                }
                 break;
             }
         }
 
         current_set_queue.push_back(combined_matches);
 
-        if current_set_queue.len() as i32 > (self.config_manager.default_config().backspace_limit + 1) {
+        while current_set_queue.len() > compute_queue_size(&default_config) {
             current_set_queue.pop_front();
         }
 
-        *was_previous_word_separator = is_current_word_separator;
+        let is_current_escape = active_config.trigger_escape_char.is_some() &&
+            current_char == active_config.trigger_escape_char;
 
-        if let Some(mtc) = found_match {
+        // The escape char is transparent to word and line boundaries, so that it can precede any trigger
+        if !is_current_escape {
+            *was_previous_word_separator = is_current_word_separator;
+            *was_previous_newline = current_char == Some('\n') || current_char == Some('\r');
+        }
+        *was_previous_escape = is_current_escape;
+
+        if let Some((mtc, trigger_offset)) = found_match {
             if let Some(last) = current_set_queue.back_mut() {
                 last.clear();
             }
+            self.typed_buffer.borrow_mut().clear();
 
             let trailing_separator = if !mtc.word {
                 // If it's not a word match, it cannot have a trailing separator
@@ -194,7 +399,52 @@ impl <'a, R: MatchReceiver, M: ConfigManager<'a>> super::Matcher for ScrollingMa
             // Force espanso to consider the last char as a separator
             *was_previous_word_separator = true;
 
-            self.receiver.on_match(mtc, trailing_separator);
+            if found_escaped {
+                self.receiver.on_escaped_match(mtc, trailing_separator, trigger_offset);
+            }else{
+                self.record_fire(mtc);
+                self.receiver.on_match(mtc, trailing_separator, trigger_offset);
+            }
+        }
+    }
+
+    fn handle_hotkey(&self, c: &str, modifiers: &KeyModifierSet) -> bool {
+        if !*(self.is_enabled.borrow()) {
+            return false;
+        }
+
+        // While CTRL is held, some platforms (X11, Windows) report the letters as control chars
+        let key = match c.chars().nth(0) {
+            Some(key) if modifiers.contains(&KeyModifier::CTRL) && key >= '\u{1}' && key <= '\u{1a}' => {
+                (b'a' + key as u8 - 1) as char
+            },
+            Some(key) if !modifiers.is_empty() => key,
+            _ => return false,
+        };
+
+        let active_config = self.config_manager.active_config();
+        let found_match = active_config.matches.iter().find(|m| {
+            match &m.hotkey {
+                Some(hotkey) => hotkey.is_fired_by(modifiers, key) &&
+                    !self.is_disabled(m) &&
+                    !self.is_in_cooldown(m) &&
+                    !self.is_spent(m) &&
+                    (m.apps.is_empty() || {
+                        let (class, exec) = self.config_manager.current_app();
+                        m.is_active_in_app(class.as_ref().map(String::as_str), exec.as_ref().map(String::as_str))
+                    }),
+                None => false,
+            }
+        });
+
+        match found_match {
+            Some(m) => {
+                self.current_set_queue.borrow_mut().clear();
+                self.record_fire(m);
+                self.receiver.on_hotkey_match(m);
+                true
+            },
+            None => false,
         }
     }
 
@@ -205,16 +455,22 @@ impl <'a, R: MatchReceiver, M: ConfigManager<'a>> super::Matcher for ScrollingMa
         // study a mechanism to avoid this problem
 
         if m == config.toggle_key {
-            check_interval(&self.toggle_press_time,
-                           u128::from(config.toggle_interval), || {
+            let toggle_callback = || {
                 self.toggle();
 
                 let is_enabled = self.is_enabled.borrow();
 
                 if !*is_enabled {
                     self.current_set_queue.borrow_mut().clear();
+                    self.typed_buffer.borrow_mut().clear();
                 }
-            });
+            };
+
+            match config.toggle_mode {
+                ToggleMode::SinglePress => toggle_callback(),
+                ToggleMode::DoublePress => check_interval(&self.toggle_press_time,
+                                                          u128::from(config.toggle_interval), toggle_callback),
+            }
         }else if m == config.passive_key {
             check_interval(&self.passive_press_time,
                            u128::from(config.toggle_interval), || {
@@ -226,6 +482,9 @@ impl <'a, R: MatchReceiver, M: ConfigManager<'a>> super::Matcher for ScrollingMa
         if m == BACKSPACE {
             let mut current_set_queue = self.current_set_queue.borrow_mut();
             current_set_queue.pop_back();
+
+            self.recent_chars.borrow_mut().pop();
+            self.typed_buffer.borrow_mut().pop();
         }
     }
 }
@@ -247,6 +506,18 @@ impl <'a, R: MatchReceiver, M: ConfigManager<'a>> ActionEventReceiver for Scroll
     }
 }
 
+fn compute_buffer_size(index: &MatchIndex, default_config: &Configs) -> usize {
+    let backspace_limit = default_config.backspace_limit.max(0) as usize;
+    index.max_trigger_length().max(backspace_limit).min(default_config.max_buffer_size)
+}
+
+// The queue holds the current partial matches, plus the previous ones for each char that can
+// be deleted with backspace, but never more than the 'max_buffer_size'.
+fn compute_queue_size(default_config: &Configs) -> usize {
+    let backspace_limit = default_config.backspace_limit.max(0) as usize;
+    (backspace_limit + 1).min(default_config.max_buffer_size).max(1)
+}
+
 fn check_interval<F>(state_var: &RefCell<SystemTime>, interval: u128, elapsed_callback: F) where F:Fn() {
     let mut press_time = state_var.borrow_mut();
     if let Ok(elapsed) = press_time.elapsed() {
@@ -256,4 +527,841 @@ fn check_interval<F>(state_var: &RefCell<SystemTime>, interval: u128, elapsed_ca
     }
 
     (*press_time) = SystemTime::now();
-}
\ No newline at end of file
+}
+
+// TESTS
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Configs, ConfigSet, LiveConfig};
+    use std::rc::Rc;
+    use crate::matcher::Matcher;
+    use crate::event::{KeyEvent, KeyEventReceiver};
+    use std::collections::HashSet;
+    use std::cell::Cell;
+
+    struct TestConfigManager {
+        set: Rc<ConfigSet>,
+        disabled_triggers: RefCell<HashSet<String>>,
+        spent_triggers: RefCell<HashSet<String>>,
+        current_app: RefCell<(Option<String>, Option<String>)>,
+    }
+
+    impl TestConfigManager {
+        fn new(config_str: &str) -> TestConfigManager {
+            let mut config: Configs = serde_yaml::from_str(config_str).unwrap();
+            config.match_index = config.build_match_index();
+
+            TestConfigManager {
+                set: Rc::new(ConfigSet::from_default(config)),
+                disabled_triggers: RefCell::new(HashSet::new()),
+                spent_triggers: RefCell::new(HashSet::new()),
+                current_app: RefCell::new((None, None)),
+            }
+        }
+    }
+
+    impl <'a> ConfigManager<'a> for TestConfigManager {
+        fn active_config(&'a self) -> LiveConfig {
+            self.default_config()
+        }
+
+        fn default_config(&'a self) -> LiveConfig {
+            LiveConfig { set: Rc::clone(&self.set), position: None, generation: 0 }
+        }
+
+        fn is_trigger_disabled(&self, trigger: &str) -> bool {
+            self.disabled_triggers.borrow().contains(trigger)
+        }
+
+        fn is_trigger_spent(&self, trigger: &str) -> bool {
+            self.spent_triggers.borrow().contains(trigger)
+        }
+
+        fn mark_trigger_spent(&self, trigger: &str) {
+            self.spent_triggers.borrow_mut().insert(trigger.to_owned());
+        }
+
+        fn current_app(&self) -> (Option<String>, Option<String>) {
+            self.current_app.borrow().clone()
+        }
+
+        fn swap_config_set(&self, _new_set: ConfigSet) {}
+    }
+
+    struct TestMatchReceiver {
+        matches: RefCell<Vec<String>>,
+        escaped_matches: RefCell<Vec<String>>,
+        hotkey_matches: RefCell<Vec<String>>,
+    }
+
+    impl TestMatchReceiver {
+        fn new() -> TestMatchReceiver {
+            TestMatchReceiver {
+                matches: RefCell::new(Vec::new()),
+                escaped_matches: RefCell::new(Vec::new()),
+                hotkey_matches: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl MatchReceiver for TestMatchReceiver {
+        fn on_match(&self, m: &Match, _trailing_separator: Option<char>, trigger_offset: usize) {
+            self.matches.borrow_mut().push(m.triggers()[trigger_offset].clone());
+        }
+
+        fn on_escaped_match(&self, m: &Match, _trailing_separator: Option<char>, trigger_offset: usize) {
+            self.escaped_matches.borrow_mut().push(m.triggers()[trigger_offset].clone());
+        }
+
+        fn on_hotkey_match(&self, m: &Match) {
+            self.hotkey_matches.borrow_mut().push(m.fire_key());
+        }
+
+        fn on_enable_update(&self, _status: bool) {}
+
+        fn on_passive(&self) {}
+    }
+
+    fn type_string<'a, R: MatchReceiver, M: ConfigManager<'a>>(matcher: &ScrollingMatcher<'a, R, M>, s: &str) {
+        for c in s.chars() {
+            matcher.handle_char(&c.to_string());
+        }
+    }
+
+    fn type_with_modifiers<'a, R: MatchReceiver, M: ConfigManager<'a>>(matcher: &ScrollingMatcher<'a, R, M>,
+                                                                      c: &str, modifiers: Vec<KeyModifier>) {
+        matcher.on_key_event(KeyEvent::Char(c.to_owned(), KeyModifierSet::new(modifiers)));
+    }
+
+    const TEST_CONFIG: &str = r###"
+    matches:
+        - trigger: ":hi"
+          replace: "hello"
+        - trigger: ":bye"
+          replace: "goodbye"
+    "###;
+
+    #[test]
+    fn test_matcher_simple_match() {
+        let config_manager = TestConfigManager::new(TEST_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, "say :hi");
+
+        assert_eq!(*receiver.matches.borrow(), vec![":hi".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_skips_disabled_triggers() {
+        let config_manager = TestConfigManager::new(TEST_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        config_manager.disabled_triggers.borrow_mut().insert(":hi".to_owned());
+
+        type_string(&matcher, ":hi :bye");
+        assert_eq!(*receiver.matches.borrow(), vec![":bye".to_owned()]);
+
+        config_manager.disabled_triggers.borrow_mut().remove(":hi");
+
+        type_string(&matcher, " :hi");
+        assert_eq!(*receiver.matches.borrow(), vec![":bye".to_owned(), ":hi".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_multi_char_word_separator() {
+        let config_manager = TestConfigManager::new(r###"
+        word_separators: ["\n"]
+        word_separator_strings: ["--"]
+        matches:
+            - trigger: "hi"
+              replace: "hello"
+              word: true
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, "x-hi\n");
+        assert!(receiver.matches.borrow().is_empty());
+
+        type_string(&matcher, "x--hi\n");
+        assert_eq!(*receiver.matches.borrow(), vec!["hi".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_unicode_triggers() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - trigger: "café:"
+              replace: "coffee"
+            - trigger: ":👍"
+              replace: "thumbs up"
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, "café: :👍");
+
+        assert_eq!(*receiver.matches.borrow(), vec!["café:".to_owned(), ":👍".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_word_match_requires_separator_before() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - trigger: "hi"
+              replace: "hello"
+              word: true
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, "ohi ");
+        assert!(receiver.matches.borrow().is_empty());
+
+        type_string(&matcher, "hi ");
+        assert_eq!(*receiver.matches.borrow(), vec!["hi".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_line_start_match_fires_after_newline() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - trigger: "##"
+              replace: "<h2>"
+              at_line_start: true
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        // The beginning of the input counts as the start of a line
+        type_string(&matcher, "##");
+        assert_eq!(*receiver.matches.borrow(), vec!["##".to_owned()]);
+
+        type_string(&matcher, "text\n##");
+        assert_eq!(*receiver.matches.borrow(), vec!["##".to_owned(), "##".to_owned()]);
+
+        type_string(&matcher, "text\r##");
+        assert_eq!(receiver.matches.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_matcher_line_start_match_does_not_fire_mid_line() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - trigger: "##"
+              replace: "<h2>"
+              at_line_start: true
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, "text ##");
+        assert!(receiver.matches.borrow().is_empty());
+
+        type_string(&matcher, "\n ##");
+        assert!(receiver.matches.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_matcher_escaped_trigger_is_not_expanded() {
+        let config_manager = TestConfigManager::new(r###"
+        trigger_escape_char: "`"
+        matches:
+            - trigger: ":hi"
+              replace: "hello"
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, "`:hi");
+        assert!(receiver.matches.borrow().is_empty());
+        assert_eq!(*receiver.escaped_matches.borrow(), vec![":hi".to_owned()]);
+
+        // The escape only applies to the trigger right after it
+        type_string(&matcher, " :hi");
+        assert_eq!(*receiver.matches.borrow(), vec![":hi".to_owned()]);
+        assert_eq!(receiver.escaped_matches.borrow().len(), 1);
+
+        type_string(&matcher, "`x:hi");
+        assert_eq!(receiver.matches.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_matcher_escaped_word_trigger() {
+        let config_manager = TestConfigManager::new(r###"
+        trigger_escape_char: "\\"
+        matches:
+            - trigger: "hi"
+              replace: "hello"
+              word: true
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, "say \\hi ");
+        assert!(receiver.matches.borrow().is_empty());
+        assert_eq!(*receiver.escaped_matches.borrow(), vec!["hi".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_escape_char_disabled_by_default() {
+        let config_manager = TestConfigManager::new(TEST_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, "`:hi");
+        assert_eq!(*receiver.matches.borrow(), vec![":hi".to_owned()]);
+        assert!(receiver.escaped_matches.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_matcher_app_scoped_match() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Sent from the terminal"
+              apps: ["Terminal"]
+            - trigger: ":hi"
+              replace: "hello"
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        *config_manager.current_app.borrow_mut() = (Some("Firefox".to_owned()), Some("/usr/bin/firefox".to_owned()));
+        type_string(&matcher, ":sig :hi");
+        assert_eq!(*receiver.matches.borrow(), vec![":hi".to_owned()]);
+
+        *config_manager.current_app.borrow_mut() = (Some("terminal".to_owned()), Some("/usr/bin/bash".to_owned()));
+        type_string(&matcher, " :sig");
+        assert_eq!(*receiver.matches.borrow(), vec![":hi".to_owned(), ":sig".to_owned()]);
+    }
+
+    // Simulate a press of the toggle key, happening the given amount of time after the previous one
+    fn press_toggle_key_after<'a, R: MatchReceiver, M: ConfigManager<'a>>(matcher: &ScrollingMatcher<'a, R, M>, millis: u64) {
+        let previous_press = SystemTime::now() - std::time::Duration::from_millis(millis);
+        *matcher.toggle_press_time.borrow_mut() = previous_press;
+        matcher.handle_modifier(KeyModifier::ALT);
+    }
+
+    #[test]
+    fn test_matcher_hotkey_fires_match() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+              hotkey: "CTRL+SHIFT+S"
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        // Missing modifier
+        type_with_modifiers(&matcher, "s", vec![KeyModifier::CTRL]);
+        assert!(receiver.hotkey_matches.borrow().is_empty());
+
+        // Extra modifier
+        type_with_modifiers(&matcher, "S", vec![KeyModifier::CTRL, KeyModifier::SHIFT, KeyModifier::ALT]);
+        assert!(receiver.hotkey_matches.borrow().is_empty());
+
+        type_with_modifiers(&matcher, "S", vec![KeyModifier::CTRL, KeyModifier::SHIFT]);
+        assert_eq!(*receiver.hotkey_matches.borrow(), vec![":sig".to_owned()]);
+
+        // Once CTRL is released, the key is typed as usual
+        type_with_modifiers(&matcher, "S", vec![KeyModifier::SHIFT]);
+        assert_eq!(receiver.hotkey_matches.borrow().len(), 1);
+
+        // The trigger still works
+        type_string(&matcher, ":sig");
+        assert_eq!(*receiver.matches.borrow(), vec![":sig".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_hotkey_only_matches() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - replace: "Best regards"
+              hotkey: "CTRL+S"
+              once: true
+            - trigger: ""
+              replace: "Kind regards"
+              hotkey: "CTRL+K"
+              once: true
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        // Without a trigger, typing never fires the matches
+        type_string(&matcher, "sk ");
+        assert!(receiver.matches.borrow().is_empty());
+
+        // Each match is tracked by its own hotkey, so firing one doesn't spend the other
+        type_with_modifiers(&matcher, "s", vec![KeyModifier::CTRL]);
+        type_with_modifiers(&matcher, "k", vec![KeyModifier::CTRL]);
+        assert_eq!(*receiver.hotkey_matches.borrow(), vec!["CTRL+S".to_owned(), "CTRL+K".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_hotkey_fired_by_control_char() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+              hotkey: "CTRL+S"
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        // X11 and Windows report CTRL+S as the \u{13} control char
+        type_with_modifiers(&matcher, "\u{13}", vec![KeyModifier::CTRL]);
+        assert_eq!(*receiver.hotkey_matches.borrow(), vec![":sig".to_owned()]);
+
+        // The control chars don't fire the hotkeys without CTRL
+        type_with_modifiers(&matcher, "\u{13}", vec![KeyModifier::ALT]);
+        assert_eq!(receiver.hotkey_matches.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_matcher_hotkey_ignored_when_disabled() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+              hotkey: "CTRL+S"
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        matcher.set_enabled(false);
+        type_with_modifiers(&matcher, "s", vec![KeyModifier::CTRL]);
+        assert!(receiver.hotkey_matches.borrow().is_empty());
+    }
+
+    struct TestClock {
+        time: Rc<Cell<SystemTime>>,
+    }
+
+    impl Clock for TestClock {
+        fn now(&self) -> SystemTime {
+            self.time.get()
+        }
+    }
+
+    fn advance_clock(time: &Rc<Cell<SystemTime>>, millis: u64) {
+        time.set(time.get() + std::time::Duration::from_millis(millis));
+    }
+
+    #[test]
+    fn test_matcher_cooldown_suppresses_refire_within_window() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+              cooldown_ms: 500
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let mut matcher = ScrollingMatcher::new(&config_manager, &receiver);
+        let time = Rc::new(Cell::new(SystemTime::now()));
+        matcher.set_clock(Box::new(TestClock { time: Rc::clone(&time) }));
+
+        type_string(&matcher, ":sig");
+        advance_clock(&time, 200);
+        type_string(&matcher, ":sig");
+
+        assert_eq!(*receiver.matches.borrow(), vec![":sig".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_cooldown_fires_again_after_window() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+              cooldown_ms: 500
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let mut matcher = ScrollingMatcher::new(&config_manager, &receiver);
+        let time = Rc::new(Cell::new(SystemTime::now()));
+        matcher.set_clock(Box::new(TestClock { time: Rc::clone(&time) }));
+
+        type_string(&matcher, ":sig");
+        advance_clock(&time, 600);
+        type_string(&matcher, ":sig");
+
+        assert_eq!(*receiver.matches.borrow(), vec![":sig".to_owned(), ":sig".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_no_cooldown_by_default() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - trigger: ":sig"
+              replace: "Best regards"
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let mut matcher = ScrollingMatcher::new(&config_manager, &receiver);
+        let time = Rc::new(Cell::new(SystemTime::now()));
+        matcher.set_clock(Box::new(TestClock { time: Rc::clone(&time) }));
+
+        type_string(&matcher, ":sig");
+        type_string(&matcher, ":sig");
+
+        assert_eq!(*receiver.matches.borrow(), vec![":sig".to_owned(), ":sig".to_owned()]);
+    }
+
+    fn type_string_timed(matcher: &ScrollingMatcher<TestMatchReceiver, TestConfigManager>,
+                         time: &Rc<Cell<SystemTime>>, s: &str, interval_ms: u64) {
+        for c in s.chars() {
+            advance_clock(time, interval_ms);
+            matcher.handle_char(&c.to_string());
+        }
+    }
+
+    const MIN_CHAR_INTERVAL_CONFIG: &str = r###"
+        matches:
+            - trigger: "teh"
+              replace: "the"
+              min_char_interval_ms: 30
+        "###;
+
+    #[test]
+    fn test_matcher_min_char_interval_fires_when_typed_slowly() {
+        let config_manager = TestConfigManager::new(MIN_CHAR_INTERVAL_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let mut matcher = ScrollingMatcher::new(&config_manager, &receiver);
+        let time = Rc::new(Cell::new(SystemTime::now()));
+        matcher.set_clock(Box::new(TestClock { time: Rc::clone(&time) }));
+
+        type_string_timed(&matcher, &time, "teh", 100);
+
+        assert_eq!(*receiver.matches.borrow(), vec!["teh".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_min_char_interval_ignores_fast_typing() {
+        let config_manager = TestConfigManager::new(MIN_CHAR_INTERVAL_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let mut matcher = ScrollingMatcher::new(&config_manager, &receiver);
+        let time = Rc::new(Cell::new(SystemTime::now()));
+        matcher.set_clock(Box::new(TestClock { time: Rc::clone(&time) }));
+
+        type_string_timed(&matcher, &time, "teh", 5);
+
+        assert!(receiver.matches.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_matcher_min_char_interval_single_fast_pair_is_enough() {
+        let config_manager = TestConfigManager::new(MIN_CHAR_INTERVAL_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let mut matcher = ScrollingMatcher::new(&config_manager, &receiver);
+        let time = Rc::new(Cell::new(SystemTime::now()));
+        matcher.set_clock(Box::new(TestClock { time: Rc::clone(&time) }));
+
+        type_string_timed(&matcher, &time, "te", 100);
+        type_string_timed(&matcher, &time, "h", 10);
+
+        assert!(receiver.matches.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_matcher_min_char_interval_ignores_time_before_trigger() {
+        let config_manager = TestConfigManager::new(MIN_CHAR_INTERVAL_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let mut matcher = ScrollingMatcher::new(&config_manager, &receiver);
+        let time = Rc::new(Cell::new(SystemTime::now()));
+        matcher.set_clock(Box::new(TestClock { time: Rc::clone(&time) }));
+
+        // The chars typed quickly before the trigger don't count
+        type_string_timed(&matcher, &time, "ab", 1);
+        type_string_timed(&matcher, &time, "teh", 100);
+
+        assert_eq!(*receiver.matches.borrow(), vec!["teh".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_no_min_char_interval_by_default() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - trigger: "teh"
+              replace: "the"
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let mut matcher = ScrollingMatcher::new(&config_manager, &receiver);
+        let time = Rc::new(Cell::new(SystemTime::now()));
+        matcher.set_clock(Box::new(TestClock { time: Rc::clone(&time) }));
+
+        type_string_timed(&matcher, &time, "teh", 0);
+
+        assert_eq!(*receiver.matches.borrow(), vec!["teh".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_multiple_triggers() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - triggers: [":sig", ":regards"]
+              replace: "Best regards"
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, ":regards :sig");
+
+        assert_eq!(*receiver.matches.borrow(), vec![":regards".to_owned(), ":sig".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_multiple_triggers_disabled_by_any_trigger() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - triggers: [":sig", ":regards"]
+              replace: "Best regards"
+        "###);
+        config_manager.disabled_triggers.borrow_mut().insert(":sig".to_owned());
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, ":regards");
+
+        assert!(receiver.matches.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_matcher_toggle_double_press() {
+        let config_manager = TestConfigManager::new(r###"
+        toggle_key: ALT
+        toggle_interval: 230
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        // Presses too far apart are ignored
+        press_toggle_key_after(&matcher, 1000);
+        press_toggle_key_after(&matcher, 500);
+        assert!(*matcher.is_enabled.borrow());
+
+        press_toggle_key_after(&matcher, 100);
+        assert!(!*matcher.is_enabled.borrow());
+    }
+
+    #[test]
+    fn test_matcher_toggle_single_press() {
+        let config_manager = TestConfigManager::new(r###"
+        toggle_key: ALT
+        toggle_interval: 230
+        toggle_mode: SinglePress
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        press_toggle_key_after(&matcher, 1000);
+        assert!(!*matcher.is_enabled.borrow());
+
+        press_toggle_key_after(&matcher, 500);
+        assert!(*matcher.is_enabled.borrow());
+
+        // Other modifiers don't toggle espanso
+        matcher.handle_modifier(KeyModifier::CTRL);
+        assert!(*matcher.is_enabled.borrow());
+    }
+
+    #[test]
+    fn test_matcher_longest_trigger_wins() {
+        let config_manager = TestConfigManager::new(r###"
+        matches:
+            - trigger: "b:"
+              replace: "short"
+            - trigger: ":ab:"
+              replace: "long"
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, ":ab:");
+        assert_eq!(*receiver.matches.borrow(), vec![":ab:".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_backspace_rewinds_partial_matches() {
+        let config_manager = TestConfigManager::new(TEST_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, ":hx");
+        matcher.handle_modifier(BACKSPACE);
+        type_string(&matcher, "i");
+
+        assert_eq!(*receiver.matches.borrow(), vec![":hi".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_buffer_contains_typed_chars() {
+        let config_manager = TestConfigManager::new(TEST_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, ":by");
+        assert_eq!(matcher.current_buffer(), ":by");
+    }
+
+    #[test]
+    fn test_matcher_buffer_resets_on_word_separator() {
+        let config_manager = TestConfigManager::new(TEST_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, "say :b");
+        assert_eq!(matcher.current_buffer(), ":b");
+
+        type_string(&matcher, " ");
+        assert_eq!(matcher.current_buffer(), "");
+    }
+
+    #[test]
+    fn test_matcher_buffer_is_trimmed() {
+        let config_manager = TestConfigManager::new(TEST_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        // The longest trigger is ':bye', which is longer than the default backspace_limit
+        assert_eq!(matcher.buffer_size(), 4);
+        type_string(&matcher, "abcdefghij");
+        assert_eq!(matcher.current_buffer(), "ghij");
+    }
+
+    #[test]
+    fn test_matcher_buffer_rewinds_on_backspace() {
+        let config_manager = TestConfigManager::new(TEST_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, ":hx");
+        matcher.handle_modifier(BACKSPACE);
+        assert_eq!(matcher.current_buffer(), ":h");
+    }
+
+    #[test]
+    fn test_matcher_buffer_resets_after_match() {
+        let config_manager = TestConfigManager::new(TEST_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, ":hi");
+        assert_eq!(*receiver.matches.borrow(), vec![":hi".to_owned()]);
+        assert_eq!(matcher.current_buffer(), "");
+    }
+
+    #[test]
+    fn test_matcher_buffer_size_covers_backspace_limit() {
+        let config_manager = TestConfigManager::new(r###"
+        backspace_limit: 10
+        matches:
+            - trigger: ":hi"
+              replace: "hello"
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        assert_eq!(matcher.buffer_size(), 10);
+    }
+
+    #[test]
+    fn test_matcher_queue_is_capped_by_buffer_size() {
+        let config_manager = TestConfigManager::new(r###"
+        backspace_limit: 10
+        max_buffer_size: 3
+        matches:
+            - trigger: ":hi"
+              replace: "hello"
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, "abcdefgh:h");
+        assert_eq!(matcher.current_set_queue.borrow().len(), 3);
+
+        // The current partial match is kept
+        type_string(&matcher, "i");
+        assert_eq!(*receiver.matches.borrow(), vec![":hi".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_queue_covers_backspace_limit() {
+        let config_manager = TestConfigManager::new(TEST_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, "abcdefgh");
+        let backspace_limit = config_manager.default_config().backspace_limit as usize;
+        assert_eq!(matcher.current_set_queue.borrow().len(), backspace_limit + 1);
+    }
+
+    #[test]
+    fn test_matcher_long_trigger_matches_beyond_buffer_size() {
+        let config_manager = TestConfigManager::new(r###"
+        max_buffer_size: 5
+        matches:
+            - trigger: ":averyveryverylongtrigger"
+              replace: "long"
+        "###);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        assert_eq!(matcher.buffer_size(), 5);
+
+        type_string(&matcher, ":averyveryverylongtrig");
+        assert_eq!(matcher.current_buffer(), "gtrig");
+
+        type_string(&matcher, "ger");
+        assert_eq!(*receiver.matches.borrow(), vec![":averyveryverylongtrigger".to_owned()]);
+    }
+
+    const ONCE_CONFIG: &str = r###"
+        matches:
+            - trigger: ":remind"
+              replace: "Remember the meeting"
+              once: true
+              hotkey: "CTRL+R"
+            - trigger: ":hi"
+              replace: "hello"
+        "###;
+
+    #[test]
+    fn test_matcher_once_fires_only_the_first_time() {
+        let config_manager = TestConfigManager::new(ONCE_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, ":remind :remind :hi :hi");
+
+        assert_eq!(*receiver.matches.borrow(), vec![":remind".to_owned(), ":hi".to_owned(), ":hi".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_once_fires_again_after_reset() {
+        let config_manager = TestConfigManager::new(ONCE_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, ":remind");
+        config_manager.spent_triggers.borrow_mut().clear();
+        type_string(&matcher, " :remind");
+
+        assert_eq!(*receiver.matches.borrow(), vec![":remind".to_owned(), ":remind".to_owned()]);
+    }
+
+    #[test]
+    fn test_matcher_once_hotkey_is_spent_by_trigger() {
+        let config_manager = TestConfigManager::new(ONCE_CONFIG);
+        let receiver = TestMatchReceiver::new();
+        let matcher = ScrollingMatcher::new(&config_manager, &receiver);
+
+        type_string(&matcher, ":remind");
+        type_with_modifiers(&matcher, "r", vec![KeyModifier::CTRL]);
+
+        assert!(receiver.hotkey_matches.borrow().is_empty());
+    }
+}
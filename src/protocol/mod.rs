@@ -20,24 +20,35 @@
 use serde::{Deserialize, Serialize};
 use std::sync::mpsc::Sender;
 use crate::event::Event;
-use crate::event::ActionType;
+use crate::event::{ActionType, ExpandRequest, PreviewRequest, StatsRequest, ReloadRequest, TriggerStateRequest, ConfigSetRequest};
+use crate::stats::StatsSnapshot;
+use crate::engine::ExpansionPreview;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::io::{BufReader, Read, Write};
 use std::error::Error;
-use log::error;
-use crate::config::ConfigSet;
+use std::path::Path;
+use log::{error, warn};
+use crate::config::{ConfigSet, ConfigCache, ConfigLoadError, IPCTransport};
+use crate::matcher::{Match, MatchContentType};
 
-#[cfg(target_os = "windows")]
-mod windows;
+mod tcp;
 
 #[cfg(not(target_os = "windows"))]
 mod unix;
 
 pub trait IPCServer {
-    fn start(&self);
+    // Bind the server and start listening for commands in a separate thread.
+    // An error is returned if the server can't bind, as espanso can't be controlled without it.
+    fn start(&self) -> Result<(), String>;
 }
 
 pub trait IPCClient {
     fn send_command(&self, command: IPCCommand) -> Result<(), String>;
+
+    // Send the command and wait for the response of the daemon
+    fn send_request(&self, command: IPCCommand) -> Result<String, String>;
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -46,9 +57,320 @@ pub struct IPCCommand {
 
     #[serde(default)]
     pub payload: String,
+
+    // Shared secret, required when the 'ipc_secret' option is set
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+// Id of the command used to query the loaded matches. The client must shut down
+// the write side of the stream after sending it, and then read the JSON
+// serialized ListMatchesResponse sent back by the daemon, or an ErrorResponse
+// if the configuration in use couldn't be obtained in time. If a tag is given as
+// payload, only the matches annotated with it are listed.
+pub const LIST_MATCHES_COMMAND_ID: &str = "list_matches";
+
+// Maximum amount of time to wait for the daemon to send the configuration in use
+const LIST_MATCHES_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MatchDescriptor {
+    // All the triggers of the match, whether defined with 'trigger' or 'triggers'
+    pub triggers: Vec<String>,
+
+    #[serde(default)]
+    pub replace: Option<String>,
+
+    #[serde(default)]
+    pub word: bool,
+
+    // Name of the config that defines the match
+    pub config: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ListMatchesResponse {
+    pub matches: Vec<MatchDescriptor>,
+}
+
+// List the matches of the configuration currently used by the daemon, which can differ
+// from the one loaded by the server if it was changed in the meantime
+fn list_matches(event_channel: &Sender<Event>, tag: Option<&str>) -> Result<ListMatchesResponse, String> {
+    let (reply, reply_receiver) = mpsc::channel();
+
+    event_channel.send(Event::ConfigSet(ConfigSetRequest { reply })).expect("Broken event channel");
+
+    let config_set = reply_receiver.recv_timeout(LIST_MATCHES_TIMEOUT)
+        .unwrap_or_else(|e| Err(format!("No reply from the daemon: {}", e)))?;
+
+    Ok(ListMatchesResponse::from_config_set(&config_set, tag))
+}
+
+// Id of the command used to expand the match whose trigger is given as payload.
+// As for the list_matches command, the daemon replies with an ExpandTriggerResponse.
+pub const EXPAND_TRIGGER_COMMAND_ID: &str = "expand_trigger";
+
+// Maximum amount of time to wait for the daemon to perform the expansion
+const EXPAND_TRIGGER_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExpandTriggerResponse {
+    pub success: bool,
+
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl ExpandTriggerResponse {
+    fn from_result(result: Result<(), String>) -> ExpandTriggerResponse {
+        match result {
+            Ok(_) => ExpandTriggerResponse { success: true, error: None },
+            Err(e) => ExpandTriggerResponse { success: false, error: Some(e) },
+        }
+    }
+}
+
+// Forward the expansion request to the daemon, waiting for its outcome
+fn expand_trigger(event_channel: &Sender<Event>, trigger: &str) -> ExpandTriggerResponse {
+    let (reply, reply_receiver) = mpsc::channel();
+
+    let request = ExpandRequest {
+        trigger: trigger.to_owned(),
+        reply,
+    };
+    event_channel.send(Event::Expand(request)).expect("Broken event channel");
+
+    let result = reply_receiver.recv_timeout(EXPAND_TRIGGER_TIMEOUT)
+        .unwrap_or_else(|e| Err(format!("No reply from the daemon: {}", e)));
+
+    ExpandTriggerResponse::from_result(result)
+}
+
+// Id of the command used to preview the expansion of the match whose trigger is given
+// as payload, without typing anything. The daemon replies with a PreviewTriggerResponse.
+pub const PREVIEW_TRIGGER_COMMAND_ID: &str = "preview_trigger";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PreviewTriggerResponse {
+    pub success: bool,
+
+    #[serde(default)]
+    pub error: Option<String>,
+
+    // Text that would be typed or pasted, without the {{key:NAME}} tokens
+    #[serde(default)]
+    pub text: String,
+
+    // Names of the keys that would be pressed, in order
+    #[serde(default)]
+    pub keys: Vec<String>,
+
+    // Number of Left presses that would bring the cursor on the $|$ hint, if present
+    #[serde(default)]
+    pub cursor_offset: Option<usize>,
+}
+
+impl PreviewTriggerResponse {
+    fn from_result(result: Result<ExpansionPreview, String>) -> PreviewTriggerResponse {
+        match result {
+            Ok(preview) => PreviewTriggerResponse {
+                success: true,
+                error: None,
+                text: preview.text,
+                keys: preview.keys.iter().map(|key| key.name()).collect(),
+                cursor_offset: preview.cursor_offset,
+            },
+            Err(e) => PreviewTriggerResponse {
+                success: false,
+                error: Some(e),
+                text: String::new(),
+                keys: Vec::new(),
+                cursor_offset: None,
+            },
+        }
+    }
+}
+
+// Forward the preview request to the daemon, waiting for its reply
+fn preview_trigger(event_channel: &Sender<Event>, trigger: &str) -> PreviewTriggerResponse {
+    let (reply, reply_receiver) = mpsc::channel();
+
+    let request = PreviewRequest {
+        trigger: trigger.to_owned(),
+        reply,
+    };
+    event_channel.send(Event::Preview(request)).expect("Broken event channel");
+
+    let result = reply_receiver.recv_timeout(EXPAND_TRIGGER_TIMEOUT)
+        .unwrap_or_else(|e| Err(format!("No reply from the daemon: {}", e)));
+
+    PreviewTriggerResponse::from_result(result)
+}
+
+// Id of the command used to query the expansion stats. As for the list_matches
+// command, the daemon replies with the JSON serialized StatsSnapshot, or with an
+// ErrorResponse if the stats couldn't be collected in time.
+pub const GET_STATS_COMMAND_ID: &str = "get_stats";
+
+// Maximum amount of time to wait for the daemon to collect the stats
+const GET_STATS_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Reply sent to the commands that can't be completed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+// Ask the daemon for the current stats, waiting for its reply
+fn get_stats(event_channel: &Sender<Event>) -> Result<StatsSnapshot, String> {
+    let (reply, reply_receiver) = mpsc::channel();
+
+    event_channel.send(Event::Stats(StatsRequest { reply })).expect("Broken event channel");
+
+    reply_receiver.recv_timeout(GET_STATS_TIMEOUT).map_err(|e| {
+        error!("No stats reply from the daemon: {}", e);
+        format!("No reply from the daemon: {}", e)
+    })
+}
+
+// Id of the command used to reload the configuration from disk. As for the list_matches
+// command, the daemon replies with a ReloadResponse.
+pub const RELOAD_COMMAND_ID: &str = "reload";
+
+// Id of the command used to switch to the profile given as payload, or to the base
+// config if the payload is empty. As for the reload command, the daemon replies with
+// a ReloadResponse.
+pub const PROFILE_COMMAND_ID: &str = "profile";
+
+// Maximum amount of time to wait for the daemon to reload the configuration
+const RELOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReloadResponse {
+    pub success: bool,
+
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl ReloadResponse {
+    fn from_result(result: Result<(), String>) -> ReloadResponse {
+        match result {
+            Ok(_) => ReloadResponse { success: true, error: None },
+            Err(e) => ReloadResponse { success: false, error: Some(e) },
+        }
+    }
+}
+
+// Ask the daemon to replace its configuration with the given one, waiting for the outcome.
+// If the daemon accepts it, the server switches to the new configuration as well.
+fn reload(event_channel: &Sender<Event>, config_set: &mut ConfigSet,
+          new_set: Result<ConfigSet, ConfigLoadError>) -> ReloadResponse {
+    let (reply, reply_receiver) = mpsc::channel();
+    let new_snapshot = new_set.as_ref().ok().cloned();
+
+    event_channel.send(Event::Reload(ReloadRequest { config_set: new_set, reply })).expect("Broken event channel");
+
+    let result = reply_receiver.recv_timeout(RELOAD_TIMEOUT)
+        .unwrap_or_else(|e| Err(format!("No reply from the daemon: {}", e)));
+
+    if let (Ok(_), Some(new_snapshot)) = (&result, new_snapshot) {
+        *config_set = new_snapshot;
+    }
+
+    ReloadResponse::from_result(result)
+}
+
+// Ask the daemon to switch to the given profile, or to the base config if empty. The choice
+// is saved in the config directory, from which it's read at startup, only if the daemon
+// accepts the new configuration.
+fn switch_profile(event_channel: &Sender<Event>, config_set: &mut ConfigSet, name: &str,
+                  config_dir: &Path, package_dir: &Path) -> ReloadResponse {
+    let name = if name.is_empty() { None } else { Some(name) };
+
+    let new_set = ConfigSet::load_profile_or_base(config_dir, package_dir, name);
+    let response = reload(event_channel, config_set, new_set);
+    if response.success {
+        if let Err(e) = crate::config::runtime::save_active_profile_in_dir(config_dir, name) {
+            warn!("Unable to save the active profile: {}", e);
+        }
+    }
+
+    response
+}
+
+impl ListMatchesResponse {
+    /// List the matches of the given set, only those annotated with the tag if given.
+    pub fn from_config_set(config_set: &ConfigSet, tag: Option<&str>) -> ListMatchesResponse {
+        let has_tag = |m: &&Match| tag.map_or(true, |tag| m.has_tag(tag));
+
+        let mut matches: Vec<MatchDescriptor> = config_set.default.matches.iter()
+            .filter(has_tag)
+            .map(|m| MatchDescriptor::new(m, &config_set.default.name))
+            .collect();
+
+        // Specific configs also contain the entries inherited from the default one,
+        // which are listed only once with the default config as source.
+        let default_matches: Vec<serde_json::Value> = config_set.default.matches.iter()
+            .filter_map(|m| serde_json::to_value(m).ok())
+            .collect();
+
+        for config in config_set.specific.iter() {
+            matches.extend(config.matches.iter()
+                .filter(|m| {
+                    match serde_json::to_value(m) {
+                        Ok(value) => !default_matches.contains(&value),
+                        Err(_) => true,
+                    }
+                })
+                .filter(has_tag)
+                .map(|m| MatchDescriptor::new(m, &config.name)));
+        }
+
+        ListMatchesResponse {
+            matches,
+        }
+    }
+}
+
+impl MatchDescriptor {
+    fn new(m: &Match, config_name: &str) -> MatchDescriptor {
+        let replace = match &m.content {
+            MatchContentType::Text(content) => Some(content.replace.clone()),
+            MatchContentType::Image(_) => None,
+        };
+
+        MatchDescriptor {
+            triggers: m.triggers().to_vec(),
+            replace,
+            word: m.word,
+            config: config_name.to_owned(),
+        }
+    }
+}
+
+/// Compare the given secrets in a time that doesn't depend on where they differ,
+/// so that the secret can't be guessed by measuring the response time.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b.iter()).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
 }
 
 impl IPCCommand {
+    /// Check if the command carries the secret required by the configuration, if any.
+    fn is_authorized(&self, config_set: &ConfigSet) -> bool {
+        match &config_set.default.ipc_secret {
+            Some(secret) => match &self.secret {
+                Some(received) => constant_time_eq(received.as_bytes(), secret.as_bytes()),
+                None => false,
+            },
+            None => true,
+        }
+    }
+
     fn to_event(&self) -> Option<Event> {
         match self.id.as_ref() {
             "exit" => {
@@ -63,22 +385,69 @@ impl IPCCommand {
             "disable" => {
                 Some(Event::Action(ActionType::Disable))
             },
+            "disable_trigger" => {
+                Some(Event::TriggerState(TriggerStateRequest { trigger: self.payload.clone(), disabled: true }))
+            },
+            "enable_trigger" => {
+                Some(Event::TriggerState(TriggerStateRequest { trigger: self.payload.clone(), disabled: false }))
+            },
             _ => None
         }
     }
 }
 
-fn process_event<R: Read, E: Error>(event_channel: &Sender<Event>, stream: Result<R, E>) {
+fn process_event<R: Read + Write, E: Error>(event_channel: &Sender<Event>, config_set: &mut ConfigSet,
+                                            config_cache: &Mutex<ConfigCache>, stream: Result<R, E>) {
     match stream {
-        Ok(stream) => {
+        Ok(mut stream) => {
             let mut json_str= String::new();
-            let mut buf_reader = BufReader::new(stream);
+            let mut buf_reader = BufReader::new(&mut stream);
             let res = buf_reader.read_to_string(&mut json_str);
 
             if res.is_ok() {
                 let command : Result<IPCCommand, serde_json::Error> = serde_json::from_str(&json_str);
                 match command {
                     Ok(command) => {
+                        if !command.is_authorized(config_set) {
+                            warn!("Rejected unauthenticated IPC command: {}", command.id);
+                            return;
+                        }
+
+                        if command.id == LIST_MATCHES_COMMAND_ID {
+                            let tag = Some(command.payload.as_str()).filter(|tag| !tag.is_empty());
+                            match list_matches(event_channel, tag) {
+                                Ok(response) => send_response(&response, stream),
+                                Err(error) => send_response(&ErrorResponse { error }, stream),
+                            }
+                            return;
+                        }else if command.id == EXPAND_TRIGGER_COMMAND_ID {
+                            let response = expand_trigger(event_channel, &command.payload);
+                            send_response(&response, stream);
+                            return;
+                        }else if command.id == PREVIEW_TRIGGER_COMMAND_ID {
+                            let response = preview_trigger(event_channel, &command.payload);
+                            send_response(&response, stream);
+                            return;
+                        }else if command.id == GET_STATS_COMMAND_ID {
+                            match get_stats(event_channel) {
+                                Ok(response) => send_response(&response, stream),
+                                Err(error) => send_response(&ErrorResponse { error }, stream),
+                            }
+                            return;
+                        }else if command.id == RELOAD_COMMAND_ID {
+                            // The configuration is parsed here, so that the input thread is not blocked
+                            let new_set = ConfigSet::load_default_with_cache(&mut config_cache.lock().unwrap());
+                            let response = reload(event_channel, config_set, new_set);
+                            send_response(&response, stream);
+                            return;
+                        }else if command.id == PROFILE_COMMAND_ID {
+                            let response = switch_profile(event_channel, config_set, &command.payload,
+                                                          &crate::context::get_config_dir(),
+                                                          &crate::context::get_package_dir());
+                            send_response(&response, stream);
+                            return;
+                        }
+
                         let event = command.to_event();
                         if let Some(event) = event {
                             event_channel.send(event).expect("Broken event channel");
@@ -96,6 +465,19 @@ fn process_event<R: Read, E: Error>(event_channel: &Sender<Event>, stream: Resul
     }
 }
 
+fn send_response<T: Serialize, W: Write>(response: &T, mut stream: W) {
+    match serde_json::to_string(response) {
+        Ok(json_str) => {
+            stream.write_all(json_str.as_bytes()).unwrap_or_else(|e| {
+                error!("Can't write IPC response: {}", e);
+            });
+        },
+        Err(e) => {
+            error!("Error serializing JSON response: {}", e);
+        },
+    }
+}
+
 fn send_command<W: Write, E: Error>(command: IPCCommand, stream: Result<W, E>) -> Result<(), String>{
     match stream {
         Ok(mut stream) => {
@@ -115,24 +497,655 @@ fn send_command<W: Write, E: Error>(command: IPCCommand, stream: Result<W, E>) -
     Err("Can't send command".to_owned())
 }
 
+// Send the command, shutting down the write side of the stream afterwards
+// so that the daemon knows it's complete, and read back its response
+fn send_request<S: Read + Write, E: Error, F>(command: IPCCommand, stream: Result<S, E>, shutdown_write: F) -> Result<String, String>
+    where F: Fn(&S) -> std::io::Result<()> {
+    let mut stream = stream.map_err(|e| format!("Can't connect to daemon: {}", e))?;
+
+    let json_str = serde_json::to_string(&command).map_err(|e| format!("Can't serialize command: {}", e))?;
+    stream.write_all(json_str.as_bytes()).map_err(|e| format!("Can't write to IPC socket: {}", e))?;
+    shutdown_write(&stream).map_err(|e| format!("Can't write to IPC socket: {}", e))?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).map_err(|e| format!("Can't read the daemon response: {}", e))?;
+
+    Ok(response)
+}
+
+fn unsupported_transport_error(transport: IPCTransport) -> String {
+    let name = match transport {
+        IPCTransport::Tcp => "tcp",
+        IPCTransport::Unix => "unix",
+        IPCTransport::Pipe => "pipe",
+    };
+
+    format!("The '{}' IPC transport is not supported on this platform, \
+             please change the 'ipc_transport' option in your configuration", name)
+}
+
 // UNIX IMPLEMENTATION
 #[cfg(not(target_os = "windows"))]
-pub fn get_ipc_server(_: ConfigSet, event_channel: Sender<Event>) -> impl IPCServer {
-    unix::UnixIPCServer::new(event_channel)
+pub fn get_ipc_server(config_set: ConfigSet, event_channel: Sender<Event>,
+                      config_cache: Arc<Mutex<ConfigCache>>) -> Result<Box<dyn IPCServer>, String> {
+    match config_set.default.ipc_transport {
+        IPCTransport::Tcp => Ok(Box::new(tcp::TcpIPCServer::new(config_set, event_channel, config_cache))),
+        IPCTransport::Unix => Ok(Box::new(unix::UnixIPCServer::new(config_set, event_channel, config_cache))),
+        transport => Err(unsupported_transport_error(transport)),
+    }
 }
 
 #[cfg(not(target_os = "windows"))]
-pub fn get_ipc_client(_: ConfigSet) -> impl IPCClient {
-    unix::UnixIPCClient::new()
+pub fn get_ipc_client(config_set: ConfigSet) -> Result<Box<dyn IPCClient>, String> {
+    match config_set.default.ipc_transport {
+        IPCTransport::Tcp => Ok(Box::new(tcp::TcpIPCClient::new(config_set))),
+        IPCTransport::Unix => Ok(Box::new(unix::UnixIPCClient::new())),
+        transport => Err(unsupported_transport_error(transport)),
+    }
 }
 
 // WINDOWS IMPLEMENTATION
 #[cfg(target_os = "windows")]
-pub fn get_ipc_server(config_set: ConfigSet, event_channel: Sender<Event>) -> impl IPCServer {
-    windows::WindowsIPCServer::new(config_set, event_channel)
+pub fn get_ipc_server(config_set: ConfigSet, event_channel: Sender<Event>,
+                      config_cache: Arc<Mutex<ConfigCache>>) -> Result<Box<dyn IPCServer>, String> {
+    match config_set.default.ipc_transport {
+        IPCTransport::Tcp => Ok(Box::new(tcp::TcpIPCServer::new(config_set, event_channel, config_cache))),
+        transport => Err(unsupported_transport_error(transport)),
+    }
 }
 
 #[cfg(target_os = "windows")]
-pub fn get_ipc_client(config_set: ConfigSet) -> impl IPCClient {
-    windows::WindowsIPCClient::new(config_set)
-}
\ No newline at end of file
+pub fn get_ipc_client(config_set: ConfigSet) -> Result<Box<dyn IPCClient>, String> {
+    match config_set.default.ipc_transport {
+        IPCTransport::Tcp => Ok(Box::new(tcp::TcpIPCClient::new(config_set))),
+        transport => Err(unsupported_transport_error(transport)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackendType;
+    use crate::keyboard::KeyCode;
+
+    const TEST_CONFIG_SET: &str = r###"
+    default:
+        matches:
+            - trigger: ":hi"
+              replace: "hello"
+    specific:
+        - name: editor
+          matches:
+            - triggers: [":fn", ":func"]
+              replace: "function"
+              word: true
+            - trigger: ":hi"
+              replace: "hello"
+    "###;
+
+    #[test]
+    fn test_list_matches_command_round_trip() {
+        let command = IPCCommand {
+            id: LIST_MATCHES_COMMAND_ID.to_owned(),
+            payload: "".to_owned(),
+            secret: None,
+        };
+
+        let json_str = serde_json::to_string(&command).unwrap();
+        let deserialized: IPCCommand = serde_json::from_str(&json_str).unwrap();
+
+        assert_eq!(deserialized.id, LIST_MATCHES_COMMAND_ID);
+        assert!(deserialized.to_event().is_none());
+    }
+
+    #[test]
+    fn test_list_matches_response_round_trip() {
+        let response = ListMatchesResponse {
+            matches: vec![
+                MatchDescriptor {
+                    triggers: vec![":hi".to_owned()],
+                    replace: Some("hello".to_owned()),
+                    word: false,
+                    config: "default".to_owned(),
+                },
+                MatchDescriptor {
+                    triggers: vec![":img".to_owned(), ":image".to_owned()],
+                    replace: None,
+                    word: true,
+                    config: "images".to_owned(),
+                },
+            ],
+        };
+
+        let json_str = serde_json::to_string(&response).unwrap();
+        let deserialized: ListMatchesResponse = serde_json::from_str(&json_str).unwrap();
+
+        assert_eq!(deserialized, response);
+    }
+
+    #[test]
+    fn test_list_matches_response_from_config_set() {
+        let config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET).unwrap();
+
+        let response = ListMatchesResponse::from_config_set(&config_set, None);
+
+        assert_eq!(response.matches, vec![
+            MatchDescriptor {
+                triggers: vec![":hi".to_owned()],
+                replace: Some("hello".to_owned()),
+                word: false,
+                config: "default".to_owned(),
+            },
+            MatchDescriptor {
+                triggers: vec![":fn".to_owned(), ":func".to_owned()],
+                replace: Some("function".to_owned()),
+                word: true,
+                config: "editor".to_owned(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_list_matches_response_from_config_set_filtered_by_tag() {
+        let config_set: ConfigSet = serde_yaml::from_str(r###"
+        default:
+            matches:
+                - trigger: ":hi"
+                  replace: "hello"
+                - trigger: ":mail"
+                  replace: "jon@snow.com"
+                  tags: [work]
+        specific:
+            - name: editor
+              matches:
+                - trigger: ":fn"
+                  replace: "function"
+                  tags: [code, work]
+        "###).unwrap();
+
+        let triggers = |tag| -> Vec<String> {
+            ListMatchesResponse::from_config_set(&config_set, Some(tag)).matches.into_iter()
+                .flat_map(|m| m.triggers)
+                .collect()
+        };
+
+        assert_eq!(triggers("work"), vec![":mail".to_owned(), ":fn".to_owned()]);
+        assert_eq!(triggers("code"), vec![":fn".to_owned()]);
+        assert!(triggers("personal").is_empty());
+    }
+
+    #[test]
+    fn test_expand_trigger_forwards_request_and_reply() {
+        let (event_channel, event_receiver) = mpsc::channel();
+
+        let daemon = std::thread::spawn(move || {
+            for _ in 0..2 {
+                if let Event::Expand(request) = event_receiver.recv().unwrap() {
+                    let result = if request.trigger == ":hi" {
+                        Ok(())
+                    }else{
+                        Err(format!("No match found for trigger '{}'", request.trigger))
+                    };
+                    request.reply.send(result).unwrap();
+                }
+            }
+        });
+
+        assert_eq!(expand_trigger(&event_channel, ":hi"), ExpandTriggerResponse {
+            success: true,
+            error: None,
+        });
+        assert_eq!(expand_trigger(&event_channel, ":missing"), ExpandTriggerResponse {
+            success: false,
+            error: Some("No match found for trigger ':missing'".to_owned()),
+        });
+
+        daemon.join().unwrap();
+    }
+
+    #[test]
+    fn test_get_stats_forwards_request_and_reply() {
+        let (event_channel, event_receiver) = mpsc::channel();
+        let expected = StatsSnapshot {
+            total_expansions: 2,
+            trigger_counts: vec![(":hi".to_owned(), 2)].into_iter().collect(),
+            uptime_secs: 10,
+            active_config: "default".to_owned(),
+            most_used: vec![(":hi".to_owned(), 2)],
+        };
+
+        let snapshot = expected.clone();
+        let daemon = std::thread::spawn(move || {
+            if let Event::Stats(request) = event_receiver.recv().unwrap() {
+                request.reply.send(snapshot).unwrap();
+            }
+        });
+
+        assert_eq!(get_stats(&event_channel), Ok(expected));
+
+        daemon.join().unwrap();
+    }
+
+    #[test]
+    fn test_get_stats_without_reply_sends_error_response() {
+        let mut config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET).unwrap();
+        let (event_channel, event_receiver) = mpsc::channel();
+
+        // The request is dropped without a reply, as if the daemon failed to collect the stats
+        let daemon = std::thread::spawn(move || {
+            event_receiver.recv().unwrap();
+        });
+
+        let mut stream = TestStream::new(&IPCCommand {
+            id: GET_STATS_COMMAND_ID.to_owned(),
+            payload: "".to_owned(),
+            secret: None,
+        });
+        process_event(&event_channel, &mut config_set, &Mutex::new(ConfigCache::new()), Ok::<&mut TestStream, std::io::Error>(&mut stream));
+        daemon.join().unwrap();
+
+        let response: ErrorResponse = serde_json::from_slice(&stream.output).unwrap();
+        assert!(response.error.starts_with("No reply from the daemon"));
+    }
+
+    #[test]
+    fn test_preview_trigger_forwards_request_and_reply() {
+        let (event_channel, event_receiver) = mpsc::channel();
+
+        let daemon = std::thread::spawn(move || {
+            for _ in 0..2 {
+                if let Event::Preview(request) = event_receiver.recv().unwrap() {
+                    let result = if request.trigger == ":hi" {
+                        Ok(ExpansionPreview {
+                            text: "Hello".to_owned(),
+                            cursor_offset: Some(2),
+                            keys: vec![KeyCode::Tab],
+                            deleted: 3,
+                            used_backend: BackendType::Inject,
+                        })
+                    }else{
+                        Err(format!("No match found for trigger '{}'", request.trigger))
+                    };
+                    request.reply.send(result).unwrap();
+                }
+            }
+        });
+
+        assert_eq!(preview_trigger(&event_channel, ":hi"), PreviewTriggerResponse {
+            success: true,
+            error: None,
+            text: "Hello".to_owned(),
+            keys: vec!["TAB".to_owned()],
+            cursor_offset: Some(2),
+        });
+        assert_eq!(preview_trigger(&event_channel, ":missing"), PreviewTriggerResponse {
+            success: false,
+            error: Some("No match found for trigger ':missing'".to_owned()),
+            text: String::new(),
+            keys: Vec::new(),
+            cursor_offset: None,
+        });
+
+        daemon.join().unwrap();
+    }
+
+    #[test]
+    fn test_expand_trigger_response_round_trip() {
+        let response = ExpandTriggerResponse {
+            success: false,
+            error: Some("No match found for trigger ':test'".to_owned()),
+        };
+
+        let json_str = serde_json::to_string(&response).unwrap();
+        let deserialized: ExpandTriggerResponse = serde_json::from_str(&json_str).unwrap();
+
+        assert_eq!(deserialized, response);
+    }
+
+    // In-memory stream used to simulate an IPC connection
+    struct TestStream {
+        input: std::io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl TestStream {
+        fn new(command: &IPCCommand) -> TestStream {
+            TestStream {
+                input: std::io::Cursor::new(serde_json::to_vec(command).unwrap()),
+                output: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for TestStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for TestStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn process_test_command(config_set: &ConfigSet, command: &IPCCommand) -> (Vec<Event>, Vec<u8>) {
+        let (event_channel, event_receiver) = mpsc::channel();
+        let mut stream = TestStream::new(command);
+        let mut config_set = config_set.clone();
+
+        process_event(&event_channel, &mut config_set, &Mutex::new(ConfigCache::new()), Ok::<&mut TestStream, std::io::Error>(&mut stream));
+
+        (event_receiver.try_iter().collect(), stream.output)
+    }
+
+    const TEST_CONFIG_SET_WITH_SECRET: &str = r###"
+    default:
+        ipc_secret: "s3cr3t"
+        matches:
+            - trigger: ":hi"
+              replace: "hello"
+    specific: []
+    "###;
+
+    #[test]
+    fn test_unauthenticated_command_is_rejected() {
+        let config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET_WITH_SECRET).unwrap();
+
+        let (events, _) = process_test_command(&config_set, &IPCCommand {
+            id: "exit".to_owned(),
+            payload: "".to_owned(),
+            secret: None,
+        });
+        assert!(events.is_empty());
+
+        let (events, _) = process_test_command(&config_set, &IPCCommand {
+            id: "exit".to_owned(),
+            payload: "".to_owned(),
+            secret: Some("wrong".to_owned()),
+        });
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_unauthenticated_list_matches_gets_no_response() {
+        let config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET_WITH_SECRET).unwrap();
+
+        let (_, output) = process_test_command(&config_set, &IPCCommand {
+            id: LIST_MATCHES_COMMAND_ID.to_owned(),
+            payload: "".to_owned(),
+            secret: None,
+        });
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_authenticated_command_is_accepted() {
+        let config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET_WITH_SECRET).unwrap();
+
+        let (events, _) = process_test_command(&config_set, &IPCCommand {
+            id: "exit".to_owned(),
+            payload: "".to_owned(),
+            secret: Some("s3cr3t".to_owned()),
+        });
+        assert_eq!(events.len(), 1);
+
+        let (_, output) = process_test_command(&config_set, &IPCCommand {
+            id: "unknown".to_owned(),
+            payload: "".to_owned(),
+            secret: Some("s3cr3t".to_owned()),
+        });
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_list_matches_uses_the_configuration_of_the_daemon() {
+        let mut config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET).unwrap();
+        let (event_channel, event_receiver) = mpsc::channel();
+
+        // The daemon switched to another configuration after the server was started
+        let daemon = std::thread::spawn(move || {
+            if let Event::ConfigSet(request) = event_receiver.recv().unwrap() {
+                let current: ConfigSet = serde_yaml::from_str(r###"
+                default:
+                    matches:
+                        - trigger: ":bye"
+                          replace: "goodbye"
+                specific: []
+                "###).unwrap();
+                request.reply.send(Ok(current)).unwrap();
+            }
+        });
+
+        let mut stream = TestStream::new(&IPCCommand {
+            id: LIST_MATCHES_COMMAND_ID.to_owned(),
+            payload: "".to_owned(),
+            secret: None,
+        });
+        process_event(&event_channel, &mut config_set, &Mutex::new(ConfigCache::new()), Ok::<&mut TestStream, std::io::Error>(&mut stream));
+        daemon.join().unwrap();
+
+        let response: ListMatchesResponse = serde_json::from_slice(&stream.output).unwrap();
+        let triggers: Vec<&Vec<String>> = response.matches.iter().map(|m| &m.triggers).collect();
+        assert_eq!(triggers, vec![&vec![":bye".to_owned()]]);
+    }
+
+    #[test]
+    fn test_list_matches_error_sends_error_response() {
+        let mut config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET).unwrap();
+        let (event_channel, event_receiver) = mpsc::channel();
+
+        let daemon = std::thread::spawn(move || {
+            if let Event::ConfigSet(request) = event_receiver.recv().unwrap() {
+                request.reply.send(Err("The configuration is not available".to_owned())).unwrap();
+            }
+        });
+
+        let mut stream = TestStream::new(&IPCCommand {
+            id: LIST_MATCHES_COMMAND_ID.to_owned(),
+            payload: "".to_owned(),
+            secret: None,
+        });
+        process_event(&event_channel, &mut config_set, &Mutex::new(ConfigCache::new()), Ok::<&mut TestStream, std::io::Error>(&mut stream));
+        daemon.join().unwrap();
+
+        let response: ErrorResponse = serde_json::from_slice(&stream.output).unwrap();
+        assert_eq!(response.error, "The configuration is not available");
+    }
+
+    #[test]
+    fn test_trigger_state_commands_are_forwarded() {
+        let config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET).unwrap();
+
+        for (id, expected_disabled) in vec![("disable_trigger", true), ("enable_trigger", false)] {
+            let (events, _) = process_test_command(&config_set, &IPCCommand {
+                id: id.to_owned(),
+                payload: ":hi".to_owned(),
+                secret: None,
+            });
+
+            match events.as_slice() {
+                [Event::TriggerState(TriggerStateRequest { trigger, disabled })] => {
+                    assert_eq!(trigger, ":hi");
+                    assert_eq!(*disabled, expected_disabled);
+                },
+                events => panic!("Unexpected events: {:?}", events),
+            }
+        }
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"s3cr3t", b"s3cr3t"));
+        assert!(constant_time_eq(b"", b""));
+        assert!(!constant_time_eq(b"s3cr3t", b"s3cr3T"));
+        assert!(!constant_time_eq(b"s3cr3t", b"s3cr3"));
+        assert!(!constant_time_eq(b"s3cr3t", b""));
+    }
+
+    #[test]
+    fn test_command_without_configured_secret_is_accepted() {
+        let config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET).unwrap();
+
+        let (events, _) = process_test_command(&config_set, &IPCCommand {
+            id: "toggle".to_owned(),
+            payload: "".to_owned(),
+            secret: None,
+        });
+        assert_eq!(events.len(), 1);
+    }
+
+    fn assert_round_trip(server: &dyn IPCServer, client: &dyn IPCClient, receive_channel: &mpsc::Receiver<Event>) {
+        server.start().unwrap();
+
+        client.send_command(IPCCommand {
+            id: "toggle".to_owned(),
+            payload: "".to_owned(),
+            secret: None,
+        }).unwrap();
+
+        let event = receive_channel.recv_timeout(Duration::from_secs(5)).unwrap();
+        match event {
+            Event::Action(ActionType::Toggle) => {},
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_tcp_transport_round_trip() {
+        // Find a free port for the server
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+        let mut config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET).unwrap();
+        config_set.default.ipc_transport = IPCTransport::Tcp;
+        config_set.default.ipc_server_port = port as i32;
+
+        let (sender, receiver) = mpsc::channel();
+        let server = get_ipc_server(config_set.clone(), sender, Arc::new(Mutex::new(ConfigCache::new()))).unwrap();
+        let client = get_ipc_client(config_set).unwrap();
+
+        assert_round_trip(server.as_ref(), client.as_ref(), &receiver);
+    }
+
+    #[test]
+    fn test_tcp_server_bind_failure_is_reported() {
+        // Keep the port busy, so that the server can't bind to it
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET).unwrap();
+        config_set.default.ipc_transport = IPCTransport::Tcp;
+        config_set.default.ipc_server_port = port as i32;
+
+        let (sender, _receiver) = mpsc::channel();
+        let server = get_ipc_server(config_set, sender, Arc::new(Mutex::new(ConfigCache::new()))).unwrap();
+
+        assert!(server.start().is_err());
+    }
+
+    #[test]
+    #[cfg(not(target_os = "windows"))]
+    fn test_unix_transport_round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let socket_path = dir.path().join("espanso.sock");
+
+        let config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET).unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let server = unix::UnixIPCServer::with_socket_path(config_set, sender, Arc::new(Mutex::new(ConfigCache::new())),
+                                                               socket_path.clone());
+        let client = unix::UnixIPCClient::with_socket_path(socket_path);
+
+        assert_round_trip(&server, &client, &receiver);
+    }
+
+    #[test]
+    fn test_reload_replies_with_outcome_and_updates_the_server_config() {
+        let mut config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET).unwrap();
+        let new_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET_WITH_SECRET).unwrap();
+        let (event_channel, event_receiver) = mpsc::channel();
+
+        // The first set is rejected by the daemon, while the second one is installed
+        let daemon = std::thread::spawn(move || {
+            for result in vec![Err("Rejected".to_owned()), Ok(())] {
+                if let Event::Reload(request) = event_receiver.recv().unwrap() {
+                    assert!(request.config_set.is_ok());
+                    request.reply.send(result).unwrap();
+                }
+            }
+        });
+
+        let response = reload(&event_channel, &mut config_set, Ok(new_set.clone()));
+        assert_eq!(response, ReloadResponse { success: false, error: Some("Rejected".to_owned()) });
+        assert_eq!(config_set.default.ipc_secret, None);
+
+        let response = reload(&event_channel, &mut config_set, Ok(new_set));
+        assert_eq!(response, ReloadResponse { success: true, error: None });
+        assert_eq!(config_set.default.ipc_secret, Some("s3cr3t".to_owned()));
+
+        daemon.join().unwrap();
+    }
+
+    #[test]
+    fn test_switch_profile_saves_the_profile_only_when_accepted() {
+        use crate::config::tests::{create_temp_espanso_directories_with_default_content, create_profile};
+
+        let (data_dir, package_dir) = create_temp_espanso_directories_with_default_content(r###"
+        matches:
+            - trigger: ":base"
+              replace: "base"
+        "###);
+        create_profile(data_dir.path(), "work", r###"
+        matches:
+            - trigger: ":work"
+              replace: "work"
+        "###);
+
+        let mut config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET).unwrap();
+        let (event_channel, event_receiver) = mpsc::channel();
+
+        // The daemon accepts the work profile, rejects the base config and then
+        // reports the error of the missing profile
+        let daemon = std::thread::spawn(move || {
+            for result in vec![Ok(()), Err("Rejected".to_owned())] {
+                if let Event::Reload(request) = event_receiver.recv().unwrap() {
+                    request.reply.send(result).unwrap();
+                }
+            }
+            if let Event::Reload(request) = event_receiver.recv().unwrap() {
+                request.reply.send(request.config_set.map(|_| ()).map_err(|e| e.to_string())).unwrap();
+            }
+        });
+
+        let response = switch_profile(&event_channel, &mut config_set, "work", data_dir.path(), package_dir.path());
+        assert!(response.success);
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":work"));
+        assert_eq!(crate::config::runtime::load_active_profile_from_dir(data_dir.path()), Some("work".to_owned()));
+
+        let response = switch_profile(&event_channel, &mut config_set, "", data_dir.path(), package_dir.path());
+        assert!(!response.success);
+        assert!(config_set.default.matches.iter().any(|m| m.trigger() == ":work"));
+        assert_eq!(crate::config::runtime::load_active_profile_from_dir(data_dir.path()), Some("work".to_owned()));
+
+        let response = switch_profile(&event_channel, &mut config_set, "missing", data_dir.path(), package_dir.path());
+        assert!(!response.success);
+        assert_eq!(crate::config::runtime::load_active_profile_from_dir(data_dir.path()), Some("work".to_owned()));
+
+        daemon.join().unwrap();
+    }
+
+    #[test]
+    fn test_unsupported_transport_is_reported() {
+        let mut config_set: ConfigSet = serde_yaml::from_str(TEST_CONFIG_SET).unwrap();
+        config_set.default.ipc_transport = IPCTransport::Pipe;
+
+        let (sender, _) = mpsc::channel();
+        let error = get_ipc_server(config_set.clone(), sender, Arc::new(Mutex::new(ConfigCache::new()))).err().unwrap();
+        assert!(error.contains("'pipe'"));
+        assert!(get_ipc_client(config_set).is_err());
+    }
+}
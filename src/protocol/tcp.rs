@@ -0,0 +1,100 @@
+/*
+ * This file is part of espanso.
+ *
+ * Copyright (C) 2019 Federico Terzi
+ *
+ * espanso is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * espanso is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with espanso.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use log::{info, warn};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::net::{TcpListener, TcpStream, Shutdown};
+use super::IPCCommand;
+
+use crate::event::*;
+use crate::protocol::{process_event, send_command, send_request};
+use crate::config::{ConfigSet, ConfigCache};
+
+pub struct TcpIPCServer {
+    config_set: ConfigSet,
+    event_channel: Sender<Event>,
+    config_cache: Arc<Mutex<ConfigCache>>,
+}
+
+impl TcpIPCServer {
+    pub fn new(config_set: ConfigSet, event_channel: Sender<Event>, config_cache: Arc<Mutex<ConfigCache>>) -> TcpIPCServer {
+        TcpIPCServer {config_set, event_channel, config_cache}
+    }
+}
+
+impl super::IPCServer for TcpIPCServer {
+    fn start(&self) -> Result<(), String> {
+        let event_channel = self.event_channel.clone();
+        let server_port = self.config_set.default.ipc_server_port;
+        let mut config_set = self.config_set.clone();
+        let config_cache = Arc::clone(&self.config_cache);
+
+        // Bind before spawning the thread, so that the server is ready when start returns
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", server_port))
+            .map_err(|e| format!("Error binding to IPC server port {}: {}", server_port, e))?;
+
+        info!("Binded to IPC tcp socket: {}", listener.local_addr().unwrap().to_string());
+
+        std::thread::Builder::new().name("ipc_server".to_string()).spawn(move || {
+            for stream in listener.incoming() {
+                // Only local processes are allowed to send commands
+                if let Ok(stream) = &stream {
+                    let is_loopback = stream.peer_addr().map(|addr| addr.ip().is_loopback()).unwrap_or(false);
+                    if !is_loopback {
+                        warn!("Rejected IPC connection from non-loopback address");
+                        continue;
+                    }
+                }
+
+                process_event(&event_channel, &mut config_set, &config_cache, stream);
+            }
+        }).expect("Unable to spawn IPC server thread");
+
+        Ok(())
+    }
+}
+
+pub struct TcpIPCClient {
+    config_set: ConfigSet,
+}
+
+impl TcpIPCClient {
+    pub fn new(config_set: ConfigSet) -> TcpIPCClient {
+        TcpIPCClient{config_set}
+    }
+}
+
+impl super::IPCClient for TcpIPCClient {
+    fn send_command(&self, command: IPCCommand) -> Result<(), String> {
+        let stream = TcpStream::connect(
+            ("127.0.0.1", self.config_set.default.ipc_server_port as u16)
+        );
+
+        send_command(command, stream)
+    }
+
+    fn send_request(&self, command: IPCCommand) -> Result<String, String> {
+        let stream = TcpStream::connect(
+            ("127.0.0.1", self.config_set.default.ipc_server_port as u16)
+        );
+
+        send_request(command, stream, |stream| stream.shutdown(Shutdown::Write))
+    }
+}
@@ -18,65 +18,102 @@
  */
 
 use std::os::unix::net::{UnixStream,UnixListener};
+use std::net::Shutdown;
+use std::os::unix::fs::PermissionsExt;
 use log::{info, warn};
 use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::path::PathBuf;
 use super::IPCCommand;
 
 use crate::context;
+use crate::config::{ConfigSet, ConfigCache};
 use crate::event::*;
-use crate::protocol::{process_event, send_command};
+use crate::protocol::{process_event, send_command, send_request};
 
 const UNIX_SOCKET_NAME : &str = "espanso.sock";
 
+fn default_socket_path() -> PathBuf {
+    context::get_data_dir().join(UNIX_SOCKET_NAME)
+}
+
 pub struct UnixIPCServer {
+    config_set: ConfigSet,
     event_channel: Sender<Event>,
+    config_cache: Arc<Mutex<ConfigCache>>,
+    socket_path: PathBuf,
 }
 
 impl UnixIPCServer {
-    pub fn new(event_channel: Sender<Event>) -> UnixIPCServer {
-        UnixIPCServer {event_channel}
+    pub fn new(config_set: ConfigSet, event_channel: Sender<Event>, config_cache: Arc<Mutex<ConfigCache>>) -> UnixIPCServer {
+        UnixIPCServer::with_socket_path(config_set, event_channel, config_cache, default_socket_path())
+    }
+
+    pub fn with_socket_path(config_set: ConfigSet, event_channel: Sender<Event>, config_cache: Arc<Mutex<ConfigCache>>,
+                            socket_path: PathBuf) -> UnixIPCServer {
+        UnixIPCServer {config_set, event_channel, config_cache, socket_path}
     }
 }
 
 impl super::IPCServer for UnixIPCServer {
-    fn start(&self) {
+    fn start(&self) -> Result<(), String> {
         let event_channel = self.event_channel.clone();
-        std::thread::Builder::new().name("ipc_server".to_string()).spawn(move || {
-            let espanso_dir = context::get_data_dir();
-            let unix_socket = espanso_dir.join(UNIX_SOCKET_NAME);
+        let mut config_set = self.config_set.clone();
+        let config_cache = Arc::clone(&self.config_cache);
+        let unix_socket = self.socket_path.clone();
 
+        if unix_socket.exists() {
             std::fs::remove_file(unix_socket.clone()).unwrap_or_else(|e| {
                 warn!("Unable to delete Unix socket: {}", e);
             });
-            let listener = UnixListener::bind(unix_socket.clone()).expect("Can't bind to Unix Socket");
+        }
+
+        // Bind before spawning the thread, so that the server is ready when start returns
+        let listener = UnixListener::bind(unix_socket.clone())
+            .map_err(|e| format!("Can't bind to Unix Socket {}: {}", unix_socket.display(), e))?;
 
-            info!("Binded to IPC unix socket: {}", unix_socket.as_path().display());
+        // Only the current user is allowed to send commands
+        std::fs::set_permissions(&unix_socket, std::fs::Permissions::from_mode(0o600)).unwrap_or_else(|e| {
+            warn!("Unable to restrict the Unix socket permissions: {}", e);
+        });
 
+        info!("Binded to IPC unix socket: {}", unix_socket.as_path().display());
+
+        std::thread::Builder::new().name("ipc_server".to_string()).spawn(move || {
             for stream in listener.incoming() {
-                process_event(&event_channel, stream);
+                process_event(&event_channel, &mut config_set, &config_cache, stream);
             }
         }).expect("Unable to spawn IPC server thread");
+
+        Ok(())
     }
 }
 
 pub struct UnixIPCClient {
-
+    socket_path: PathBuf,
 }
 
 impl UnixIPCClient {
     pub fn new() -> UnixIPCClient {
-        UnixIPCClient{}
+        UnixIPCClient::with_socket_path(default_socket_path())
+    }
+
+    pub fn with_socket_path(socket_path: PathBuf) -> UnixIPCClient {
+        UnixIPCClient{socket_path}
     }
 }
 
 impl super::IPCClient for UnixIPCClient {
     fn send_command(&self, command: IPCCommand) -> Result<(), String> {
-        let espanso_dir = context::get_data_dir();
-        let unix_socket = espanso_dir.join(UNIX_SOCKET_NAME);
-
         // Open the stream
-        let stream = UnixStream::connect(unix_socket);
+        let stream = UnixStream::connect(&self.socket_path);
 
         send_command(command, stream)
     }
+
+    fn send_request(&self, command: IPCCommand) -> Result<String, String> {
+        let stream = UnixStream::connect(&self.socket_path);
+
+        send_request(command, stream, |stream| stream.shutdown(Shutdown::Write))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,95 @@
+/*
+ * This file is part of espanso.
+ *
+ * Copyright (C) 2020 Federico Terzi
+ *
+ * espanso is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * espanso is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with espanso.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::HashMap;
+use regex::{Regex, Captures};
+
+/*
+ * Sections of a replacement included only if a variable has a non-empty value,
+ * such as {{#if name}}Hi {{name}}{{/if}}. They are evaluated once the variables
+ * are rendered, so that the excluded sections don't need any of their values.
+ * Sections can't be nested: the first {{/if}} closes the section it follows.
+ */
+
+lazy_static! {
+    static ref CONDITIONAL_REGEX: Regex = Regex::new("(?s)\\{\\{\\s*#if\\s+(?P<name>\\w+)\\s*\\}\\}(?P<body>.*?)\\{\\{\\s*/if\\s*\\}\\}").unwrap();
+}
+
+/// Check if the text contains at least a conditional section.
+pub fn has_conditionals(text: &str) -> bool {
+    CONDITIONAL_REGEX.is_match(text)
+}
+
+/// Replace each conditional section with its body if the variable has a non-empty
+/// value in the given map, or remove it otherwise.
+pub fn render_conditionals(text: &str, output_map: &HashMap<String, String>) -> String {
+    CONDITIONAL_REGEX.replace_all(text, |caps: &Captures| {
+        let is_set = output_map.get(&caps["name"]).map_or(false, |value| !value.is_empty());
+        if is_set {
+            caps["body"].to_owned()
+        }else{
+            String::new()
+        }
+    }).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output_map(values: &[(&str, &str)]) -> HashMap<String, String> {
+        values.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_render_conditionals_included_when_set() {
+        let map = output_map(&[("name", "John")]);
+        assert_eq!(render_conditionals("Hi{{#if name}} {{name}}{{/if}}!", &map), "Hi {{name}}!");
+    }
+
+    #[test]
+    fn test_render_conditionals_excluded_when_empty() {
+        let map = output_map(&[("name", "")]);
+        assert_eq!(render_conditionals("Hi{{#if name}} {{name}}{{/if}}!", &map), "Hi!");
+    }
+
+    #[test]
+    fn test_render_conditionals_excluded_when_missing() {
+        assert_eq!(render_conditionals("Hi{{#if name}} there{{/if}}!", &HashMap::new()), "Hi!");
+    }
+
+    #[test]
+    fn test_render_conditionals_multiline_and_spaces() {
+        let map = output_map(&[("a", "1"), ("b", "")]);
+        assert_eq!(render_conditionals("{{ #if a }}first\nline{{ /if }}{{#if b}}second{{/if}}", &map), "first\nline");
+    }
+
+    #[test]
+    fn test_render_conditionals_nested_sections_are_not_supported() {
+        let map = output_map(&[("a", "1"), ("b", "1")]);
+        assert_eq!(render_conditionals("{{#if a}}x{{#if b}}y{{/if}}z{{/if}}", &map), "x{{#if b}}yz{{/if}}");
+    }
+
+    #[test]
+    fn test_has_conditionals() {
+        assert!(has_conditionals("{{#if a}}x{{/if}}"));
+        assert!(!has_conditionals("{{a}}"));
+        assert!(!has_conditionals("{{#if a}}x"));
+    }
+}
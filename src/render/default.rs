@@ -19,23 +19,39 @@
 
 use serde_yaml::{Mapping, Value};
 use std::path::PathBuf;
+use std::fs;
 use std::collections::HashMap;
+use std::cell::RefCell;
 use regex::{Regex, Captures};
-use log::{warn, error};
+use log::{debug, warn, error};
 use super::*;
-use crate::matcher::{Match, MatchContentType};
+use crate::matcher::{Match, MatchContentType, MatchVariable};
 use crate::config::Configs;
 use crate::extension::Extension;
+use crate::clipboard::ClipboardManager;
+use crate::render::filter::apply_filters;
+use crate::render::conditional::{has_conditionals, render_conditionals};
+
+// Name of the built-in variable replaced with the trigger of the match
+pub(crate) const TRIGGER_VAR_NAME: &str = "trigger";
+
+// Name of the built-in variable replaced with the current clipboard content
+pub(crate) const CLIPBOARD_VAR_NAME: &str = "clipboard";
 
 lazy_static! {
-    static ref VAR_REGEX: Regex = Regex::new("\\{\\{\\s*(?P<name>\\w+)\\s*\\}\\}").unwrap();
+    // Variables can be followed by a chain of filters, such as {{clipboard|trim|upper}}
+    pub(crate) static ref VAR_REGEX: Regex = Regex::new("\\{\\{\\s*(?P<name>\\w+)(?P<filters>(?:\\s*\\|\\s*\\w+)*)\\s*\\}\\}").unwrap();
 }
 
 pub struct DefaultRenderer {
     extension_map: HashMap<String, Box<dyn Extension>>,
 
-    // Regex used to identify matches (and arguments) in passive expansions
-    passive_match_regex: Regex,
+    // Regex used to identify matches (and arguments) in passive expansions,
+    // compiled again when the configuration changes it
+    passive_match_regex: RefCell<Regex>,
+
+    // Source of the clipboard variable, if None the variable is always empty
+    clipboard_manager: Option<Box<dyn ClipboardManager>>,
 }
 
 impl DefaultRenderer {
@@ -54,7 +70,25 @@ impl DefaultRenderer {
 
         DefaultRenderer{
             extension_map,
-            passive_match_regex,
+            passive_match_regex: RefCell::new(passive_match_regex),
+            clipboard_manager: None,
+        }
+    }
+
+    pub fn set_clipboard_manager(&mut self, clipboard_manager: Box<dyn ClipboardManager>) {
+        self.clipboard_manager = Some(clipboard_manager);
+    }
+
+    fn clipboard_content(&self) -> String {
+        let content = self.clipboard_manager.as_ref()
+            .and_then(|clipboard_manager| clipboard_manager.get_clipboard());
+
+        match content {
+            Some(content) if !content.is_empty() => content,
+            _ => {
+                debug!("The clipboard is empty or doesn't contain text, the clipboard variable will be empty");
+                "".to_owned()
+            },
         }
     }
 
@@ -63,7 +97,7 @@ impl DefaultRenderer {
 
         // TODO: if performances become a problem, implement a more efficient lookup
         for m in config.matches.iter() {
-            if m.trigger == trigger {
+            if m.triggers().iter().any(|t| t == trigger) {
                 result = Some(m.clone());
                 break;
             }
@@ -73,17 +107,158 @@ impl DefaultRenderer {
     }
 }
 
+/// Sort the variables so that each one comes after the variables referenced in its params,
+/// keeping the declaration order otherwise. If the references form a cycle, the names of the
+/// variables involved are returned as error.
+fn sort_variables<'a>(variables: Vec<&'a MatchVariable>) -> Result<Vec<&'a MatchVariable>, Vec<String>> {
+    // Local variables shadow the global ones with the same name
+    let mut name_index = HashMap::new();
+    for (i, variable) in variables.iter().enumerate() {
+        name_index.insert(variable.name.as_str(), i);
+    }
+
+    let dependencies: Vec<Vec<usize>> = variables.iter().map(|variable| {
+        let mut references = Vec::new();
+        for (_, value) in variable.params.iter() {
+            collect_var_references(value, &mut references);
+        }
+        references.iter().filter_map(|name| name_index.get(name.as_str()).cloned()).collect()
+    }).collect();
+
+    // 0: not visited, 1: in the current path, 2: sorted
+    let mut state = vec![0; variables.len()];
+    let mut sorted = Vec::new();
+
+    fn visit(i: usize, dependencies: &Vec<Vec<usize>>, state: &mut Vec<u8>,
+             path: &mut Vec<usize>, sorted: &mut Vec<usize>) -> Result<(), Vec<usize>> {
+        match state[i] {
+            2 => return Ok(()),
+            1 => {
+                let start = path.iter().position(|&p| p == i).unwrap();
+                let mut cycle = path[start..].to_vec();
+                cycle.push(i);
+                return Err(cycle);
+            },
+            _ => {},
+        }
+
+        state[i] = 1;
+        path.push(i);
+        for &dependency in dependencies[i].iter() {
+            visit(dependency, dependencies, state, path, sorted)?;
+        }
+        path.pop();
+        state[i] = 2;
+        sorted.push(i);
+
+        Ok(())
+    }
+
+    for i in 0..variables.len() {
+        let mut path = Vec::new();
+        if let Err(cycle) = visit(i, &dependencies, &mut state, &mut path, &mut sorted) {
+            return Err(cycle.into_iter().map(|i| variables[i].name.clone()).collect());
+        }
+    }
+
+    Ok(sorted.into_iter().map(|i| variables[i]).collect())
+}
+
+// Find the names of the variables used in the strings contained in the given value
+fn collect_var_references(value: &Value, references: &mut Vec<String>) {
+    match value {
+        Value::String(s) => {
+            for caps in VAR_REGEX.captures_iter(s) {
+                references.push(caps["name"].to_owned());
+            }
+        },
+        Value::Sequence(seq) => {
+            for item in seq.iter() {
+                collect_var_references(item, references);
+            }
+        },
+        Value::Mapping(map) => {
+            for (_, item) in map.iter() {
+                collect_var_references(item, references);
+            }
+        },
+        _ => {},
+    }
+}
+
+// Replace the references to the already evaluated variables in the strings of the given value
+fn render_var_references(value: &Value, output_map: &HashMap<String, String>) -> Value {
+    match value {
+        Value::String(s) => {
+            let result = VAR_REGEX.replace_all(s, |caps: &Captures| {
+                match output_map.get(&caps["name"]) {
+                    Some(output) => apply_filters(output, &caps["filters"]),
+                    None => caps[0].to_owned(),
+                }
+            });
+            Value::String(result.into_owned())
+        },
+        Value::Sequence(seq) => {
+            Value::Sequence(seq.iter().map(|item| render_var_references(item, output_map)).collect())
+        },
+        Value::Mapping(map) => Value::Mapping(render_params(map, output_map)),
+        _ => value.clone(),
+    }
+}
+
+fn render_params(params: &Mapping, output_map: &HashMap<String, String>) -> Mapping {
+    params.iter().map(|(key, value)| {
+        (key.clone(), render_var_references(value, output_map))
+    }).collect()
+}
+
 impl super::Renderer for DefaultRenderer {
-    fn render_match(&self, m: &Match, config: &Configs, args: Vec<String>) -> RenderResult {
+    fn render_match(&self, m: &Match, trigger: &str, config: &Configs, args: Vec<String>) -> RenderResult {
         // Manage the different types of matches
         match &m.content {
             // Text Match
             MatchContentType::Text(content) => {
-                let target_string = if content._has_vars || !config.global_vars.is_empty(){
+                // Replacements stored in a file are read each time the match is triggered
+                let file_content;
+                let (replace, has_vars) = match &content.replace_file {
+                    Some(replace_file) => {
+                        match fs::read_to_string(replace_file) {
+                            Ok(replace) => {
+                                file_content = replace;
+                                (&file_content, VAR_REGEX.is_match(&file_content) || has_conditionals(&file_content))
+                            },
+                            Err(e) => {
+                                warn!("Unable to read replace file {:?}, skipping the expansion: {}", replace_file, e);
+                                return RenderResult::Error;
+                            },
+                        }
+                    },
+                    None => (&content.replace, content._has_vars),
+                };
+
+                let target_string = if has_vars || !config.global_vars.is_empty(){
                     let mut output_map = HashMap::new();
 
-                    // Cycle through both the local and global variables
-                    for variable in config.global_vars.iter().chain(&content.vars) {
+                    // The typed trigger is always available, but user defined variables can shadow it
+                    output_map.insert(TRIGGER_VAR_NAME.to_owned(), trigger.to_owned());
+
+                    // The same goes for the clipboard, which is read only if used
+                    if VAR_REGEX.captures_iter(replace).any(|caps| &caps["name"] == CLIPBOARD_VAR_NAME) {
+                        output_map.insert(CLIPBOARD_VAR_NAME.to_owned(), self.clipboard_content());
+                    }
+
+                    // Cycle through both the local and global variables, evaluating first
+                    // the ones referenced in the params of the others
+                    let variables = config.global_vars.iter().chain(&content.vars).collect();
+                    let variables = match sort_variables(variables) {
+                        Ok(variables) => variables,
+                        Err(cycle) => {
+                            error!("Circular reference between the variables of match '{}': {}", m.trigger(), cycle.join(" -> "));
+                            return RenderResult::Error;
+                        },
+                    };
+
+                    for variable in variables {
                         // In case of variables of type match, we need to recursively call
                         // the render function
                         if variable.var_type == "match" {
@@ -93,13 +268,13 @@ impl super::Renderer for DefaultRenderer {
                                 warn!("Missing param 'trigger' in match variable: {}", variable.name);
                                 continue;
                             }
-                            let trigger = trigger.unwrap();
+                            let inner_trigger = trigger.unwrap().as_str().unwrap_or("");
 
                             // Find the given match from the active configs
-                            let inner_match = DefaultRenderer::find_match(config, trigger.as_str().unwrap_or(""));
+                            let inner_match = DefaultRenderer::find_match(config, inner_trigger);
 
                             if inner_match.is_none() {
-                                warn!("Could not find inner match with trigger: '{}'", trigger.as_str().unwrap_or("undefined"));
+                                warn!("Could not find inner match with trigger: '{}'", inner_trigger);
                                 continue
                             }
 
@@ -107,7 +282,7 @@ impl super::Renderer for DefaultRenderer {
 
                             // Render the inner match
                             // TODO: inner arguments
-                            let result = self.render_match(&inner_match, config, vec![]);
+                            let result = self.render_match(&inner_match, inner_trigger, config, vec![]);
 
                             // Inner matches are only supported for text-expansions, warn the user otherwise
                             match result {
@@ -121,7 +296,8 @@ impl super::Renderer for DefaultRenderer {
                         }else{  // Normal extension variables
                             let extension = self.extension_map.get(&variable.var_type);
                             if let Some(extension) = extension {
-                                let ext_out = extension.calculate(&variable.params, &args);
+                                let params = render_params(&variable.params, &output_map);
+                                let ext_out = extension.calculate(&params, &args);
                                 if let Some(output) = ext_out {
                                     output_map.insert(variable.name.clone(), output);
                                 }else{
@@ -134,16 +310,17 @@ impl super::Renderer for DefaultRenderer {
                         }
                     }
 
-                    // Replace the variables
-                    let result = VAR_REGEX.replace_all(&content.replace, |caps: &Captures| {
+                    // Drop the conditional sections whose variable is empty, then replace the variables
+                    let replace = render_conditionals(replace, &output_map);
+                    let result = VAR_REGEX.replace_all(&replace, |caps: &Captures| {
                         let var_name = caps.name("name").unwrap().as_str();
-                        let output = output_map.get(var_name);
-                        output.unwrap()
+                        let output = output_map.get(var_name).unwrap();
+                        apply_filters(output, &caps["filters"])
                     });
 
                     result.to_string()
                 }else{  // No variables, simple text substitution
-                    content.replace.clone()
+                    replace.clone()
                 };
 
                 // Render any argument that may be present
@@ -166,8 +343,16 @@ impl super::Renderer for DefaultRenderer {
     }
 
     fn render_passive(&self, text: &str, config: &Configs) -> RenderResult {
+        if self.passive_match_regex.borrow().as_str() != config.passive_match_regex {
+            match Regex::new(&config.passive_match_regex) {
+                Ok(regex) => *self.passive_match_regex.borrow_mut() = regex,
+                Err(e) => warn!("Invalid passive match regex, keeping the previous one: {}", e),
+            }
+        }
+
         // Render the matches
-        let result = self.passive_match_regex.replace_all(&text, |caps: &Captures| {
+        let passive_match_regex = self.passive_match_regex.borrow();
+        let result = passive_match_regex.replace_all(&text, |caps: &Captures| {
             let match_name = if let Some(name) = caps.name("name") {
                 name.as_str()
             }else{
@@ -198,7 +383,7 @@ impl super::Renderer for DefaultRenderer {
 
             let m = m.unwrap();
             // Render the actual match
-            let result = self.render_match(&m, &config, args);
+            let result = self.render_match(&m, match_name, &config, args);
 
             match result {
                 RenderResult::Text(out) => {
@@ -341,6 +526,31 @@ mod tests {
         verify_render(rendered, "hi john");
     }
 
+    #[test]
+    fn test_render_passive_nested_match_by_secondary_trigger() {
+        let text = ":greet";
+
+        let config = get_config_for(r###"
+        matches:
+            - trigger: ':greet'
+              replace: "hi {{name}}"
+              vars:
+                - name: name
+                  type: match
+                  params:
+                    trigger: ":n"
+
+            - triggers: [':name', ':n']
+              replace: john
+        "###);
+
+        let renderer = get_renderer(config.clone());
+
+        let rendered = renderer.render_passive(text, &config);
+
+        verify_render(rendered, "hi john");
+    }
+
     #[test]
     fn test_render_passive_simple_match_with_args() {
         let text = ":greet/Jon/";
@@ -481,4 +691,288 @@ mod tests {
 
         verify_render(rendered, "this is my local");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_render_match_var_referencing_another_var() {
+        let config = get_config_for(r###"
+        matches:
+            - trigger: ':test'
+              replace: "{{greeting}}"
+              vars:
+                - name: greeting
+                  type: dummy
+                  params:
+                    echo: "hello {{name}}"
+                - name: name
+                  type: dummy
+                  params:
+                    echo: "{{first}} doe"
+                - name: first
+                  type: dummy
+                  params:
+                    echo: "john"
+        "###);
+
+        let renderer = get_renderer(config.clone());
+
+        let rendered = renderer.render_match(&config.matches[0], config.matches[0].trigger(), &config, vec![]);
+
+        verify_render(rendered, "hello john doe");
+    }
+
+    fn render_conditional_match(name: &str) -> RenderResult {
+        let config = get_config_for(&format!(r###"
+        matches:
+            - trigger: ':greet'
+              replace: "Hello{{{{#if name}}}} {{{{name}}}}{{{{/if}}}}, welcome!"
+              vars:
+                - name: name
+                  type: dummy
+                  params:
+                    echo: "{}"
+        "###, name));
+
+        let renderer = get_renderer(config.clone());
+
+        renderer.render_match(&config.matches[0], config.matches[0].trigger(), &config, vec![])
+    }
+
+    #[test]
+    fn test_render_match_conditional_section_included() {
+        verify_render(render_conditional_match("John"), "Hello John, welcome!");
+    }
+
+    #[test]
+    fn test_render_match_conditional_section_excluded() {
+        verify_render(render_conditional_match(""), "Hello, welcome!");
+    }
+
+    #[test]
+    fn test_render_match_conditional_without_var_reference() {
+        let config = get_config_for(r###"
+        matches:
+            - trigger: ':sig'
+              replace: "Best regards{{#if title}}\nCEO{{/if}}"
+              vars:
+                - name: title
+                  type: dummy
+                  params:
+                    echo: "yes"
+        "###);
+
+        let renderer = get_renderer(config.clone());
+
+        let rendered = renderer.render_match(&config.matches[0], config.matches[0].trigger(), &config, vec![]);
+
+        verify_render(rendered, "Best regards\nCEO");
+    }
+
+    #[test]
+    fn test_render_match_var_cycle_is_an_error() {
+        let config = get_config_for(r###"
+        matches:
+            - trigger: ':test'
+              replace: "{{first}}"
+              vars:
+                - name: first
+                  type: dummy
+                  params:
+                    echo: "{{second}}"
+                - name: second
+                  type: dummy
+                  params:
+                    echo: "{{first}}"
+        "###);
+
+        let renderer = get_renderer(config.clone());
+
+        let rendered = renderer.render_match(&config.matches[0], config.matches[0].trigger(), &config, vec![]);
+
+        match rendered {
+            RenderResult::Error => {},
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_sort_variables_reports_cycle() {
+        let config = get_config_for(r###"
+        global_vars:
+            - name: a
+              type: dummy
+              params:
+                echo: "{{b}}"
+            - name: b
+              type: dummy
+              params:
+                echo: "{{c}}"
+            - name: c
+              type: dummy
+              params:
+                echo: "{{a}}"
+        "###);
+
+        let cycle = sort_variables(config.global_vars.iter().collect()).unwrap_err();
+
+        assert_eq!(cycle, vec!["a", "b", "c", "a"]);
+    }
+
+    struct TestClipboardManager {
+        content: Option<String>,
+    }
+
+    impl ClipboardManager for TestClipboardManager {
+        fn get_clipboard(&self) -> Option<String> {
+            self.content.clone()
+        }
+
+        fn set_clipboard(&self, _payload: &str) {}
+
+        fn set_clipboard_image(&self, _image_path: &std::path::Path) {}
+    }
+
+    #[test]
+    fn test_render_match_clipboard_var() {
+        let config = get_config_for(r###"
+        matches:
+            - trigger: ':bold'
+              replace: "<b>{{clipboard}}</b>"
+        "###);
+
+        let mut renderer = get_renderer(config.clone());
+        renderer.set_clipboard_manager(Box::new(TestClipboardManager { content: Some("copied text".to_owned()) }));
+
+        let rendered = renderer.render_match(&config.matches[0], config.matches[0].trigger(), &config, vec![]);
+
+        verify_render(rendered, "<b>copied text</b>");
+    }
+
+    #[test]
+    fn test_render_match_clipboard_var_with_filters() {
+        let config = get_config_for(r###"
+        matches:
+            - trigger: ':bold'
+              replace: "<b>{{clipboard|trim|upper}}</b>"
+        "###);
+
+        let mut renderer = get_renderer(config.clone());
+        renderer.set_clipboard_manager(Box::new(TestClipboardManager { content: Some("  copied text\n".to_owned()) }));
+
+        let rendered = renderer.render_match(&config.matches[0], config.matches[0].trigger(), &config, vec![]);
+
+        verify_render(rendered, "<b>COPIED TEXT</b>");
+    }
+
+    #[test]
+    fn test_render_match_clipboard_var_empty_clipboard() {
+        let config = get_config_for(r###"
+        matches:
+            - trigger: ':bold'
+              replace: "<b>{{clipboard}}</b>"
+        "###);
+
+        let mut renderer = get_renderer(config.clone());
+        renderer.set_clipboard_manager(Box::new(TestClipboardManager { content: None }));
+
+        let rendered = renderer.render_match(&config.matches[0], config.matches[0].trigger(), &config, vec![]);
+
+        verify_render(rendered, "<b></b>");
+    }
+
+    #[test]
+    fn test_render_match_trigger_var() {
+        let config = get_config_for(r###"
+        matches:
+            - trigger: ':todo'
+              replace: "TODO(me) from {{trigger}}: "
+        "###);
+
+        let renderer = get_renderer(config.clone());
+
+        let rendered = renderer.render_match(&config.matches[0], config.matches[0].trigger(), &config, vec![]);
+
+        verify_render(rendered, "TODO(me) from :todo: ");
+    }
+
+    #[test]
+    fn test_render_match_trigger_var_is_the_typed_trigger() {
+        let config = get_config_for(r###"
+        matches:
+            - triggers: [':todo', ':td']
+              replace: "from {{trigger}}"
+        "###);
+
+        let renderer = get_renderer(config.clone());
+
+        let rendered = renderer.render_match(&config.matches[0], ":td", &config, vec![]);
+
+        verify_render(rendered, "from :td");
+    }
+
+    #[test]
+    fn test_render_passive_trigger_var() {
+        let text = "this is :test/arg/";
+
+        let config = get_config_for(r###"
+        matches:
+            - trigger: ':test'
+              replace: "{{trigger}} with $0$"
+        "###);
+
+        let renderer = get_renderer(config.clone());
+
+        let rendered = renderer.render_passive(text, &config);
+
+        verify_render(rendered, "this is :test with arg");
+    }
+
+    #[test]
+    fn test_render_match_trigger_var_is_overridden_by_local() {
+        let config = get_config_for(r###"
+        matches:
+            - trigger: ':test'
+              replace: "{{trigger}}"
+              vars:
+                - name: "trigger"
+                  type: dummy
+                  params:
+                    echo: "local"
+        "###);
+
+        let renderer = get_renderer(config.clone());
+
+        let rendered = renderer.render_match(&config.matches[0], config.matches[0].trigger(), &config, vec![]);
+
+        verify_render(rendered, "local");
+    }
+
+    #[test]
+    fn test_render_match_replace_file_read_at_trigger_time() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file_path = dir.path().join("disclaimer.txt");
+
+        let mut config = get_config_for(r###"
+        matches:
+            - trigger: ':legal'
+              replace_file: "disclaimer.txt"
+        "###);
+        config.matches[0].load_replace_file(dir.path());
+
+        let renderer = get_renderer(config.clone());
+
+        // Missing files skip the expansion
+        let rendered = renderer.render_match(&config.matches[0], config.matches[0].trigger(), &config, vec![]);
+        match rendered {
+            RenderResult::Error => {},
+            _ => assert!(false),
+        }
+
+        fs::write(&file_path, "first {{trigger}}").unwrap();
+        let rendered = renderer.render_match(&config.matches[0], config.matches[0].trigger(), &config, vec![]);
+        verify_render(rendered, "first :legal");
+
+        fs::write(&file_path, "second").unwrap();
+        let rendered = renderer.render_match(&config.matches[0], config.matches[0].trigger(), &config, vec![]);
+        verify_render(rendered, "second");
+    }
+}
@@ -0,0 +1,127 @@
+/*
+ * This file is part of espanso.
+ *
+ * Copyright (C) 2020 Federico Terzi
+ *
+ * espanso is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * espanso is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with espanso.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use log::warn;
+use super::default::VAR_REGEX;
+
+/*
+ * Filters applied to the value of a variable with the pipe syntax, such as
+ * {{clipboard|upper}} or {{output|trim|lower}}. They are applied from left to right.
+ */
+
+/// Apply the filter with the given name to the value, returning None if the filter is unknown.
+pub fn apply_filter(name: &str, value: &str) -> Option<String> {
+    let result = match name {
+        "upper" => value.to_uppercase(),
+        "lower" => value.to_lowercase(),
+        "trim" => value.trim().to_owned(),
+        // Only the first char is changed, the rest of the value is left untouched
+        "capitalize" => {
+            let mut chars = value.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        },
+        "reverse" => value.chars().rev().collect(),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+// Split the filters captured after the name of a variable, such as "|upper|trim"
+fn parse_filters(filters: &str) -> impl Iterator<Item=&str> {
+    filters.split('|').map(str::trim).filter(|filter| !filter.is_empty())
+}
+
+/// Apply the given chain of filters to the value. The unknown filters are
+/// skipped with a warning, as they can't always be detected at load time.
+pub fn apply_filters(value: &str, filters: &str) -> String {
+    let mut result = value.to_owned();
+    for filter in parse_filters(filters) {
+        match apply_filter(filter, &result) {
+            Some(filtered) => result = filtered,
+            None => warn!("Unknown filter '{}', it will be ignored", filter),
+        }
+    }
+
+    result
+}
+
+/// Return the unknown filters used by the variables of the given text.
+pub fn find_unknown_filters(text: &str) -> Vec<String> {
+    VAR_REGEX.captures_iter(text)
+        .filter_map(|caps| caps.name("filters"))
+        .flat_map(|filters| parse_filters(filters.as_str()).map(str::to_owned).collect::<Vec<String>>())
+        .filter(|filter| apply_filter(filter, "").is_none())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_upper() {
+        assert_eq!(apply_filter("upper", "Hello World"), Some("HELLO WORLD".to_owned()));
+    }
+
+    #[test]
+    fn test_filter_lower() {
+        assert_eq!(apply_filter("lower", "Hello World"), Some("hello world".to_owned()));
+    }
+
+    #[test]
+    fn test_filter_trim() {
+        assert_eq!(apply_filter("trim", "  Hello World\n"), Some("Hello World".to_owned()));
+    }
+
+    #[test]
+    fn test_filter_capitalize() {
+        assert_eq!(apply_filter("capitalize", "hello World"), Some("Hello World".to_owned()));
+        assert_eq!(apply_filter("capitalize", ""), Some("".to_owned()));
+    }
+
+    #[test]
+    fn test_filter_reverse() {
+        assert_eq!(apply_filter("reverse", "abc"), Some("cba".to_owned()));
+    }
+
+    #[test]
+    fn test_filter_unknown() {
+        assert_eq!(apply_filter("shout", "abc"), None);
+    }
+
+    #[test]
+    fn test_apply_filters_chain() {
+        assert_eq!(apply_filters("  hello ", "|trim|upper"), "HELLO");
+        assert_eq!(apply_filters("  hello ", " | trim | reverse "), "olleh");
+    }
+
+    #[test]
+    fn test_apply_filters_unknown_is_noop() {
+        assert_eq!(apply_filters("hello", "|shout|upper"), "HELLO");
+    }
+
+    #[test]
+    fn test_find_unknown_filters() {
+        assert_eq!(find_unknown_filters("{{clipboard|upper}} {{output | shout}} {{name}}"), vec!["shout".to_owned()]);
+    }
+}
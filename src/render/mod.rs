@@ -23,10 +23,12 @@ use crate::config::Configs;
 
 pub(crate) mod default;
 pub(crate) mod utils;
+pub(crate) mod filter;
+pub(crate) mod conditional;
 
 pub trait Renderer {
-    // Render a match output
-    fn render_match(&self, m: &Match, config: &Configs, args: Vec<String>) -> RenderResult;
+    // Render a match output, given the trigger that was typed among the ones of the match
+    fn render_match(&self, m: &Match, trigger: &str, config: &Configs, args: Vec<String>) -> RenderResult;
 
     // Render a passive expansion text
     fn render_passive(&self, text: &str, config: &Configs) -> RenderResult;
@@ -0,0 +1,364 @@
+/*
+ * This file is part of espanso.
+ *
+ * Copyright (C) 2019 Federico Terzi
+ *
+ * espanso is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * espanso is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with espanso.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::fs;
+use log::warn;
+use crate::matcher::Match;
+
+const USAGE_STATS_FILE_NAME: &str = "usage_stats.json";
+
+// Minimum time between two writes of the usage stats file
+const USAGE_STATS_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    // Serializes the accesses to the usage stats file, so that concurrent updates are not lost
+    static ref USAGE_STATS_FILE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Return the number of expansions of each trigger saved in the given directory.
+/// If the file is missing or corrupted, no count is returned.
+pub fn load_stats(dir: &Path) -> HashMap<String, u64> {
+    let _lock = USAGE_STATS_FILE_LOCK.lock().unwrap();
+    read_stats_file(&dir.join(USAGE_STATS_FILE_NAME))
+}
+
+/// Increment the saved count of the given trigger, without going through a UsageStats.
+pub fn record_expansion(dir: &Path, trigger: &str) {
+    let file = dir.join(USAGE_STATS_FILE_NAME);
+
+    let _lock = USAGE_STATS_FILE_LOCK.lock().unwrap();
+    let mut counts = read_stats_file(&file);
+    *counts.entry(trigger.to_owned()).or_insert(0) += 1;
+
+    if let Err(e) = write_stats_file(&file, &counts) {
+        warn!("Unable to save the usage stats: {}", e);
+    }
+}
+
+fn read_stats_file(file: &Path) -> HashMap<String, u64> {
+    match fs::read_to_string(file) {
+        Ok(content) => {
+            serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!("Unable to parse the usage stats file, resetting it: {}", e);
+                HashMap::new()
+            })
+        },
+        Err(_) => HashMap::new(),
+    }
+}
+
+// The counts are written to a temporary file first, so that the readers never see a partial file
+fn write_stats_file(file: &Path, counts: &HashMap<String, u64>) -> Result<(), String> {
+    let content = serde_json::to_string(counts).map_err(|e| e.to_string())?;
+
+    let tmp_file = file.with_extension("json.tmp");
+    fs::write(&tmp_file, content).map_err(|e| e.to_string())?;
+    fs::rename(&tmp_file, file).map_err(|e| e.to_string())
+}
+
+/*
+ * Counters of the expansions performed since espanso started.
+ * Updating them only requires atomic increments, the lock is
+ * taken for writing only the first time a trigger is expanded.
+ */
+pub struct ExpansionStats {
+    start_time: Instant,
+    total: AtomicUsize,
+    per_trigger: RwLock<HashMap<String, AtomicUsize>>,
+}
+
+// Serializable view of the stats, sent to the IPC clients
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StatsSnapshot {
+    pub total_expansions: usize,
+    pub trigger_counts: HashMap<String, usize>,
+    pub uptime_secs: u64,
+    pub active_config: String,
+
+    // Triggers expanded the most across sessions, with their count, see UsageStats::most_used
+    #[serde(default)]
+    pub most_used: Vec<(String, u64)>,
+}
+
+impl ExpansionStats {
+    pub fn new() -> ExpansionStats {
+        ExpansionStats {
+            start_time: Instant::now(),
+            total: AtomicUsize::new(0),
+            per_trigger: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Record a successful expansion of the given trigger.
+    pub fn record(&self, trigger: &str) {
+        self.total.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(count) = self.per_trigger.read().unwrap().get(trigger) {
+            count.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        self.per_trigger.write().unwrap()
+            .entry(trigger.to_owned())
+            .or_insert_with(|| AtomicUsize::new(0))
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, active_config: &str) -> StatsSnapshot {
+        let trigger_counts = self.per_trigger.read().unwrap().iter()
+            .map(|(trigger, count)| (trigger.clone(), count.load(Ordering::Relaxed)))
+            .collect();
+
+        StatsSnapshot {
+            total_expansions: self.total.load(Ordering::Relaxed),
+            trigger_counts,
+            uptime_secs: self.start_time.elapsed().as_secs(),
+            active_config: active_config.to_owned(),
+            most_used: Vec::new(),
+        }
+    }
+}
+
+/*
+ * Number of times each trigger was expanded, persisted in the config directory
+ * so that the most used matches can be surfaced first across restarts.
+ * To avoid writing the file on every expansion, the counts are saved at most
+ * once every USAGE_STATS_SAVE_INTERVAL, and when the stats are dropped.
+ */
+pub struct UsageStats {
+    file: Option<PathBuf>,  // If None, the counts are only kept in memory
+    counts: RefCell<HashMap<String, u64>>,
+    dirty: Cell<bool>,
+    last_save: Cell<Instant>,
+}
+
+impl UsageStats {
+    pub fn new() -> UsageStats {
+        UsageStats {
+            file: None,
+            counts: RefCell::new(HashMap::new()),
+            dirty: Cell::new(false),
+            last_save: Cell::new(Instant::now()),
+        }
+    }
+
+    /// Load the usage stats saved in the given directory. If the file is
+    /// corrupted, the counts are reset instead of failing.
+    pub fn load(dir: &Path) -> UsageStats {
+        UsageStats {
+            file: Some(dir.join(USAGE_STATS_FILE_NAME)),
+            counts: RefCell::new(load_stats(dir)),
+            dirty: Cell::new(false),
+            last_save: Cell::new(Instant::now()),
+        }
+    }
+
+    /// Record an expansion of the given trigger, saving the counts if enough
+    /// time passed since the last save.
+    pub fn increment(&self, trigger: &str) {
+        *self.counts.borrow_mut().entry(trigger.to_owned()).or_insert(0) += 1;
+        self.dirty.set(true);
+
+        if self.last_save.get().elapsed() >= USAGE_STATS_SAVE_INTERVAL {
+            self.flush();
+        }
+    }
+
+    pub fn count(&self, trigger: &str) -> u64 {
+        self.counts.borrow().get(trigger).cloned().unwrap_or(0)
+    }
+
+    /// Return at most `n` of the given matches that were expanded at least once,
+    /// sorted from the most used one. Ties are sorted by trigger.
+    pub fn most_used<'m>(&self, matches: &'m [Match], n: usize) -> Vec<(&'m Match, u64)> {
+        let mut used: Vec<(&Match, u64)> = matches.iter()
+            .map(|m| (m, self.count(&m.fire_key())))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+
+        used.sort_by(|(a, a_count), (b, b_count)| {
+            b_count.cmp(a_count).then_with(|| a.trigger().cmp(b.trigger()))
+        });
+        used.truncate(n);
+
+        used
+    }
+
+    /// Write the counts to the stats file, if they changed since the last save.
+    pub fn flush(&self) {
+        self.last_save.set(Instant::now());
+
+        if !self.dirty.get() {
+            return;
+        }
+
+        if let Some(file) = &self.file {
+            let _lock = USAGE_STATS_FILE_LOCK.lock().unwrap();
+            match write_stats_file(file, &self.counts.borrow()) {
+                Ok(_) => self.dirty.set(false),
+                Err(e) => warn!("Unable to save the usage stats: {}", e),
+            }
+        }
+    }
+}
+
+impl Drop for UsageStats {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_expansion_stats_counts_triggers() {
+        let stats = ExpansionStats::new();
+        stats.record(":hi");
+        stats.record(":sig");
+        stats.record(":hi");
+
+        let snapshot = stats.snapshot("default");
+        assert_eq!(snapshot.total_expansions, 3);
+        assert_eq!(snapshot.trigger_counts.get(":hi"), Some(&2));
+        assert_eq!(snapshot.trigger_counts.get(":sig"), Some(&1));
+        assert_eq!(snapshot.active_config, "default");
+    }
+
+    fn test_matches(triggers: &[&str]) -> Vec<Match> {
+        triggers.iter().map(|trigger| {
+            serde_yaml::from_str(&format!("trigger: \"{}\"\nreplace: \"test\"", trigger)).unwrap()
+        }).collect()
+    }
+
+    #[test]
+    fn test_usage_stats_increment() {
+        let stats = UsageStats::new();
+        stats.increment(":hi");
+        stats.increment(":hi");
+
+        assert_eq!(stats.count(":hi"), 2);
+        assert_eq!(stats.count(":sig"), 0);
+    }
+
+    #[test]
+    fn test_usage_stats_most_used_ordering() {
+        let matches = test_matches(&[":a", ":b", ":c", ":d"]);
+        let stats = UsageStats::new();
+        stats.increment(":c");
+        stats.increment(":b");
+        stats.increment(":c");
+        stats.increment(":a");
+
+        let most_used: Vec<(&str, u64)> = stats.most_used(&matches, 2).into_iter()
+            .map(|(m, count)| (m.trigger(), count))
+            .collect();
+        assert_eq!(most_used, vec![(":c", 2), (":a", 1)]);
+    }
+
+    #[test]
+    fn test_usage_stats_persisted_across_loads() {
+        let dir = TempDir::new().unwrap();
+
+        {
+            let stats = UsageStats::load(dir.path());
+            stats.increment(":hi");
+
+            // The save is throttled
+            assert!(!dir.path().join(USAGE_STATS_FILE_NAME).exists());
+        }
+
+        let stats = UsageStats::load(dir.path());
+        assert_eq!(stats.count(":hi"), 1);
+    }
+
+    #[test]
+    fn test_usage_stats_saved_after_interval() {
+        let dir = TempDir::new().unwrap();
+        let stats = UsageStats::load(dir.path());
+        stats.last_save.set(Instant::now() - USAGE_STATS_SAVE_INTERVAL);
+
+        stats.increment(":hi");
+        assert!(dir.path().join(USAGE_STATS_FILE_NAME).exists());
+    }
+
+    #[test]
+    fn test_usage_stats_corrupted_file_is_reset() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(USAGE_STATS_FILE_NAME), "corrupted").unwrap();
+
+        let stats = UsageStats::load(dir.path());
+        assert_eq!(stats.count(":hi"), 0);
+
+        stats.increment(":hi");
+        stats.flush();
+        assert_eq!(UsageStats::load(dir.path()).count(":hi"), 1);
+    }
+
+    #[test]
+    fn test_record_expansion_round_trip() {
+        let dir = TempDir::new().unwrap();
+        record_expansion(dir.path(), ":hi");
+        record_expansion(dir.path(), ":sig");
+        record_expansion(dir.path(), ":hi");
+
+        let stats = load_stats(dir.path());
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats.get(":hi"), Some(&2));
+        assert_eq!(stats.get(":sig"), Some(&1));
+        assert!(!dir.path().join("usage_stats.json.tmp").exists());
+
+        // The counts are shared with the UsageStats
+        assert_eq!(UsageStats::load(dir.path()).count(":hi"), 2);
+    }
+
+    #[test]
+    fn test_load_stats_missing_file() {
+        let dir = TempDir::new().unwrap();
+        assert!(load_stats(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_record_expansion_concurrent_updates() {
+        let dir = TempDir::new().unwrap();
+
+        let handles: Vec<_> = (0..4).map(|_| {
+            let path = dir.path().to_owned();
+            std::thread::spawn(move || {
+                for _ in 0..10 {
+                    record_expansion(&path, ":hi");
+                }
+            })
+        }).collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(load_stats(dir.path()).get(":hi"), Some(&40));
+    }
+}
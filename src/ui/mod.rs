@@ -26,6 +26,8 @@ mod linux;
 #[cfg(target_os = "macos")]
 mod macos;
 
+pub mod notifier;
+
 pub trait UIManager {
     fn notify(&self, message: &str);
     fn show_menu(&self, menu: Vec<MenuItem>);
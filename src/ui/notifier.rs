@@ -0,0 +1,64 @@
+/*
+ * This file is part of espanso.
+ *
+ * Copyright (C) 2019 Federico Terzi
+ *
+ * espanso is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * espanso is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with espanso.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::config::ConfigLoadError;
+use crate::ui::UIManager;
+
+/// Receives the state changes of espanso that the user should be told about.
+pub trait Notifier {
+    fn on_toggle(&self, enabled: bool);
+    fn on_reload_error(&self, error: &ConfigLoadError);
+}
+
+/// Notifier used by the engine until another one is installed.
+pub struct NoopNotifier {}
+
+impl Notifier for NoopNotifier {
+    fn on_toggle(&self, _enabled: bool) {}
+
+    fn on_reload_error(&self, _error: &ConfigLoadError) {}
+}
+
+/// Notifier showing a desktop notification through the platform UIManager
+/// (on macOS, the notify helper bundled with espanso).
+pub struct UINotifier<'a, U: UIManager> {
+    ui_manager: &'a U,
+}
+
+impl <'a, U: UIManager> UINotifier<'a, U> {
+    pub fn new(ui_manager: &'a U) -> UINotifier<'a, U> {
+        UINotifier { ui_manager }
+    }
+}
+
+impl <'a, U: UIManager> Notifier for UINotifier<'a, U> {
+    fn on_toggle(&self, enabled: bool) {
+        let message = if enabled {
+            "espanso enabled"
+        }else{
+            "espanso disabled"
+        };
+
+        self.ui_manager.notify(message);
+    }
+
+    fn on_reload_error(&self, error: &ConfigLoadError) {
+        self.ui_manager.notify(&format!("Unable to reload the configuration: {}", error));
+    }
+}